@@ -0,0 +1,327 @@
+//! ECDSA adaptor signatures over ecc_compact keys.
+//!
+//! This offers the same flow as the ed25519 adaptor signatures for hotspot
+//! keys, which sign with ECDSA over P-256. ECDSA is not linear in the nonce
+//! like Schnorr signatures are, so the pre-signature instead commits to the
+//! nonce twice. With the adaptor point `Y = yG`, the pre-signature over the
+//! message hash `m` is
+//!
+//! ```text
+//! R_a = kG, R = kY, r = x(R), s' = (m + r x) / k
+//! ```
+//!
+//! along with a proof that `R_a` and `R` share the discrete log `k`. The
+//! adapted signature is the standard ECDSA signature `(r, s' / y)`, normalized
+//! to low-S, and the adaptor secret is extracted as `s' / s`.
+use crate::*;
+use p256::{
+    ecdsa,
+    elliptic_curve::{
+        bigint::U256,
+        ff::Field,
+        group::Curve,
+        ops::Reduce,
+        sec1::{FromEncodedPoint, ToEncodedPoint},
+    },
+    NonZeroScalar, ProjectivePoint, Scalar,
+};
+use sha2::{Digest, Sha256};
+
+/// Domain separator for the discrete log equality proof
+const DLEQ_DOMAIN: &[u8] = b"helium-crypto-ecdsa-adaptor-dleq";
+
+/// The secret `y` that completes a pre-signature
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdaptorSecret(NonZeroScalar);
+
+/// The public adaptor point `Y = yG`, SEC1 compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptorPoint(pub [u8; 33]);
+
+/// A pre-signature that can be adapted into a full ECDSA signature with the
+/// adaptor secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreSignature {
+    /// The nonce point `R = kY`, whose x coordinate is the signature `r`
+    pub r: [u8; 33],
+    /// The nonce point `R_a = kG`
+    pub r_a: [u8; 33],
+    /// The pre-signature scalar `s'`
+    pub s: [u8; 32],
+    /// The proof that `R` and `R_a` share the discrete log `k`
+    pub proof: [u8; 64],
+}
+
+impl std::fmt::Debug for AdaptorSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("AdaptorSecret")
+            .field("point", &self.point())
+            .finish()
+    }
+}
+
+impl AdaptorSecret {
+    pub fn generate<R>(csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        Self(NonZeroScalar::random(csprng))
+    }
+
+    /// Construct an adaptor secret from its 32 byte big endian encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Self(
+            p256::SecretKey::from_be_bytes(bytes)?.to_nonzero_scalar(),
+        ))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    /// The adaptor point for this secret
+    pub fn point(&self) -> AdaptorPoint {
+        AdaptorPoint(encode(&(ProjectivePoint::GENERATOR * *self.0)))
+    }
+}
+
+fn encode(point: &ProjectivePoint) -> [u8; 33] {
+    let mut result = [0u8; 33];
+    result.copy_from_slice(point.to_affine().to_encoded_point(true).as_bytes());
+    result
+}
+
+fn decode(bytes: &[u8]) -> Result<ProjectivePoint> {
+    let encoded = p256::EncodedPoint::from_bytes(bytes).map_err(|_| Error::invalid_curve())?;
+    let point: Option<p256::AffinePoint> = p256::AffinePoint::from_encoded_point(&encoded).into();
+    match point {
+        // The identity has no compressed encoding, so any decoded point is
+        // safe to encode again
+        Some(point) if bytes.len() == 33 => Ok(point.into()),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+fn reduce(bytes: &[u8]) -> Scalar {
+    <Scalar as Reduce<U256>>::from_be_bytes_reduced(p256::FieldBytes::clone_from_slice(bytes))
+}
+
+fn scalar_from_slice(bytes: &[u8]) -> Result<NonZeroScalar> {
+    Ok(p256::SecretKey::from_be_bytes(bytes)
+        .map_err(|_| signature::Error::new())?
+        .to_nonzero_scalar())
+}
+
+fn message_scalar(msg: &[u8]) -> Scalar {
+    reduce(&Sha256::digest(msg))
+}
+
+/// The x coordinate of the given SEC1 compressed point as a scalar
+fn x_scalar(point: &[u8; 33]) -> Scalar {
+    reduce(&point[1..])
+}
+
+fn dleq_challenge(y: &[u8], r_a: &[u8], r: &[u8], a_g: &[u8], a_y: &[u8]) -> Scalar {
+    reduce(
+        &Sha256::new()
+            .chain_update(DLEQ_DOMAIN)
+            .chain_update(y)
+            .chain_update(r_a)
+            .chain_update(r)
+            .chain_update(a_g)
+            .chain_update(a_y)
+            .finalize(),
+    )
+}
+
+/// Create a pre-signature over the given message, locked to the given adaptor
+/// point.
+pub fn presign<R>(
+    keypair: &ecc_compact::Keypair,
+    adaptor: &AdaptorPoint,
+    msg: &[u8],
+    csprng: &mut R,
+) -> Result<PreSignature>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let adaptor_point = decode(&adaptor.0)?;
+    let k = NonZeroScalar::random(&mut *csprng);
+    let r = encode(&(adaptor_point * *k));
+    let r_a = encode(&(ProjectivePoint::GENERATOR * *k));
+    let k_inv: Option<Scalar> = (*k).invert().into();
+    let k_inv = k_inv.ok_or_else(signature::Error::new)?;
+    let s = k_inv * (message_scalar(msg) + x_scalar(&r) * *keypair.secret_scalar());
+    if bool::from(s.is_zero()) {
+        return Err(signature::Error::new().into());
+    }
+
+    // Chaum-Pedersen proof that R_a = kG and R = kY
+    let a = NonZeroScalar::random(&mut *csprng);
+    let e = dleq_challenge(
+        &adaptor.0,
+        &r_a,
+        &r,
+        &encode(&(ProjectivePoint::GENERATOR * *a)),
+        &encode(&(adaptor_point * *a)),
+    );
+    let z = *a + e * *k;
+    let mut proof = [0u8; 64];
+    proof[..32].copy_from_slice(&e.to_bytes());
+    proof[32..].copy_from_slice(&z.to_bytes());
+
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&s.to_bytes());
+    Ok(PreSignature {
+        r,
+        r_a,
+        s: s_bytes,
+        proof,
+    })
+}
+
+/// Verify that the given pre-signature by the given public key over the given
+/// message is locked to the given adaptor point, which means it will adapt
+/// into a valid signature with the matching adaptor secret.
+pub fn verify_presignature(
+    public_key: &PublicKey,
+    adaptor: &AdaptorPoint,
+    msg: &[u8],
+    presignature: &PreSignature,
+) -> Result {
+    let public_key: &ecc_compact::PublicKey = public_key.try_into()?;
+    let adaptor_point = decode(&adaptor.0)?;
+    let r = decode(&presignature.r)?;
+    let r_a = decode(&presignature.r_a)?;
+    let s = scalar_from_slice(&presignature.s)?;
+    let x = x_scalar(&presignature.r);
+    if bool::from(x.is_zero()) {
+        return Err(signature::Error::new().into());
+    }
+
+    // R_a = (m G + r X) / s'
+    let s_inv: Option<Scalar> = (*s).invert().into();
+    let s_inv = s_inv.ok_or_else(signature::Error::new)?;
+    let expected = (ProjectivePoint::GENERATOR * message_scalar(msg)
+        + public_key.0.to_projective() * x)
+        * s_inv;
+    if expected != r_a {
+        return Err(signature::Error::new().into());
+    }
+
+    // R and R_a share the same discrete log
+    let e = reduce(&presignature.proof[..32]);
+    let z = reduce(&presignature.proof[32..]);
+    let a_g = ProjectivePoint::GENERATOR * z - r_a * e;
+    let a_y = adaptor_point * z - r * e;
+    if a_g == ProjectivePoint::IDENTITY || a_y == ProjectivePoint::IDENTITY {
+        return Err(signature::Error::new().into());
+    }
+    let expected = dleq_challenge(
+        &adaptor.0,
+        &presignature.r_a,
+        &presignature.r,
+        &encode(&a_g),
+        &encode(&a_y),
+    );
+    if expected.to_bytes().as_slice() != &presignature.proof[..32] {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+/// Adapt the given pre-signature with the adaptor secret into a standard DER
+/// encoded low-S ECDSA signature.
+pub fn adapt(presignature: &PreSignature, secret: &AdaptorSecret) -> Result<Vec<u8>> {
+    let s = scalar_from_slice(&presignature.s)?;
+    let y_inv: Option<Scalar> = (*secret.0).invert().into();
+    let y_inv = y_inv.ok_or_else(signature::Error::new)?;
+    let signature = ecdsa::Signature::from_scalars(
+        x_scalar(&presignature.r).to_bytes(),
+        (*s * y_inv).to_bytes(),
+    )?;
+    let signature = ecc_compact::normalize_s(signature)?;
+    Ok(signature.to_der().as_bytes().to_vec())
+}
+
+/// Extract the adaptor secret from a pre-signature locked to the given
+/// adaptor point and the DER encoded signature it was adapted into.
+pub fn extract_secret(
+    presignature: &PreSignature,
+    adaptor: &AdaptorPoint,
+    adapted: &[u8],
+) -> Result<AdaptorSecret> {
+    let signature = ecdsa::Signature::from_der(adapted)?;
+    let (r, s) = signature.split_scalars();
+    if *r != x_scalar(&presignature.r) {
+        return Err(signature::Error::new().into());
+    }
+    let presigned = scalar_from_slice(&presignature.s)?;
+    let s_inv: Option<Scalar> = (*s).invert().into();
+    let s_inv = s_inv.ok_or_else(signature::Error::new)?;
+    let y = *presigned * s_inv;
+    // The adapted signature may have been normalized to low-S, which negates
+    // the extracted secret
+    for y in [y, -y] {
+        let candidate: Option<NonZeroScalar> = NonZeroScalar::new(y).into();
+        if let Some(candidate) = candidate {
+            let secret = AdaptorSecret(candidate);
+            if &secret.point() == adaptor {
+                return Ok(secret);
+            }
+        }
+    }
+    Err(signature::Error::new().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn adapt_extract() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let secret = AdaptorSecret::generate(&mut OsRng);
+        let adaptor = secret.point();
+        const MSG: &[u8] = b"transfer hotspot";
+
+        let presignature = presign(&keypair, &adaptor, MSG, &mut OsRng).expect("presign");
+        assert!(verify_presignature(&keypair.public_key, &adaptor, MSG, &presignature).is_ok());
+        assert!(
+            verify_presignature(&keypair.public_key, &adaptor, b"other", &presignature).is_err()
+        );
+
+        let signature = adapt(&presignature, &secret).expect("adapt");
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+        assert!(keypair.public_key.verify_strict(MSG, &signature).is_ok());
+
+        let extracted = extract_secret(&presignature, &adaptor, &signature).expect("extract");
+        assert_eq!(secret, extracted);
+        assert_eq!(
+            secret,
+            AdaptorSecret::from_bytes(&secret.to_bytes()).expect("secret")
+        );
+    }
+
+    #[test]
+    fn wrong_adaptor() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let adaptor = AdaptorSecret::generate(&mut OsRng).point();
+        let other = AdaptorSecret::generate(&mut OsRng);
+        let presignature = presign(&keypair, &adaptor, b"msg", &mut OsRng).expect("presign");
+        assert!(
+            verify_presignature(&keypair.public_key, &other.point(), b"msg", &presignature)
+                .is_err()
+        );
+
+        // Adapting with the wrong secret does not make a valid signature
+        let signature = adapt(&presignature, &other).expect("adapt");
+        assert!(keypair.public_key.verify(b"msg", &signature).is_err());
+
+        // A tampered proof is rejected
+        let mut tampered = presignature;
+        tampered.proof[40] ^= 1;
+        assert!(verify_presignature(&keypair.public_key, &adaptor, b"msg", &tampered).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Adaptor signatures over ed25519 keys.
+//!
+//! An adaptor (or pre-) signature is tied to an adaptor point `T = tG`. It can
+//! be checked by anyone with [`verify_presignature`] but is not itself a valid
+//! signature. Whoever knows the secret `t` can [`adapt`] the pre-signature into
+//! a standard ed25519 signature which verifies with [`PublicKey::verify`].
+//! Conversely, once the adapted signature is published, the holder of the
+//! pre-signature can [`extract_secret`] `t` from it.
+//!
+//! This enables atomic swap style flows where publishing one signature
+//! necessarily reveals the secret needed to complete another.
+//!
+//! Pre-signatures are computed as `R = rG + T`, `s' = r + H(R || A || msg) x`,
+//! and adapted to the signature `(R, s' + t)`.
+//!
+//! The [`ecdsa`] module offers the same flow for ecc_compact keys, which sign
+//! with ECDSA, and the [`schnorr`] module for BIP340 Schnorr signatures by
+//! secp256k1 keys.
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, public_key_point, random_scalar},
+    *,
+};
+use curve25519_dalek::scalar::Scalar;
+
+pub mod ecdsa;
+#[cfg(feature = "secp256k1")]
+pub mod schnorr;
+
+/// The secret `t` that completes a pre-signature
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdaptorSecret(Scalar);
+
+/// The public adaptor point `T = tG`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptorPoint(pub [u8; 32]);
+
+/// A pre-signature that can be adapted into a full signature with the adaptor
+/// secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreSignature {
+    /// The final nonce commitment `R`, which includes the adaptor point
+    pub r: [u8; 32],
+    /// The pre-signature scalar `s'`
+    pub s: [u8; 32],
+}
+
+impl std::fmt::Debug for AdaptorSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("AdaptorSecret")
+            .field("point", &self.point())
+            .finish()
+    }
+}
+
+impl AdaptorSecret {
+    pub fn generate<R>(csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        Self(random_scalar(csprng))
+    }
+
+    /// Construct an adaptor secret from its canonical 32 byte encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ed25519::curve::scalar_from_slice(bytes).map(Self)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    /// The adaptor point for this secret
+    pub fn point(&self) -> AdaptorPoint {
+        AdaptorPoint(base_mul(&self.0).compress().to_bytes())
+    }
+}
+
+fn challenge(r: &[u8; 32], public_key: &[u8], msg: &[u8]) -> Scalar {
+    hash_to_scalar(&[&r[..], public_key, msg])
+}
+
+/// Create a pre-signature over the given message, locked to the given adaptor
+/// point.
+pub fn presign<R>(
+    keypair: &ed25519::Keypair,
+    adaptor: &AdaptorPoint,
+    msg: &[u8],
+    csprng: &mut R,
+) -> Result<PreSignature>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let adaptor_point = decompress(&adaptor.0)?;
+    let public_key: &ed25519::PublicKey = (&keypair.public_key).try_into()?;
+    let nonce = random_scalar(csprng);
+    let r = (base_mul(&nonce) + adaptor_point).compress().to_bytes();
+    let s = nonce + challenge(&r, public_key.as_ref(), msg) * keypair.secret_scalar();
+    Ok(PreSignature { r, s: s.to_bytes() })
+}
+
+/// Verify that the given pre-signature by the given public key over the given
+/// message is locked to the given adaptor point, which means it will adapt
+/// into a valid signature with the matching adaptor secret.
+pub fn verify_presignature(
+    public_key: &PublicKey,
+    adaptor: &AdaptorPoint,
+    msg: &[u8],
+    presignature: &PreSignature,
+) -> Result {
+    let public_point = public_key_point(public_key)?;
+    let adaptor_point = decompress(&adaptor.0)?;
+    let r = decompress(&presignature.r)?;
+    let s = ed25519::curve::scalar_from_slice(&presignature.s)?;
+    let c = challenge(&presignature.r, public_point.compress().as_bytes(), msg);
+    if base_mul(&s) != r - adaptor_point + c * public_point {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+/// Adapt the given pre-signature with the adaptor secret into a standard
+/// ed25519 signature.
+pub fn adapt(presignature: &PreSignature, secret: &AdaptorSecret) -> Result<Vec<u8>> {
+    let s = ed25519::curve::scalar_from_slice(&presignature.s)?;
+    let mut result = presignature.r.to_vec();
+    result.extend_from_slice((s + secret.0).as_bytes());
+    Ok(result)
+}
+
+/// Extract the adaptor secret from a pre-signature and the signature it was
+/// adapted into.
+pub fn extract_secret(presignature: &PreSignature, adapted: &[u8]) -> Result<AdaptorSecret> {
+    if adapted.len() != 64 || adapted[..32] != presignature.r {
+        return Err(signature::Error::new().into());
+    }
+    let s = ed25519::curve::scalar_from_slice(&adapted[32..])?;
+    let presigned = ed25519::curve::scalar_from_slice(&presignature.s)?;
+    Ok(AdaptorSecret(s - presigned))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn adapt_extract() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let secret = AdaptorSecret::generate(&mut OsRng);
+        let adaptor = secret.point();
+        const MSG: &[u8] = b"transfer hotspot";
+
+        let presignature = presign(&keypair, &adaptor, MSG, &mut OsRng).expect("presign");
+        assert!(verify_presignature(&keypair.public_key, &adaptor, MSG, &presignature).is_ok());
+        // The pre-signature is not a valid signature by itself
+        let mut unadapted = presignature.r.to_vec();
+        unadapted.extend_from_slice(&presignature.s);
+        assert!(keypair.public_key.verify(MSG, &unadapted).is_err());
+
+        let signature = adapt(&presignature, &secret).expect("adapt");
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+
+        let extracted = extract_secret(&presignature, &signature).expect("extract");
+        assert_eq!(secret, extracted);
+    }
+
+    #[test]
+    fn wrong_adaptor() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let adaptor = AdaptorSecret::generate(&mut OsRng).point();
+        let other = AdaptorSecret::generate(&mut OsRng).point();
+        let presignature = presign(&keypair, &adaptor, b"msg", &mut OsRng).expect("presign");
+        assert!(verify_presignature(&keypair.public_key, &other, b"msg", &presignature).is_err());
+    }
+}
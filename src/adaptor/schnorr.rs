@@ -0,0 +1,272 @@
+//! BIP340 Schnorr adaptor signatures over secp256k1 keys.
+//!
+//! This offers the same flow as the ed25519 adaptor signatures for the
+//! [BIP340][BIP340] Schnorr signatures made by
+//! [`secp256k1::Keypair::sign_schnorr`]. With the adaptor point `T = tG` and
+//! the even-y public key `P = dG`, the pre-signature over the 32 byte message
+//! `m` is
+//!
+//! ```text
+//! R = kG + T, s' = k + e d, where e = H_BIP0340/challenge(x(R) || x(P) || m)
+//! ```
+//!
+//! with the nonce `k` chosen so that `R` has an even y coordinate. The adapted
+//! signature is the standard 64 byte BIP340 signature `(x(R), s' + t)`, which
+//! verifies with [`secp256k1::PublicKey::verify_schnorr`], and the adaptor
+//! secret is extracted as `s - s'`.
+//!
+//! [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+use crate::*;
+use k256::{
+    elliptic_curve::{
+        bigint::U256,
+        ff::PrimeField,
+        group::Curve,
+        ops::Reduce,
+        sec1::{FromEncodedPoint, ToEncodedPoint},
+    },
+    NonZeroScalar, ProjectivePoint, Scalar,
+};
+
+/// The secret `t` that completes a pre-signature
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdaptorSecret(NonZeroScalar);
+
+/// The public adaptor point `T = tG`, SEC1 compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptorPoint(pub [u8; 33]);
+
+/// A pre-signature that can be adapted into a full BIP340 signature with the
+/// adaptor secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreSignature {
+    /// The final nonce commitment `R`, which includes the adaptor point, SEC1
+    /// compressed with an even y coordinate
+    pub r: [u8; 33],
+    /// The pre-signature scalar `s'`
+    pub s: [u8; 32],
+}
+
+impl std::fmt::Debug for AdaptorSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("AdaptorSecret")
+            .field("point", &self.point())
+            .finish()
+    }
+}
+
+impl AdaptorSecret {
+    pub fn generate<R>(csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        Self(NonZeroScalar::random(csprng))
+    }
+
+    /// Construct an adaptor secret from its 32 byte big endian encoding
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Self(
+            k256::SecretKey::from_be_bytes(bytes)?.to_nonzero_scalar(),
+        ))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    /// The adaptor point for this secret
+    pub fn point(&self) -> AdaptorPoint {
+        AdaptorPoint(encode(&(ProjectivePoint::GENERATOR * *self.0)))
+    }
+}
+
+fn encode(point: &ProjectivePoint) -> [u8; 33] {
+    let mut result = [0u8; 33];
+    result.copy_from_slice(point.to_affine().to_encoded_point(true).as_bytes());
+    result
+}
+
+fn decode(bytes: &[u8]) -> Result<ProjectivePoint> {
+    let encoded = k256::EncodedPoint::from_bytes(bytes).map_err(|_| Error::invalid_curve())?;
+    let point: Option<k256::AffinePoint> = k256::AffinePoint::from_encoded_point(&encoded).into();
+    match point {
+        // The identity has no compressed encoding, so any decoded point is
+        // safe to encode again
+        Some(point) if bytes.len() == 33 => Ok(point.into()),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+fn has_even_y(point: &[u8; 33]) -> bool {
+    point[0] == 0x02
+}
+
+fn reduce(bytes: &[u8]) -> Scalar {
+    <Scalar as Reduce<U256>>::from_be_bytes_reduced(k256::FieldBytes::clone_from_slice(bytes))
+}
+
+fn scalar_from_slice(bytes: &[u8]) -> Result<Scalar> {
+    let scalar: Option<Scalar> =
+        Scalar::from_repr(k256::FieldBytes::clone_from_slice(bytes)).into();
+    scalar.ok_or_else(|| signature::Error::new().into())
+}
+
+/// The BIP340 challenge `e` for the given nonce commitment, even-y public key
+/// and message
+fn challenge(r: &[u8; 33], public_key: &[u8; 33], msg: &[u8; 32]) -> Scalar {
+    reduce(&secp256k1::tagged_hash(
+        b"BIP0340/challenge",
+        &[&r[1..], &public_key[1..], &msg[..]].concat(),
+    ))
+}
+
+/// Create a pre-signature over the given 32 byte message, locked to the given
+/// adaptor point.
+pub fn presign<R>(
+    keypair: &secp256k1::Keypair,
+    adaptor: &AdaptorPoint,
+    msg: &[u8; 32],
+    csprng: &mut R,
+) -> Result<PreSignature>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let adaptor_point = decode(&adaptor.0)?;
+    // BIP340 signs with the secret for the even-y public key
+    let mut secret = *keypair.secret_scalar();
+    let mut public_key = encode(&(ProjectivePoint::GENERATOR * secret));
+    if !has_even_y(&public_key) {
+        secret = -secret;
+        public_key = encode(&(ProjectivePoint::GENERATOR * secret));
+    }
+
+    // Negating k does not negate R = kG + T, so draw nonces until R has an
+    // even y coordinate
+    let (k, r) = loop {
+        let k = NonZeroScalar::random(&mut *csprng);
+        let r = ProjectivePoint::GENERATOR * *k + adaptor_point;
+        if r == ProjectivePoint::IDENTITY {
+            continue;
+        }
+        let r = encode(&r);
+        if has_even_y(&r) {
+            break (k, r);
+        }
+    };
+
+    let s = *k + challenge(&r, &public_key, msg) * secret;
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&s.to_bytes());
+    Ok(PreSignature { r, s: s_bytes })
+}
+
+/// Verify that the given pre-signature by the given public key over the given
+/// 32 byte message is locked to the given adaptor point, which means it will
+/// adapt into a valid BIP340 signature with the matching adaptor secret.
+pub fn verify_presignature(
+    public_key: &PublicKey,
+    adaptor: &AdaptorPoint,
+    msg: &[u8; 32],
+    presignature: &PreSignature,
+) -> Result {
+    let public_key: &secp256k1::PublicKey = public_key.try_into()?;
+    let mut public_key_bytes = [0x02; 33];
+    public_key_bytes[1..].copy_from_slice(&public_key.x_only());
+    let public_key_point = decode(&public_key_bytes)?;
+    let adaptor_point = decode(&adaptor.0)?;
+    if !has_even_y(&presignature.r) {
+        return Err(signature::Error::new().into());
+    }
+    let r = decode(&presignature.r)?;
+    let s = scalar_from_slice(&presignature.s)?;
+
+    // s'G + T = R + eP
+    let e = challenge(&presignature.r, &public_key_bytes, msg);
+    if ProjectivePoint::GENERATOR * s + adaptor_point != r + public_key_point * e {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+/// Adapt the given pre-signature with the adaptor secret into a standard 64
+/// byte BIP340 signature.
+pub fn adapt(presignature: &PreSignature, secret: &AdaptorSecret) -> Result<Vec<u8>> {
+    let s = scalar_from_slice(&presignature.s)? + *secret.0;
+    let mut signature = Vec::with_capacity(64);
+    signature.extend_from_slice(&presignature.r[1..]);
+    signature.extend_from_slice(&s.to_bytes());
+    Ok(signature)
+}
+
+/// Extract the adaptor secret from a pre-signature locked to the given
+/// adaptor point and the BIP340 signature it was adapted into.
+pub fn extract_secret(
+    presignature: &PreSignature,
+    adaptor: &AdaptorPoint,
+    adapted: &[u8],
+) -> Result<AdaptorSecret> {
+    if adapted.len() != 64 || adapted[..32] != presignature.r[1..] {
+        return Err(signature::Error::new().into());
+    }
+    let t = scalar_from_slice(&adapted[32..])? - scalar_from_slice(&presignature.s)?;
+    let t: Option<NonZeroScalar> = NonZeroScalar::new(t).into();
+    let secret = AdaptorSecret(t.ok_or_else(signature::Error::new)?);
+    if &secret.point() != adaptor {
+        return Err(signature::Error::new().into());
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    const MSG: &[u8; 32] = b"transfer hotspot to new owner!!!";
+
+    #[test]
+    fn adapt_extract() {
+        let keypair = secp256k1::Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &secp256k1::PublicKey = (&keypair.public_key).try_into().unwrap();
+        let secret = AdaptorSecret::generate(&mut OsRng);
+        let adaptor = secret.point();
+
+        let presignature = presign(&keypair, &adaptor, MSG, &mut OsRng).expect("presign");
+        assert!(verify_presignature(&keypair.public_key, &adaptor, MSG, &presignature).is_ok());
+        assert!(
+            verify_presignature(&keypair.public_key, &adaptor, &[0u8; 32], &presignature).is_err()
+        );
+
+        // The pre-signature is not itself a valid signature
+        let mut unadapted = presignature.r[1..].to_vec();
+        unadapted.extend_from_slice(&presignature.s);
+        assert!(public_key.verify_schnorr(MSG, &unadapted).is_err());
+
+        let signature = adapt(&presignature, &secret).expect("adapt");
+        assert!(public_key.verify_schnorr(MSG, &signature).is_ok());
+
+        let extracted = extract_secret(&presignature, &adaptor, &signature).expect("extract");
+        assert_eq!(secret, extracted);
+        assert_eq!(
+            secret,
+            AdaptorSecret::from_bytes(&secret.to_bytes()).expect("secret")
+        );
+    }
+
+    #[test]
+    fn wrong_adaptor() {
+        let keypair = secp256k1::Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &secp256k1::PublicKey = (&keypair.public_key).try_into().unwrap();
+        let adaptor = AdaptorSecret::generate(&mut OsRng).point();
+        let other = AdaptorSecret::generate(&mut OsRng);
+        let presignature = presign(&keypair, &adaptor, MSG, &mut OsRng).expect("presign");
+        assert!(
+            verify_presignature(&keypair.public_key, &other.point(), MSG, &presignature).is_err()
+        );
+
+        // Adapting with the wrong secret does not make a valid signature
+        let signature = adapt(&presignature, &other).expect("adapt");
+        assert!(public_key.verify_schnorr(MSG, &signature).is_err());
+        assert!(extract_secret(&presignature, &adaptor, &signature).is_err());
+    }
+}
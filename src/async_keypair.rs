@@ -0,0 +1,228 @@
+//! Non-blocking adapters for keypairs.
+//!
+//! Hardware backed keypairs, like the ECC608 and TPM ones, block the calling
+//! thread for the duration of every operation. An [`AsyncKeypair`] moves a
+//! keypair onto a dedicated worker thread and queues operations to it, so
+//! async tasks never block the runtime they run on.
+//!
+//! Operations can be given a timeout after which the caller gets an
+//! [`Error::Timeout`]. Queued operations whose caller has given up are skipped
+//! by the worker.
+use crate::{keypair::SharedSecret, *};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+enum Job {
+    Sign(Vec<u8>, oneshot::Sender<Result<Vec<u8>>>),
+    Ecdh(PublicKey, oneshot::Sender<Result<SharedSecret>>),
+}
+
+impl Job {
+    fn is_cancelled(&self) -> bool {
+        match self {
+            Self::Sign(_, sender) => sender.is_closed(),
+            Self::Ecdh(_, sender) => sender.is_closed(),
+        }
+    }
+
+    fn run(self, keypair: &Keypair) {
+        // Send errors mean the caller has gone away, which is fine to ignore
+        match self {
+            Self::Sign(msg, sender) => {
+                let _ = sender.send(keypair.sign(&msg));
+            }
+            Self::Ecdh(public_key, sender) => {
+                let _ = sender.send(keypair.ecdh(&public_key));
+            }
+        }
+    }
+}
+
+/// A keypair whose operations run on a dedicated worker thread.
+///
+/// The worker thread exits once the async keypair is dropped and all queued
+/// operations have completed. Use [`AsyncKeypair::close`] to wait for that to
+/// happen.
+pub struct AsyncKeypair {
+    key_tag: KeyTag,
+    public_key: PublicKey,
+    timeout: Option<Duration>,
+    queue_depth: Arc<AtomicUsize>,
+    sender: mpsc::Sender<Job>,
+    closed: oneshot::Receiver<()>,
+}
+
+impl std::fmt::Debug for AsyncKeypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("AsyncKeypair")
+            .field("tag", &self.key_tag)
+            .field("public", &self.public_key)
+            .field("queue_depth", &self.queue_depth())
+            .finish()
+    }
+}
+
+impl AsyncKeypair {
+    /// Move the given keypair onto a new worker thread
+    pub fn new(keypair: Keypair) -> Result<Self> {
+        let key_tag = keypair.key_tag();
+        let public_key = keypair.public_key().clone();
+        let queue_depth = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (closed_sender, closed) = oneshot::channel();
+        let worker_depth = queue_depth.clone();
+        thread::Builder::new()
+            .name(format!("helium-crypto-{}", key_tag.key_type))
+            .spawn(move || {
+                for job in receiver {
+                    worker_depth.fetch_sub(1, Ordering::SeqCst);
+                    if !job.is_cancelled() {
+                        job.run(&keypair);
+                    }
+                }
+                drop(keypair);
+                let _ = closed_sender.send(());
+            })?;
+        Ok(Self {
+            key_tag,
+            public_key,
+            timeout: None,
+            queue_depth,
+            sender,
+            closed,
+        })
+    }
+
+    /// Close the keypair, waiting for queued operations to complete and for
+    /// the keypair to be dropped on the worker thread.
+    ///
+    /// This releases the keypair but not the global hardware connection it
+    /// uses, which may be shared with other keypairs. Call the `shutdown`
+    /// function of the hardware backend once all its keypairs are closed.
+    pub async fn close(self) -> Result {
+        let Self { sender, closed, .. } = self;
+        drop(sender);
+        closed.await.map_err(|_| Error::worker_unavailable())
+    }
+
+    /// Set the timeout applied to every operation. The timeout includes the
+    /// time an operation spends waiting in the queue.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.key_tag
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the number of operations waiting in the queue
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth.load(Ordering::SeqCst)
+    }
+
+    /// Sign the given message on the worker thread
+    pub async fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let (sender, receiver) = oneshot::channel();
+        self.submit(Job::Sign(msg.to_vec(), sender))?;
+        self.receive(receiver, self.timeout).await
+    }
+
+    /// Sign the given message on the worker thread with the given timeout,
+    /// overriding the configured one.
+    pub async fn sign_timeout(&self, msg: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+        let (sender, receiver) = oneshot::channel();
+        self.submit(Job::Sign(msg.to_vec(), sender))?;
+        self.receive(receiver, Some(timeout)).await
+    }
+
+    /// Perform an ECDH with the given public key on the worker thread
+    pub async fn ecdh(&self, public_key: &PublicKey) -> Result<SharedSecret> {
+        let (sender, receiver) = oneshot::channel();
+        self.submit(Job::Ecdh(public_key.clone(), sender))?;
+        self.receive(receiver, self.timeout).await
+    }
+
+    fn submit(&self, job: Job) -> Result {
+        self.queue_depth.fetch_add(1, Ordering::SeqCst);
+        self.sender.send(job).map_err(|_| {
+            self.queue_depth.fetch_sub(1, Ordering::SeqCst);
+            Error::worker_unavailable()
+        })
+    }
+
+    async fn receive<T>(
+        &self,
+        receiver: oneshot::Receiver<Result<T>>,
+        timeout: Option<Duration>,
+    ) -> Result<T> {
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, receiver)
+                .await
+                .map_err(|_| Error::timeout())?,
+            None => receiver.await,
+        };
+        result.map_err(|_| Error::worker_unavailable())?
+    }
+}
+
+impl From<Keypair> for AsyncKeypair {
+    /// Convert a keypair into an async keypair.
+    ///
+    /// Panics if the worker thread can not be spawned.
+    fn from(keypair: Keypair) -> Self {
+        Self::new(keypair).expect("async keypair worker")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[tokio::test]
+    async fn sign() {
+        let keypair = AsyncKeypair::new(Keypair::generate(KeyTag::default(), &mut OsRng))
+            .expect("async keypair")
+            .with_timeout(Duration::from_secs(5));
+        let signature = keypair.sign(b"hello world").await.expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert_eq!(0, keypair.queue_depth());
+    }
+
+    #[tokio::test]
+    async fn ecdh() {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        let keypair = AsyncKeypair::new(Keypair::generate(key_tag, &mut OsRng)).expect("keypair");
+        let other = Keypair::generate(key_tag, &mut OsRng);
+        let shared = keypair.ecdh(other.public_key()).await.expect("ecdh");
+        let other_shared = other.ecdh(keypair.public_key()).expect("other ecdh");
+        assert_eq!(shared.raw_secret_bytes(), other_shared.raw_secret_bytes());
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let keypair = AsyncKeypair::new(Keypair::generate(KeyTag::default(), &mut OsRng))
+            .expect("async keypair");
+        let pending = keypair.sign(b"hello world");
+        assert!(pending.await.is_ok());
+        assert!(keypair.close().await.is_ok());
+    }
+}
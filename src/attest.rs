@@ -0,0 +1,270 @@
+//! Hardware attestation of keys.
+//!
+//! Keypairs implement [`Attest`] to produce [`Evidence`] for a challenge
+//! chosen by a verifier: a signature by the hardware key over the challenge
+//! and the key itself, plus the certificate chain that vouches for the key
+//! living in hardware. The host side [`verify`] function checks the evidence
+//! and returns an [`AttestedKey`].
+//!
+//! Only ECC608 secure elements produce evidence. It carries the factory
+//! device certificate of a Trust&GO part, so only the factory key in slot 0
+//! of such a part can be attested. The signer certificate that issued it is
+//! not included, since rebuilding it needs the Microchip root public key,
+//! which the verifier holds along with the published signer certificates.
+//! TPM quotes and TEE evidence are not supported.
+//!
+//! This crate does not parse certificates. Checking the certificate chain
+//! against the trust anchors of a backend, like the Microchip root for
+//! ECC608 devices, is up to the [`CertificateVerifier`] passed to
+//! [`verify`].
+use crate::{
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use std::io;
+
+/// Domain separator prefixed to the signed bytes of attestation evidence
+const ATTEST_DOMAIN: &[u8] = b"helium-attestation";
+
+/// The kind of hardware that produced attestation evidence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attestation {
+    Ecc608,
+}
+
+impl From<Attestation> for u8 {
+    fn from(v: Attestation) -> Self {
+        match v {
+            Attestation::Ecc608 => 0,
+        }
+    }
+}
+
+impl TryFrom<u8> for Attestation {
+    type Error = Error;
+
+    fn try_from(v: u8) -> Result<Self> {
+        match v {
+            0 => Ok(Self::Ecc608),
+            _ => Err(Error::invalid_keytype(v)),
+        }
+    }
+}
+
+/// Evidence that a key lives in attesting hardware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evidence {
+    pub attestation: Attestation,
+    /// The attested public key
+    pub public_key: PublicKey,
+    /// The signature by the attested key over the challenge
+    pub signature: Vec<u8>,
+    /// The DER encoded certificate chain for the key, leaf first
+    pub certificates: Vec<Vec<u8>>,
+}
+
+impl Evidence {
+    /// Sign the given challenge with the given keypair as evidence of the
+    /// given kind
+    #[cfg_attr(not(feature = "ecc608"), allow(dead_code))]
+    fn sign(attestation: Attestation, keypair: &Keypair, challenge: &[u8]) -> Result<Self> {
+        let public_key = keypair.public_key().clone();
+        let signature = keypair.sign(&signing_bytes(attestation, &public_key, challenge)?)?;
+        Ok(Self {
+            attestation,
+            public_key,
+            signature,
+            certificates: vec![],
+        })
+    }
+
+    /// Attach the certificate chain for the attested key, leaf first
+    pub fn with_certificates(mut self, certificates: Vec<Vec<u8>>) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    /// Convert the evidence to its binary form. Fails if there are more than
+    /// 255 certificates.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_to(&mut result)?;
+        Ok(result)
+    }
+}
+
+impl WriteTo for Evidence {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        output.write_all(&[u8::from(self.attestation)])?;
+        self.public_key.write_to(output)?;
+        write_u32_prefixed(output, &self.signature)?;
+        let count = u8::try_from(self.certificates.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many certificates"))?;
+        output.write_all(&[count])?;
+        for certificate in &self.certificates {
+            write_u32_prefixed(output, certificate)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadFrom for Evidence {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let mut attestation = [0u8; 1];
+        input.read_exact(&mut attestation)?;
+        let attestation = Attestation::try_from(attestation[0])?;
+        let public_key = PublicKey::read_from(input)?;
+        let signature = read_u32_prefixed(input)?;
+        let mut count = [0u8; 1];
+        input.read_exact(&mut count)?;
+        let mut certificates = vec![];
+        for _ in 0..count[0] {
+            certificates.push(read_u32_prefixed(input)?);
+        }
+        Ok(Self {
+            attestation,
+            public_key,
+            signature,
+            certificates,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Evidence {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// Produces attestation evidence for a verifier chosen challenge
+pub trait Attest {
+    fn attest(&self, challenge: &[u8]) -> Result<Evidence>;
+}
+
+/// Checks the certificate chain of attestation evidence against the trust
+/// anchors for its kind of hardware
+pub trait CertificateVerifier {
+    fn verify_certificates(
+        &self,
+        attestation: Attestation,
+        public_key: &PublicKey,
+        certificates: &[Vec<u8>],
+    ) -> Result;
+}
+
+/// A public key that was verified to live in attesting hardware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestedKey {
+    pub public_key: PublicKey,
+    pub attestation: Attestation,
+}
+
+/// Verify the given evidence for the given challenge, checking its
+/// certificate chain with the given certificate verifier
+pub fn verify<V: CertificateVerifier + ?Sized>(
+    evidence: &Evidence,
+    challenge: &[u8],
+    certificates: &V,
+) -> Result<AttestedKey> {
+    let msg = signing_bytes(evidence.attestation, &evidence.public_key, challenge)?;
+    evidence.public_key.verify(&msg, &evidence.signature)?;
+    certificates.verify_certificates(
+        evidence.attestation,
+        &evidence.public_key,
+        &evidence.certificates,
+    )?;
+    Ok(AttestedKey {
+        public_key: evidence.public_key.clone(),
+        attestation: evidence.attestation,
+    })
+}
+
+/// Returns the bytes the signature in attestation evidence is calculated over
+fn signing_bytes(
+    attestation: Attestation,
+    public_key: &PublicKey,
+    challenge: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut result = ATTEST_DOMAIN.to_vec();
+    result.push(u8::from(attestation));
+    public_key.write_to(&mut result)?;
+    write_u32_prefixed(&mut result, challenge)?;
+    Ok(result)
+}
+
+impl Attest for Keypair {
+    /// Attest a hardware keypair with its factory certificate. Only ECC608
+    /// factory keys can be attested, other keypairs fail with a not
+    /// permitted error.
+    fn attest(&self, challenge: &[u8]) -> Result<Evidence> {
+        match self {
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(keypair) => {
+                let certificate = keypair.factory_certificate()?;
+                Ok(Evidence::sign(Attestation::Ecc608, self, challenge)?
+                    .with_certificates(vec![certificate]))
+            }
+            _ => Err(Error::not_permitted()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    /// Accepts any chain with at least one certificate
+    struct AnyCertificate;
+
+    impl CertificateVerifier for AnyCertificate {
+        fn verify_certificates(&self, _: Attestation, _: &PublicKey, certs: &[Vec<u8>]) -> Result {
+            if certs.is_empty() {
+                return Err(Error::not_permitted());
+            }
+            Ok(())
+        }
+    }
+
+    fn evidence(keypair: &Keypair, challenge: &[u8]) -> Evidence {
+        Evidence::sign(Attestation::Ecc608, keypair, challenge)
+            .expect("evidence")
+            .with_certificates(vec![b"leaf".to_vec()])
+    }
+
+    #[test]
+    fn verify_evidence() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let evidence = evidence(&keypair, b"challenge");
+        let attested = verify(&evidence, b"challenge", &AnyCertificate).expect("attested");
+        assert_eq!(keypair.public_key(), &attested.public_key);
+        assert_eq!(Attestation::Ecc608, attested.attestation);
+
+        assert!(verify(&evidence, b"other challenge", &AnyCertificate).is_err());
+        let uncertified = evidence.with_certificates(vec![]);
+        assert!(verify(&uncertified, b"challenge", &AnyCertificate).is_err());
+    }
+
+    #[test]
+    fn software() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(matches!(
+            keypair.attest(b"challenge"),
+            Err(Error::NotPermitted)
+        ));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let evidence = evidence(&keypair, b"challenge");
+        let mut bytes = evidence.to_vec().expect("bytes");
+        assert_eq!(evidence, Evidence::try_from(&bytes[..]).expect("evidence"));
+        // Unknown kinds of evidence are rejected
+        bytes[0] = 1;
+        assert!(Evidence::try_from(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! Passphrase encrypted backups of software keypairs.
+//!
+//! A key bundle is a single archive of any number of keypairs, each with a
+//! label and creation time, for disaster recovery of deployments that use
+//! several keys. The archive is versioned and encrypted with AES-256-GCM
+//! under a key derived from a passphrase with Argon2id, the same way as an
+//! encrypted keypair, so any tampering with the archive, including its
+//! header, makes it fail to import.
+//!
+//! The binary form of a bundle is:
+//!
+//! ```text
+//! magic || version (u8) || m_cost (u32) || t_cost (u32) || p_cost (u32) ||
+//!     salt (16) || nonce (12) || ciphertext
+//! ```
+//!
+//! The KDF parameters are read before the header can be authenticated, so
+//! each is limited to [`MAX_COST_FACTOR`] times its default, both when
+//! exporting and before deriving the key when importing.
+//!
+//! A [`KeyBundle`] holds labeled keypairs in memory and exports all of them as
+//! a single bundle. Hardware backed keypairs can not be exported and are
+//! rejected.
+use crate::{
+    passphrase::Container,
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use std::io::{self, Read};
+
+pub use crate::passphrase::{KdfParams, MAX_COST_FACTOR};
+
+/// Identifies the start of a key bundle
+const BUNDLE_MAGIC: &[u8] = b"helium-key-bundle";
+/// The current version of the bundle format
+pub const BUNDLE_VERSION: u8 = 1;
+const BUNDLE: Container = Container {
+    magic: BUNDLE_MAGIC,
+    version: BUNDLE_VERSION,
+    context_length: 0,
+    name: "key bundle",
+};
+
+/// A keypair stored in a key bundle along with its metadata
+#[derive(Debug, PartialEq)]
+pub struct BundleEntry {
+    pub label: String,
+    /// Seconds since the unix epoch at which the key was created
+    pub created_at: u64,
+    pub keypair: Keypair,
+}
+
+impl BundleEntry {
+    pub fn new(label: &str, created_at: u64, keypair: Keypair) -> Self {
+        Self {
+            label: label.to_string(),
+            created_at,
+            keypair,
+        }
+    }
+}
+
+/// Labeled keypairs that are backed up and restored together as a bundle
+#[derive(Debug, Default, PartialEq)]
+pub struct KeyBundle {
+    entries: Vec<BundleEntry>,
+}
+
+impl KeyBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the given entry, returning the entry previously stored under the
+    /// same label, if any
+    pub fn insert(&mut self, entry: BundleEntry) -> Option<BundleEntry> {
+        let previous = self.remove(&entry.label);
+        self.entries.push(entry);
+        previous
+    }
+
+    pub fn get(&self, label: &str) -> Option<&BundleEntry> {
+        self.entries.iter().find(|entry| entry.label == label)
+    }
+
+    pub fn remove(&mut self, label: &str) -> Option<BundleEntry> {
+        let index = self.entries.iter().position(|entry| entry.label == label)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Returns the stored entries in the order they were inserted
+    pub fn entries(&self) -> &[BundleEntry] {
+        &self.entries
+    }
+
+    /// Export all stored entries as a bundle encrypted with the given
+    /// passphrase
+    pub fn export_bundle<R>(&self, passphrase: &[u8], csprng: &mut R) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        export_bundle(&self.entries, passphrase, csprng)
+    }
+
+    /// Import the entries of a bundle encrypted with the given passphrase. Of
+    /// entries with the same label, the last one in the bundle is kept.
+    pub fn import_bundle(bundle: &[u8], passphrase: &[u8]) -> Result<Self> {
+        let mut result = Self::new();
+        for entry in import_bundle(bundle, passphrase)? {
+            result.insert(entry);
+        }
+        Ok(result)
+    }
+}
+
+/// Export the given entries as a bundle encrypted with the given passphrase
+pub fn export_bundle<R>(
+    entries: &[BundleEntry],
+    passphrase: &[u8],
+    csprng: &mut R,
+) -> Result<Vec<u8>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    export_bundle_with_params(entries, passphrase, KdfParams::default(), csprng)
+}
+
+/// Like [`export_bundle`] but with the given KDF parameters
+pub fn export_bundle_with_params<R>(
+    entries: &[BundleEntry],
+    passphrase: &[u8],
+    params: KdfParams,
+    csprng: &mut R,
+) -> Result<Vec<u8>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let mut plaintext = vec![];
+    let count = u32::try_from(entries.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many entries"))?;
+    plaintext.extend_from_slice(&count.to_be_bytes());
+    for entry in entries {
+        match &entry.keypair {
+            Keypair::Ed25519(_) | Keypair::EccCompact(_) => (),
+            #[cfg(feature = "secp256k1")]
+            Keypair::Secp256k1(_) => (),
+            #[cfg(feature = "bls")]
+            Keypair::Bls(_) => (),
+            #[cfg(feature = "x25519")]
+            Keypair::X25519(_) => (),
+            #[cfg(feature = "ed448")]
+            Keypair::Ed448(_) => (),
+            #[cfg(feature = "sr25519")]
+            Keypair::Sr25519(_) => (),
+            #[cfg(feature = "p384")]
+            Keypair::EccP384(_) => (),
+            #[cfg(feature = "pqc")]
+            Keypair::Dilithium(_) => (),
+            #[cfg(feature = "pqc")]
+            Keypair::Hybrid(_) => (),
+            #[cfg(feature = "pqc")]
+            Keypair::Kyber(_) => (),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::not_permitted()),
+        }
+        write_u32_prefixed(&mut plaintext, entry.label.as_bytes())?;
+        plaintext.extend_from_slice(&entry.created_at.to_be_bytes());
+        write_u32_prefixed(&mut plaintext, &entry.keypair.to_vec())?;
+    }
+    BUNDLE.seal(&[], params, passphrase, &plaintext, csprng)
+}
+
+/// Import the entries of a bundle encrypted with the given passphrase. Fails
+/// if the passphrase is wrong or the bundle was tampered with.
+pub fn import_bundle(bundle: &[u8], passphrase: &[u8]) -> Result<Vec<BundleEntry>> {
+    let (_, plaintext) = BUNDLE.open(bundle, passphrase)?;
+    let mut input = io::Cursor::new(&plaintext[..]);
+    let mut count = [0u8; 4];
+    input.read_exact(&mut count)?;
+    let mut entries = vec![];
+    for _ in 0..u32::from_be_bytes(count) {
+        let label = String::from_utf8(read_u32_prefixed(&mut input)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut created_at = [0u8; 8];
+        input.read_exact(&mut created_at)?;
+        let keypair = Keypair::try_from(&read_u32_prefixed(&mut input)?[..])?;
+        entries.push(BundleEntry {
+            label,
+            created_at: u64::from_be_bytes(created_at),
+            keypair,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    // Keep tests fast, the KDF cost does not affect correctness
+    const PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn entries() -> Vec<BundleEntry> {
+        vec![
+            BundleEntry::new(
+                "ed25519",
+                1000,
+                Keypair::generate(KeyTag::default(), &mut OsRng),
+            ),
+            BundleEntry::new(
+                "ecc_compact",
+                2000,
+                Keypair::generate(
+                    KeyTag {
+                        network: Network::TestNet,
+                        key_type: KeyType::EccCompact,
+                    },
+                    &mut OsRng,
+                ),
+            ),
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let entries = entries();
+        let bundle =
+            export_bundle_with_params(&entries, b"passphrase", PARAMS, &mut OsRng).expect("export");
+        assert_eq!(
+            entries,
+            import_bundle(&bundle, b"passphrase").expect("import")
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let bundle = export_bundle_with_params(&entries(), b"passphrase", PARAMS, &mut OsRng)
+            .expect("export");
+        assert!(matches!(
+            import_bundle(&bundle, b"wrong passphrase"),
+            Err(Error::Encryption)
+        ));
+    }
+
+    #[test]
+    fn tampered() {
+        let bundle = export_bundle_with_params(&entries(), b"passphrase", PARAMS, &mut OsRng)
+            .expect("export");
+        // Flipping a bit in the header or the ciphertext is detected
+        for index in [BUNDLE_MAGIC.len() + 5, bundle.len() - 1] {
+            let mut tampered = bundle.clone();
+            tampered[index] ^= 1;
+            assert!(import_bundle(&tampered, b"passphrase").is_err());
+        }
+        let mut versioned = bundle;
+        versioned[BUNDLE_MAGIC.len()] = BUNDLE_VERSION + 1;
+        assert!(matches!(
+            import_bundle(&versioned, b"passphrase"),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn params() {
+        let params = KdfParams {
+            m_cost: 64 * 1024 * MAX_COST_FACTOR + 1,
+            ..KdfParams::default()
+        };
+        assert!(export_bundle_with_params(&entries(), b"passphrase", params, &mut OsRng).is_err());
+        // Excessive KDF parameters are rejected before deriving a key
+        let bundle = export_bundle_with_params(&entries(), b"passphrase", PARAMS, &mut OsRng)
+            .expect("export");
+        let mut tampered = bundle;
+        tampered[BUNDLE_MAGIC.len() + 1..BUNDLE_MAGIC.len() + 5]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            import_bundle(&tampered, b"passphrase"),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn key_bundle() {
+        let mut key_bundle = KeyBundle::new();
+        for entry in entries() {
+            assert!(key_bundle.insert(entry).is_none());
+        }
+        let replaced = key_bundle
+            .insert(BundleEntry::new(
+                "ed25519",
+                3000,
+                Keypair::generate(KeyTag::default(), &mut OsRng),
+            ))
+            .expect("replaced");
+        assert_eq!(1000, replaced.created_at);
+        assert_eq!(2, key_bundle.entries().len());
+
+        let bundle =
+            export_bundle_with_params(key_bundle.entries(), b"passphrase", PARAMS, &mut OsRng)
+                .expect("export");
+        let imported = KeyBundle::import_bundle(&bundle, b"passphrase").expect("import");
+        assert_eq!(key_bundle, imported);
+        assert_eq!(3000, imported.get("ed25519").expect("entry").created_at);
+        assert!(imported.get("missing").is_none());
+    }
+}
@@ -0,0 +1,255 @@
+//! Bech32 and bech32m encodings of public keys.
+//!
+//! Public keys encode as the [bech32][BIP173] or [bech32m][BIP350] string of
+//! their binary form, including the key tag, under a human readable prefix
+//! chosen by the application, like `helium1...`. The checksum catches any
+//! mistyped or swapped characters, and the encoding is case insensitive, which
+//! makes keys easy to read out loud or over the phone. Bech32m is the default
+//! since it fixes a weakness of the bech32 checksum, but either variant is
+//! accepted when parsing.
+//!
+//! Key encodings are longer than the 90 characters the checksum is designed
+//! for, so the checksum guarantees of the specifications do not strictly
+//! apply, but errors are still detected with very high probability.
+//!
+//! [BIP173]: https://github.com/bitcoin/bips/blob/master/bip-0173.mediawiki
+//! [BIP350]: https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki
+use crate::*;
+
+/// The bech32 alphabet, indexed by 5 bit value
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Separates the human readable prefix from the data
+const SEPARATOR: char = '1';
+const CHECKSUM_LENGTH: usize = 6;
+const MAX_HRP_LENGTH: usize = 83;
+
+/// The checksum variants of the encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// The original BIP-173 checksum
+    Bech32,
+    /// The BIP-350 checksum
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Self::Bech32 => 1,
+            Self::Bech32m => 0x2bc8_30a3,
+        }
+    }
+}
+
+impl PublicKey {
+    /// Returns the bech32m encoding of the public key with the given human
+    /// readable prefix
+    pub fn to_bech32(&self, hrp: &str) -> Result<String> {
+        self.to_bech32_with_variant(hrp, Variant::Bech32m)
+    }
+
+    /// Like [`PublicKey::to_bech32`] but with the given checksum variant
+    pub fn to_bech32_with_variant(&self, hrp: &str, variant: Variant) -> Result<String> {
+        let hrp = hrp.to_ascii_lowercase();
+        if !valid_hrp(&hrp) {
+            return Err(Error::invalid_bech32());
+        }
+        let data = convert_bits(&self.to_vec(), 8, 5, true).ok_or_else(Error::invalid_bech32)?;
+        let mut result = hrp.clone();
+        result.push(SEPARATOR);
+        result.extend(
+            data.iter()
+                .chain(&checksum(&hrp, &data, variant))
+                .map(|value| CHARSET[*value as usize] as char),
+        );
+        Ok(result)
+    }
+
+    /// Parse a public key from its bech32 or bech32m encoding with the given
+    /// human readable prefix. The encoding may be all upper or all lower
+    /// case.
+    pub fn from_bech32(hrp: &str, value: &str) -> Result<PublicKey> {
+        let (decoded_hrp, data, _) = decode(value)?;
+        if decoded_hrp != hrp.to_ascii_lowercase() {
+            return Err(Error::invalid_bech32());
+        }
+        let bytes = convert_bits(&data, 5, 8, false).ok_or_else(Error::invalid_bech32)?;
+        PublicKey::try_from(&bytes[..])
+    }
+}
+
+/// Decodes the given string into its lower case human readable prefix, 5 bit
+/// data and checksum variant
+fn decode(value: &str) -> Result<(String, Vec<u8>, Variant)> {
+    if !value.bytes().all(|b| (33..=126).contains(&b))
+        || (value.bytes().any(|b| b.is_ascii_lowercase())
+            && value.bytes().any(|b| b.is_ascii_uppercase()))
+    {
+        return Err(Error::invalid_bech32());
+    }
+    let value = value.to_ascii_lowercase();
+    let (hrp, data) = value
+        .rsplit_once(SEPARATOR)
+        .ok_or_else(Error::invalid_bech32)?;
+    if !valid_hrp(hrp) || data.len() < CHECKSUM_LENGTH {
+        return Err(Error::invalid_bech32());
+    }
+    let data = data
+        .bytes()
+        .map(|c| CHARSET.iter().position(|v| *v == c).map(|v| v as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(Error::invalid_bech32)?;
+    let residue = polymod(hrp_expand(hrp).chain(data.iter().copied()));
+    let variant = [Variant::Bech32, Variant::Bech32m]
+        .iter()
+        .copied()
+        .find(|variant| variant.constant() == residue)
+        .ok_or_else(Error::invalid_bech32)?;
+    let data_length = data.len() - CHECKSUM_LENGTH;
+    Ok((hrp.to_string(), data[..data_length].to_vec(), variant))
+}
+
+fn valid_hrp(hrp: &str) -> bool {
+    (1..=MAX_HRP_LENGTH).contains(&hrp.len()) && hrp.bytes().all(|b| (33..=126).contains(&b))
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|b| b & 0x1f))
+}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    values.fold(1, |checksum, value| {
+        let top = checksum >> 25;
+        GENERATOR
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(
+                ((checksum & 0x1ff_ffff) << 5) ^ value as u32,
+                |checksum, (_, generator)| checksum ^ generator,
+            )
+    })
+}
+
+fn checksum(hrp: &str, data: &[u8], variant: Variant) -> [u8; CHECKSUM_LENGTH] {
+    let values = hrp_expand(hrp)
+        .chain(data.iter().copied())
+        .chain([0; CHECKSUM_LENGTH]);
+    let residue = polymod(values) ^ variant.constant();
+    let mut result = [0u8; CHECKSUM_LENGTH];
+    for (i, value) in result.iter_mut().enumerate() {
+        *value = ((residue >> (5 * (CHECKSUM_LENGTH - 1 - i))) & 0x1f) as u8;
+    }
+    result
+}
+
+/// Regroups the given values of `from` bits into values of `to` bits. Without
+/// padding, left over bits must be zero and fewer than `from`.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max = (1u32 << to) - 1;
+    let mut result = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for value in data {
+        acc = (acc << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn checksum_vectors() {
+        // Valid strings of BIP-173 and BIP-350
+        for (value, hrp, variant) in [
+            ("A12UEL5L", "a", Variant::Bech32),
+            (
+                "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+                "abcdef",
+                Variant::Bech32,
+            ),
+            ("A1LQFN3A", "a", Variant::Bech32m),
+            (
+                "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+                "abcdef",
+                Variant::Bech32m,
+            ),
+        ] {
+            let (decoded_hrp, _, decoded_variant) = decode(value).expect("valid");
+            assert_eq!(hrp, decoded_hrp);
+            assert_eq!(variant, decoded_variant);
+        }
+        // Mixed case, a missing separator, an invalid checksum and an empty
+        // human readable prefix
+        for value in ["A12uEL5L", "pzry9x0s0muk", "a12uel5m", "1qzzfhee"] {
+            assert!(decode(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let public_key = Keypair::generate(
+                KeyTag {
+                    network: Network::TestNet,
+                    key_type,
+                },
+                &mut OsRng,
+            )
+            .public_key()
+            .clone();
+            for variant in [Variant::Bech32, Variant::Bech32m] {
+                let encoded = public_key
+                    .to_bech32_with_variant("helium", variant)
+                    .expect("bech32");
+                assert!(encoded.starts_with("helium1"));
+                assert_eq!(
+                    public_key,
+                    PublicKey::from_bech32("helium", &encoded).expect("public key")
+                );
+                assert_eq!(
+                    public_key,
+                    PublicKey::from_bech32("HELIUM", &encoded.to_uppercase()).expect("public key")
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn errors_detected() {
+        let public_key = Keypair::generate(KeyTag::default(), &mut OsRng)
+            .public_key()
+            .clone();
+        let encoded = public_key.to_bech32("helium").expect("bech32");
+        assert!(PublicKey::from_bech32("other", &encoded).is_err());
+        // A single mistyped character
+        let mut mistyped = encoded.into_bytes();
+        let last = mistyped.len() - 10;
+        mistyped[last] = if mistyped[last] == b'q' { b'p' } else { b'q' };
+        let mistyped = String::from_utf8(mistyped).expect("string");
+        assert!(matches!(
+            PublicKey::from_bech32("helium", &mistyped),
+            Err(Error::Decode(_))
+        ));
+        assert!(public_key.to_bech32("").is_err());
+    }
+}
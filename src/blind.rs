@@ -0,0 +1,157 @@
+//! Experimental blind signatures over ed25519 keys.
+//!
+//! This implements the classic three move blind Schnorr protocol, arranged so
+//! that the unblinded result is a standard ed25519 signature which verifies
+//! with [`PublicKey::verify`]:
+//!
+//! 1. The signer creates a [`SignerSession`] and sends its [`Commitment`] `R =
+//!    kG` to the requester.
+//! 2. The requester picks blinding factors `a` and `b`, computes `R' = R + aG +
+//!    bA` and `c' = H(R' || A || msg)`, and sends the [`BlindedChallenge`] `c =
+//!    c' + b` to the signer.
+//! 3. The signer responds with the [`BlindSignature`] `s = k + cx`.
+//! 4. The requester unblinds to the signature `(R', s + a)`.
+//!
+//! The signer learns neither the message nor the final signature.
+//!
+//! NOTE: Blind Schnorr signatures are vulnerable to forgery when a signer runs
+//! many sessions concurrently (the ROS attack). Signers must complete or abort
+//! a session before starting the next one. This module is experimental and
+//! gated behind the `blind` feature.
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, public_key_point, random_scalar},
+    *,
+};
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar};
+
+/// The signer commitment `R` sent to the requester
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Commitment(pub [u8; 32]);
+
+/// The blinded challenge `c` sent to the signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlindedChallenge(pub [u8; 32]);
+
+/// The blind signature `s` returned to the requester
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlindSignature(pub [u8; 32]);
+
+/// The signer side of a blind signing session. A session can only be used to
+/// sign once.
+pub struct SignerSession {
+    nonce: Scalar,
+    commitment: Commitment,
+}
+
+/// The requester side of a blind signing session.
+pub struct Requester {
+    alpha: Scalar,
+    blinded_commitment: EdwardsPoint,
+}
+
+impl SignerSession {
+    /// Start a new session, generating a fresh nonce
+    pub fn new<R>(csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let nonce = random_scalar(csprng);
+        let commitment = Commitment(base_mul(&nonce).compress().to_bytes());
+        Self { nonce, commitment }
+    }
+
+    /// The commitment to send to the requester
+    pub fn commitment(&self) -> Commitment {
+        self.commitment
+    }
+
+    /// Sign the given blinded challenge with the given keypair, consuming the
+    /// session.
+    pub fn sign(self, keypair: &ed25519::Keypair, challenge: &BlindedChallenge) -> BlindSignature {
+        let challenge = Scalar::from_bytes_mod_order(challenge.0);
+        let s = self.nonce + challenge * keypair.secret_scalar();
+        BlindSignature(s.to_bytes())
+    }
+}
+
+impl Requester {
+    /// Blind the given message for the given ed25519 public key and signer
+    /// commitment. Returns the requester state and the challenge to send to
+    /// the signer.
+    pub fn blind<R>(
+        public_key: &PublicKey,
+        commitment: &Commitment,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<(Self, BlindedChallenge)>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let public_point = public_key_point(public_key)?;
+        let commitment = decompress(&commitment.0)?;
+        let alpha = random_scalar(csprng);
+        let beta = random_scalar(csprng);
+
+        let blinded_commitment = commitment + base_mul(&alpha) + beta * public_point;
+        let challenge = hash_to_scalar(&[
+            &blinded_commitment.compress().as_bytes()[..],
+            &public_point.compress().as_bytes()[..],
+            msg,
+        ]);
+        Ok((
+            Self {
+                alpha,
+                blinded_commitment,
+            },
+            BlindedChallenge((challenge + beta).to_bytes()),
+        ))
+    }
+
+    /// Unblind the signature returned by the signer into a standard ed25519
+    /// signature over the message
+    pub fn unblind(self, blind_signature: &BlindSignature) -> Result<Vec<u8>> {
+        let s = Scalar::from_canonical_bytes(blind_signature.0)
+            .ok_or_else(|| Error::from(signature::Error::new()))?;
+        let mut result = self.blinded_commitment.compress().to_bytes().to_vec();
+        result.extend_from_slice((s + self.alpha).as_bytes());
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn blind_sign() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        const MSG: &[u8] = b"voucher 42";
+
+        let session = SignerSession::new(&mut OsRng);
+        let (requester, challenge) =
+            Requester::blind(&keypair.public_key, &session.commitment(), MSG, &mut OsRng)
+                .expect("blind");
+        let blind_signature = session.sign(&keypair, &challenge);
+        let signature = requester.unblind(&blind_signature).expect("unblind");
+
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"voucher 43", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn wrong_key_type() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let session = SignerSession::new(&mut OsRng);
+        assert!(Requester::blind(
+            &keypair.public_key,
+            &session.commitment(),
+            b"voucher",
+            &mut OsRng
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! BLS signatures over BLS12-381 with signature aggregation.
+//!
+//! Public keys are points in G1 and signatures points in G2, both in their
+//! compressed form, as used by Ethereum. Messages are hashed to G2 following
+//! the proof of possession ciphersuite of the IETF BLS signature draft.
+//!
+//! Signatures by many keys over the same message can be combined with
+//! [`aggregate_signatures`] into a single signature, which is checked against
+//! all of the keys at once with [`verify_aggregate`]. Aggregating keys is
+//! only safe once every key has proven possession of its secret with
+//! [`Keypair::prove_possession`], which must be checked with
+//! [`verify_possession`] before a key is accepted. Otherwise an attacker can
+//! pick a key that cancels out the keys of others.
+use crate::*;
+use ::bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    pairing, G1Affine, G1Projective, G2Affine, G2Projective, Scalar,
+};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+/// The domain separation tag for message signatures
+const BLS_SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// The domain separation tag for proofs of possession
+const BLS_POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey(pub(crate) G1Affine);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: Scalar,
+}
+
+pub const KEYPAIR_LENGTH: usize = 33;
+pub const PUBLIC_KEY_LENGTH: usize = 49;
+pub const SIGNATURE_LENGTH: usize = 96;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        Ok(sign_with(&self.secret, msg, BLS_SIGNATURE_DST))
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        Self::generate_from_entropy(network, &input[1..usize::min(input.len(), KEYPAIR_LENGTH)])
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.secret.to_bytes())
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut wide = [0u8; 64];
+        let mut secret = Scalar::zero();
+        while secret == Scalar::zero() {
+            csprng.fill_bytes(&mut wide);
+            secret = Scalar::from_bytes_wide(&wide);
+        }
+        Self::from_secret(network, secret)
+    }
+
+    /// Construct a keypair from the little endian encoding of a non-zero
+    /// secret scalar
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let bytes = <[u8; 32]>::try_from(entropy).map_err(|_| Error::invalid_curve())?;
+        let secret: Option<Scalar> = Scalar::from_bytes(&bytes).into();
+        match secret {
+            Some(secret) if secret != Scalar::zero() => Ok(Self::from_secret(network, secret)),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    fn from_secret(network: Network, secret: Scalar) -> Self {
+        let public_key = G1Affine::from(G1Projective::generator() * secret);
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(public_key)),
+            secret,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Bls,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.to_bytes().to_vec()
+    }
+
+    /// Prove possession of the secret key by signing the 48 byte compressed
+    /// public key, without its key tag. Proofs are checked with
+    /// [`verify_possession`].
+    pub fn prove_possession(&self) -> Vec<u8> {
+        // Unwrap ok here since the public key of a BLS keypair is a BLS public
+        // key
+        let public_key: &PublicKey = (&self.public_key).try_into().unwrap();
+        sign_with(&self.secret, &public_key.0.to_compressed(), BLS_POP_DST)
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        verify_with(&self.0, msg, signature, BLS_SIGNATURE_DST)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        let point: Option<G1Affine> = G1Affine::from_compressed(&buf).into();
+        match point {
+            Some(point) if !bool::from(point.is_identity()) => Ok(PublicKey(point)),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.0.to_compressed())
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0.to_compressed())
+    }
+}
+
+/// Combine the given signatures into a single signature
+pub fn aggregate_signatures(signatures: &[&[u8]]) -> Result<Vec<u8>> {
+    if signatures.is_empty() {
+        return Err(signature::Error::new().into());
+    }
+    let mut aggregate = G2Projective::identity();
+    for sig in signatures {
+        aggregate += parse_signature(sig)?;
+    }
+    Ok(G2Affine::from(aggregate).to_compressed().to_vec())
+}
+
+/// Combine the given BLS public keys into a single public key, which
+/// verifies the aggregate of signatures over the same message by all of the
+/// keys. All keys must be on the same network.
+pub fn aggregate_public_keys(
+    public_keys: &[public_key::PublicKey],
+) -> Result<public_key::PublicKey> {
+    let network = public_keys
+        .first()
+        .map(|public_key| public_key.network)
+        .ok_or_else(Error::invalid_curve)?;
+    let mut aggregate = G1Projective::identity();
+    for public_key in public_keys {
+        if public_key.network != network {
+            return Err(Error::invalid_network());
+        }
+        let public_key: &PublicKey = public_key.try_into()?;
+        aggregate += public_key.0;
+    }
+    let aggregate = G1Affine::from(aggregate);
+    if bool::from(aggregate.is_identity()) {
+        return Err(Error::invalid_curve());
+    }
+    Ok(public_key::PublicKey::for_network(
+        network,
+        PublicKey(aggregate),
+    ))
+}
+
+/// Verify an aggregate signature over the given message by all of the given
+/// public keys. Every key must have had its proof of possession verified.
+pub fn verify_aggregate(
+    public_keys: &[public_key::PublicKey],
+    msg: &[u8],
+    signature: &[u8],
+) -> Result {
+    aggregate_public_keys(public_keys)?.verify(msg, signature)
+}
+
+/// Verify a proof of possession made with [`Keypair::prove_possession`]
+pub fn verify_possession(public_key: &public_key::PublicKey, proof: &[u8]) -> Result {
+    let key: &PublicKey = public_key.try_into()?;
+    verify_with(&key.0, &key.0.to_compressed(), proof, BLS_POP_DST)
+}
+
+fn hash_to_g2(msg: &[u8], dst: &[u8]) -> G2Affine {
+    G2Affine::from(
+        <G2Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(msg, dst),
+    )
+}
+
+fn sign_with(secret: &Scalar, msg: &[u8], dst: &[u8]) -> Vec<u8> {
+    G2Affine::from(hash_to_g2(msg, dst) * secret)
+        .to_compressed()
+        .to_vec()
+}
+
+fn verify_with(public_key: &G1Affine, msg: &[u8], sig: &[u8], dst: &[u8]) -> Result {
+    let point = G2Affine::from(parse_signature(sig)?);
+    if pairing(public_key, &hash_to_g2(msg, dst)) != pairing(&G1Affine::generator(), &point) {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+fn parse_signature(sig: &[u8]) -> Result<G2Projective> {
+    let bytes = <[u8; SIGNATURE_LENGTH]>::try_from(sig).map_err(|_| signature::Error::new())?;
+    let point: Option<G2Affine> = G2Affine::from_compressed(&bytes).into();
+    point
+        .map(G2Projective::from)
+        .ok_or_else(|| signature::Error::new().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn keypair() -> Keypair {
+        Keypair::generate(Network::MainNet, &mut OsRng)
+    }
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = keypair();
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(SIGNATURE_LENGTH, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = keypair();
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_vec()[..]).expect("keypair")
+        );
+        let public_key = &keypair.public_key;
+        assert_eq!(PUBLIC_KEY_LENGTH, public_key.to_vec().len());
+        let decoded: public_key::PublicKey = public_key.to_string().parse().expect("b58");
+        assert_eq!(public_key, &decoded);
+    }
+
+    #[test]
+    fn aggregate() {
+        let keypairs: Vec<Keypair> = (0..3).map(|_| keypair()).collect();
+        let public_keys: Vec<public_key::PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect();
+        let signatures: Vec<Vec<u8>> = keypairs
+            .iter()
+            .map(|keypair| keypair.sign(b"attestation").expect("signature"))
+            .collect();
+        let signatures: Vec<&[u8]> = signatures.iter().map(Vec::as_slice).collect();
+        let aggregate = aggregate_signatures(&signatures).expect("aggregate");
+        assert!(verify_aggregate(&public_keys, b"attestation", &aggregate).is_ok());
+        assert!(verify_aggregate(&public_keys[1..], b"attestation", &aggregate).is_err());
+        assert!(verify_aggregate(&public_keys, b"other", &aggregate).is_err());
+        assert!(verify_aggregate(&[], b"attestation", &aggregate).is_err());
+    }
+
+    #[test]
+    fn possession() {
+        let (keypair, other) = (keypair(), keypair());
+        let proof = keypair.prove_possession();
+        assert!(verify_possession(&keypair.public_key, &proof).is_ok());
+        assert!(verify_possession(&other.public_key, &proof).is_err());
+        // A proof of possession is not a signature over the public key
+        assert!(keypair
+            .public_key
+            .verify(&keypair.public_key.to_vec(), &proof)
+            .is_err());
+    }
+
+    #[test]
+    fn possession_network_independent() {
+        // Proofs cover the raw public key, so they do not depend on the
+        // network in the key tag
+        let entropy = keypair().secret_to_vec();
+        let mainnet = Keypair::generate_from_entropy(Network::MainNet, &entropy).expect("mainnet");
+        let testnet = Keypair::generate_from_entropy(Network::TestNet, &entropy).expect("testnet");
+        let proof = mainnet.prove_possession();
+        assert_eq!(proof, testnet.prove_possession());
+        assert!(verify_possession(&testnet.public_key, &proof).is_ok());
+    }
+}
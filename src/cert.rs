@@ -0,0 +1,691 @@
+//! Self-signed X.509 certificates and PKCS#10 certificate signing requests
+//! for keys, for use as device certificates in mutual TLS.
+//!
+//! The subject and issuer of a certificate are the common name made of the
+//! base58 encoding of the public key. Certificates carry a key usage limited
+//! to digital signatures, mark the key as not being a certificate authority,
+//! and carry any given subject alternative names.
+//!
+//! Certificate signing requests let a device enroll with a certificate
+//! authority without exporting its secret key. Their subject and extensions
+//! are configurable, and default to the same subject as a self-signed
+//! certificate.
+//!
+//! Certificates and requests are signed through the [`Sign`] trait, so
+//! hardware backed keypairs, like ECC608 and TPM ones, can sign them. Ed25519 keys sign with `Ed25519`
+//! and ecc_compact and secp256k1 keys with `ecdsa-with-SHA256`, following
+//! [RFC 8410][RFC8410] and [RFC 5758][RFC5758].
+//!
+//! [RFC8410]: https://www.rfc-editor.org/rfc/rfc8410
+//! [RFC5758]: https://www.rfc-editor.org/rfc/rfc5758
+use crate::{validity::Validity, *};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+#[cfg(feature = "secp256k1")]
+const OID_SECP256K1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x0a];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_SERIAL_NUMBER: &[u8] = &[0x55, 0x04, 0x05];
+const OID_COUNTRY: &[u8] = &[0x55, 0x04, 0x06];
+const OID_LOCALITY: &[u8] = &[0x55, 0x04, 0x07];
+const OID_STATE: &[u8] = &[0x55, 0x04, 0x08];
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+const OID_ORGANIZATIONAL_UNIT: &[u8] = &[0x55, 0x04, 0x0b];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_ATTRIBUTES: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+const TAG_DNS_NAME: u8 = 0x82;
+const TAG_URI: u8 = 0x86;
+const TAG_IP_ADDRESS: u8 = 0x87;
+
+const CERTIFICATE_LABEL: &str = "CERTIFICATE";
+const CSR_LABEL: &str = "CERTIFICATE REQUEST";
+
+/// A subject alternative name of a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+    Uri(String),
+}
+
+/// An attribute of a distinguished name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameAttribute {
+    CommonName,
+    SerialNumber,
+    /// The two letter ISO 3166 country code
+    Country,
+    Locality,
+    State,
+    Organization,
+    OrganizationalUnit,
+}
+
+impl NameAttribute {
+    fn oid(&self) -> &'static [u8] {
+        match self {
+            Self::CommonName => OID_COMMON_NAME,
+            Self::SerialNumber => OID_SERIAL_NUMBER,
+            Self::Country => OID_COUNTRY,
+            Self::Locality => OID_LOCALITY,
+            Self::State => OID_STATE,
+            Self::Organization => OID_ORGANIZATION,
+            Self::OrganizationalUnit => OID_ORGANIZATIONAL_UNIT,
+        }
+    }
+
+    /// Country codes and serial numbers are printable strings, the rest utf8
+    /// strings
+    fn string_tag(&self) -> u8 {
+        match self {
+            Self::Country | Self::SerialNumber => TAG_PRINTABLE_STRING,
+            _ => TAG_UTF8_STRING,
+        }
+    }
+}
+
+/// A certificate extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    /// The object identifier of the extension in dotted decimal form, like
+    /// `2.5.29.37`
+    pub oid: String,
+    pub critical: bool,
+    /// The DER encoded value of the extension
+    pub value: Vec<u8>,
+}
+
+impl Extension {
+    pub fn new(oid: &str, critical: bool, value: &[u8]) -> Self {
+        Self {
+            oid: oid.to_string(),
+            critical,
+            value: value.to_vec(),
+        }
+    }
+}
+
+/// The parameters of a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertParams {
+    /// The window in which the certificate is valid
+    pub validity: Validity,
+    pub subject_alt_names: Vec<SubjectAltName>,
+    /// The serial number of the certificate. When not given the serial
+    /// number is derived from the public key and the validity window.
+    pub serial_number: Option<u64>,
+}
+
+impl CertParams {
+    pub fn new(validity: Validity) -> Self {
+        Self {
+            validity,
+            subject_alt_names: vec![],
+            serial_number: None,
+        }
+    }
+
+    /// Add the given subject alternative name
+    pub fn with_subject_alt_name(mut self, name: SubjectAltName) -> Self {
+        self.subject_alt_names.push(name);
+        self
+    }
+}
+
+/// The parameters of a certificate signing request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsrParams {
+    /// The attributes of the subject name, in order. When empty the subject
+    /// is the common name made of the base58 public key.
+    pub subject: Vec<(NameAttribute, String)>,
+    pub subject_alt_names: Vec<SubjectAltName>,
+    /// Additional extensions to request
+    pub extensions: Vec<Extension>,
+}
+
+impl CsrParams {
+    /// Add the given attribute to the subject name
+    pub fn with_subject(mut self, attribute: NameAttribute, value: &str) -> Self {
+        self.subject.push((attribute, value.to_string()));
+        self
+    }
+
+    /// Add the given subject alternative name
+    pub fn with_subject_alt_name(mut self, name: SubjectAltName) -> Self {
+        self.subject_alt_names.push(name);
+        self
+    }
+
+    /// Add the given extension
+    pub fn with_extension(mut self, extension: Extension) -> Self {
+        self.extensions.push(extension);
+        self
+    }
+}
+
+/// Returns the DER encoding of a self-signed certificate for the given
+/// public key, signed by the given signer, which is expected to hold the
+/// secret key of the public key.
+pub fn self_signed<S: Sign + ?Sized>(
+    signer: &S,
+    public_key: &PublicKey,
+    params: &CertParams,
+) -> Result<Vec<u8>> {
+    let (algorithm, public_key_info) = key_info(public_key)?;
+    let name = key_name(public_key);
+    let serial_number = params
+        .serial_number
+        .unwrap_or_else(|| default_serial_number(public_key, &params.validity));
+    let validity = sequence(&[
+        &time(params.validity.not_before)?,
+        &time(params.validity.not_after)?,
+    ]);
+
+    let mut extensions = vec![
+        extension(OID_BASIC_CONSTRAINTS, true, &sequence(&[])),
+        // The digitalSignature bit, with the 7 unused bits of its byte
+        extension(OID_KEY_USAGE, true, &tlv(TAG_BIT_STRING, &[0x07, 0x80])),
+    ];
+    extensions.extend(subject_alt_names_extension(&params.subject_alt_names));
+    let extensions = sequence_of(&extensions);
+
+    let tbs_certificate = sequence(&[
+        // Version 3, encoded as 2
+        &tlv(TAG_VERSION, &tlv(TAG_INTEGER, &[0x02])),
+        &integer(&serial_number.to_be_bytes()),
+        &algorithm,
+        &name,
+        &validity,
+        &name,
+        &public_key_info,
+        &tlv(TAG_EXTENSIONS, &extensions),
+    ]);
+    let signature = signer.sign(&tbs_certificate)?;
+    Ok(sequence(&[
+        &tbs_certificate,
+        &algorithm,
+        &bit_string(&signature),
+    ]))
+}
+
+/// Returns the DER encoding of a PKCS#10 certificate signing request for the
+/// given public key, signed by the given signer, which is expected to hold
+/// the secret key of the public key.
+pub fn signing_request<S: Sign + ?Sized>(
+    signer: &S,
+    public_key: &PublicKey,
+    params: &CsrParams,
+) -> Result<Vec<u8>> {
+    let (algorithm, public_key_info) = key_info(public_key)?;
+    let name = if params.subject.is_empty() {
+        key_name(public_key)
+    } else {
+        let attributes = params
+            .subject
+            .iter()
+            .map(|(attribute, value)| {
+                set(&[&sequence(&[
+                    &tlv(TAG_OID, attribute.oid()),
+                    &tlv(attribute.string_tag(), value.as_bytes()),
+                ])])
+            })
+            .collect::<Vec<_>>();
+        sequence_of(&attributes)
+    };
+
+    let mut extensions: Vec<Vec<u8>> = subject_alt_names_extension(&params.subject_alt_names)
+        .into_iter()
+        .collect();
+    for custom in &params.extensions {
+        extensions.push(extension(
+            &oid(&custom.oid)?,
+            custom.critical,
+            &custom.value,
+        ));
+    }
+    let attributes = if extensions.is_empty() {
+        vec![]
+    } else {
+        sequence(&[
+            &tlv(TAG_OID, OID_EXTENSION_REQUEST),
+            &set(&[&sequence_of(&extensions)]),
+        ])
+    };
+
+    let request_info = sequence(&[
+        &tlv(TAG_INTEGER, &[0x00]),
+        &name,
+        &public_key_info,
+        &tlv(TAG_ATTRIBUTES, &attributes),
+    ]);
+    let signature = signer.sign(&request_info)?;
+    Ok(sequence(&[
+        &request_info,
+        &algorithm,
+        &bit_string(&signature),
+    ]))
+}
+
+/// Returns the PEM encoding of the given DER encoded certificate
+pub fn to_pem(der: &[u8]) -> String {
+    pem(CERTIFICATE_LABEL, der)
+}
+
+/// Returns the PEM encoding of the given DER encoded certificate signing
+/// request
+pub fn csr_to_pem(der: &[u8]) -> String {
+    pem(CSR_LABEL, der)
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let mut result = format!("-----BEGIN {}-----\n", label);
+    let encoded = base64::encode(der);
+    // Safe to unwrap since base64 output is ascii, and every chunk is valid
+    // utf8
+    for line in encoded.as_bytes().chunks(64) {
+        result.push_str(std::str::from_utf8(line).unwrap());
+        result.push('\n');
+    }
+    result.push_str(&format!("-----END {}-----\n", label));
+    result
+}
+
+/// Returns the name made of the common name of the base58 public key
+fn key_name(public_key: &PublicKey) -> Vec<u8> {
+    sequence(&[&set(&[&sequence(&[
+        &tlv(TAG_OID, OID_COMMON_NAME),
+        &tlv(TAG_UTF8_STRING, public_key.to_string().as_bytes()),
+    ])])])
+}
+
+/// Returns the subject alternative name extension for the given names, if
+/// there are any
+fn subject_alt_names_extension(names: &[SubjectAltName]) -> Option<Vec<u8>> {
+    if names.is_empty() {
+        return None;
+    }
+    let names: Vec<Vec<u8>> = names
+        .iter()
+        .map(|name| match name {
+            SubjectAltName::Dns(dns) => tlv(TAG_DNS_NAME, dns.as_bytes()),
+            SubjectAltName::Uri(uri) => tlv(TAG_URI, uri.as_bytes()),
+            SubjectAltName::Ip(IpAddr::V4(ip)) => tlv(TAG_IP_ADDRESS, &ip.octets()),
+            SubjectAltName::Ip(IpAddr::V6(ip)) => tlv(TAG_IP_ADDRESS, &ip.octets()),
+        })
+        .collect();
+    Some(extension(OID_SUBJECT_ALT_NAME, false, &sequence_of(&names)))
+}
+
+/// Returns the signature algorithm identifier and the SubjectPublicKeyInfo
+/// of the given public key
+fn key_info(public_key: &PublicKey) -> Result<(Vec<u8>, Vec<u8>)> {
+    let ecdsa = || sequence(&[&tlv(TAG_OID, OID_ECDSA_WITH_SHA256)]);
+    let ec_key_info = |curve: &[u8], point: &[u8]| {
+        sequence(&[
+            &sequence(&[&tlv(TAG_OID, OID_EC_PUBLIC_KEY), &tlv(TAG_OID, curve)]),
+            &bit_string(point),
+        ])
+    };
+    match public_key.key_type() {
+        KeyType::Ed25519 => {
+            let ed25519_key: &ed25519::PublicKey = public_key.try_into()?;
+            let algorithm = sequence(&[&tlv(TAG_OID, OID_ED25519)]);
+            let info = sequence(&[&algorithm, &bit_string(ed25519_key.as_ref())]);
+            Ok((algorithm, info))
+        }
+        KeyType::EccCompact => {
+            let ecc_key: &ecc_compact::PublicKey = public_key.try_into()?;
+            let point = ecc_key.0.to_encoded_point(false);
+            Ok((ecdsa(), ec_key_info(OID_PRIME256V1, point.as_bytes())))
+        }
+        #[cfg(feature = "secp256k1")]
+        KeyType::Secp256k1 => {
+            let secp_key: &secp256k1::PublicKey = public_key.try_into()?;
+            let point = secp_key.0.to_encoded_point(false);
+            Ok((ecdsa(), ec_key_info(OID_SECP256K1, point.as_bytes())))
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+fn default_serial_number(public_key: &PublicKey, validity: &Validity) -> u64 {
+    let digest = Sha256::new()
+        .chain_update(public_key.to_vec())
+        .chain_update(validity.not_before.to_be_bytes())
+        .chain_update(validity.not_after.to_be_bytes())
+        .finalize();
+    let mut serial_number = [0u8; 8];
+    serial_number.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(serial_number)
+}
+
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    if critical {
+        sequence(&[
+            &tlv(TAG_OID, oid),
+            &tlv(TAG_BOOLEAN, &[0xff]),
+            &tlv(TAG_OCTET_STRING, value),
+        ])
+    } else {
+        sequence(&[&tlv(TAG_OID, oid), &tlv(TAG_OCTET_STRING, value)])
+    }
+}
+
+/// Encodes the given seconds since the unix epoch as a UTCTime for the years
+/// 1950 through 2049, and as a GeneralizedTime otherwise
+fn time(timestamp: u64) -> Result<Vec<u8>> {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+    let (year, month, day) = civil_from_days(days);
+    let time = format!(
+        "{:02}{:02}{:02}{:02}{:02}Z",
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    match year {
+        1950..=2049 => Ok(tlv(
+            TAG_UTC_TIME,
+            format!("{:02}{}", year % 100, time).as_bytes(),
+        )),
+        2050..=9999 => Ok(tlv(
+            TAG_GENERALIZED_TIME,
+            format!("{:04}{}", year, time).as_bytes(),
+        )),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "certificate time out of range",
+        )
+        .into()),
+    }
+}
+
+/// Converts days since the unix epoch to a (year, month, day) date in the
+/// proleptic Gregorian calendar
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+pub(crate) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut result = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        result.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let skip = len_bytes.iter().take_while(|byte| **byte == 0).count();
+        result.push(0x80 | (len_bytes.len() - skip) as u8);
+        result.extend_from_slice(&len_bytes[skip..]);
+    }
+    result.extend_from_slice(content);
+    result
+}
+
+pub(crate) fn sequence(items: &[&[u8]]) -> Vec<u8> {
+    tlv(TAG_SEQUENCE, &items.concat())
+}
+
+fn sequence_of(items: &[Vec<u8>]) -> Vec<u8> {
+    sequence(&items.iter().map(Vec::as_slice).collect::<Vec<_>>())
+}
+
+pub(crate) fn set(items: &[&[u8]]) -> Vec<u8> {
+    tlv(TAG_SET, &items.concat())
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    tlv(TAG_BIT_STRING, &[&[0x00][..], bytes].concat())
+}
+
+/// Returns the content bytes of the DER encoding of the given dotted decimal
+/// object identifier
+fn oid(dotted: &str) -> Result<Vec<u8>> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid oid");
+    let arcs = dotted
+        .split('.')
+        .map(|arc| arc.parse::<u64>())
+        .collect::<std::result::Result<Vec<u64>, _>>()
+        .map_err(|_| invalid())?;
+    let (first, second) = match arcs[..] {
+        [first @ 0..=1, second @ 0..=39, ..] | [first @ 2, second, ..] => (first, second),
+        _ => return Err(invalid().into()),
+    };
+    let mut result = vec![];
+    let first_arc = first
+        .checked_mul(40)
+        .and_then(|arc| arc.checked_add(second))
+        .ok_or_else(invalid)?;
+    for arc in std::iter::once(first_arc).chain(arcs[2..].iter().copied()) {
+        let mut encoded = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            encoded.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        result.extend(encoded.iter().rev());
+    }
+    Ok(result)
+}
+
+/// Encodes the given big endian unsigned integer as a minimal positive DER
+/// integer
+pub(crate) fn integer(bytes: &[u8]) -> Vec<u8> {
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    let bytes = &bytes[skip.min(bytes.len() - 1)..];
+    if bytes[0] & 0x80 != 0 {
+        tlv(TAG_INTEGER, &[&[0x00][..], bytes].concat())
+    } else {
+        tlv(TAG_INTEGER, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+    use std::net::Ipv4Addr;
+
+    /// Splits the DER element at the start of the given bytes into its tag,
+    /// its content and the remaining bytes
+    fn read_tlv(bytes: &[u8]) -> (u8, &[u8], &[u8]) {
+        let (tag, len, header) = match bytes[1] {
+            len if len < 0x80 => (bytes[0], len as usize, 2),
+            0x81 => (bytes[0], bytes[2] as usize, 3),
+            0x82 => (
+                bytes[0],
+                u16::from_be_bytes([bytes[2], bytes[3]]) as usize,
+                4,
+            ),
+            _ => panic!("unsupported length"),
+        };
+        (tag, &bytes[header..header + len], &bytes[header + len..])
+    }
+
+    #[test]
+    fn self_signed_roundtrip() {
+        let params = CertParams::new(Validity::new(1_700_000_000, 1_800_000_000))
+            .with_subject_alt_name(SubjectAltName::Dns("hotspot.example.com".to_string()))
+            .with_subject_alt_name(SubjectAltName::Ip(Ipv4Addr::LOCALHOST.into()));
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::MainNet,
+                key_type,
+            };
+            let keypair = Keypair::generate(key_tag, &mut OsRng);
+            let public_key = keypair.public_key();
+            let der = self_signed(&keypair, public_key, &params).expect("certificate");
+
+            let (tag, certificate, rest) = read_tlv(&der);
+            assert_eq!((TAG_SEQUENCE, 0), (tag, rest.len()));
+            let (_, _, rest) = read_tlv(certificate);
+            let tbs_certificate = &certificate[..certificate.len() - rest.len()];
+            let (_, algorithm, rest) = read_tlv(rest);
+            let (tag, signature, rest) = read_tlv(rest);
+            assert_eq!((TAG_BIT_STRING, 0), (tag, rest.len()));
+            assert_eq!(0, signature[0]);
+            public_key
+                .verify(tbs_certificate, &signature[1..])
+                .expect("signature");
+
+            let (expected_algorithm, _) = key_info(public_key).expect("key info");
+            assert_eq!(&expected_algorithm[2..], algorithm);
+            // The subject name carries the base58 public key
+            let b58 = public_key.to_string();
+            assert!(tbs_certificate
+                .windows(b58.len())
+                .any(|window| window == b58.as_bytes()));
+            assert!(to_pem(&der).starts_with("-----BEGIN CERTIFICATE-----\n"));
+        }
+    }
+
+    #[test]
+    fn signing_request_roundtrip() {
+        let params = CsrParams::default()
+            .with_subject(NameAttribute::Country, "US")
+            .with_subject(NameAttribute::Organization, "Helium")
+            .with_subject(NameAttribute::CommonName, "hotspot")
+            .with_subject_alt_name(SubjectAltName::Uri("urn:helium:hotspot".to_string()))
+            // Extended key usage of TLS client authentication
+            .with_extension(Extension::new(
+                "2.5.29.37",
+                false,
+                &hex!("300a06082b06010505070302"),
+            ));
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let public_key = keypair.public_key();
+        let der = signing_request(&keypair, public_key, &params).expect("csr");
+
+        let (tag, request, rest) = read_tlv(&der);
+        assert_eq!((TAG_SEQUENCE, 0), (tag, rest.len()));
+        let (_, request_info, rest) = read_tlv(request);
+        let signed = &request[..request.len() - rest.len()];
+        let (_, _, rest) = read_tlv(rest);
+        let (_, signature, _) = read_tlv(rest);
+        public_key
+            .verify(signed, &signature[1..])
+            .expect("signature");
+
+        let (_, version, rest) = read_tlv(request_info);
+        assert_eq!(&[0x00], version);
+        let (_, name, rest) = read_tlv(rest);
+        assert!(name.windows(6).any(|window| window == b"Helium"));
+        let (_, _, rest) = read_tlv(rest);
+        let (tag, attributes, rest) = read_tlv(rest);
+        assert_eq!((TAG_ATTRIBUTES, 0), (tag, rest.len()));
+        assert!(attributes
+            .windows(OID_EXTENSION_REQUEST.len())
+            .any(|window| window == OID_EXTENSION_REQUEST));
+        assert!(csr_to_pem(&der).starts_with("-----BEGIN CERTIFICATE REQUEST-----\n"));
+
+        // Without parameters the subject is the public key
+        let der = signing_request(&keypair, public_key, &CsrParams::default()).expect("csr");
+        let b58 = public_key.to_string();
+        assert!(der
+            .windows(b58.len())
+            .any(|window| window == b58.as_bytes()));
+    }
+
+    #[test]
+    fn oids() {
+        assert_eq!(
+            &OID_EXTENSION_REQUEST[..],
+            &oid("1.2.840.113549.1.9.14").expect("oid")[..]
+        );
+        assert_eq!(
+            &OID_SUBJECT_ALT_NAME[..],
+            &oid("2.5.29.17").expect("oid")[..]
+        );
+        assert!(oid("1").is_err());
+        assert!(oid("1.40").is_err());
+        assert!(oid("1.2.x").is_err());
+    }
+
+    #[test]
+    fn ed25519_key_info() {
+        // The public key example from RFC 8410 section 10.1
+        let key = hex!("19bf44096984cdfe8541bac167dc3b96c85086aa30b6b6cb0c5c38ad703166e1");
+        let public_key =
+            PublicKey::try_from(&[&[u8::from(KeyTag::default())][..], &key[..]].concat()[..])
+                .expect("public key");
+        let (algorithm, info) = key_info(&public_key).expect("key info");
+        assert_eq!(hex!("300506032b6570"), &algorithm[..]);
+        assert_eq!(
+            hex!("302a300506032b657003210019bf44096984cdfe8541bac167dc3b96c85086aa30b6b6cb0c5c38ad703166e1"),
+            &info[..]
+        );
+    }
+
+    #[test]
+    fn times() {
+        // 2023-11-14T22:13:20Z
+        assert_eq!(b"231114221320Z", &time(1_700_000_000).expect("time")[2..]);
+        assert_eq!(TAG_UTC_TIME, time(0).expect("time")[0]);
+        assert_eq!(b"700101000000Z", &time(0).expect("time")[2..]);
+        // 2050-01-01T00:00:00Z switches to a four digit year
+        let time_2050 = time(2_524_608_000).expect("time");
+        assert_eq!(TAG_GENERALIZED_TIME, time_2050[0]);
+        assert_eq!(b"20500101000000Z", &time_2050[2..]);
+        // 2024-02-29T12:00:00Z
+        assert_eq!(b"240229120000Z", &time(1_709_208_000).expect("time")[2..]);
+        assert!(time(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(vec![0x02, 0x01, 0x00], integer(&0u64.to_be_bytes()));
+        assert_eq!(vec![0x02, 0x01, 0x7f], integer(&0x7fu64.to_be_bytes()));
+        assert_eq!(
+            vec![0x02, 0x02, 0x00, 0x80],
+            integer(&0x80u64.to_be_bytes())
+        );
+        assert_eq!(
+            &[TAG_OCTET_STRING, 0x81, 197][..],
+            &tlv(TAG_OCTET_STRING, &[0u8; 197])[..3]
+        );
+    }
+}
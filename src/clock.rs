@@ -0,0 +1,100 @@
+//! Sources of the current time.
+//!
+//! Time dependent features, like the freshness window of a
+//! [`ReplayGuard`](crate::replay::ReplayGuard), read the current time from a
+//! [`Clock`] rather than the system time directly, so tests can simulate the
+//! passing of time with a [`ManualClock`].
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A source of the current time in seconds since the unix epoch
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// The system time. This is the default clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        unix_timestamp()
+    }
+}
+
+/// A clock which only moves when told to. Clones share the same time, so a
+/// test can keep a clone to advance the clock it handed out.
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Construct a clock starting at the given time
+    pub fn new(now: u64) -> Self {
+        Self {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst)
+    }
+
+    /// Move the clock forward by the given number of seconds
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual() {
+        let clock = ManualClock::new(1000);
+        let shared = clock.clone();
+        shared.advance(10);
+        assert_eq!(1010, clock.now());
+        clock.set(5);
+        assert_eq!(5, shared.now());
+        assert!(SystemClock.now() > 1_600_000_000);
+    }
+}
@@ -0,0 +1,212 @@
+//! A conformance suite for signing backends.
+//!
+//! [`check`] runs a battery of cross-checks between a signer and the public
+//! key it claims to sign for, using only the public [`Sign`] and [`Verify`]
+//! interfaces. Authors of new signing backends can use it to certify that
+//! their signatures are interchangeable with the ones produced by the built-in
+//! keypairs.
+//!
+//! ECDSA signatures are inherently malleable by negating `s`, so the
+//! malleability check for ecc_compact keys only covers tampered encodings.
+use crate::*;
+
+/// The messages every check signs, including the empty message and one that
+/// spans multiple hash blocks
+const MESSAGES: &[&[u8]] = &[b"", b"hello world", &[0xa5; 1024]];
+
+/// The order of the ed25519 base point in little endian byte order
+const ED25519_ORDER: [u8; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+];
+
+/// The outcome of running the conformance checks
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The result of every check, by check name
+    pub results: Vec<(&'static str, Result)>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    /// Returns a description of every failed check
+    pub fn failures(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter_map(|(name, result)| {
+                result
+                    .as_ref()
+                    .err()
+                    .map(|err| format!("{}: {}", name, err))
+            })
+            .collect()
+    }
+
+    /// Panics with a description of every failed check, if any
+    pub fn assert_ok(&self) {
+        let failures = self.failures();
+        if !failures.is_empty() {
+            panic!("conformance failed: {}", failures.join(", "));
+        }
+    }
+}
+
+/// Run all conformance checks for the given signer and its public key
+pub fn check<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Report {
+    Report {
+        results: vec![
+            ("round_trip", check_round_trip(signer, public_key)),
+            ("wrong_message", check_wrong_message(signer, public_key)),
+            ("digest", check_digest(signer, public_key)),
+            ("strictness", check_strictness(signer, public_key)),
+            ("malleability", check_malleability(signer, public_key)),
+        ],
+    }
+}
+
+/// The error used for a check that ran but did not hold
+fn failed() -> Error {
+    signature::Error::new().into()
+}
+
+/// Turns the result of a verification that should have failed into the
+/// result of the check
+fn rejected(result: Result) -> Result {
+    match result {
+        Ok(()) => Err(failed()),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Every message signed verifies against the public key
+fn check_round_trip<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Result {
+    for msg in MESSAGES {
+        let signature = signer.sign(msg)?;
+        public_key.verify(msg, &signature)?;
+    }
+    Ok(())
+}
+
+/// A signature does not verify for a different message
+fn check_wrong_message<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Result {
+    let signature = signer.sign(b"hello world")?;
+    rejected(public_key.verify(b"hello world!", &signature))
+}
+
+/// For key types that support streaming verification, verifying the streamed
+/// digest of a message agrees with verifying the full message
+fn check_digest<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Result {
+    if public_key.verifier().is_err() {
+        return Ok(());
+    }
+    for msg in MESSAGES {
+        let signature = signer.sign(msg)?;
+        public_key.verify_reader(&mut &msg[..], &signature)?;
+    }
+    Ok(())
+}
+
+/// Truncated, extended and empty signatures are rejected
+fn check_strictness<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Result {
+    let signature = signer.sign(b"hello world")?;
+    if signature.is_empty() || (public_key.key_type() == KeyType::Ed25519 && signature.len() != 64)
+    {
+        return Err(failed());
+    }
+    rejected(public_key.verify(b"hello world", &[]))?;
+    rejected(public_key.verify(b"hello world", &signature[..signature.len() - 1]))?;
+    let mut extended = signature;
+    extended.push(0);
+    rejected(public_key.verify(b"hello world", &extended))
+}
+
+/// No single bit flip in a signature verifies, and neither does an ed25519
+/// signature with a non-canonical `s`
+fn check_malleability<S: Sign + ?Sized>(signer: &S, public_key: &PublicKey) -> Result {
+    let signature = signer.sign(b"hello world")?;
+    for index in 0..signature.len() {
+        let mut flipped = signature.clone();
+        flipped[index] ^= 0x01;
+        rejected(public_key.verify(b"hello world", &flipped))?;
+    }
+    if public_key.key_type() == KeyType::Ed25519 {
+        let malleated = non_canonical_ed25519(&signature).ok_or_else(failed)?;
+        rejected(public_key.verify(b"hello world", &malleated))?;
+    }
+    Ok(())
+}
+
+/// Returns the given ed25519 signature with `s` replaced by `s + l`, which is
+/// the same scalar but not canonically encoded, or None if the signature is
+/// not 64 bytes long
+pub(crate) fn non_canonical_ed25519(signature: &[u8]) -> Option<Vec<u8>> {
+    if signature.len() != 64 {
+        return None;
+    }
+    let mut result = signature.to_vec();
+    let mut carry = 0u16;
+    for (byte, order) in result[32..].iter_mut().zip(ED25519_ORDER.iter()) {
+        let sum = *byte as u16 + *order as u16 + carry;
+        *byte = sum as u8;
+        carry = sum >> 8;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn keypairs() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let keypair = Keypair::generate(
+                KeyTag {
+                    network: Network::MainNet,
+                    key_type,
+                },
+                &mut OsRng,
+            );
+            let report = check(&keypair, keypair.public_key());
+            report.assert_ok();
+            assert_eq!(5, report.results.len());
+        }
+    }
+
+    #[test]
+    fn wrong_key() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let other = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let report = check(&keypair, other.public_key());
+        assert!(!report.is_ok());
+        assert!(report.failures()[0].starts_with("round_trip"));
+    }
+
+    /// A signer whose signatures are truncated to the given length
+    struct Truncating<'a>(&'a Keypair, usize);
+
+    impl Sign for Truncating<'_> {
+        fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+            let mut signature = self.0.sign(msg)?;
+            signature.truncate(self.1);
+            Ok(signature)
+        }
+    }
+
+    #[test]
+    fn short_signatures() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        for length in [0, 16] {
+            let report = check(&Truncating(&keypair, length), keypair.public_key());
+            assert!(!report.is_ok());
+            assert!(report
+                .failures()
+                .iter()
+                .any(|failure| failure.starts_with("strictness")));
+        }
+    }
+}
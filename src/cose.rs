@@ -0,0 +1,221 @@
+//! COSE_Key encoding of public keys.
+//!
+//! Ed25519 public keys encode as `OKP` keys on the `Ed25519` curve with the
+//! `EdDSA` algorithm, and ecc_compact public keys as `EC2` keys on the `P-256`
+//! curve with the `ES256` algorithm, following [RFC 9053][RFC9053]. Note that
+//! `ES256` signatures are the raw `r || s` form made by
+//! [`ecc_compact::Keypair::sign_raw`], not the DER form of regular
+//! ecc_compact signatures. A COSE_Key has no notion of a Helium network, so
+//! the network is given when importing a key.
+//!
+//! [RFC9053]: https://www.rfc-editor.org/rfc/rfc9053
+use crate::*;
+use ciborium::value::Value;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+/// The COSE algorithm identifier for EdDSA
+pub const ALG_EDDSA: i64 = -8;
+/// The COSE algorithm identifier for ECDSA with SHA-256
+pub const ALG_ES256: i64 = -7;
+
+const LABEL_KTY: i64 = 1;
+const LABEL_ALG: i64 = 3;
+const LABEL_CRV: i64 = -1;
+const LABEL_X: i64 = -2;
+const LABEL_Y: i64 = -3;
+
+const KTY_OKP: i64 = 1;
+const KTY_EC2: i64 = 2;
+const CRV_P256: i64 = 1;
+const CRV_ED25519: i64 = 6;
+
+impl KeyType {
+    /// Returns the COSE algorithm identifier for signatures by keys of this
+    /// type
+    pub fn cose_algorithm(&self) -> Result<i64> {
+        match self {
+            KeyType::Ed25519 => Ok(ALG_EDDSA),
+            KeyType::EccCompact => Ok(ALG_ES256),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
+impl PublicKey {
+    /// Returns the CBOR encoded COSE_Key of an ed25519 or ecc_compact public
+    /// key
+    pub fn to_cose_key(&self) -> Result<Vec<u8>> {
+        let key_type = self.key_type();
+        let mut entries = vec![];
+        match key_type {
+            KeyType::Ed25519 => {
+                let public_key: &ed25519::PublicKey = self.try_into()?;
+                entries.push((LABEL_KTY, Value::from(KTY_OKP)));
+                entries.push((LABEL_ALG, Value::from(ALG_EDDSA)));
+                entries.push((LABEL_CRV, Value::from(CRV_ED25519)));
+                entries.push((LABEL_X, Value::Bytes(public_key.as_ref().to_vec())));
+            }
+            KeyType::EccCompact => {
+                let public_key: &ecc_compact::PublicKey = self.try_into()?;
+                let point = public_key.0.to_encoded_point(false);
+                // Unwraps ok since an uncompressed point always has both
+                // coordinates
+                entries.push((LABEL_KTY, Value::from(KTY_EC2)));
+                entries.push((LABEL_ALG, Value::from(ALG_ES256)));
+                entries.push((LABEL_CRV, Value::from(CRV_P256)));
+                entries.push((LABEL_X, Value::Bytes(point.x().unwrap().to_vec())));
+                entries.push((LABEL_Y, Value::Bytes(point.y().unwrap().to_vec())));
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::invalid_curve()),
+        }
+        let map = Value::Map(
+            entries
+                .into_iter()
+                .map(|(label, value)| (Value::from(label), value))
+                .collect(),
+        );
+        let mut result = vec![];
+        ciborium::ser::into_writer(&map, &mut result).map_err(|_| Error::invalid_cose())?;
+        Ok(result)
+    }
+
+    /// Import an ed25519 or ecc_compact public key for the given network from
+    /// the given CBOR encoded COSE_Key. The algorithm of the key, if present,
+    /// must match its key type. P-256 keys may have a compressed `y`
+    /// coordinate, and are rejected if they are not compactable.
+    pub fn from_cose_key(network: Network, bytes: &[u8]) -> Result<PublicKey> {
+        let value: Value = ciborium::de::from_reader(bytes).map_err(|_| Error::invalid_cose())?;
+        let entries = match value {
+            Value::Map(entries) => entries,
+            _ => return Err(Error::invalid_cose()),
+        };
+        let get = |label: i64| {
+            entries
+                .iter()
+                .find(|(key, _)| int(key) == Some(label))
+                .map(|(_, value)| value)
+        };
+        let coordinate = |label: i64| match get(label) {
+            Some(Value::Bytes(bytes)) if bytes.len() == 32 => Ok(&bytes[..]),
+            _ => Err(Error::invalid_cose()),
+        };
+
+        let key_type = match (get(LABEL_KTY).and_then(int), get(LABEL_CRV).and_then(int)) {
+            (Some(KTY_OKP), Some(CRV_ED25519)) => KeyType::Ed25519,
+            (Some(KTY_EC2), Some(CRV_P256)) => KeyType::EccCompact,
+            _ => return Err(Error::invalid_cose()),
+        };
+        if let Some(alg) = get(LABEL_ALG) {
+            if int(alg) != Some(key_type.cose_algorithm()?) {
+                return Err(Error::invalid_cose());
+            }
+        }
+        match key_type {
+            KeyType::Ed25519 => {
+                let mut data = vec![u8::from(KeyTag { network, key_type })];
+                data.extend_from_slice(coordinate(LABEL_X)?);
+                PublicKey::try_from(&data[..])
+            }
+            _ => {
+                let x = coordinate(LABEL_X)?;
+                let point = match get(LABEL_Y) {
+                    Some(Value::Bool(sign)) => [&[0x02 + *sign as u8][..], x].concat(),
+                    _ => [&[0x04][..], x, coordinate(LABEL_Y)?].concat(),
+                };
+                let point = p256::PublicKey::from_sec1_bytes(&point)?.to_encoded_point(false);
+                let public_key = ecc_compact::PublicKey::try_from(point.as_bytes())?;
+                Ok(PublicKey::for_network(network, public_key))
+            }
+        }
+    }
+}
+
+fn int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(integer) => i64::try_from(i128::from(*integer)).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn ed25519_encoding() {
+        let public_key = Keypair::generate_from_entropy(KeyTag::default(), &[7u8; 32])
+            .expect("keypair")
+            .public_key()
+            .clone();
+        let encoded = public_key.to_cose_key().expect("cose key");
+        // {1: 1, 3: -8, -1: 6, -2: h'...'}
+        assert_eq!(hex!("a40101032720062158 20"), encoded[..10]);
+        assert_eq!(&public_key.to_vec()[1..], &encoded[10..]);
+        assert_eq!(
+            public_key,
+            PublicKey::from_cose_key(Network::MainNet, &encoded).expect("decoded")
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            let public_key = Keypair::generate(key_tag, &mut OsRng).public_key().clone();
+            let encoded = public_key.to_cose_key().expect("cose key");
+            assert_eq!(
+                public_key,
+                PublicKey::from_cose_key(Network::TestNet, &encoded).expect("decoded")
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_y() {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        };
+        let public_key = Keypair::generate(key_tag, &mut OsRng).public_key().clone();
+        let inner: &ecc_compact::PublicKey = (&public_key).try_into().expect("ecc");
+        let compressed = inner.0.to_encoded_point(true);
+        let map = Value::Map(vec![
+            (Value::from(LABEL_KTY), Value::from(KTY_EC2)),
+            (Value::from(LABEL_CRV), Value::from(CRV_P256)),
+            (
+                Value::from(LABEL_X),
+                Value::Bytes(compressed.x().expect("x").to_vec()),
+            ),
+            (
+                Value::from(LABEL_Y),
+                Value::Bool(compressed.as_bytes()[0] == 0x03),
+            ),
+        ]);
+        let mut encoded = vec![];
+        ciborium::ser::into_writer(&map, &mut encoded).expect("cbor");
+        assert_eq!(
+            public_key,
+            PublicKey::from_cose_key(Network::MainNet, &encoded).expect("decoded")
+        );
+    }
+
+    #[test]
+    fn mismatched_algorithm() {
+        let map = Value::Map(vec![
+            (Value::from(LABEL_KTY), Value::from(KTY_OKP)),
+            (Value::from(LABEL_ALG), Value::from(ALG_ES256)),
+            (Value::from(LABEL_CRV), Value::from(CRV_ED25519)),
+            (Value::from(LABEL_X), Value::Bytes(vec![0u8; 32])),
+        ]);
+        let mut encoded = vec![];
+        ciborium::ser::into_writer(&map, &mut encoded).expect("cbor");
+        assert!(PublicKey::from_cose_key(Network::MainNet, &encoded).is_err());
+    }
+}
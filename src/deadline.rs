@@ -0,0 +1,133 @@
+//! Deadlines for blocking hardware operations.
+//!
+//! A wedged I2C bus or a stalled TPM can block a hardware operation forever.
+//! Operations with a deadline run on a helper thread, and the caller gets an
+//! [`Error::Timeout`] once the deadline passes. The helper thread is left to
+//! finish, or stay blocked, in the background.
+//!
+//! A timed out operation usually stays blocked on the device, and every later
+//! operation would block behind it. So while a timed out operation is still
+//! outstanding, operations with a deadline fail right away with
+//! [`Error::WorkerUnavailable`] instead of piling up more helper threads.
+use crate::*;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The number of timed out operations whose helper thread has not finished
+static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Run the given operation, giving up with a timeout error if it does not
+/// complete in the given time. Without a timeout the operation runs on the
+/// calling thread.
+pub(crate) fn run<T, F>(timeout: Option<Duration>, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    run_counted(&OUTSTANDING, timeout, f)
+}
+
+fn run_counted<T, F>(
+    outstanding: &'static AtomicUsize,
+    timeout: Option<Duration>,
+    f: F,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return f(),
+    };
+    if outstanding.load(Ordering::Acquire) > 0 {
+        return Err(Error::worker_unavailable());
+    }
+    // Set by whichever of the helper finishing and the caller timing out
+    // happens first, so the second one knows whether the helper was counted
+    // as outstanding
+    let settled = Arc::new(AtomicBool::new(false));
+    let helper_settled = settled.clone();
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::Builder::new()
+        .name("helium-crypto-deadline".to_string())
+        .spawn(move || {
+            // Send errors mean the caller has timed out, which is fine to ignore
+            let _ = sender.send(f());
+            if helper_settled.swap(true, Ordering::AcqRel) {
+                outstanding.fetch_sub(1, Ordering::AcqRel);
+            }
+        })?;
+    receiver.recv_timeout(timeout).map_err(|err| match err {
+        mpsc::RecvTimeoutError::Timeout => {
+            outstanding.fetch_add(1, Ordering::AcqRel);
+            if settled.swap(true, Ordering::AcqRel) {
+                outstanding.fetch_sub(1, Ordering::AcqRel);
+            }
+            Error::timeout()
+        }
+        mpsc::RecvTimeoutError::Disconnected => Error::worker_unavailable(),
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes() {
+        assert_eq!(
+            42,
+            run(Some(Duration::from_secs(5)), || Ok(42)).expect("result")
+        );
+        assert_eq!(42, run(None, || Ok(42)).expect("result"));
+    }
+
+    #[test]
+    fn times_out() {
+        // Count outstanding operations separately so other tests do not fail
+        // fast while this one is still sleeping
+        static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+        let result = run_counted(&OUTSTANDING, Some(Duration::from_millis(10)), || {
+            thread::sleep(Duration::from_secs(1));
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn fails_fast_while_outstanding() {
+        static OUTSTANDING: AtomicUsize = AtomicUsize::new(0);
+        let (release, released) = mpsc::channel::<()>();
+        let result = run_counted(&OUTSTANDING, Some(Duration::from_millis(10)), move || {
+            let _ = released.recv();
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(matches!(
+            run_counted(&OUTSTANDING, Some(Duration::from_secs(5)), || Ok(42)),
+            Err(Error::WorkerUnavailable)
+        ));
+        // Operations without a deadline still run
+        assert_eq!(
+            42,
+            run_counted(&OUTSTANDING, None, || Ok(42)).expect("result")
+        );
+
+        // Once the timed out operation finishes, operations run again
+        release.send(()).expect("release");
+        while OUTSTANDING.load(Ordering::Acquire) > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            42,
+            run_counted(&OUTSTANDING, Some(Duration::from_secs(5)), || Ok(42)).expect("result")
+        );
+    }
+}
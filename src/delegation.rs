@@ -0,0 +1,483 @@
+//! Delegation certificates for hierarchical key management.
+//!
+//! A root keypair issues a signed [`Delegation`] to an intermediate key, which
+//! can in turn issue delegations to leaf device keys, so services can sign on
+//! behalf of an organization without holding its root key. A
+//! [`DelegationChain`] links the delegations from the root down to a leaf key
+//! and is verified against the expected root public key.
+//!
+//! Every delegation carries [`Constraints`] on the networks of the keys it
+//! covers, the operations they may perform and when it expires. A chain only
+//! permits what every delegation in it permits, so an intermediate key can
+//! narrow what it was delegated but never widen it.
+use crate::{
+    clock::Clock,
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use std::io;
+
+/// Domain separator prefixed to the signed bytes of a delegation
+const DELEGATION_DOMAIN: &[u8] = b"helium-delegation";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// The networks delegated keys may be on. Empty allows any network.
+    pub networks: Vec<Network>,
+    /// The operations delegated keys may perform. Empty allows any
+    /// operation.
+    pub operations: Vec<String>,
+    /// Seconds since the unix epoch after which the delegation is no longer
+    /// valid
+    pub not_after: Option<u64>,
+}
+
+impl Constraints {
+    pub fn with_networks(mut self, networks: &[Network]) -> Self {
+        self.networks = networks.to_vec();
+        self
+    }
+
+    pub fn with_operations(mut self, operations: &[&str]) -> Self {
+        self.operations = operations.iter().map(|op| op.to_string()).collect();
+        self
+    }
+
+    pub fn with_not_after(mut self, not_after: u64) -> Self {
+        self.not_after = Some(not_after);
+        self
+    }
+
+    pub fn allows_network(&self, network: Network) -> bool {
+        self.networks.is_empty() || self.networks.contains(&network)
+    }
+
+    pub fn allows_operation(&self, operation: &str) -> bool {
+        self.operations.is_empty() || self.operations.iter().any(|op| op == operation)
+    }
+
+    /// Returns an error if the given operation is not permitted at the given
+    /// current time
+    pub fn check(&self, operation: &str, now: u64) -> Result {
+        match self.not_after {
+            Some(not_after) if now > not_after => Err(Error::expired(not_after)),
+            _ if !self.allows_operation(operation) => Err(Error::not_permitted()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl WriteTo for Constraints {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        write_count(output, self.networks.len())?;
+        for network in &self.networks {
+            output.write_all(&[u8::from(*network)])?;
+        }
+        write_count(output, self.operations.len())?;
+        for operation in &self.operations {
+            write_u32_prefixed(output, operation.as_bytes())?;
+        }
+        // The expiry is preceded by a flag for whether it is present
+        match self.not_after {
+            Some(not_after) => {
+                output.write_all(&[1])?;
+                output.write_all(&not_after.to_be_bytes())
+            }
+            None => output.write_all(&[0]),
+        }
+    }
+}
+
+impl ReadFrom for Constraints {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let mut networks = vec![];
+        for _ in 0..read_count(input)? {
+            let mut network = [0u8; 1];
+            input.read_exact(&mut network)?;
+            networks.push(Network::try_from(network[0])?);
+        }
+        let mut operations = vec![];
+        for _ in 0..read_count(input)? {
+            let operation = String::from_utf8(read_u32_prefixed(input)?)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            operations.push(operation);
+        }
+        let mut present = [0u8; 1];
+        input.read_exact(&mut present)?;
+        let not_after = match present[0] {
+            0 => None,
+            1 => {
+                let mut not_after = [0u8; 8];
+                input.read_exact(&mut not_after)?;
+                Some(u64::from_be_bytes(not_after))
+            }
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidData).into()),
+        };
+        Ok(Self {
+            networks,
+            operations,
+            not_after,
+        })
+    }
+}
+
+/// A signed statement by an issuer key that delegates to a subject key
+/// under the given constraints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    pub issuer: PublicKey,
+    pub subject: PublicKey,
+    pub constraints: Constraints,
+    /// The signature by the issuer over the issuer, subject and constraints
+    pub signature: Vec<u8>,
+}
+
+impl Delegation {
+    /// Delegate to the given subject key under the given constraints
+    pub fn issue(issuer: &Keypair, subject: &PublicKey, constraints: Constraints) -> Result<Self> {
+        let issuer_key = issuer.public_key().clone();
+        let signature = issuer.sign(&signing_bytes(&issuer_key, subject, &constraints)?)?;
+        Ok(Self {
+            issuer: issuer_key,
+            subject: subject.clone(),
+            constraints,
+            signature,
+        })
+    }
+
+    /// Verify the signature of the issuer. This does not check the
+    /// constraints.
+    pub fn verify(&self) -> Result {
+        let msg = signing_bytes(&self.issuer, &self.subject, &self.constraints)?;
+        self.issuer.verify(&msg, &self.signature)
+    }
+
+    /// Convert the delegation to its binary form. Fails if the constraints
+    /// have more than 255 networks or operations.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_to(&mut result)?;
+        Ok(result)
+    }
+}
+
+impl WriteTo for Delegation {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        self.issuer.write_to(output)?;
+        self.subject.write_to(output)?;
+        self.constraints.write_to(output)?;
+        write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for Delegation {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        Ok(Self {
+            issuer: PublicKey::read_from(input)?,
+            subject: PublicKey::read_from(input)?,
+            constraints: Constraints::read_from(input)?,
+            signature: read_u32_prefixed(input)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Delegation {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// The delegations from a root key down to a leaf key, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DelegationChain {
+    pub delegations: Vec<Delegation>,
+}
+
+impl DelegationChain {
+    pub fn new(delegations: Vec<Delegation>) -> Self {
+        Self { delegations }
+    }
+
+    /// Returns the key the chain delegates to, if the chain is not empty
+    pub fn leaf(&self) -> Option<&PublicKey> {
+        self.delegations
+            .last()
+            .map(|delegation| &delegation.subject)
+    }
+
+    /// Verify that the chain delegates the given operation from the given
+    /// root key, using the current time of the given clock. Returns the leaf
+    /// key of the chain.
+    pub fn verify<C: Clock>(
+        &self,
+        root: &PublicKey,
+        operation: &str,
+        clock: &C,
+    ) -> Result<&PublicKey> {
+        self.verify_at(root, operation, clock.now())
+    }
+
+    /// Like [`DelegationChain::verify`] but at the given current time
+    pub fn verify_at(&self, root: &PublicKey, operation: &str, now: u64) -> Result<&PublicKey> {
+        let mut issuer = root;
+        for (index, delegation) in self.delegations.iter().enumerate() {
+            if &delegation.issuer != issuer {
+                return Err(signature::Error::new().into());
+            }
+            delegation.verify()?;
+            delegation.constraints.check(operation, now)?;
+            // The network constraints cover every key delegated to further
+            // down the chain
+            let networks_allowed = self.delegations[index..]
+                .iter()
+                .all(|later| delegation.constraints.allows_network(later.subject.network));
+            if !networks_allowed {
+                return Err(Error::not_permitted());
+            }
+            issuer = &delegation.subject;
+        }
+        self.leaf().ok_or_else(Error::not_permitted)
+    }
+
+    /// Verify that the chain delegates the given operation from the given
+    /// root key, and that the given signature over the given message is by
+    /// the leaf key of the chain.
+    pub fn verify_message<C: Clock>(
+        &self,
+        root: &PublicKey,
+        operation: &str,
+        msg: &[u8],
+        signature: &[u8],
+        clock: &C,
+    ) -> Result {
+        self.verify(root, operation, clock)?.verify(msg, signature)
+    }
+
+    /// Convert the chain to its binary form. Fails if the chain has more than
+    /// 255 delegations or a delegation can not be converted.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_to(&mut result)?;
+        Ok(result)
+    }
+}
+
+impl WriteTo for DelegationChain {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        write_count(output, self.delegations.len())?;
+        for delegation in &self.delegations {
+            delegation.write_to(output)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadFrom for DelegationChain {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let mut delegations = vec![];
+        for _ in 0..read_count(input)? {
+            delegations.push(Delegation::read_from(input)?);
+        }
+        Ok(Self { delegations })
+    }
+}
+
+impl TryFrom<&[u8]> for DelegationChain {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// Returns the bytes the signature of a delegation is calculated over
+fn signing_bytes(
+    issuer: &PublicKey,
+    subject: &PublicKey,
+    constraints: &Constraints,
+) -> Result<Vec<u8>> {
+    let mut result = DELEGATION_DOMAIN.to_vec();
+    issuer.write_to(&mut result)?;
+    subject.write_to(&mut result)?;
+    constraints.write_to(&mut result)?;
+    Ok(result)
+}
+
+/// Writes the given number of items as a single byte
+fn write_count<W: io::Write>(output: &mut W, count: usize) -> io::Result<()> {
+    let count = u8::try_from(count)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many items"))?;
+    output.write_all(&[count])
+}
+
+fn read_count<R: io::Read>(input: &mut R) -> Result<u8> {
+    let mut count = [0u8; 1];
+    input.read_exact(&mut count)?;
+    Ok(count[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use rand::rngs::OsRng;
+
+    fn keypair() -> Keypair {
+        Keypair::generate(KeyTag::default(), &mut OsRng)
+    }
+
+    fn chain(root: &Keypair, intermediate: &Keypair, leaf: &Keypair) -> DelegationChain {
+        DelegationChain::new(vec![
+            Delegation::issue(
+                root,
+                intermediate.public_key(),
+                Constraints::default()
+                    .with_networks(&[Network::MainNet])
+                    .with_operations(&["sign", "rotate"])
+                    .with_not_after(2000),
+            )
+            .expect("intermediate"),
+            Delegation::issue(
+                intermediate,
+                leaf.public_key(),
+                Constraints::default()
+                    .with_operations(&["sign", "burn"])
+                    .with_not_after(1500),
+            )
+            .expect("leaf"),
+        ])
+    }
+
+    #[test]
+    fn verify() {
+        let (root, intermediate, leaf) = (keypair(), keypair(), keypair());
+        let chain = chain(&root, &intermediate, &leaf);
+
+        assert_eq!(
+            leaf.public_key(),
+            chain
+                .verify_at(root.public_key(), "sign", 1000)
+                .expect("verify")
+        );
+        // Operations not allowed by every delegation are not permitted
+        assert!(matches!(
+            chain.verify_at(root.public_key(), "rotate", 1000),
+            Err(Error::NotPermitted)
+        ));
+        assert!(matches!(
+            chain.verify_at(root.public_key(), "burn", 1000),
+            Err(Error::NotPermitted)
+        ));
+        assert!(matches!(
+            chain.verify_at(root.public_key(), "sign", 1600),
+            Err(Error::Expired(1500))
+        ));
+        assert!(chain
+            .verify_at(intermediate.public_key(), "sign", 1000)
+            .is_err());
+        assert!(DelegationChain::default()
+            .verify_at(root.public_key(), "sign", 1000)
+            .is_err());
+    }
+
+    #[test]
+    fn network() {
+        let root = keypair();
+        let leaf = Keypair::generate(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::Ed25519,
+            },
+            &mut OsRng,
+        );
+        let chain = DelegationChain::new(vec![Delegation::issue(
+            &root,
+            leaf.public_key(),
+            Constraints::default().with_networks(&[Network::MainNet]),
+        )
+        .expect("delegation")]);
+        assert!(matches!(
+            chain.verify_at(root.public_key(), "sign", 1000),
+            Err(Error::NotPermitted)
+        ));
+    }
+
+    #[test]
+    fn tampered() {
+        let (root, intermediate, leaf) = (keypair(), keypair(), keypair());
+        let mut chain = chain(&root, &intermediate, &leaf);
+        chain.delegations[1].constraints.not_after = None;
+        assert!(chain.verify_at(root.public_key(), "sign", 1000).is_err());
+    }
+
+    #[test]
+    fn message() {
+        let (root, intermediate, leaf) = (keypair(), keypair(), keypair());
+        let chain = chain(&root, &intermediate, &leaf);
+        let clock = ManualClock::new(1000);
+        let signature = leaf.sign(b"hello world").expect("signature");
+        assert!(chain
+            .verify_message(
+                root.public_key(),
+                "sign",
+                b"hello world",
+                &signature,
+                &clock
+            )
+            .is_ok());
+        clock.advance(1000);
+        assert!(chain
+            .verify_message(
+                root.public_key(),
+                "sign",
+                b"hello world",
+                &signature,
+                &clock
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let (root, intermediate, leaf) = (keypair(), keypair(), keypair());
+        let chain = chain(&root, &intermediate, &leaf);
+        let decoded =
+            DelegationChain::try_from(&chain.to_vec().expect("bytes")[..]).expect("chain");
+        assert_eq!(chain, decoded);
+        assert!(decoded.verify_at(root.public_key(), "sign", 1000).is_ok());
+    }
+
+    #[test]
+    fn too_many_items() {
+        let (root, intermediate, leaf) = (keypair(), keypair(), keypair());
+        let mut chain = chain(&root, &intermediate, &leaf);
+        let operations = vec!["sign".to_string(); 256];
+        chain.delegations[1].constraints.operations = operations;
+        assert!(chain.delegations[1].to_vec().is_err());
+        assert!(chain.to_vec().is_err());
+
+        let delegation = chain.delegations[0].clone();
+        chain.delegations = vec![delegation; 256];
+        assert!(chain.to_vec().is_err());
+    }
+
+    #[test]
+    fn not_after_roundtrip() {
+        for not_after in [None, Some(0), Some(u64::MAX)] {
+            let constraints = Constraints {
+                not_after,
+                ..Default::default()
+            };
+            let mut bytes = vec![];
+            constraints.write_to(&mut bytes).expect("bytes");
+            let decoded = Constraints::read_from(&mut &bytes[..]).expect("decoded");
+            assert_eq!(not_after, decoded.not_after);
+        }
+        // Only 0 and 1 are valid presence flags
+        assert!(Constraints::read_from(&mut &[0, 0, 2][..]).is_err());
+    }
+}
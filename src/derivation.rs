@@ -0,0 +1,424 @@
+//! SLIP-0010 hierarchical deterministic key derivation.
+//!
+//! A single backed up seed derives a tree of keypairs, addressed by paths
+//! like `m/44'/904'/0'`, so a fleet of keys can be recovered from one seed.
+//! Derivation follows [SLIP-0010][SLIP-0010], which matches [BIP32][BIP32]
+//! for secp256k1 keys:
+//!
+//! * ed25519 keys use the `ed25519 seed` curve and only support hardened
+//!   derivation.
+//! * ecc_compact keys use the `Nist256p1 seed` curve.
+//! * secp256k1 keys use the `Bitcoin seed` curve.
+//!
+//! ecc_compact keypairs need a compactable public key, which only one of a
+//! P-256 secret scalar and its negation has. A derived P-256 secret whose
+//! public key is not compactable is negated when it is converted to a
+//! keypair, so every path yields a usable ecc_compact keypair. Derivation of
+//! children always uses the unmodified SLIP-0010 secret.
+//!
+//! [SLIP-0010]: https://github.com/satoshilabs/slips/blob/master/slip-0010.md
+//! [BIP32]: https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
+use crate::{ecc_compact::IsCompactable, *};
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::{
+    ff::{Field, PrimeField},
+    sec1::ToEncodedPoint,
+};
+use std::{fmt, str::FromStr};
+
+type HmacSha512 = Hmac<sha2::Sha512>;
+
+/// The bit set in the index of hardened children
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// The index of a child key, including whether it is hardened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChildIndex(u32);
+
+/// A path of child indexes from a master key, like `m/44'/904'/0'`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<ChildIndex>);
+
+/// A secret key with the chain code needed to derive its children
+#[derive(Clone)]
+pub struct ExtendedKey {
+    key_type: KeyType,
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl ChildIndex {
+    /// A normal child index, which must be below [`HARDENED`]
+    pub fn normal(index: u32) -> Result<Self> {
+        if index >= HARDENED {
+            return Err(Error::invalid_path(&index.to_string()));
+        }
+        Ok(Self(index))
+    }
+
+    /// A hardened child index, which must be below [`HARDENED`]
+    pub fn hardened(index: u32) -> Result<Self> {
+        Self::normal(index).map(|index| Self(index.0 | HARDENED))
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        self.0 & HARDENED != 0
+    }
+
+    /// Returns the index without the hardened bit
+    pub fn index(&self) -> u32 {
+        self.0 & !HARDENED
+    }
+}
+
+impl fmt::Display for ChildIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_hardened() {
+            write!(f, "{}'", self.index())
+        } else {
+            write!(f, "{}", self.index())
+        }
+    }
+}
+
+impl FromStr for ChildIndex {
+    type Err = Error;
+
+    /// Parse an index, marked as hardened with a trailing `'`, `h` or `H`
+    fn from_str(s: &str) -> Result<Self> {
+        let (index, hardened) = match s.strip_suffix(&['\'', 'h', 'H'][..]) {
+            Some(index) => (index, true),
+            None => (s, false),
+        };
+        let index: u32 = index.parse().map_err(|_| Error::invalid_path(s))?;
+        if hardened {
+            Self::hardened(index)
+        } else {
+            Self::normal(index)
+        }
+    }
+}
+
+impl DerivationPath {
+    pub fn new(indexes: Vec<ChildIndex>) -> Self {
+        Self(indexes)
+    }
+
+    pub fn indexes(&self) -> &[ChildIndex] {
+        &self.0
+    }
+
+    /// Returns this path extended with the given child index
+    pub fn child(&self, index: ChildIndex) -> Self {
+        let mut indexes = self.0.clone();
+        indexes.push(index);
+        Self(indexes)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for index in &self.0 {
+            write!(f, "/{}", index)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Error::invalid_path(s));
+        }
+        parts
+            .map(ChildIndex::from_str)
+            .collect::<Result<Vec<_>>>()
+            .map(Self)
+            .map_err(|_| Error::invalid_path(s))
+    }
+}
+
+impl fmt::Debug for ExtendedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedKey")
+            .field("key_type", &self.key_type)
+            .finish()
+    }
+}
+
+impl ExtendedKey {
+    /// Derive the master key for the given key type from the given seed.
+    /// Only ed25519, ecc_compact and secp256k1 keys support derivation.
+    pub fn from_seed(key_type: KeyType, seed: &[u8]) -> Result<Self> {
+        let curve: &[u8] = match key_type {
+            KeyType::Ed25519 => b"ed25519 seed",
+            KeyType::EccCompact => b"Nist256p1 seed",
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => b"Bitcoin seed",
+            _ => return Err(Error::invalid_curve()),
+        };
+        let mut digest = hmac_sha512(curve, &[seed]);
+        // Retry with the digest as data until the secret is a valid scalar
+        while key_type != KeyType::Ed25519 && tweak_secret(key_type, &digest[..32], None).is_none()
+        {
+            digest = hmac_sha512(curve, &[&digest]);
+        }
+        Ok(Self::from_digest(key_type, &digest))
+    }
+
+    fn from_digest(key_type: KeyType, digest: &[u8; 64]) -> Self {
+        let mut secret = [0u8; 32];
+        let mut chain_code = [0u8; 32];
+        secret.copy_from_slice(&digest[..32]);
+        chain_code.copy_from_slice(&digest[32..]);
+        Self {
+            key_type,
+            secret,
+            chain_code,
+        }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /// Returns the SLIP-0010 secret key bytes
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.to_vec()
+    }
+
+    /// Derive the child key at the given index. ed25519 keys only support
+    /// hardened children.
+    pub fn derive_child(&self, index: ChildIndex) -> Result<Self> {
+        let index_bytes = index.0.to_be_bytes();
+        let public_key;
+        let data: [&[u8]; 3] = if index.is_hardened() {
+            [&[0], &self.secret, &index_bytes]
+        } else {
+            public_key = self.compressed_public_key()?;
+            [&[], &public_key, &index_bytes]
+        };
+        let mut digest = hmac_sha512(&self.chain_code, &data);
+        if self.key_type == KeyType::Ed25519 {
+            return Ok(Self::from_digest(self.key_type, &digest));
+        }
+        loop {
+            if let Some(secret) = tweak_secret(self.key_type, &digest[..32], Some(&self.secret)) {
+                digest[..32].copy_from_slice(&secret);
+                return Ok(Self::from_digest(self.key_type, &digest));
+            }
+            // Retry as SLIP-0010 specifies for the negligible chance of an
+            // invalid child secret
+            let chain_code = digest[32..].to_vec();
+            digest = hmac_sha512(&self.chain_code, &[&[1], &chain_code, &index_bytes]);
+        }
+    }
+
+    /// Derive the key at the given path below this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self> {
+        path.0
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    /// Convert to a keypair on the given network
+    pub fn to_keypair(&self, network: Network) -> Result<Keypair> {
+        match self.key_type {
+            KeyType::Ed25519 => {
+                Ok(ed25519::Keypair::generate_from_entropy(network, &self.secret)?.into())
+            }
+            KeyType::EccCompact => {
+                let secret = p256::SecretKey::from_be_bytes(&self.secret)?;
+                let secret = if secret.public_key().is_compactable() {
+                    self.secret
+                } else {
+                    (-*secret.to_nonzero_scalar()).to_repr().into()
+                };
+                Ok(ecc_compact::Keypair::generate_from_entropy(network, &secret)?.into())
+            }
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => {
+                Ok(secp256k1::Keypair::generate_from_entropy(network, &self.secret)?.into())
+            }
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Returns the SEC1 compressed public key used to derive normal children
+    fn compressed_public_key(&self) -> Result<Vec<u8>> {
+        match self.key_type {
+            KeyType::EccCompact => Ok(p256::SecretKey::from_be_bytes(&self.secret)?
+                .public_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec()),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Ok(k256::SecretKey::from_be_bytes(&self.secret)?
+                .public_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec()),
+            // ed25519 keys only support hardened derivation
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    // Unwrap ok here since HMAC accepts keys of any length
+    let mut mac = HmacSha512::new_from_slice(key).unwrap();
+    for part in data {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Returns the given tweak added to the given parent secret, or the tweak
+/// itself without a parent, as the secret of the given ECDSA key type. Returns
+/// `None` if the result is not a valid secret.
+fn tweak_secret(key_type: KeyType, tweak: &[u8], parent: Option<&[u8; 32]>) -> Option<[u8; 32]> {
+    match key_type {
+        KeyType::EccCompact => {
+            let tweak: Option<p256::Scalar> =
+                p256::Scalar::from_repr(*p256::FieldBytes::from_slice(tweak)).into();
+            let parent: Option<p256::Scalar> = match parent {
+                Some(parent) => {
+                    p256::Scalar::from_repr(*p256::FieldBytes::from_slice(parent)).into()
+                }
+                None => Some(p256::Scalar::zero()),
+            };
+            let secret = tweak? + parent?;
+            (!bool::from(secret.is_zero())).then(|| secret.to_repr().into())
+        }
+        #[cfg(feature = "secp256k1")]
+        KeyType::Secp256k1 => {
+            let tweak: Option<k256::Scalar> =
+                k256::Scalar::from_repr(*k256::FieldBytes::from_slice(tweak)).into();
+            let parent: Option<k256::Scalar> = match parent {
+                Some(parent) => {
+                    k256::Scalar::from_repr(*k256::FieldBytes::from_slice(parent)).into()
+                }
+                None => Some(k256::Scalar::zero()),
+            };
+            let secret = tweak? + parent?;
+            (!bool::from(secret.is_zero())).then(|| secret.to_repr().into())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    // SLIP-0010 test vector 1
+    const SEED: [u8; 16] = hex!("000102030405060708090a0b0c0d0e0f");
+
+    #[test]
+    fn path_roundtrip() {
+        let path: DerivationPath = "m/44'/904'/0h/1".parse().expect("path");
+        assert_eq!(
+            vec![
+                ChildIndex::hardened(44).unwrap(),
+                ChildIndex::hardened(904).unwrap(),
+                ChildIndex::hardened(0).unwrap(),
+                ChildIndex::normal(1).unwrap(),
+            ],
+            path.indexes()
+        );
+        assert_eq!("m/44'/904'/0'/1", path.to_string());
+        assert_eq!(DerivationPath::default(), "m".parse().expect("master"));
+        assert!("44'/0'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648".parse::<DerivationPath>().is_err());
+        assert!("m/x".parse::<DerivationPath>().is_err());
+    }
+
+    #[test]
+    fn ed25519_vector() {
+        let master = ExtendedKey::from_seed(KeyType::Ed25519, &SEED).expect("master");
+        assert_eq!(
+            hex!("2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7").to_vec(),
+            master.secret_to_vec()
+        );
+        assert_eq!(
+            &hex!("90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"),
+            master.chain_code()
+        );
+        let child = master.derive_path(&"m/0'".parse().unwrap()).expect("child");
+        assert_eq!(
+            hex!("68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3").to_vec(),
+            child.secret_to_vec()
+        );
+        assert_eq!(
+            &hex!("8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69"),
+            child.chain_code()
+        );
+        // ed25519 only supports hardened derivation
+        assert!(master.derive_child(ChildIndex::normal(0).unwrap()).is_err());
+    }
+
+    #[test]
+    fn nist256p1_vector() {
+        let master = ExtendedKey::from_seed(KeyType::EccCompact, &SEED).expect("master");
+        assert_eq!(
+            hex!("612091aaa12e22dd2abef664f8a01a82cae99ad7441b7ef8110424915c268bc2").to_vec(),
+            master.secret_to_vec()
+        );
+        let child = master
+            .derive_path(&"m/0'/1".parse().unwrap())
+            .expect("child");
+        assert_eq!(
+            hex!("284e9d38d07d21e4e281b645089a94f4cf5a5a81369acf151a1c3a57f18b2129").to_vec(),
+            child.secret_to_vec()
+        );
+        assert_eq!(
+            &hex!("4187afff1aafa8445010097fb99d23aee9f599450c7bd140b6826ac22ba21d0c"),
+            child.chain_code()
+        );
+    }
+
+    #[test]
+    fn ecc_compact_keypairs() {
+        let master = ExtendedKey::from_seed(KeyType::EccCompact, &SEED).expect("master");
+        // Every derived secret converts to a compact keypair, whether or not
+        // it needs to be negated
+        for i in 0..16 {
+            let child = master
+                .derive_child(ChildIndex::hardened(i).unwrap())
+                .expect("child");
+            let keypair = child.to_keypair(Network::MainNet).expect("keypair");
+            assert_eq!(KeyType::EccCompact, keypair.key_tag().key_type);
+            assert_eq!(
+                keypair,
+                child.to_keypair(Network::MainNet).expect("same keypair")
+            );
+        }
+    }
+
+    #[test]
+    fn keypair_derive_path() {
+        let path: DerivationPath = "m/44'/904'/0'".parse().expect("path");
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let master = Keypair::generate(
+                KeyTag {
+                    network: Network::TestNet,
+                    key_type,
+                },
+                &mut rand::rngs::OsRng,
+            );
+            let child = master.derive_path(&path).expect("child");
+            assert_eq!(master.key_tag(), child.key_tag());
+            assert_ne!(master, child);
+            assert_eq!(child, master.derive_path(&path).expect("same child"));
+        }
+    }
+}
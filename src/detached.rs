@@ -0,0 +1,217 @@
+//! Detached signatures are stand-alone signature artifacts for files, such as
+//! firmware images or release archives.
+//!
+//! A detached signature file (usually with a `.sig` extension) is made up of:
+//!
+//! * the magic bytes `HSIG`
+//! * a format version byte
+//! * the binary form of the signer public key
+//! * the signing algorithm, which is the key type byte of the signer
+//! * the u32 length prefixed signature over the file contents
+//! * a u32 length prefixed, utf-8 encoded comment, which is empty if absent
+//!
+//! Note that the comment is _not_ covered by the signature and should be
+//! treated as untrusted.
+use crate::{
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use std::{fs, path::Path};
+
+/// The magic bytes every detached signature starts with
+pub const DETACHED_MAGIC: &[u8; 4] = b"HSIG";
+/// The current version of the detached signature format
+pub const DETACHED_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedSignature {
+    /// The format version this signature was read from or will be written as
+    pub version: u8,
+    /// The public key of the signer
+    pub public_key: PublicKey,
+    /// The signature algorithm
+    pub algorithm: KeyType,
+    pub signature: Vec<u8>,
+    /// An optional, unauthenticated comment
+    pub comment: Option<String>,
+}
+
+impl DetachedSignature {
+    /// Sign the given data with the given keypair
+    pub fn sign(keypair: &Keypair, data: &[u8], comment: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            version: DETACHED_VERSION,
+            public_key: keypair.public_key().clone(),
+            algorithm: keypair.key_tag().key_type,
+            signature: keypair.sign(data)?,
+            comment: comment.map(str::to_string),
+        })
+    }
+
+    /// Sign the contents of the file at the given path with the given keypair
+    pub fn sign_file<P: AsRef<Path>>(
+        keypair: &Keypair,
+        path: P,
+        comment: Option<&str>,
+    ) -> Result<Self> {
+        Self::sign(keypair, &fs::read(path)?, comment)
+    }
+
+    /// Verify the given data against this signature
+    pub fn verify(&self, data: &[u8]) -> Result {
+        if self.algorithm != self.public_key.key_type() {
+            return Err(Error::invalid_keytype(self.algorithm.into()));
+        }
+        self.public_key.verify(data, &self.signature)
+    }
+
+    /// Read a detached signature from the file at the given path
+    pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = fs::File::open(path)?;
+        Self::read_from(&mut file)
+    }
+
+    /// Write this detached signature to the file at the given path
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result {
+        fs::write(path, self.to_vec())?;
+        Ok(())
+    }
+
+    /// Convert the detached signature to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+/// Verify the file at the given path against the detached signature file at
+/// the given signature path.
+pub fn verify_file<P: AsRef<Path>, S: AsRef<Path>>(path: P, sig_path: S) -> Result {
+    let signature = DetachedSignature::read_file(sig_path)?;
+    signature.verify(&fs::read(path)?)
+}
+
+impl WriteTo for DetachedSignature {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(DETACHED_MAGIC)?;
+        output.write_all(&[self.version])?;
+        self.public_key.write_to(output)?;
+        output.write_all(&[u8::from(self.algorithm)])?;
+        write_u32_prefixed(output, &self.signature)?;
+        write_u32_prefixed(
+            output,
+            self.comment.as_deref().unwrap_or_default().as_bytes(),
+        )
+    }
+}
+
+impl ReadFrom for DetachedSignature {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != DETACHED_MAGIC {
+            return Err(Error::invalid_magic());
+        }
+        let mut buf = [0u8];
+        input.read_exact(&mut buf)?;
+        let version = buf[0];
+        if version != DETACHED_VERSION {
+            return Err(Error::invalid_version(version));
+        }
+        let public_key = PublicKey::read_from(input)?;
+        input.read_exact(&mut buf)?;
+        let algorithm = KeyType::try_from(buf[0])?;
+        let signature = read_u32_prefixed(input)?;
+        let comment = read_u32_prefixed(input)?;
+        let comment = if comment.is_empty() {
+            None
+        } else {
+            Some(
+                String::from_utf8(comment)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?,
+            )
+        };
+        Ok(Self {
+            version,
+            public_key,
+            algorithm,
+            signature,
+            comment,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for DetachedSignature {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature =
+            DetachedSignature::sign(&keypair, b"hello world", Some("release")).expect("signature");
+        let decoded = DetachedSignature::try_from(&signature.to_vec()[..]).expect("decoded");
+        assert_eq!(signature, decoded);
+        assert!(decoded.verify(b"hello world").is_ok());
+        assert!(decoded.verify(b"hello there").is_err());
+    }
+
+    #[test]
+    fn bad_magic() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature = DetachedSignature::sign(&keypair, b"hello world", None).expect("signature");
+        let mut bytes = signature.to_vec();
+        bytes[0] = b'X';
+        assert!(DetachedSignature::try_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn invalid_comment() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature =
+            DetachedSignature::sign(&keypair, b"hello world", Some("release")).expect("signature");
+        let mut bytes = signature.to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] = 0xff;
+        assert!(matches!(
+            DetachedSignature::try_from(&bytes[..]),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn verify_files() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("helium-crypto-detached-{}", std::process::id()));
+        let sig_path = path.with_extension("sig");
+        fs::write(&path, b"firmware image").expect("write file");
+
+        DetachedSignature::sign_file(&keypair, &path, None)
+            .and_then(|signature| signature.write_file(&sig_path))
+            .expect("signature file");
+        let result = verify_file(&path, &sig_path);
+
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&sig_path);
+        assert!(result.is_ok());
+    }
+}
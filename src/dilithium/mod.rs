@@ -0,0 +1,286 @@
+//! Post-quantum ML-DSA (Dilithium) keypairs.
+//!
+//! Keypairs use the Dilithium3 parameter set. Dilithium keys are far larger
+//! than elliptic curve keys, so their binary forms carry the key bytes with a
+//! multihash style unsigned varint length prefix after the key tag:
+//!
+//! ```text
+//! public key: tag || varint(len) || public key
+//! keypair:    tag || varint(len) || secret key || varint(len) || public key
+//! ```
+//!
+//! Signatures are the raw detached Dilithium3 signature.
+//!
+//! Key generation draws from the operating system random number generator,
+//! and keypairs can not be derived from entropy.
+use crate::*;
+use pqcrypto_dilithium::dilithium3;
+use pqcrypto_traits::sign::{DetachedSignature as _, PublicKey as _, SecretKey as _};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Clone)]
+pub struct PublicKey(Box<dilithium3::PublicKey>);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: dilithium3::SecretKey,
+}
+
+const RAW_PUBLIC_KEY_LENGTH: usize = dilithium3::public_key_bytes();
+const RAW_SECRET_KEY_LENGTH: usize = dilithium3::secret_key_bytes();
+
+pub const PUBLIC_KEY_LENGTH: usize = 1 + varint_len(RAW_PUBLIC_KEY_LENGTH) + RAW_PUBLIC_KEY_LENGTH;
+pub const KEYPAIR_LENGTH: usize = 1
+    + varint_len(RAW_SECRET_KEY_LENGTH)
+    + RAW_SECRET_KEY_LENGTH
+    + varint_len(RAW_PUBLIC_KEY_LENGTH)
+    + RAW_PUBLIC_KEY_LENGTH;
+pub const SIGNATURE_LENGTH: usize = dilithium3::signature_bytes();
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        Ok(dilithium3::detached_sign(msg, &self.secret)
+            .as_bytes()
+            .to_vec())
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        let mut input = std::io::Cursor::new(&input[1..]);
+        let secret = read_prefixed(&mut input, RAW_SECRET_KEY_LENGTH)?;
+        let secret =
+            dilithium3::SecretKey::from_bytes(&secret).map_err(|_| Error::invalid_curve())?;
+        let public_key = PublicKey::read_from(&mut input)?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            secret,
+        })
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        write_prefixed(output, self.secret.as_bytes())?;
+        // The public key is written without its key tag
+        let public_key: &PublicKey = (&self.public_key)
+            .try_into()
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+        public_key.write_to(output)
+    }
+}
+
+impl Keypair {
+    /// Generate a keypair. Dilithium key generation always draws from the
+    /// operating system random number generator, so the given one is unused.
+    pub fn generate<R>(network: Network, _csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let (public_key, secret) = dilithium3::keypair();
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(
+                network,
+                PublicKey(Box::new(public_key)),
+            ),
+            secret,
+        }
+    }
+
+    /// Dilithium keypairs can not be derived from entropy, so this always
+    /// fails
+    pub fn generate_from_entropy(_network: Network, _entropy: &[u8]) -> Result<Keypair> {
+        Err(Error::invalid_curve())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Dilithium,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.as_bytes().to_vec()
+    }
+}
+
+impl std::fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_tuple("PublicKey").finish()
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        let signature = dilithium3::DetachedSignature::from_bytes(signature)
+            .map_err(|_| signature::Error::new())?;
+        dilithium3::verify_detached_signature(&signature, msg, &self.0)
+            .map_err(|_| signature::Error::new().into())
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let buf = read_prefixed(input, RAW_PUBLIC_KEY_LENGTH)?;
+        let public_key =
+            dilithium3::PublicKey::from_bytes(&buf).map_err(|_| Error::invalid_curve())?;
+        Ok(PublicKey(Box::new(public_key)))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        write_prefixed(output, self.0.as_bytes())
+    }
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes() == other.0.as_bytes()
+    }
+}
+
+impl Eq for PublicKey {}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.0.as_bytes())
+    }
+}
+
+/// Returns the number of bytes in the unsigned varint encoding of the given
+/// value
+pub(crate) const fn varint_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// Writes the given bytes prefixed with their length as an unsigned varint
+pub(crate) fn write_prefixed<W: std::io::Write>(
+    output: &mut W,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut len = data.len();
+    while len >= 0x80 {
+        output.write_all(&[(len as u8 & 0x7f) | 0x80])?;
+        len >>= 7;
+    }
+    output.write_all(&[len as u8])?;
+    output.write_all(data)
+}
+
+/// Reads bytes prefixed with their length as an unsigned varint, which must
+/// be the given expected length
+pub(crate) fn read_prefixed<R: std::io::Read>(input: &mut R, expected: usize) -> Result<Vec<u8>> {
+    let mut len = 0usize;
+    for shift in (0..varint_len(expected) * 7).step_by(7) {
+        let mut byte = [0u8];
+        input.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            if len != expected {
+                return Err(Error::invalid_curve());
+            }
+            let mut buf = vec![0u8; len];
+            input.read_exact(&mut buf)?;
+            return Ok(buf);
+        }
+    }
+    Err(Error::invalid_curve())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(SIGNATURE_LENGTH, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_vec();
+        assert_eq!(KEYPAIR_LENGTH, bytes.len());
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+
+        let public_key = &keypair.public_key;
+        let bytes = public_key.to_vec();
+        assert_eq!(PUBLIC_KEY_LENGTH, bytes.len());
+        assert_eq!(
+            public_key,
+            &public_key::PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+    }
+
+    #[test]
+    fn length_prefix() {
+        let mut output = vec![];
+        write_prefixed(&mut output, &[0u8; 300]).expect("write");
+        assert_eq!([0xac, 0x02], output[..2]);
+        assert_eq!(302, output.len());
+        assert!(read_prefixed(&mut std::io::Cursor::new(&output), 300).is_ok());
+        assert!(read_prefixed(&mut std::io::Cursor::new(&output), 301).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! Distributed key generation for FROST threshold keys.
+//!
+//! Implements the Pedersen DKG with Feldman verifiable secret sharing from
+//! the [FROST paper][FROST], so that `max_signers` participants can create a
+//! [`frost::KeyPackage`] each without any one of them, or a dealer, ever
+//! holding the group secret. The group public key is a plain ed25519
+//! [`PublicKey`], and any `min_signers` of the participants can sign for it
+//! with the [`frost`] module.
+//!
+//! Every participant picks a distinct non-zero identifier up to
+//! `max_signers`, and the ceremony takes two rounds:
+//!
+//! 1. Every participant calls [`part1`] and broadcasts the resulting
+//!    [`Round1Package`] to all other participants.
+//! 2. Once it has the round one packages of all other participants, every
+//!    participant calls [`part2`], which checks them and returns a
+//!    [`Round2Package`] for every other participant. These carry secret
+//!    shares and must be sent to their recipient only, over a confidential
+//!    channel.
+//!
+//! Finally, every participant calls [`part3`] with the round two packages
+//! sent to it, which checks every share against the commitments of its
+//! sender and returns the key package of the participant.
+//!
+//! The round messages implement serde serialization for transport.
+//!
+//! [FROST]: https://eprint.iacr.org/2020/852
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, random_scalar, scalar_from_slice},
+    frost::{identifier_scalar, KeyPackage, CONTEXT},
+    *,
+};
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar};
+use serde::{Deserialize, Serialize};
+
+/// The secret state of a participant between [`part1`] and [`part2`]
+pub struct Round1Secret {
+    identifier: u16,
+    max_signers: u16,
+    min_signers: u16,
+    coefficients: Vec<Scalar>,
+}
+
+/// The round one message of a participant, broadcast to all other
+/// participants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Round1Package {
+    pub identifier: u16,
+    /// The commitments to the coefficients of the secret polynomial of the
+    /// participant
+    pub commitments: Vec<[u8; 32]>,
+    /// The nonce commitment of the proof of knowledge of the secret of the
+    /// participant
+    pub proof_r: [u8; 32],
+    /// The response of the proof of knowledge of the secret of the
+    /// participant
+    pub proof_mu: [u8; 32],
+}
+
+/// The secret state of a participant between [`part2`] and [`part3`]
+pub struct Round2Secret {
+    identifier: u16,
+    min_signers: u16,
+    own_share: Scalar,
+    commitments: Vec<(u16, Vec<EdwardsPoint>)>,
+}
+
+/// The round two message from one participant to another, carrying the
+/// secret share of the recipient
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Round2Package {
+    pub sender: u16,
+    pub recipient: u16,
+    pub share: [u8; 32],
+}
+
+impl std::fmt::Debug for Round2Package {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Round2Package")
+            .field("sender", &self.sender)
+            .field("recipient", &self.recipient)
+            .finish()
+    }
+}
+
+/// Round one: pick the secret polynomial of the participant with the given
+/// identifier. Keep the returned secret and broadcast the package.
+pub fn part1<R>(
+    identifier: u16,
+    max_signers: u16,
+    min_signers: u16,
+    csprng: &mut R,
+) -> Result<(Round1Secret, Round1Package)>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    if identifier == 0 || identifier > max_signers || min_signers < 2 || min_signers > max_signers {
+        return Err(Error::invalid_threshold());
+    }
+    let coefficients: Vec<Scalar> = (0..min_signers).map(|_| random_scalar(csprng)).collect();
+    let commitments: Vec<[u8; 32]> = coefficients
+        .iter()
+        .map(|coefficient| base_mul(coefficient).compress().to_bytes())
+        .collect();
+
+    // Schnorr proof of knowledge of the constant coefficient, which keeps a
+    // participant from cancelling out the secrets of others
+    let nonce = random_scalar(csprng);
+    let proof_r = base_mul(&nonce).compress().to_bytes();
+    let c = proof_challenge(identifier, &commitments[0], &proof_r);
+    let proof_mu = (nonce + coefficients[0] * c).to_bytes();
+
+    Ok((
+        Round1Secret {
+            identifier,
+            max_signers,
+            min_signers,
+            coefficients,
+        },
+        Round1Package {
+            identifier,
+            commitments,
+            proof_r,
+            proof_mu,
+        },
+    ))
+}
+
+/// Round two: check the round one packages of all other participants and
+/// compute their shares. Keep the returned secret and send every package to
+/// its recipient only.
+pub fn part2(
+    secret: Round1Secret,
+    round1_packages: &[Round1Package],
+) -> Result<(Round2Secret, Vec<Round2Package>)> {
+    if round1_packages.len() != secret.max_signers as usize - 1 {
+        return Err(Error::invalid_threshold());
+    }
+    let mut commitments = vec![(
+        secret.identifier,
+        secret.coefficients.iter().map(base_mul).collect::<Vec<_>>(),
+    )];
+    for package in round1_packages {
+        if package.identifier == 0
+            || package.identifier > secret.max_signers
+            || package.commitments.len() != secret.min_signers as usize
+            || commitments
+                .iter()
+                .any(|(identifier, _)| *identifier == package.identifier)
+        {
+            return Err(Error::invalid_threshold());
+        }
+        let points = package
+            .commitments
+            .iter()
+            .map(|commitment| decompress(commitment))
+            .collect::<Result<Vec<_>>>()?;
+        let c = proof_challenge(
+            package.identifier,
+            &package.commitments[0],
+            &package.proof_r,
+        );
+        let mu = scalar_from_slice(&package.proof_mu)?;
+        if base_mul(&mu) - c * points[0] != decompress(&package.proof_r)? {
+            return Err(signature::Error::new().into());
+        }
+        commitments.push((package.identifier, points));
+    }
+
+    let packages = round1_packages
+        .iter()
+        .map(|package| Round2Package {
+            sender: secret.identifier,
+            recipient: package.identifier,
+            share: evaluate(&secret.coefficients, package.identifier).to_bytes(),
+        })
+        .collect();
+    Ok((
+        Round2Secret {
+            identifier: secret.identifier,
+            min_signers: secret.min_signers,
+            own_share: evaluate(&secret.coefficients, secret.identifier),
+            commitments,
+        },
+        packages,
+    ))
+}
+
+/// Finish the ceremony: check the round two packages sent to the participant
+/// against the commitments of their senders, and return the key package of
+/// the participant for a group public key on the given network.
+pub fn part3(
+    network: Network,
+    secret: Round2Secret,
+    round2_packages: &[Round2Package],
+) -> Result<KeyPackage> {
+    if round2_packages.len() != secret.commitments.len() - 1 {
+        return Err(Error::invalid_threshold());
+    }
+    let x = identifier_scalar(secret.identifier);
+    let mut signing_share = secret.own_share;
+    let mut group_point = EdwardsPoint::default();
+    for (sender, commitments) in &secret.commitments {
+        group_point += commitments[0];
+        if *sender == secret.identifier {
+            continue;
+        }
+        let package = round2_packages
+            .iter()
+            .find(|package| package.sender == *sender && package.recipient == secret.identifier)
+            .ok_or_else(Error::invalid_threshold)?;
+        let share = scalar_from_slice(&package.share)?;
+        // Feldman check of the share against the commitments of its sender
+        let expected = commitments
+            .iter()
+            .rev()
+            .fold(EdwardsPoint::default(), |acc, commitment| {
+                acc * x + commitment
+            });
+        if base_mul(&share) != expected {
+            return Err(signature::Error::new().into());
+        }
+        signing_share += share;
+    }
+    if group_point.is_small_order() {
+        return Err(Error::invalid_curve());
+    }
+    Ok(KeyPackage {
+        identifier: secret.identifier,
+        signing_share: signing_share.to_bytes(),
+        group_public_key: frost::group_public_key(network, &group_point)?,
+        min_signers: secret.min_signers,
+    })
+}
+
+fn proof_challenge(identifier: u16, commitment: &[u8], proof_r: &[u8]) -> Scalar {
+    hash_to_scalar(&[
+        CONTEXT,
+        b"dkg",
+        identifier_scalar(identifier).as_bytes(),
+        commitment,
+        proof_r,
+    ])
+}
+
+/// Horner evaluation of the polynomial with the given coefficients at the
+/// given identifier
+fn evaluate(coefficients: &[Scalar], identifier: u16) -> Scalar {
+    let x = identifier_scalar(identifier);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn round1(max_signers: u16, min_signers: u16) -> (Vec<Round1Secret>, Vec<Round1Package>) {
+        (1..=max_signers)
+            .map(|identifier| {
+                part1(identifier, max_signers, min_signers, &mut OsRng).expect("part1")
+            })
+            .unzip()
+    }
+
+    fn others<T: Clone>(packages: &[T], index: usize) -> Vec<T> {
+        packages
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(_, package)| package.clone())
+            .collect()
+    }
+
+    fn round2(
+        secrets: Vec<Round1Secret>,
+        packages: &[Round1Package],
+    ) -> (Vec<Round2Secret>, Vec<Round2Package>) {
+        let mut round2_secrets = vec![];
+        let mut round2_packages = vec![];
+        for (index, secret) in secrets.into_iter().enumerate() {
+            let (secret, packages) = part2(secret, &others(packages, index)).expect("part2");
+            round2_secrets.push(secret);
+            round2_packages.extend(packages);
+        }
+        (round2_secrets, round2_packages)
+    }
+
+    fn received(packages: &[Round2Package], identifier: u16) -> Vec<Round2Package> {
+        packages
+            .iter()
+            .filter(|package| package.recipient == identifier)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn ceremony() {
+        let (secrets, packages) = round1(5, 3);
+        let (secrets, packages) = round2(secrets, &packages);
+        let key_packages: Vec<KeyPackage> = secrets
+            .into_iter()
+            .map(|secret| {
+                let identifier = secret.identifier;
+                part3(Network::MainNet, secret, &received(&packages, identifier)).expect("part3")
+            })
+            .collect();
+        let group_public_key = &key_packages[0].group_public_key;
+        assert_eq!(KeyType::Ed25519, group_public_key.key_type());
+        assert!(key_packages
+            .iter()
+            .all(|package| &package.group_public_key == group_public_key));
+
+        // Any threshold of participants can sign for the group key
+        let signers = [&key_packages[4], &key_packages[0], &key_packages[2]];
+        let (nonces, commitments): (Vec<_>, Vec<_>) = signers
+            .iter()
+            .map(|package| frost::commit(package, &mut OsRng))
+            .unzip();
+        let signing_package = frost::SigningPackage {
+            message: b"hello world".to_vec(),
+            commitments,
+        };
+        let shares = signers
+            .iter()
+            .zip(nonces)
+            .map(|(package, nonces)| frost::sign(&signing_package, nonces, package))
+            .collect::<Result<Vec<_>>>()
+            .expect("shares");
+        let signature =
+            frost::aggregate(&signing_package, &shares, group_public_key).expect("signature");
+        assert!(group_public_key.verify(b"hello world", &signature).is_ok());
+    }
+
+    #[test]
+    fn bad_proof() {
+        let (mut secrets, mut packages) = round1(3, 2);
+        packages[1].proof_mu = Scalar::one().to_bytes();
+        assert!(part2(secrets.remove(0), &others(&packages, 0)).is_err());
+        // A missing participant
+        assert!(part2(secrets.remove(0), &packages[2..]).is_err());
+    }
+
+    #[test]
+    fn bad_share() {
+        let (secrets, packages) = round1(3, 2);
+        let (mut secrets, packages) = round2(secrets, &packages);
+        let mut packages = received(&packages, 1);
+        packages[0].share = Scalar::one().to_bytes();
+        assert!(part3(Network::MainNet, secrets.remove(0), &packages).is_err());
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let (_, package) = part1(1, 3, 2, &mut OsRng).expect("part1");
+        let json = serde_json::to_string(&package).expect("serialize");
+        assert_eq!(package, serde_json::from_str(&json).expect("deserialize"));
+    }
+}
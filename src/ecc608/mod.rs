@@ -1,24 +1,101 @@
 use crate::{
+    deadline,
     ecc_compact::{self, Signature},
-    keypair, public_key, Error, KeyTag, KeyType as CrateKeyType, Network, Result,
+    health::{FailureCount, Health},
+    keypair, public_key,
+    retry::RetryPolicy,
+    telemetry, Error, KeyTag, KeyType as CrateKeyType, Network, Result,
 };
 pub use ecc608_linux::{
     address, key_config, slot_config, Ecc, KeyConfig, KeyType, SlotConfig, Zone, MAX_SLOT,
 };
+use key_config::KeyConfigType;
 
+pub mod provision;
+pub mod tng;
+
+use lazy_static::lazy_static;
 use p256::{ecdsa, elliptic_curve};
 use std::{
+    collections::HashMap,
     convert::{TryFrom, TryInto},
-    sync::{Mutex, Once},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
 };
 
-static INIT: Once = Once::new();
-static mut ECC: Option<Mutex<Ecc>> = None;
+/// An ECC608 on an I2C bus, identified by the path of the bus and the address
+/// of the ECC on it. Any number of devices can be initialized and used at the
+/// same time, each with its own connection. The first device initialized is
+/// the default device, which the free functions of this module and
+/// [`Keypair::from_slot`] use.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Device {
+    pub path: String,
+    pub address: u16,
+}
+
+/// An open ECC along with the device it was opened for
+struct Connection {
+    ecc: Ecc,
+    device: Device,
+    failures: FailureCount,
+    retry_policy: RetryPolicy,
+}
+
+impl Connection {
+    fn reopen(&mut self) -> Result {
+        self.ecc = Ecc::from_path(&self.device.path, self.device.address)?;
+        Ok(())
+    }
+}
+
+/// The retry policy of a newly initialized device: a single immediate retry
+/// of operations which fail with a [`crate::ErrorClass::Transport`] or
+/// [`crate::ErrorClass::Timeout`] error. Errors reported by the ECC itself are
+/// [`crate::ErrorClass::Device`] errors and fail right away. Wake and idle timing is
+/// fixed by the ECC608 driver and is not part of the policy.
+pub fn default_retry_policy() -> RetryPolicy {
+    RetryPolicy::default()
+        .with_max_attempts(2)
+        .with_backoff(Duration::ZERO, Duration::ZERO)
+}
+
+/// The connection of a device, which is `None` while the device is closed
+type Handle = Arc<Mutex<Option<Connection>>>;
+
+/// The connections of all devices ever initialized, and the default device
+#[derive(Default)]
+struct Registry {
+    devices: HashMap<Device, Handle>,
+    default: Option<Device>,
+}
+
+lazy_static! {
+    static ref ECCS: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+/// Locks the registry of devices. The registry is only locked to look up a
+/// device, and no device is locked while holding it.
+fn registry() -> MutexGuard<'static, Registry> {
+    ECCS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the default device, failing with a closed error if no device was
+/// initialized
+fn default_device() -> Result<Device> {
+    registry().default.clone().ok_or_else(Error::closed)
+}
+
+/// The slot holding the key of a keypair unless given otherwise
+pub const DEFAULT_SLOT: u8 = 0;
 
 pub struct Keypair {
     pub network: Network,
     pub public_key: public_key::PublicKey,
+    device: Device,
     slot: u8,
+    timeout: Option<Duration>,
 }
 
 impl PartialEq for Keypair {
@@ -30,6 +107,8 @@ impl PartialEq for Keypair {
 impl std::fmt::Debug for Keypair {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("Keypair")
+            .field("device", &self.device)
+            .field("slot", &self.slot)
             .field("tag", &self.key_tag())
             .field("public", &self.public_key)
             .finish()
@@ -38,57 +117,355 @@ impl std::fmt::Debug for Keypair {
 
 impl keypair::Sign for Keypair {
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
-        use signature::Signer;
-        let signature = self.try_sign(msg)?;
+        let signature = self.sign_with_deadline(msg)?;
         Ok(signature.to_vec())
     }
 }
 
+/// Initializes the ECC at the given address on the I2C bus at the given
+/// path. The first ECC initialized becomes the default device.
 pub fn init(path: &str, address: u16) -> Result {
-    if INIT.is_completed() {
-        return Ok(());
+    init_with_timeout(path, address, None)
+}
+
+/// Initializes the ECC like [`init`], failing with a timeout error if the ECC
+/// can not be opened in the given time.
+pub fn init_with_timeout(path: &str, address: u16, timeout: Option<Duration>) -> Result {
+    Device::new(path, address).init_with_timeout(timeout)
+}
+
+/// Returns the health of the default ECC connection
+pub fn health() -> Health {
+    default_device().map_or(Health::Uninitialized, |device| device.health())
+}
+
+/// Reopens the default ECC using the path and address it was initialized
+/// with. Fails with a closed error if no ECC was initialized.
+pub fn reconnect() -> Result {
+    default_device()?.reconnect()
+}
+
+/// Closes all initialized ECCs once any in-flight operations complete. The
+/// ECC608 driver puts the chip to sleep after every command, so closing the
+/// connection leaves the chip asleep. Later operations fail with a closed
+/// error until an ECC is initialized again.
+pub fn shutdown() {
+    let handles: Vec<Handle> = {
+        let mut registry = registry();
+        registry.default = None;
+        registry.devices.values().cloned().collect()
+    };
+    for handle in handles {
+        *lock(&handle) = None;
+    }
+}
+
+impl Device {
+    pub fn new(path: &str, address: u16) -> Self {
+        Self {
+            path: path.to_string(),
+            address,
+        }
+    }
+
+    /// Opens this device unless it is open already.
+    pub fn init(&self) -> Result {
+        self.init_with_timeout(None)
+    }
+
+    /// Opens this device like [`Device::init`], failing with a timeout error
+    /// if the ECC can not be opened in the given time.
+    pub fn init_with_timeout(&self, timeout: Option<Duration>) -> Result {
+        let handle = self.handle();
+        if lock(&handle).is_none() {
+            let device = self.clone();
+            let ecc = telemetry::span("init", None, "ecc608").in_scope(|| {
+                deadline::run(timeout, move || {
+                    Ok(ecc608_linux::Ecc::from_path(&device.path, device.address)?)
+                })
+            })?;
+            lock(&handle).get_or_insert_with(|| Connection {
+                ecc,
+                device: self.clone(),
+                failures: FailureCount::default(),
+                retry_policy: default_retry_policy(),
+            });
+        }
+        registry().default.get_or_insert_with(|| self.clone());
+        Ok(())
+    }
+
+    /// Returns the health of the connection to this device
+    pub fn health(&self) -> Health {
+        lock(&self.handle())
+            .as_ref()
+            .map_or(Health::Uninitialized, |connection| {
+                connection.failures.health()
+            })
     }
-    let ecc = ecc608_linux::Ecc::from_path(path, address)?;
-    unsafe {
-        INIT.call_once(|| ECC = Some(Mutex::new(ecc)));
+
+    /// Reopens this device. Fails with a closed error if the device is not
+    /// initialized.
+    pub fn reconnect(&self) -> Result {
+        lock(&self.handle())
+            .as_mut()
+            .ok_or_else(Error::closed)?
+            .reopen()
+    }
+
+    /// Closes this device once any in-flight operation completes, like
+    /// [`shutdown`] does for all devices. Closing the default device leaves
+    /// no default device until another device is initialized.
+    pub fn shutdown(&self) {
+        *lock(&self.handle()) = None;
+        let mut registry = registry();
+        if registry.default.as_ref() == Some(self) {
+            registry.default = None;
+        }
+    }
+
+    /// Returns the connection of this device, registering it as closed if it
+    /// was never initialized
+    fn handle(&self) -> Handle {
+        registry().devices.entry(self.clone()).or_default().clone()
+    }
+
+    /// Locks this device and runs the given function, passing in the ECC.
+    /// Fails with a closed error if the device is not initialized.
+    pub fn with_ecc<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Ecc) -> Result<R>,
+    {
+        let handle = self.handle();
+        let mut connection = lock(&handle);
+        f(&mut connection.as_mut().ok_or_else(Error::closed)?.ecc)
+    }
+
+    /// Sets the retry policy for operations on this device, which applies
+    /// until the device is closed. Fails with a closed error if the device is
+    /// not initialized.
+    pub fn set_retry_policy(&self, retry_policy: RetryPolicy) -> Result {
+        lock(&self.handle())
+            .as_mut()
+            .ok_or_else(Error::closed)?
+            .retry_policy = retry_policy;
+        Ok(())
+    }
+
+    /// Runs the given operation on this device. If the operation fails with an
+    /// error the retry policy of the device retries, the ECC is reopened and
+    /// the operation is replayed after the backoff delay. The device stays
+    /// locked while waiting to retry, so operations waiting for the ECC in the
+    /// meantime run against the reopened ECC.
+    fn supervised<F, R>(&self, f: F) -> Result<R>
+    where
+        F: Fn(&mut Ecc) -> std::result::Result<R, ecc608_linux::Error>,
+    {
+        let handle = self.handle();
+        let mut connection = lock(&handle);
+        let connection = connection.as_mut().ok_or_else(Error::closed)?;
+        let mut result = f(&mut connection.ecc).map_err(Error::from);
+        for attempt in 1..connection.retry_policy.max_attempts {
+            match &result {
+                Err(err) if connection.retry_policy.is_retryable(err) => (),
+                _ => break,
+            }
+            thread::sleep(connection.retry_policy.backoff(attempt));
+            if connection.reopen().is_ok() {
+                result = f(&mut connection.ecc).map_err(Error::from);
+            }
+        }
+        connection.failures.record(&result);
+        result
+    }
+
+    /// Reads the serial number, revision and zone lock status of this device
+    pub fn device_info(&self) -> Result<DeviceInfo> {
+        self.supervised(|ecc| {
+            Ok(DeviceInfo {
+                serial: ecc.get_serial()?.to_vec(),
+                revision: ecc.get_info()?.to_vec(),
+                config_locked: ecc.get_locked(&Zone::Config)?,
+                data_locked: ecc.get_locked(&Zone::Data)?,
+            })
+        })
+    }
+
+    /// Returns the slots of this device which hold a P-256 private key with
+    /// a compactable public key, like [`key_slots`].
+    pub fn key_slots(&self) -> Result<Vec<u8>> {
+        self.with_ecc(|ecc| {
+            let mut slots = Vec::new();
+            for slot in 0..=MAX_SLOT {
+                let key_config = ecc.get_key_config(slot)?;
+                if !key_config.private() || key_config.key_type() != KeyConfigType::Ecc {
+                    continue;
+                }
+                // A slot configured for a private key may not have had a key
+                // generated in it yet, in which case the chip refuses to
+                // derive a public key or derives one which is not a valid
+                // point.
+                if let Ok(bytes) = ecc.genkey(KeyType::Public, slot) {
+                    if public_key_from_bytes(bytes.as_ref()).is_ok() {
+                        slots.push(slot);
+                    }
+                }
+            }
+            Ok(slots)
+        })
+    }
+
+    /// Computes the SHA-256 digest of the given message with the SHA engine
+    /// of this device.
+    pub fn sha256(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.supervised(|ecc| Ok(ecc.sha256(msg)?.to_vec()))
+    }
+
+    /// Computes the HMAC-SHA256 of the given message keyed with the secret
+    /// stored in the given slot of this device. The secret never leaves the
+    /// device, so the resulting MAC is bound to it.
+    pub fn hmac(&self, slot: u8, msg: &[u8]) -> Result<Vec<u8>> {
+        self.supervised(|ecc| Ok(ecc.hmac(slot, msg)?.to_vec()))
+    }
+}
+
+/// Locks the given device connection. A panic while holding the lock leaves
+/// the connection itself intact, so a poisoned lock is recovered rather than
+/// failing every later operation.
+fn lock(handle: &Handle) -> MutexGuard<'_, Option<Connection>> {
+    handle.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Identifying information about the ECC608 chip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// The unique 9 byte serial number of the chip
+    pub serial: Vec<u8>,
+    /// The revision of the chip as returned by its Info command
+    pub revision: Vec<u8>,
+    /// Whether the configuration zone is locked
+    pub config_locked: bool,
+    /// Whether the data zone is locked
+    pub data_locked: bool,
+}
+
+impl DeviceInfo {
+    /// Returns the serial number as a lower case hex string, like the
+    /// serial numbers printed by the Microchip tools.
+    pub fn serial_string(&self) -> String {
+        self.serial.iter().map(|b| format!("{:02x}", b)).collect()
     }
-    Ok(())
 }
 
-fn ecc<'a>() -> &'a Mutex<Ecc> {
-    unsafe { ECC.as_ref().unwrap() }
+/// Sets the retry policy for operations on the default ECC. Fails with a
+/// closed error if no ECC was initialized.
+pub fn set_retry_policy(retry_policy: RetryPolicy) -> Result {
+    default_device()?.set_retry_policy(retry_policy)
+}
+
+/// Reads the serial number, revision and zone lock status of the default
+/// ECC. Fails with a closed error if no ECC was initialized.
+pub fn device_info() -> Result<DeviceInfo> {
+    default_device()?.device_info()
+}
+
+/// Returns the slots of the default ECC which hold a P-256 private key with a
+/// compactable public key, in slot order. Keypairs can be constructed from
+/// any of these slots with [`Keypair::from_slot`], and keypairs for
+/// different slots can be held and used at the same time.
+///
+/// Slots are checked while holding the lock on the ECC, so no other
+/// operation runs during the enumeration. Fails with a closed error if no ECC
+/// was initialized.
+pub fn key_slots() -> Result<Vec<u8>> {
+    default_device()?.key_slots()
+}
+
+/// Computes the SHA-256 digest of the given message with the SHA engine of
+/// the default ECC. Fails with a closed error if no ECC was initialized.
+pub fn sha256(msg: &[u8]) -> Result<Vec<u8>> {
+    default_device()?.sha256(msg)
+}
+
+/// Computes the HMAC-SHA256 of the given message keyed with the secret
+/// stored in the given slot of the default ECC. The slot must be configured
+/// for a 32 byte secret which can be used by the HMAC command. Fails with a
+/// closed error if no ECC was initialized.
+pub fn hmac(slot: u8, msg: &[u8]) -> Result<Vec<u8>> {
+    default_device()?.hmac(slot, msg)
+}
+
+/// Returns the public key for the given x and y coordinates read from a slot
+fn public_key_from_bytes(bytes: &[u8]) -> Result<ecc_compact::PublicKey> {
+    // Start with the "decompressed" sec1 tag since the ecc does not include it.
+    let mut key_bytes = vec![4u8];
+    // Add the keybytes from the slot.
+    key_bytes.extend_from_slice(bytes);
+    ecc_compact::PublicKey::try_from(key_bytes.as_ref())
 }
 
 impl Keypair {
-    /// Constructs a keypair from the given slot. The returned keypair will use
-    /// the private key in the given slot to sign data.
+    /// Constructs a keypair from the given slot, one of [`DEFAULT_SLOT`] to
+    /// [`MAX_SLOT`]. The returned keypair will use the private key in the
+    /// given slot of the default ECC to sign data. Keypairs for different
+    /// slots share the ECC and can be used at the same time.
     ///
     /// NOTE: The init function _must have been called once, before using this
     /// function.
     pub fn from_slot(network: Network, slot: u8) -> Result<Keypair> {
-        with_ecc(|ecc| Self::from_ecc_slot(ecc, network, slot))
+        Self::from_device_slot(&default_device()?, network, slot)
+    }
+
+    /// Constructs a keypair from the given slot of the given device, which
+    /// must have been initialized.
+    pub fn from_device_slot(device: &Device, network: Network, slot: u8) -> Result<Keypair> {
+        device.with_ecc(|ecc| Self::from_device_ecc_slot(device, ecc, network, slot))
     }
 
     /// Constructs a keypair from the given slot using the given ECC. The
-    /// returned keypair will use the private key in the given slot to sign
-    /// data.
+    /// returned keypair will use the private key in the given slot of the
+    /// default ECC to sign data.
     ///
     /// The normal use case is to call this function within the `with_ecc`
     /// callback to use a locked global instance of the ECC.
     pub fn from_ecc_slot(ecc: &mut Ecc, network: Network, slot: u8) -> Result<Keypair> {
+        Self::from_device_ecc_slot(&default_device()?, ecc, network, slot)
+    }
+
+    fn from_device_ecc_slot(
+        device: &Device,
+        ecc: &mut Ecc,
+        network: Network,
+        slot: u8,
+    ) -> Result<Keypair> {
         let bytes = ecc.genkey(KeyType::Public, slot)?;
-        // Start with the "decompressed" sec1 tag since the ecc does not include it.
-        let mut key_bytes = vec![4u8];
-        // Add the keybytes from the slot.
-        key_bytes.extend_from_slice(bytes.as_ref());
-        let public_key = ecc_compact::PublicKey::try_from(key_bytes.as_ref())?;
+        let public_key = public_key_from_bytes(bytes.as_ref())?;
         Ok(Keypair {
+            device: device.clone(),
             slot,
             network,
             public_key: public_key::PublicKey::for_network(network, public_key),
+            timeout: None,
         })
     }
 
+    /// Set the timeout for sign and ecdh operations on this keypair. An
+    /// operation which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the slot holding the private key of this keypair
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    /// Returns the device holding the private key of this keypair
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
     pub fn key_tag(&self) -> KeyTag {
         KeyTag {
             network: self.network,
@@ -103,33 +480,42 @@ impl Keypair {
         use elliptic_curve::sec1::ToEncodedPoint;
         let key = public_key.try_into()?;
         let point = key.0.to_encoded_point(false);
-        let shared_secret_bytes =
-            with_ecc(|ecc| ecc.ecdh(self.slot, point.x().unwrap(), point.y().unwrap()))?;
+        let slot = self.slot;
+        let device = self.device.clone();
+        let (x, y) = (*point.x().unwrap(), *point.y().unwrap());
+        let shared_secret_bytes = deadline::run(self.timeout, move || {
+            device.supervised(|ecc| ecc.ecdh(slot, &x, &y))
+        })?;
         Ok(ecc_compact::SharedSecret(p256::ecdh::SharedSecret::from(
             *p256::FieldBytes::from_slice(&shared_secret_bytes),
         )))
     }
+
+    fn sign_with_deadline(&self, msg: &[u8]) -> Result<Signature> {
+        let slot = self.slot;
+        let device = self.device.clone();
+        let msg = msg.to_vec();
+        let bytes = deadline::run(self.timeout, move || {
+            device.supervised(|ecc| ecc.sign(slot, &msg))
+        })?;
+        let signature = ecdsa::Signature::try_from(&bytes[..])?;
+        Ok(Signature(signature))
+    }
 }
 
-/// Locks the global ECC and runs the given function, passing in the ECC. The
-/// lock on the ecc is dropped as soon as this function returns.
-pub fn with_ecc<F, R>(f: F) -> R
+/// Locks the default ECC and runs the given function, passing in the ECC.
+/// The lock on the ecc is dropped as soon as this function returns. Fails
+/// with a closed error if no ECC was initialized or it was shut down.
+pub fn with_ecc<F, R>(f: F) -> Result<R>
 where
-    F: FnOnce(&mut Ecc) -> R,
+    F: FnOnce(&mut Ecc) -> Result<R>,
 {
-    let mut ecc = ecc().lock().unwrap();
-    f(&mut ecc)
+    default_device()?.with_ecc(f)
 }
 
 impl signature::Signer<Signature> for Keypair {
     fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
-        let sign_result = with_ecc(|ecc| ecc.sign(self.slot, msg));
-        match sign_result {
-            Ok(bytes) => {
-                let signature = ecdsa::Signature::try_from(&bytes[..])?;
-                Ok(Signature(signature))
-            }
-            Err(err) => Err(signature::Error::from_source(err)),
-        }
+        self.sign_with_deadline(msg)
+            .map_err(signature::Error::from_source)
     }
 }
@@ -0,0 +1,153 @@
+//! Provisioning of ECC608 secure elements for use with this crate.
+//!
+//! Provisioning writes the slot and key configuration of a [`Layout`] to the
+//! configuration zone, locks the configuration and data zones, and generates
+//! a private key in every key slot of the layout. Each of these is a
+//! [`Step`], and only the steps the secure element still needs are taken, so
+//! provisioning a partially or fully provisioned secure element again is
+//! safe. Keys already present in a key slot are never replaced.
+//!
+//! Locking a zone can not be undone. A dry run returns the steps provisioning
+//! would take without writing anything, so the steps can be reviewed first.
+use super::{
+    default_device, public_key_from_bytes, Device, Ecc, KeyConfig, KeyType, SlotConfig, Zone,
+    DEFAULT_SLOT, MAX_SLOT,
+};
+use crate::{telemetry, Result};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("configuration zone locked with a different configuration for slot {0}")]
+    ConfigMismatch(u8),
+
+    #[error("invalid key slot {0}")]
+    InvalidSlot(u8),
+}
+
+/// The configuration zone layout to provision and the slots to generate keys
+/// in
+#[derive(Debug)]
+pub struct Layout {
+    /// The slot configuration written for every slot
+    pub slot_config: SlotConfig,
+    /// The key configuration written for every slot
+    pub key_config: KeyConfig,
+    /// The slots to generate a private key in
+    pub key_slots: Vec<u8>,
+}
+
+impl Default for Layout {
+    /// The layout used for Helium hotspots, with the slot and key
+    /// configuration of the ECC608 driver defaults for every slot and a key
+    /// in [`DEFAULT_SLOT`].
+    fn default() -> Self {
+        Self {
+            slot_config: SlotConfig::default(),
+            key_config: KeyConfig::default(),
+            key_slots: vec![DEFAULT_SLOT],
+        }
+    }
+}
+
+/// A single provisioning step
+#[derive(Debug)]
+pub enum Step {
+    /// Write the slot configuration of the given slot
+    SlotConfig(u8),
+    /// Write the key configuration of the given slot
+    KeyConfig(u8),
+    /// Lock the configuration zone
+    LockConfig,
+    /// Lock the data zone
+    LockData,
+    /// Generate a private key in the given slot
+    GenerateKey(u8),
+}
+
+/// Provisions the default ECC with the given layout, returning the steps
+/// taken, or with `dry_run` the steps that would be taken without taking
+/// them. The lock on the ECC is held for the whole of the provisioning.
+///
+/// Fails with a closed error if no ECC was initialized.
+pub fn provision(layout: &Layout, dry_run: bool) -> Result<Vec<Step>> {
+    provision_device(&default_device()?, layout, dry_run)
+}
+
+/// Provisions the given device like [`provision`].
+pub fn provision_device(device: &Device, layout: &Layout, dry_run: bool) -> Result<Vec<Step>> {
+    device.with_ecc(|ecc| provision_ecc(ecc, layout, dry_run))
+}
+
+/// Provisions the given ECC like [`provision`].
+pub fn provision_ecc(ecc: &mut Ecc, layout: &Layout, dry_run: bool) -> Result<Vec<Step>> {
+    telemetry::span("provision", None, "ecc608").in_scope(|| {
+        let steps = plan(ecc, layout)?;
+        if !dry_run {
+            for step in &steps {
+                apply(ecc, layout, step)?;
+            }
+        }
+        Ok(steps)
+    })
+}
+
+/// Returns the steps needed to provision the given ECC with the given layout
+fn plan(ecc: &mut Ecc, layout: &Layout) -> Result<Vec<Step>> {
+    if let Some(slot) = layout.key_slots.iter().find(|slot| **slot > MAX_SLOT) {
+        return Err(Error::InvalidSlot(*slot).into());
+    }
+    let mut steps = Vec::new();
+    let config_locked = ecc.get_locked(&Zone::Config)?;
+    for slot in 0..=MAX_SLOT {
+        let slot_matches = ecc.get_slot_config(slot)? == layout.slot_config;
+        let key_matches = ecc.get_key_config(slot)? == layout.key_config;
+        if config_locked && !(slot_matches && key_matches) {
+            return Err(Error::ConfigMismatch(slot).into());
+        }
+        if !slot_matches {
+            steps.push(Step::SlotConfig(slot));
+        }
+        if !key_matches {
+            steps.push(Step::KeyConfig(slot));
+        }
+    }
+    if !config_locked {
+        steps.push(Step::LockConfig);
+    }
+    if !ecc.get_locked(&Zone::Data)? {
+        steps.push(Step::LockData);
+    }
+    for slot in &layout.key_slots {
+        // Public keys can only be derived once the configuration is locked,
+        // and a slot without a generated key derives no valid public key.
+        // Device errors are returned rather than taken to mean there is no
+        // key, since generating a key would replace one that is present.
+        let has_key = config_locked && {
+            let bytes = ecc.genkey(KeyType::Public, *slot)?;
+            public_key_from_bytes(bytes.as_ref()).is_ok()
+        };
+        if !has_key {
+            steps.push(Step::GenerateKey(*slot));
+        }
+    }
+    Ok(steps)
+}
+
+fn apply(ecc: &mut Ecc, layout: &Layout, step: &Step) -> Result {
+    match step {
+        Step::SlotConfig(slot) => ecc.set_slot_config(*slot, &layout.slot_config)?,
+        Step::KeyConfig(slot) => ecc.set_key_config(*slot, &layout.key_config)?,
+        Step::LockConfig => ecc.set_locked(Zone::Config)?,
+        Step::LockData => ecc.set_locked(Zone::Data)?,
+        // Keypairs need a compactable public key, so keys which are not are
+        // replaced until one is.
+        Step::GenerateKey(slot) => loop {
+            let bytes = ecc.genkey(KeyType::Private, *slot)?;
+            if public_key_from_bytes(bytes.as_ref()).is_ok() {
+                break;
+            }
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,605 @@
+//! Factory provisioned identities of Microchip Trust&GO parts.
+//!
+//! ATECC608B-TNGTLS parts ship with a device key in slot 0 and a chain of
+//! certificates stored in compressed form: the device certificate in slot 10,
+//! the public key of the signer in slot 11 and the signer certificate in slot
+//! 12. A compressed certificate holds only what varies between certificates,
+//! the signature, validity dates and identifiers, and the full X.509
+//! certificate is rebuilt from it with the certificate template it names.
+//!
+//! This module reads and decodes the compressed certificates and the signer
+//! public key, and rebuilds the DER encoded X.509 certificates with the
+//! TNGTLS device and signer certificate templates of CryptoAuthLib. Serial
+//! numbers are rebuilt from a hash of the public key and the compressed
+//! dates, the only serial number source TNGTLS certificates use.
+//!
+//! The signer certificate is issued by the Microchip root certificate
+//! authority, and its authority key identifier is derived from the root
+//! public key, so the root public key has to be given to rebuild it.
+use super::{address::Address, Device, Ecc, KeyType};
+use crate::{
+    cert::{integer, sequence, set, tlv, TAG_OCTET_STRING, TAG_OID},
+    ecc_compact, Result,
+};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::convert::{TryFrom, TryInto};
+use thiserror::Error;
+
+/// The slot holding the compressed device certificate
+pub const DEVICE_CERTIFICATE_SLOT: u8 = 10;
+/// The slot holding the public key of the signer certificate
+pub const SIGNER_PUBLIC_KEY_SLOT: u8 = 11;
+/// The slot holding the compressed signer certificate
+pub const SIGNER_CERTIFICATE_SLOT: u8 = 12;
+
+/// The length of a compressed certificate
+pub const COMPRESSED_CERTIFICATE_LENGTH: usize = 72;
+
+/// The serial number source for a hash of the public key and the compressed
+/// dates
+const SN_SOURCE_PUBLIC_KEY_HASH: u8 = 0xa;
+/// The length of rebuilt certificate serial numbers
+const SERIAL_NUMBER_LENGTH: usize = 16;
+
+const ORGANIZATION: &str = "Microchip Technology Inc";
+const ROOT_COMMON_NAME: &str = "Crypto Authentication Root CA 002";
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+const OID_SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_CLIENT_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_VERSION: u8 = 0xa0;
+const TAG_EXTENSIONS: u8 = 0xa3;
+const TAG_KEY_IDENTIFIER: u8 = 0x80;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid compressed certificate: {0}")]
+    InvalidCertificate(&'static str),
+
+    #[error("invalid signer public key")]
+    InvalidPublicKey,
+
+    #[error("unsupported serial number source {0}")]
+    UnsupportedSnSource(u8),
+}
+
+/// The issue date of a compressed certificate, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IssueDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+}
+
+/// A certificate in the compressed format of Microchip parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedCertificate {
+    /// The raw `r || s` form of the certificate signature
+    pub signature: [u8; 64],
+    pub issue_date: IssueDate,
+    /// The number of years the certificate is valid for, with 0 for a
+    /// certificate which does not expire
+    pub expire_years: u8,
+    /// The id of the signer which issued the certificate
+    pub signer_id: u16,
+    /// The id of the template the certificate is rebuilt with
+    pub template_id: u8,
+    /// The id of the certificate chain the certificate is part of
+    pub chain_id: u8,
+    /// Where the certificate serial number comes from
+    pub sn_source: u8,
+}
+
+impl TryFrom<&[u8]> for CompressedCertificate {
+    type Error = crate::Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        if input.len() != COMPRESSED_CERTIFICATE_LENGTH {
+            return Err(Error::InvalidCertificate("invalid length").into());
+        }
+        // Only format version 0 is defined
+        if input[70] & 0x0f != 0 {
+            return Err(Error::InvalidCertificate("unsupported format version").into());
+        }
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&input[..64]);
+        let dates = &input[64..67];
+        let issue_date = IssueDate {
+            year: 2000 + u16::from(dates[0] >> 3),
+            month: ((dates[0] & 0x07) << 1) | (dates[1] >> 7),
+            day: (dates[1] >> 2) & 0x1f,
+            hour: ((dates[1] & 0x03) << 3) | (dates[2] >> 5),
+        };
+        if !(1..=12).contains(&issue_date.month)
+            || !(1..=31).contains(&issue_date.day)
+            || issue_date.hour > 23
+        {
+            return Err(Error::InvalidCertificate("invalid issue date").into());
+        }
+        Ok(Self {
+            signature,
+            issue_date,
+            expire_years: dates[2] & 0x1f,
+            signer_id: u16::from_be_bytes([input[67], input[68]]),
+            template_id: input[69] >> 4,
+            chain_id: input[69] & 0x0f,
+            sn_source: input[70] >> 4,
+        })
+    }
+}
+
+impl CompressedCertificate {
+    /// Returns the issue date and expiry in their compressed form
+    fn encoded_dates(&self) -> [u8; 3] {
+        let date = &self.issue_date;
+        // Issue dates are validated when decoding, so the year fits
+        let year = (date.year - 2000) as u8;
+        [
+            (year << 3) | (date.month >> 1),
+            ((date.month & 0x01) << 7) | (date.day << 2) | (date.hour >> 3),
+            ((date.hour & 0x07) << 5) | self.expire_years,
+        ]
+    }
+
+    /// Returns the serial number of the certificate for the given subject
+    /// public key
+    fn serial_number(&self, public_key: &p256::PublicKey) -> Result<Vec<u8>> {
+        if self.sn_source != SN_SOURCE_PUBLIC_KEY_HASH {
+            return Err(Error::UnsupportedSnSource(self.sn_source).into());
+        }
+        let point = public_key.to_encoded_point(false);
+        let digest = Sha256::new()
+            .chain_update(&point.as_bytes()[1..])
+            .chain_update(self.encoded_dates())
+            .finalize();
+        let mut serial_number = digest[..SERIAL_NUMBER_LENGTH].to_vec();
+        // Keep the serial number positive and of a fixed length
+        serial_number[0] = (serial_number[0] & 0x7f) | 0x40;
+        Ok(serial_number)
+    }
+
+    /// Returns the DER encoded validity of the certificate. Certificates that
+    /// do not expire are valid until the end of 9999.
+    fn validity(&self) -> Vec<u8> {
+        let date = &self.issue_date;
+        let time = |year: u16| {
+            let time = format!("{:02}{:02}{:02}0000Z", date.month, date.day, date.hour);
+            if year < 2050 {
+                tlv(
+                    TAG_UTC_TIME,
+                    format!("{:02}{}", year % 100, time).as_bytes(),
+                )
+            } else {
+                tlv(
+                    TAG_GENERALIZED_TIME,
+                    format!("{:04}{}", year, time).as_bytes(),
+                )
+            }
+        };
+        let not_after = match self.expire_years {
+            0 => tlv(TAG_GENERALIZED_TIME, b"99991231235959Z"),
+            years => time(date.year + u16::from(years)),
+        };
+        sequence(&[&time(date.year), &not_after])
+    }
+
+    /// Returns the DER encoded ECDSA signature of the certificate
+    fn signature_value(&self) -> Vec<u8> {
+        let signature = sequence(&[
+            &integer(&self.signature[..32]),
+            &integer(&self.signature[32..]),
+        ]);
+        tlv(TAG_BIT_STRING, &[&[0x00][..], &signature].concat())
+    }
+}
+
+/// The factory provisioned identity of a Trust&GO part
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactoryCertificates {
+    /// The unique 9 byte serial number of the chip
+    pub serial: Vec<u8>,
+    /// The public key of the device key in slot 0
+    pub device_public_key: p256::PublicKey,
+    pub device_certificate: CompressedCertificate,
+    pub signer_public_key: p256::PublicKey,
+    pub signer_certificate: CompressedCertificate,
+}
+
+impl FactoryCertificates {
+    /// Returns the DER encoded X.509 device certificate
+    pub fn device_certificate_der(&self) -> Result<Vec<u8>> {
+        Ok(certificate_der(
+            &self.device_certificate,
+            &self.device_tbs_certificate()?,
+        ))
+    }
+
+    /// Returns the DER encoded X.509 signer certificate, issued by the given
+    /// Microchip root certificate authority public key
+    pub fn signer_certificate_der(&self, root_public_key: &p256::PublicKey) -> Result<Vec<u8>> {
+        Ok(certificate_der(
+            &self.signer_certificate,
+            &self.signer_tbs_certificate(root_public_key)?,
+        ))
+    }
+
+    fn signer_common_name(&self) -> String {
+        format!(
+            "Crypto Authentication Signer {:04X}",
+            self.device_certificate.signer_id
+        )
+    }
+
+    fn device_tbs_certificate(&self) -> Result<Vec<u8>> {
+        let certificate = &self.device_certificate;
+        let serial: String = self.serial.iter().map(|b| format!("{:02X}", b)).collect();
+        let extensions = [
+            extension(OID_BASIC_CONSTRAINTS, true, &sequence(&[])),
+            // The digitalSignature and keyAgreement bits, with the 3 unused
+            // bits of their byte
+            extension(OID_KEY_USAGE, true, &tlv(TAG_BIT_STRING, &[0x03, 0x88])),
+            extension(
+                OID_EXTENDED_KEY_USAGE,
+                true,
+                &sequence(&[&tlv(TAG_OID, OID_CLIENT_AUTH)]),
+            ),
+            subject_key_identifier(&self.device_public_key),
+            authority_key_identifier(&self.signer_public_key),
+        ];
+        Ok(tbs_certificate(
+            &certificate.serial_number(&self.device_public_key)?,
+            &name(&self.signer_common_name()),
+            &certificate.validity(),
+            &name(&format!("sn{}", serial)),
+            &self.device_public_key,
+            &extensions,
+        ))
+    }
+
+    fn signer_tbs_certificate(&self, root_public_key: &p256::PublicKey) -> Result<Vec<u8>> {
+        let certificate = &self.signer_certificate;
+        let extensions = [
+            // The digitalSignature, keyCertSign and cRLSign bits, with the 1
+            // unused bit of their byte
+            extension(OID_KEY_USAGE, true, &tlv(TAG_BIT_STRING, &[0x01, 0x86])),
+            // A certificate authority that can only issue end entity
+            // certificates
+            extension(
+                OID_BASIC_CONSTRAINTS,
+                true,
+                &sequence(&[&tlv(TAG_BOOLEAN, &[0xff]), &tlv(TAG_INTEGER, &[0x00])]),
+            ),
+            subject_key_identifier(&self.signer_public_key),
+            authority_key_identifier(root_public_key),
+        ];
+        Ok(tbs_certificate(
+            &certificate.serial_number(&self.signer_public_key)?,
+            &name(ROOT_COMMON_NAME),
+            &certificate.validity(),
+            &name(&self.signer_common_name()),
+            &self.signer_public_key,
+            &extensions,
+        ))
+    }
+}
+
+fn certificate_der(certificate: &CompressedCertificate, tbs_certificate: &[u8]) -> Vec<u8> {
+    sequence(&[
+        tbs_certificate,
+        &sequence(&[&tlv(TAG_OID, OID_ECDSA_WITH_SHA256)]),
+        &certificate.signature_value(),
+    ])
+}
+
+fn tbs_certificate(
+    serial_number: &[u8],
+    issuer: &[u8],
+    validity: &[u8],
+    subject: &[u8],
+    public_key: &p256::PublicKey,
+    extensions: &[Vec<u8>],
+) -> Vec<u8> {
+    let point = public_key.to_encoded_point(false);
+    let public_key_info = sequence(&[
+        &sequence(&[
+            &tlv(TAG_OID, OID_EC_PUBLIC_KEY),
+            &tlv(TAG_OID, OID_PRIME256V1),
+        ]),
+        &tlv(TAG_BIT_STRING, &[&[0x00][..], point.as_bytes()].concat()),
+    ]);
+    sequence(&[
+        // Version 3, encoded as 2
+        &tlv(TAG_VERSION, &tlv(TAG_INTEGER, &[0x02])),
+        &integer(serial_number),
+        &sequence(&[&tlv(TAG_OID, OID_ECDSA_WITH_SHA256)]),
+        issuer,
+        validity,
+        subject,
+        &public_key_info,
+        &tlv(
+            TAG_EXTENSIONS,
+            &sequence(&extensions.iter().map(Vec::as_slice).collect::<Vec<_>>()),
+        ),
+    ])
+}
+
+/// Returns the DER encoded name with the Microchip organization and the
+/// given common name
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute = |oid: &[u8], value: &str| {
+        set(&[&sequence(&[
+            &tlv(TAG_OID, oid),
+            &tlv(TAG_UTF8_STRING, value.as_bytes()),
+        ])])
+    };
+    sequence(&[
+        &attribute(OID_ORGANIZATION, ORGANIZATION),
+        &attribute(OID_COMMON_NAME, common_name),
+    ])
+}
+
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    if critical {
+        sequence(&[
+            &tlv(TAG_OID, oid),
+            &tlv(TAG_BOOLEAN, &[0xff]),
+            &tlv(TAG_OCTET_STRING, value),
+        ])
+    } else {
+        sequence(&[&tlv(TAG_OID, oid), &tlv(TAG_OCTET_STRING, value)])
+    }
+}
+
+/// Returns the key identifier of the given public key, the SHA-1 hash of its
+/// uncompressed point as in RFC 5280
+fn key_identifier(public_key: &p256::PublicKey) -> Vec<u8> {
+    Sha1::digest(public_key.to_encoded_point(false).as_bytes()).to_vec()
+}
+
+fn subject_key_identifier(public_key: &p256::PublicKey) -> Vec<u8> {
+    extension(
+        OID_SUBJECT_KEY_IDENTIFIER,
+        false,
+        &tlv(TAG_OCTET_STRING, &key_identifier(public_key)),
+    )
+}
+
+fn authority_key_identifier(public_key: &p256::PublicKey) -> Vec<u8> {
+    extension(
+        OID_AUTHORITY_KEY_IDENTIFIER,
+        false,
+        &sequence(&[&tlv(TAG_KEY_IDENTIFIER, &key_identifier(public_key))]),
+    )
+}
+
+impl Device {
+    /// Reads the factory provisioned identity of this device, which must be
+    /// a Trust&GO part.
+    pub fn factory_certificates(&self) -> Result<FactoryCertificates> {
+        self.with_ecc(read_factory_certificates)
+    }
+}
+
+impl super::Keypair {
+    /// Returns the DER encoded factory device certificate of this keypair,
+    /// which must be the factory key in slot 0 of a Trust&GO part. Fails with
+    /// a not permitted error for keypairs in other slots, since no factory
+    /// certificate vouches for them.
+    pub fn factory_certificate(&self) -> Result<Vec<u8>> {
+        if self.slot != 0 {
+            return Err(crate::Error::not_permitted());
+        }
+        let certificates = self.device.factory_certificates()?;
+        let public_key: &ecc_compact::PublicKey = (&self.public_key).try_into()?;
+        if public_key.0 != certificates.device_public_key {
+            return Err(crate::Error::not_permitted());
+        }
+        certificates.device_certificate_der()
+    }
+}
+
+/// Reads the factory provisioned identity of the default ECC, which must be a
+/// Trust&GO part. Fails with a closed error if no ECC was initialized.
+pub fn factory_certificates() -> Result<FactoryCertificates> {
+    super::default_device()?.factory_certificates()
+}
+
+fn read_factory_certificates(ecc: &mut Ecc) -> Result<FactoryCertificates> {
+    let device_key = ecc.genkey(KeyType::Public, 0)?;
+    let device_certificate = read_slot(ecc, DEVICE_CERTIFICATE_SLOT)?;
+    let signer_key = read_slot(ecc, SIGNER_PUBLIC_KEY_SLOT)?;
+    let signer_certificate = read_slot(ecc, SIGNER_CERTIFICATE_SLOT)?;
+    Ok(FactoryCertificates {
+        serial: ecc.get_serial()?.to_vec(),
+        // Factory keys are not generated to be compactable, so the device
+        // key is decoded as a plain P-256 key.
+        device_public_key: p256::PublicKey::from_sec1_bytes(
+            &[&[4u8][..], device_key.as_ref()].concat(),
+        )?,
+        device_certificate: CompressedCertificate::try_from(&device_certificate[..])?,
+        signer_public_key: padded_public_key(&signer_key)?,
+        signer_certificate: CompressedCertificate::try_from(&signer_certificate[..])?,
+    })
+}
+
+/// Reads the first 72 bytes of the given data slot
+fn read_slot(ecc: &mut Ecc, slot: u8) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(96);
+    for block in 0..3 {
+        bytes.extend_from_slice(ecc.read(true, Address::slot(slot, block)?)?.as_ref());
+    }
+    bytes.truncate(COMPRESSED_CERTIFICATE_LENGTH);
+    Ok(bytes)
+}
+
+/// Decodes a public key stored in the padded slot format, with each of the x
+/// and y coordinates preceded by 4 zero bytes
+fn padded_public_key(bytes: &[u8]) -> Result<p256::PublicKey> {
+    if bytes.len() != 72 || bytes[..4] != [0; 4] || bytes[36..40] != [0; 4] {
+        return Err(Error::InvalidPublicKey.into());
+    }
+    let point = [&[4u8][..], &bytes[4..36], &bytes[40..72]].concat();
+    Ok(p256::PublicKey::from_sec1_bytes(&point).map_err(|_| Error::InvalidPublicKey)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+    use rand::rngs::OsRng;
+
+    fn certificate() -> Vec<u8> {
+        let mut bytes = vec![0xaa; 64];
+        // Issued 2019-03-14 at 17:00 and valid for 28 years
+        bytes.extend_from_slice(&[0x99, 0xba, 0x3c]);
+        bytes.extend_from_slice(&[0x3c, 0x01, 0x10, 0xa0, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn compressed_certificates() {
+        let decoded = CompressedCertificate::try_from(&certificate()[..]).expect("certificate");
+        assert_eq!([0xaa; 64], decoded.signature);
+        assert_eq!(
+            IssueDate {
+                year: 2019,
+                month: 3,
+                day: 14,
+                hour: 17,
+            },
+            decoded.issue_date
+        );
+        assert_eq!(28, decoded.expire_years);
+        assert_eq!(0x3c01, decoded.signer_id);
+        assert_eq!(1, decoded.template_id);
+        assert_eq!(0, decoded.chain_id);
+        assert_eq!(0xa, decoded.sn_source);
+
+        assert!(CompressedCertificate::try_from(&certificate()[1..]).is_err());
+        let mut version = certificate();
+        version[70] |= 0x01;
+        assert!(CompressedCertificate::try_from(&version[..]).is_err());
+        let mut month = certificate();
+        month[64] &= 0xf8;
+        month[65] &= 0x7f;
+        assert!(CompressedCertificate::try_from(&month[..]).is_err());
+    }
+
+    #[test]
+    fn padded_public_keys() {
+        let key = p256::SecretKey::random(&mut OsRng).public_key();
+        let point = key.to_encoded_point(false);
+        let padded = [
+            &[0u8; 4][..],
+            point.x().unwrap(),
+            &[0u8; 4][..],
+            point.y().unwrap(),
+        ]
+        .concat();
+        assert_eq!(key, padded_public_key(&padded).expect("public key"));
+        assert!(padded_public_key(&padded[..71]).is_err());
+        let mut unpadded = padded;
+        unpadded[0] = 1;
+        assert!(padded_public_key(&unpadded).is_err());
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    /// Checks that the given DER encoding is a single sequence
+    fn assert_sequence(der: &[u8]) {
+        assert_eq!([0x30, 0x82], der[..2]);
+        assert_eq!(
+            der.len(),
+            4 + usize::from(u16::from_be_bytes([der[2], der[3]]))
+        );
+    }
+
+    fn factory_certificates(signer: &p256::SecretKey) -> FactoryCertificates {
+        let compressed = CompressedCertificate::try_from(&certificate()[..]).expect("certificate");
+        FactoryCertificates {
+            serial: vec![0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xee],
+            device_public_key: p256::SecretKey::random(&mut OsRng).public_key(),
+            device_certificate: compressed.clone(),
+            signer_public_key: signer.public_key(),
+            signer_certificate: compressed,
+        }
+    }
+
+    #[test]
+    fn serial_numbers() {
+        let compressed = CompressedCertificate::try_from(&certificate()[..]).expect("certificate");
+        assert_eq!(certificate()[64..67], compressed.encoded_dates());
+        let key = p256::SecretKey::random(&mut OsRng).public_key();
+        let serial_number = compressed.serial_number(&key).expect("serial number");
+        assert_eq!(SERIAL_NUMBER_LENGTH, serial_number.len());
+        assert_eq!(0x40, serial_number[0] & 0xc0);
+        let stored = CompressedCertificate {
+            sn_source: 0,
+            ..compressed
+        };
+        assert!(stored.serial_number(&key).is_err());
+    }
+
+    #[test]
+    fn device_certificate() {
+        let signer = p256::SecretKey::random(&mut OsRng);
+        let mut certificates = factory_certificates(&signer);
+        let tbs_certificate = certificates.device_tbs_certificate().expect("tbs");
+        let signature: Signature = SigningKey::from(&signer).sign(&tbs_certificate);
+        certificates
+            .device_certificate
+            .signature
+            .copy_from_slice(signature.as_ref());
+
+        let der = certificates.device_certificate_der().expect("der");
+        assert_sequence(&der);
+        assert!(contains(&der, &tbs_certificate));
+        assert!(contains(&der, b"sn0123456789ABCDEFEE"));
+        assert!(contains(&der, b"Crypto Authentication Signer 3C01"));
+        assert!(contains(&der, b"190314170000Z"));
+        assert!(contains(&der, b"470314170000Z"));
+        assert!(contains(&der, &key_identifier(&signer.public_key())));
+        // The signature is the DER encoding of the compressed signature
+        assert!(der.ends_with(&tlv(
+            TAG_BIT_STRING,
+            &[&[0x00][..], signature.to_der().as_bytes()].concat()
+        )));
+    }
+
+    #[test]
+    fn signer_certificate() {
+        let signer = p256::SecretKey::random(&mut OsRng);
+        let root = p256::SecretKey::random(&mut OsRng);
+        let certificates = factory_certificates(&signer);
+        let der = certificates
+            .signer_certificate_der(&root.public_key())
+            .expect("der");
+        assert_sequence(&der);
+        assert!(contains(&der, ROOT_COMMON_NAME.as_bytes()));
+        assert!(contains(&der, b"Crypto Authentication Signer 3C01"));
+        assert!(contains(&der, &key_identifier(&root.public_key())));
+        assert!(contains(&der, &key_identifier(&signer.public_key())));
+    }
+}
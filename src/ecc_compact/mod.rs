@@ -26,6 +26,61 @@ pub struct Keypair {
 
 pub const KEYPAIR_LENGTH: usize = 33;
 pub const PUBLIC_KEY_LENGTH: usize = 33;
+/// Raw signatures are the fixed size big endian `r || s` pair
+pub const RAW_SIGNATURE_LENGTH: usize = 64;
+/// Recoverable signatures are the fixed size `r || s` signature followed by a
+/// one byte recovery id
+pub const RECOVERABLE_SIGNATURE_LENGTH: usize = 65;
+
+/// How the per-signature ECDSA nonce is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nonce {
+    /// RFC 6979 deterministic nonces, derived from only the secret key and
+    /// the message. Signing never depends on a random number generator.
+    Deterministic,
+    /// RFC 6979 nonces with added randomness from a random number generator,
+    /// which stay safe if either the generator or the derivation is weak.
+    Hedged,
+}
+
+impl Default for Nonce {
+    fn default() -> Self {
+        Self::Deterministic
+    }
+}
+
+/// Options for [`Keypair::sign_with_options`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SignOptions {
+    pub nonce: Nonce,
+    /// Normalize signatures to low-S form, which is the only form
+    /// [`PublicKey::verify_strict`] accepts
+    pub low_s: bool,
+}
+
+/// Half the order of the P-256 group. Signatures with an `s` above this are
+/// high-S, and have an equally valid low-S twin.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xde, 0x73, 0x7d, 0x56, 0xd3, 0x8b, 0xcf, 0x42, 0x79, 0xdc, 0xe5, 0x61, 0x7e, 0x31, 0x92, 0xa8,
+];
+
+fn is_high_s(signature: &ecdsa::Signature) -> bool {
+    let (_, s) = signature.split_scalars();
+    s.to_bytes().as_slice() > &HALF_ORDER[..]
+}
+
+/// Returns the low-S twin of the given signature if it is high-S
+pub(crate) fn normalize_s(signature: ecdsa::Signature) -> Result<ecdsa::Signature> {
+    if !is_high_s(&signature) {
+        return Ok(signature);
+    }
+    let (r, s) = signature.split_scalars();
+    Ok(ecdsa::Signature::from_scalars(
+        r.to_bytes(),
+        (-*s).to_bytes(),
+    )?)
+}
 
 pub trait IsCompactable {
     fn is_compactable(&self) -> bool;
@@ -132,6 +187,71 @@ impl Keypair {
         self.secret.to_bytes().as_slice().to_vec()
     }
 
+    /// Returns the secret scalar of this keypair
+    pub(crate) fn secret_scalar(&self) -> p256::NonZeroScalar {
+        // Unwrap ok since the signing key always holds a valid secret
+        p256::SecretKey::from_be_bytes(&self.secret.to_bytes())
+            .unwrap()
+            .to_nonzero_scalar()
+    }
+
+    /// Sign the given message with the given options. The random number
+    /// generator is only used for hedged nonces. Signing with [`Sign::sign`]
+    /// always uses deterministic nonces.
+    pub fn sign_with_options<R>(
+        &self,
+        msg: &[u8],
+        options: SignOptions,
+        csprng: &mut R,
+    ) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let signature: ecdsa::Signature = match options.nonce {
+            Nonce::Deterministic => signature::Signer::try_sign(&self.secret, msg)?,
+            Nonce::Hedged => {
+                signature::RandomizedSigner::try_sign_with_rng(&self.secret, &mut *csprng, msg)?
+            }
+        };
+        let signature = if options.low_s {
+            normalize_s(signature)?
+        } else {
+            signature
+        };
+        Ok(Signature(signature).to_vec())
+    }
+
+    /// Sign the given message, returning the fixed size raw `r || s` form of
+    /// the signature rather than the DER encoded form [`Sign::sign`] returns
+    pub fn sign_raw(&self, msg: &[u8]) -> Result<[u8; RAW_SIGNATURE_LENGTH]> {
+        use signature::Signer;
+        Ok(self.try_sign(msg)?.to_raw())
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    /// The resulting signature is identical to signing the full message.
+    pub fn sign_digest(&self, digest: sha2::Sha256) -> Result<Signature> {
+        use signature::DigestSigner;
+        Ok(Signature(self.secret.try_sign_digest(digest)?))
+    }
+
+    /// Sign the given message with a recoverable signature, from which
+    /// [`PublicKey::recover`] recovers the public key of this keypair, so the
+    /// public key does not need to be sent along with the signature.
+    pub fn sign_recoverable(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&self.secret, msg)?;
+        let public_key: &PublicKey = (&self.public_key).try_into()?;
+        for recovery_id in 0..2u8 {
+            let mut result = signature.as_ref().to_vec();
+            result.push(recovery_id);
+            match PublicKey::recover(msg, &result) {
+                Ok(recovered) if &recovered == public_key => return Ok(result),
+                _ => continue,
+            }
+        }
+        Err(signature::Error::new().into())
+    }
+
     pub fn ecdh<'a, C>(&self, public_key: C) -> Result<SharedSecret>
     where
         C: TryInto<&'a PublicKey, Error = Error>,
@@ -167,13 +287,29 @@ impl signature::Signer<Signature> for Keypair {
 }
 
 impl Signature {
+    /// Construct a signature from its fixed size raw `r || s` form
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         Ok(Signature(signature::Signature::from_bytes(bytes)?))
     }
 
+    /// Construct a signature from its DER encoded form
+    pub fn from_der(bytes: &[u8]) -> Result<Self> {
+        Ok(Signature(
+            ecdsa::Signature::from_der(bytes).map_err(Error::from)?,
+        ))
+    }
+
+    /// Convert to the DER encoded form used for signatures
     pub fn to_vec(&self) -> Vec<u8> {
         self.0.to_der().as_bytes().to_vec()
     }
+
+    /// Convert to the fixed size raw `r || s` form
+    pub fn to_raw(&self) -> [u8; RAW_SIGNATURE_LENGTH] {
+        let mut result = [0u8; RAW_SIGNATURE_LENGTH];
+        result.copy_from_slice(self.0.as_ref());
+        result
+    }
 }
 
 impl PublicKeySize for PublicKey {
@@ -188,6 +324,104 @@ impl public_key::Verify for PublicKey {
     }
 }
 
+/// Returns the SHA-256 digest ECDSA context signatures sign: the message
+/// prefixed with the length of the context, which must be between 1 and 255
+/// bytes long, and the context itself.
+#[cfg(feature = "context")]
+pub(crate) fn context_digest(context: &[u8], msg: &[u8]) -> Result<sha2::Sha256> {
+    use sha2::Digest;
+    if context.is_empty() || context.len() > 255 {
+        return Err(signature::Error::new().into());
+    }
+    Ok(sha2::Sha256::new()
+        .chain_update([context.len() as u8])
+        .chain_update(context)
+        .chain_update(msg))
+}
+
+#[cfg(feature = "context")]
+impl Keypair {
+    /// Sign the given message bound to the given context. Signatures only
+    /// verify with [`PublicKey::verify_with_context`] and the same context.
+    pub fn sign_with_context(&self, context: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.sign_digest(context_digest(context, msg)?)?.to_vec())
+    }
+}
+
+impl PublicKey {
+    /// Recover the public key that made the given recoverable signature over
+    /// the given message with [`Keypair::sign_recoverable`]. Recovery ids 2
+    /// and 3, for the negligibly rare nonces whose x coordinate exceeds the
+    /// group order, are not supported.
+    pub fn recover(msg: &[u8], signature: &[u8]) -> Result<Self> {
+        use p256::elliptic_curve::{
+            bigint::U256, ff::Field, group::Curve, ops::Reduce, subtle::Choice, DecompressPoint,
+        };
+        use sha2::Digest;
+        if signature.len() != RECOVERABLE_SIGNATURE_LENGTH || signature[64] > 1 {
+            return Err(signature::Error::new().into());
+        }
+        let ecdsa_signature = ecdsa::Signature::try_from(&signature[..64])?;
+        let (r, s) = ecdsa_signature.split_scalars();
+        let big_r: Option<p256::AffinePoint> =
+            p256::AffinePoint::decompress(&r.to_bytes(), Choice::from(signature[64])).into();
+        let big_r = big_r.ok_or_else(signature::Error::new)?;
+        let r_inv: Option<p256::Scalar> = p256::Scalar::invert(&r).into();
+        let r_inv = r_inv.ok_or_else(signature::Error::new)?;
+        let z = <p256::Scalar as Reduce<U256>>::from_be_bytes_reduced(sha2::Sha256::digest(msg));
+        let q = (p256::ProjectivePoint::from(big_r) * *s - p256::ProjectivePoint::GENERATOR * z)
+            * r_inv;
+        let public_key = p256::PublicKey::from_affine(q.to_affine())?;
+        if !public_key.is_compactable() {
+            return Err(Error::not_compact());
+        }
+        // Only return keys the signature actually verifies against
+        signature::Verifier::verify(
+            &ecdsa::VerifyingKey::from(public_key),
+            msg,
+            &ecdsa_signature,
+        )?;
+        Ok(PublicKey(public_key))
+    }
+
+    /// Verify a DER encoded signature over the given message, made with the
+    /// given context
+    #[cfg(feature = "context")]
+    pub fn verify_with_context(&self, context: &[u8], msg: &[u8], signature: &[u8]) -> Result {
+        self.verify_digest(context_digest(context, msg)?, signature)
+    }
+
+    /// Verify the given fixed size raw `r || s` signature over the given
+    /// message
+    pub fn verify_raw(&self, msg: &[u8], signature: &[u8]) -> Result {
+        use signature::Verifier;
+        if signature.len() != RAW_SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let signature = Signature::from_bytes(signature)?;
+        Ok(p256::ecdsa::VerifyingKey::from(self.0).verify(msg, &signature.0)?)
+    }
+
+    /// Verify the given DER encoded signature like [`Verify::verify`], but
+    /// reject signatures that are high-S or not canonically DER encoded, so
+    /// a valid signature can not be altered into another valid signature.
+    pub fn verify_strict(&self, msg: &[u8], signature: &[u8]) -> Result {
+        let parsed = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        if parsed.to_der().as_bytes() != signature || is_high_s(&parsed) {
+            return Err(signature::Error::new().into());
+        }
+        self.verify(msg, signature)
+    }
+
+    /// Verify the given DER encoded signature against the incrementally
+    /// computed SHA-256 digest of a message.
+    pub fn verify_digest(&self, digest: sha2::Sha256, signature: &[u8]) -> Result {
+        use signature::DigestVerifier;
+        let signature = p256::ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        Ok(p256::ecdsa::VerifyingKey::from(self.0).verify_digest(digest, &signature)?)
+    }
+}
+
 impl TryFrom<&[u8]> for PublicKey {
     type Error = Error;
 
@@ -255,7 +489,7 @@ impl Deref for SharedSecret {
 
 #[cfg(test)]
 mod tests {
-    use super::{Keypair, PublicKey, TryFrom};
+    use super::{Keypair, PublicKey, TryFrom, TryInto};
     use crate::{Network, Sign, Verify};
     use hex_literal::hex;
     use rand::rngs::OsRng;
@@ -270,6 +504,153 @@ mod tests {
             .is_ok())
     }
 
+    #[test]
+    fn sign_digest_roundtrip() {
+        use sha2::{Digest, Sha256};
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair
+            .sign_digest(Sha256::new().chain_update(b"hello world"))
+            .expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature.to_vec())
+            .is_ok());
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("ecc key");
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(public_key
+            .verify_digest(Sha256::new().chain_update(b"hello world"), &signature)
+            .is_ok());
+    }
+
+    #[cfg(feature = "context")]
+    #[test]
+    fn sign_with_context() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("ecc key");
+        let signature = keypair
+            .sign_with_context(b"foo", b"hello world")
+            .expect("signature");
+        assert!(public_key
+            .verify_with_context(b"foo", b"hello world", &signature)
+            .is_ok());
+        assert!(public_key
+            .verify_with_context(b"bar", b"hello world", &signature)
+            .is_err());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_err());
+        assert!(keypair.sign_with_context(b"", b"hello world").is_err());
+    }
+
+    #[test]
+    fn recover() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("ecc key");
+        for msg in [&b"hello world"[..], b"hello there", b""] {
+            let signature = keypair.sign_recoverable(msg).expect("signature");
+            assert_eq!(super::RECOVERABLE_SIGNATURE_LENGTH, signature.len());
+            assert_eq!(
+                public_key,
+                &PublicKey::recover(msg, &signature).expect("recover")
+            );
+        }
+        let signature = keypair.sign_recoverable(b"hello world").expect("signature");
+        assert_ne!(
+            Some(public_key),
+            PublicKey::recover(b"hello there", &signature).ok().as_ref()
+        );
+        assert!(PublicKey::recover(b"hello world", &signature[..64]).is_err());
+    }
+
+    #[test]
+    fn raw_signature() {
+        use super::{Signature, RAW_SIGNATURE_LENGTH};
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("ecc key");
+        let raw = keypair.sign_raw(b"hello world").expect("signature");
+        assert_eq!(RAW_SIGNATURE_LENGTH, raw.len());
+        assert!(public_key.verify_raw(b"hello world", &raw).is_ok());
+        assert!(public_key.verify_raw(b"hello there", &raw).is_err());
+
+        // Raw and DER forms convert into each other
+        let der = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(
+            raw,
+            Signature::from_der(&der).expect("der signature").to_raw()
+        );
+        assert_eq!(
+            der,
+            Signature::from_bytes(&raw).expect("raw signature").to_vec()
+        );
+        assert!(public_key.verify_raw(b"hello world", &der).is_err());
+    }
+
+    #[test]
+    fn verify_strict() {
+        use super::{ecdsa, is_high_s, normalize_s, SignOptions};
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("ecc key");
+        let options = SignOptions {
+            low_s: true,
+            ..Default::default()
+        };
+        for i in 0..16u8 {
+            let signature = keypair
+                .sign_with_options(&[i], options, &mut OsRng)
+                .expect("signature");
+            assert!(public_key.verify_strict(&[i], &signature).is_ok());
+
+            // The high-S twin verifies, but not strictly
+            let parsed = ecdsa::Signature::from_der(&signature).expect("der");
+            let (r, s) = parsed.split_scalars();
+            let twin =
+                ecdsa::Signature::from_scalars(r.to_bytes(), (-*s).to_bytes()).expect("twin");
+            assert!(is_high_s(&twin));
+            assert_eq!(parsed, normalize_s(twin).expect("normalize"));
+            let twin = twin.to_der().as_bytes().to_vec();
+            assert!(public_key.verify(&[i], &twin).is_ok());
+            assert!(public_key.verify_strict(&[i], &twin).is_err());
+
+            // Trailing data is not a canonical encoding
+            let mut padded = signature.clone();
+            padded.push(0);
+            assert!(public_key.verify_strict(&[i], &padded).is_err());
+        }
+    }
+
+    #[test]
+    fn deterministic_nonce() {
+        use super::{Nonce, SignOptions};
+        use rand::rngs::OsRng;
+        // The P-256 SHA-256 "sample" test vector from RFC 6979 section A.2.5
+        let keypair = Keypair::try_from(
+            &hex!("00c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721")[..],
+        )
+        .expect("keypair");
+        let expected = hex!("3046022100efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716022100f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8");
+        assert_eq!(
+            expected.to_vec(),
+            keypair.sign(b"sample").expect("signature")
+        );
+        assert_eq!(
+            expected.to_vec(),
+            keypair
+                .sign_with_options(b"sample", SignOptions::default(), &mut OsRng)
+                .expect("signature")
+        );
+
+        let hedged = SignOptions {
+            nonce: Nonce::Hedged,
+            ..Default::default()
+        };
+        let signature = keypair
+            .sign_with_options(b"sample", hedged, &mut OsRng)
+            .expect("hedged signature");
+        assert_ne!(expected.to_vec(), signature);
+        assert!(keypair.public_key.verify(b"sample", &signature).is_ok());
+    }
+
     #[test]
     fn bytes_roundtrip() {
         use rand::rngs::OsRng;
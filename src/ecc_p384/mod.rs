@@ -0,0 +1,331 @@
+//! NIST P-384 (secp384r1) keypairs.
+//!
+//! Keypairs sign with ECDSA over SHA-384, with DER encoded signatures. Public
+//! keys are SEC1 compressed points.
+use crate::*;
+use p384::{ecdsa, elliptic_curve::sec1::ToEncodedPoint};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+#[derive(Debug, Clone)]
+pub struct PublicKey(pub(crate) p384::PublicKey);
+
+pub struct SharedSecret(pub(crate) p384::ecdh::SharedSecret);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Signature(pub(crate) ecdsa::Signature);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: p384::ecdsa::SigningKey,
+}
+
+pub const KEYPAIR_LENGTH: usize = 49;
+/// Public keys are SEC1 compressed points prefixed with the key tag
+pub const PUBLIC_KEY_LENGTH: usize = 50;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(Error::invalid_curve());
+        }
+        let network = Network::try_from(input[0])?;
+        Self::from_secret(network, p384::SecretKey::from_be_bytes(&input[1..])?)
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.secret.to_bytes())
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let secret = p384::SecretKey::random(&mut *csprng);
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(secret.public_key())),
+            secret: p384::ecdsa::SigningKey::from(secret),
+        }
+    }
+
+    /// Constructs a keypair from the given entropy. Entropy of 48 bytes is
+    /// the big endian secret scalar itself. Shorter entropy, like the 32 bytes
+    /// used for every other key type, must be at least 32 bytes long and is
+    /// expanded with the deterministic key generation DRBG described in
+    /// [`crate::Keypair::generate_deterministic`], using the first candidate
+    /// that is a valid scalar, which almost always is the first.
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let secret = match entropy.len() {
+            48 => p384::SecretKey::from_be_bytes(entropy)?,
+            32..=47 => (0u32..)
+                .find_map(|counter| {
+                    let candidate =
+                        keypair::expand_seed(u8::from(KeyType::EccP384), entropy, counter, 48);
+                    p384::SecretKey::from_be_bytes(&candidate).ok()
+                })
+                .ok_or_else(Error::invalid_curve)?,
+            _ => return Err(Error::invalid_curve()),
+        };
+        Self::from_secret(network, secret)
+    }
+
+    fn from_secret(network: Network, secret: p384::SecretKey) -> Result<Keypair> {
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(secret.public_key())),
+            secret: p384::ecdsa::SigningKey::from(secret),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::EccP384,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.to_bytes().as_slice().to_vec()
+    }
+
+    pub fn ecdh<'a, C>(&self, public_key: C) -> Result<SharedSecret>
+    where
+        C: TryInto<&'a PublicKey, Error = Error>,
+    {
+        let public_key = public_key.try_into()?;
+        let secret_key = p384::SecretKey::from_be_bytes(&self.secret.to_bytes())?;
+        let shared_secret =
+            p384::ecdh::diffie_hellman(secret_key.to_nonzero_scalar(), public_key.0.as_affine());
+        Ok(SharedSecret(shared_secret))
+    }
+}
+
+impl signature::Signature for Signature {
+    fn from_bytes(input: &[u8]) -> std::result::Result<Self, signature::Error> {
+        Ok(Signature(signature::Signature::from_bytes(input)?))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&self.secret, msg)?;
+        Ok(Signature(signature))
+    }
+}
+
+impl Signature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Signature(signature::Signature::from_bytes(bytes)?))
+    }
+
+    /// Convert to the DER encoded form used for signatures
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.to_der().as_bytes().to_vec()
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        use signature::Verifier;
+        let signature = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        Ok(ecdsa::VerifyingKey::from(self.0).verify(msg, &signature)?)
+    }
+}
+
+impl PublicKey {
+    /// Verify the given DER encoded signature against the incrementally
+    /// computed SHA-384 digest of a message.
+    pub fn verify_digest(&self, digest: sha2::Sha384, signature: &[u8]) -> Result {
+        use signature::DigestVerifier;
+        let signature = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        Ok(ecdsa::VerifyingKey::from(self.0).verify_digest(digest, &signature)?)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        Ok(PublicKey(p384::PublicKey::from_sec1_bytes(&buf)?))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let encoded = self.0.as_affine().to_encoded_point(true);
+        output.write_all(encoded.as_bytes())
+    }
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let encoded = self.0.as_affine().to_encoded_point(true);
+        state.write(encoded.as_bytes())
+    }
+}
+
+impl Deref for SharedSecret {
+    type Target = p384::ecdh::SharedSecret;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, TryFrom};
+    use crate::{KeyTag, KeyType, Network, Sign, Verify};
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn short_entropy() {
+        let keypair =
+            Keypair::generate_from_entropy(Network::MainNet, &[7u8; 32]).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::generate_from_entropy(Network::MainNet, &[7u8; 32]).expect("keypair")
+        );
+        assert!(Keypair::generate_from_entropy(Network::MainNet, &[7u8; 31]).is_err());
+        assert!(Keypair::generate_from_entropy(Network::MainNet, &[7u8; 49]).is_err());
+    }
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn verify_reader() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let mut verifier = keypair.public_key.verifier().expect("verifier");
+        verifier.update(b"hello ");
+        verifier.update(b"world");
+        assert!(verifier.finalize(&signature).is_ok());
+        assert!(keypair
+            .public_key
+            .verify_reader(&mut &b"hello there"[..], &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn ecdh() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = Keypair::generate(Network::MainNet, &mut OsRng);
+        let shared = keypair.ecdh(&other.public_key).expect("ecdh");
+        let other_shared = other.ecdh(&keypair.public_key).expect("other ecdh");
+        assert_eq!(48, shared.raw_secret_bytes().len());
+        assert_eq!(shared.raw_secret_bytes(), other_shared.raw_secret_bytes());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_vec();
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+
+        let public_key = &keypair.public_key;
+        assert_eq!(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::EccP384,
+            },
+            public_key.key_tag()
+        );
+        let bytes = public_key.to_vec();
+        assert_eq!(super::PUBLIC_KEY_LENGTH, bytes.len());
+        assert_eq!(
+            public_key,
+            &crate::PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+        let decoded: crate::PublicKey = public_key.to_string().parse().expect("b58 public key");
+        assert_eq!(public_key, &decoded);
+    }
+
+    #[test]
+    fn truncated_keypair() {
+        let bytes = Keypair::generate(Network::TestNet, &mut OsRng).to_vec();
+        for len in 1..super::KEYPAIR_LENGTH {
+            assert!(Keypair::try_from(&bytes[..len]).is_err());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Low level curve25519 helpers shared by the protocols that build on ed25519
+//! keys, such as blind and ring signatures.
+use crate::*;
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_TABLE,
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+    traits::IsIdentity,
+};
+use sha2::{Digest, Sha512};
+
+/// Generate a uniformly random scalar
+pub(crate) fn random_scalar<R>(csprng: &mut R) -> Scalar
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let mut bytes = [0u8; 64];
+    csprng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// Hash the given parts with SHA-512 and reduce the result to a scalar
+pub(crate) fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut bytes = [0u8; 64];
+    bytes.copy_from_slice(&hasher.finalize());
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// Hash the given parts to a point in the prime order subgroup with unknown
+/// discrete logarithm, using try-and-increment over SHA-512 output.
+pub(crate) fn hash_to_point(parts: &[&[u8]]) -> EdwardsPoint {
+    let mut counter: u32 = 0;
+    loop {
+        let mut hasher = Sha512::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.update(counter.to_be_bytes());
+        let digest = hasher.finalize();
+        if let Some(point) = CompressedEdwardsY::from_slice(&digest[..32]).decompress() {
+            let point = point.mul_by_cofactor();
+            if !point.is_identity() {
+                return point;
+            }
+        }
+        counter += 1;
+    }
+}
+
+/// Multiply the ed25519 base point by the given scalar
+pub(crate) fn base_mul(scalar: &Scalar) -> EdwardsPoint {
+    scalar * &ED25519_BASEPOINT_TABLE
+}
+
+/// Decompress the given 32 bytes to a point
+pub(crate) fn decompress(bytes: &[u8]) -> Result<EdwardsPoint> {
+    if bytes.len() != 32 {
+        return Err(Error::invalid_curve());
+    }
+    CompressedEdwardsY::from_slice(bytes)
+        .decompress()
+        .ok_or_else(Error::invalid_curve)
+}
+
+/// Decode a canonically encoded scalar
+pub(crate) fn scalar_from_slice(bytes: &[u8]) -> Result<Scalar> {
+    if bytes.len() != 32 {
+        return Err(signature::Error::new().into());
+    }
+    let mut buf = [0u8; 32];
+    buf.copy_from_slice(bytes);
+    Scalar::from_canonical_bytes(buf).ok_or_else(|| signature::Error::new().into())
+}
+
+/// Returns the ed25519 public key point of the given public key
+pub(crate) fn public_key_point(public_key: &public_key::PublicKey) -> Result<EdwardsPoint> {
+    let public_key: &ed25519::PublicKey = public_key.try_into()?;
+    decompress(public_key.as_ref())
+}
+
+impl ed25519::Keypair {
+    /// Returns the secret scalar of the keypair, reduced modulo the group
+    /// order.
+    pub(crate) fn secret_scalar(&self) -> Scalar {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&self.expanded_secret()[..32]);
+        Scalar::from_bytes_mod_order(bytes)
+    }
+}
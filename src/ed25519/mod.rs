@@ -4,6 +4,9 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+#[cfg(feature = "curve25519-dalek")]
+pub(crate) mod curve;
+
 #[derive(Debug, Clone)]
 pub struct PublicKey(ed25519_dalek::PublicKey);
 
@@ -93,6 +96,59 @@ impl Keypair {
     pub fn secret_to_vec(&self) -> Vec<u8> {
         self.secret.secret.as_bytes().to_vec()
     }
+
+    /// Returns the expanded form of the secret key: the clamped secret scalar
+    /// followed by the nonce prefix used for deterministic signing.
+    pub(crate) fn expanded_secret(&self) -> [u8; ed25519_dalek::EXPANDED_SECRET_KEY_LENGTH] {
+        ed25519_dalek::ExpandedSecretKey::from(&self.secret.secret).to_bytes()
+    }
+
+    /// Sign a SHA-512 digest of a message using Ed25519ph, with an optional
+    /// context of at most 255 bytes. Since the message is hashed by the
+    /// caller, large messages can be hashed incrementally.
+    ///
+    /// Ed25519ph signatures are not interchangeable with regular ed25519
+    /// signatures and must be verified with [`PublicKey::verify_prehashed`].
+    pub fn sign_prehashed(
+        &self,
+        prehashed: sha2::Sha512,
+        context: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        let signature = self.secret.sign_prehashed(prehashed, context)?;
+        Ok(Signature(signature).to_vec())
+    }
+}
+
+/// Returns the `dom2` prefix RFC 8032 uses to domain separate Ed25519ctx
+/// signatures. Contexts must be between 1 and 255 bytes long.
+#[cfg(feature = "context")]
+fn dom2(context: &[u8]) -> Result<Vec<u8>> {
+    if context.is_empty() || context.len() > 255 {
+        return Err(signature::Error::new().into());
+    }
+    let mut result = b"SigEd25519 no Ed25519 collisions".to_vec();
+    result.extend_from_slice(&[0, context.len() as u8]);
+    result.extend_from_slice(context);
+    Ok(result)
+}
+
+#[cfg(feature = "context")]
+impl Keypair {
+    /// Sign the given message using Ed25519ctx with the given context, which
+    /// must be between 1 and 255 bytes long. Signatures only verify with
+    /// [`PublicKey::verify_with_context`] and the same context.
+    pub fn sign_with_context(&self, context: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+        use curve::{base_mul, hash_to_scalar};
+        let dom = dom2(context)?;
+        let expanded = self.expanded_secret();
+        let r = hash_to_scalar(&[&dom, &expanded[32..], msg]);
+        let big_r = base_mul(&r).compress();
+        let k = hash_to_scalar(&[&dom, big_r.as_bytes(), self.secret.public.as_bytes(), msg]);
+        let s = r + k * self.secret_scalar();
+        let mut result = big_r.as_bytes().to_vec();
+        result.extend_from_slice(s.as_bytes());
+        Ok(result)
+    }
 }
 
 impl signature::Signature for Signature {
@@ -165,6 +221,55 @@ impl public_key::Verify for PublicKey {
     }
 }
 
+impl PublicKey {
+    /// Verify an Ed25519ph signature against a SHA-512 digest of a message,
+    /// using the same context the signature was made with
+    pub fn verify_prehashed(
+        &self,
+        prehashed: sha2::Sha512,
+        context: Option<&[u8]>,
+        signature: &[u8],
+    ) -> Result {
+        let signature = Signature::try_from(signature)?;
+        self.0
+            .verify_prehashed(prehashed, context, &signature.0)
+            .map_err(Error::from)
+    }
+}
+
+#[cfg(feature = "context")]
+impl PublicKey {
+    /// Verify an Ed25519ctx signature over the given message, made with the
+    /// given context
+    pub fn verify_with_context(&self, context: &[u8], msg: &[u8], signature: &[u8]) -> Result {
+        use curve::{decompress, hash_to_scalar, scalar_from_slice};
+        use curve25519_dalek::edwards::EdwardsPoint;
+        let dom = dom2(context)?;
+        if signature.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let (big_r, s) = signature.split_at(32);
+        let s = scalar_from_slice(s)?;
+        let a = decompress(self.as_ref())?;
+        let k = hash_to_scalar(&[&dom, big_r, self.as_ref(), msg]);
+        let check = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-k, &a, &s);
+        if check.compress().as_bytes() != big_r {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+}
+
+impl PublicKey {
+    /// Verify the given signature like [`Verify::verify`], but also reject
+    /// signatures with non-canonical or small order components, so a valid
+    /// signature can not be altered into another valid signature.
+    pub fn verify_strict(&self, msg: &[u8], signature: &[u8]) -> Result {
+        let signature = Signature::try_from(signature)?;
+        self.0.verify_strict(msg, &signature.0).map_err(Error::from)
+    }
+}
+
 impl WriteTo for PublicKey {
     fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
         output.write_all(self.as_ref())
@@ -211,7 +316,7 @@ mod tests {
     use super::Keypair;
     use crate::{Network, Sign, Verify};
     use hex_literal::hex;
-    use std::convert::TryFrom;
+    use std::convert::{TryFrom, TryInto};
 
     #[test]
     fn seed() {
@@ -269,6 +374,57 @@ mod tests {
         assert!(public_key.verify(MSG, SIG).is_ok());
     }
 
+    #[test]
+    fn sign_prehashed() {
+        use sha2::Digest;
+        // Ed25519ph test vector from RFC 8032 section 7.3
+        const SECRET: [u8; 32] =
+            hex!("833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42");
+        const SIG: &[u8] =
+            &hex!("98a70222f0b8121aa9d30f813d683f809e462b469c7ff87639499bb94e6dae4131f85042463c2a355a2003d062adf5aaa10b8c61e636062aaad11c2a26083406");
+
+        let keypair = Keypair::generate_from_entropy(Network::MainNet, &SECRET).expect("keypair");
+        let mut prehashed = sha2::Sha512::new();
+        prehashed.update(b"a");
+        prehashed.update(b"bc");
+        let signature = keypair
+            .sign_prehashed(prehashed.clone(), None)
+            .expect("signature");
+        assert_eq!(SIG, &signature[..]);
+
+        let public_key: &super::PublicKey = (&keypair.public_key).try_into().expect("ed25519 key");
+        assert!(public_key
+            .verify_prehashed(prehashed.clone(), None, &signature)
+            .is_ok());
+        assert!(public_key
+            .verify_prehashed(prehashed, Some(b"helium"), &signature)
+            .is_err());
+        // Ed25519ph signatures do not verify as regular ed25519 signatures
+        assert!(keypair.public_key.verify(b"abc", &signature).is_err());
+    }
+
+    #[cfg(feature = "context")]
+    #[test]
+    fn sign_with_context() {
+        // Ed25519ctx test vector from RFC 8032 section 7.2
+        const SECRET: [u8; 32] =
+            hex!("0305334e381af78f141cb666f6199f57bc3495335a256a95bd2a55bf546663f6");
+        const MSG: &[u8] = &hex!("f726936d19c800494e3fdaff20b276a8");
+        const SIG: &[u8] =
+            &hex!("55a4cc2f70a54e04288c5f4cd1e45a7bb520b36292911876cada7323198dd87a8b36950b95130022907a7fb7c4e9b2d5f6cca685a587b4b21f4b888e4e7edb0d");
+
+        let keypair = Keypair::generate_from_entropy(Network::MainNet, &SECRET).expect("keypair");
+        let signature = keypair.sign_with_context(b"foo", MSG).expect("signature");
+        assert_eq!(SIG, &signature[..]);
+
+        let public_key: &super::PublicKey = (&keypair.public_key).try_into().expect("ed25519 key");
+        assert!(public_key.verify_with_context(b"foo", MSG, SIG).is_ok());
+        assert!(public_key.verify_with_context(b"bar", MSG, SIG).is_err());
+        assert!(keypair.public_key.verify(MSG, SIG).is_err());
+        assert!(keypair.sign_with_context(b"", MSG).is_err());
+        assert!(keypair.sign_with_context(&[0u8; 256], MSG).is_err());
+    }
+
     #[test]
     fn b58_roundtrip_ecc() {
         const B58: &str = "14HZVR4bdF9QMowYxWrumcFBNfWnhDdD5XXA5za1fWwUhHxxFS1";
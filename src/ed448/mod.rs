@@ -0,0 +1,287 @@
+//! Ed448 keypairs.
+//!
+//! Ed448 signatures, as specified in [RFC 8032][RFC8032], offer a 224-bit
+//! security level for deployments where the 128-bit level of ed25519 is not
+//! enough. Signatures are the pure Ed448 variant with an empty context.
+//!
+//! [RFC8032]: https://www.rfc-editor.org/rfc/rfc8032
+use crate::*;
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey([u8; ed448_rust::KEY_LENGTH]);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: ed448_rust::PrivateKey,
+}
+
+pub const KEYPAIR_LENGTH: usize = ed448_rust::KEY_LENGTH + 1;
+pub const PUBLIC_KEY_LENGTH: usize = ed448_rust::KEY_LENGTH + 1;
+pub const SIGNATURE_LENGTH: usize = ed448_rust::SIG_LENGTH;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature = self
+            .secret
+            .sign(msg, None)
+            .map_err(|_| signature::Error::new())?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        if input.len() != KEYPAIR_LENGTH {
+            return Err(Error::invalid_curve());
+        }
+        let network = Network::try_from(input[0])?;
+        let secret =
+            ed448_rust::PrivateKey::try_from(&input[1..]).map_err(|_| Error::invalid_curve())?;
+        Ok(Self::from_secret(network, secret))
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(self.secret.as_bytes())
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        Self::from_secret(network, ed448_rust::PrivateKey::new(csprng))
+    }
+
+    /// Constructs a keypair from the given entropy. Entropy of
+    /// [`ed448_rust::KEY_LENGTH`] bytes is the secret key itself, like the
+    /// RFC 8032 test vectors. Shorter entropy, like the 32 bytes used for
+    /// every other key type, must be at least 32 bytes long and is expanded to
+    /// a secret key with the deterministic key generation DRBG described in
+    /// [`crate::Keypair::generate_deterministic`].
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let secret = match entropy.len() {
+            ed448_rust::KEY_LENGTH => ed448_rust::PrivateKey::try_from(entropy),
+            len if (32..ed448_rust::KEY_LENGTH).contains(&len) => {
+                let expanded = keypair::expand_seed(
+                    u8::from(KeyType::Ed448),
+                    entropy,
+                    0,
+                    ed448_rust::KEY_LENGTH,
+                );
+                ed448_rust::PrivateKey::try_from(&expanded[..])
+            }
+            _ => return Err(Error::invalid_curve()),
+        }
+        .map_err(|_| Error::invalid_curve())?;
+        Ok(Self::from_secret(network, secret))
+    }
+
+    fn from_secret(network: Network, secret: ed448_rust::PrivateKey) -> Self {
+        let public_key = PublicKey(ed448_rust::PublicKey::from(&secret).as_byte());
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            secret,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Ed448,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.as_bytes().to_vec()
+    }
+}
+
+#[cfg(feature = "context")]
+impl Keypair {
+    /// Sign the given message with the given Ed448 context, which must be
+    /// between 1 and 255 bytes long
+    pub fn sign_with_context(&self, context: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+        if context.is_empty() {
+            return Err(signature::Error::new().into());
+        }
+        let signature = self
+            .secret
+            .sign(msg, Some(context))
+            .map_err(|_| signature::Error::new())?;
+        Ok(signature.to_vec())
+    }
+}
+
+#[cfg(feature = "context")]
+impl PublicKey {
+    /// Verify a signature over the given message, made with the given Ed448
+    /// context
+    pub fn verify_with_context(&self, context: &[u8], msg: &[u8], signature: &[u8]) -> Result {
+        if context.is_empty() || signature.len() != SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        ed448_rust::PublicKey::from(self.0)
+            .verify(msg, signature, Some(context))
+            .map_err(|_| signature::Error::new().into())
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        ed448_rust::PublicKey::from(self.0)
+            .verify(msg, signature, None)
+            .map_err(|_| signature::Error::new().into())
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        // Decoding checks that the bytes are a point on the curve
+        ed448_rust::PublicKey::try_from(&buf[..]).map_err(|_| Error::invalid_curve())?;
+        Ok(PublicKey(buf))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.0)
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert_eq!(SIGNATURE_LENGTH, signature.len());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn rfc8032_vector() {
+        // The blank message test vector from RFC 8032 section 7.4
+        let keypair = Keypair::generate_from_entropy(
+            Network::MainNet,
+            &hex!("6c82a562cb808d10d632be89c8513ebf6c929f34ddfa8c9f63c9960ef6e348a3528c8a3fcc2f044e39a3fc5b94492f8f032e7549a20098f95b"),
+        )
+        .expect("keypair");
+        assert_eq!(
+            hex!("5fd7449b59b461fd2ce787ec616ad46a1da1342485a70e1f8a0ea75d80e96778edf124769b46c7061bd6783df1e50f6cd1fa1abeafe8256180")[..],
+            keypair.public_key.to_vec()[1..]
+        );
+        assert_eq!(
+            hex!("533a37f6bbe457251f023c0d88f976ae2dfb504a843e34d2074fd823d41a591f2b233f034f628281f2fd7a22ddd47d7828c59bd0a21bfd3980ff0d2028d4b18a9df63e006c5d1c2d345b925d8dc00b4104852db99ac5c7cdda8530a113a0f4dbb61149f05a7363268c71d95808ff2e652600").to_vec(),
+            keypair.sign(b"").expect("signature")
+        );
+    }
+
+    #[test]
+    fn short_entropy() {
+        let keypair =
+            Keypair::generate_from_entropy(Network::MainNet, &[7u8; 32]).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::generate_from_entropy(Network::MainNet, &[7u8; 32]).expect("keypair")
+        );
+        assert_ne!(
+            keypair,
+            Keypair::generate_from_entropy(Network::MainNet, &[8u8; 32]).expect("keypair")
+        );
+        assert!(Keypair::generate_from_entropy(Network::MainNet, &[7u8; 31]).is_err());
+        assert!(Keypair::generate_from_entropy(Network::MainNet, &[7u8; 58]).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_vec()[..]).expect("keypair")
+        );
+        let public_key = &keypair.public_key;
+        assert_eq!(PUBLIC_KEY_LENGTH, public_key.to_vec().len());
+        assert_eq!(
+            public_key,
+            &public_key::PublicKey::try_from(&public_key.to_vec()[..]).expect("public key")
+        );
+        let decoded: public_key::PublicKey = public_key.to_string().parse().expect("b58");
+        assert_eq!(public_key, &decoded);
+    }
+
+    #[test]
+    fn truncated_keypair() {
+        let bytes = Keypair::generate(Network::TestNet, &mut OsRng).to_vec();
+        for len in 1..KEYPAIR_LENGTH {
+            assert!(Keypair::try_from(&bytes[..len]).is_err());
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Password encrypted keypair files.
+//!
+//! An encrypted keypair is the binary form of a software keypair encrypted
+//! with AES-256-GCM under a key derived from a password with Argon2id. The
+//! header is authenticated along with the ciphertext, so any tampering with
+//! the file, including its KDF parameters, makes it fail to decrypt.
+//!
+//! The binary form of an encrypted keypair is:
+//!
+//! ```text
+//! magic || version (u8) || key tag (u8) || m_cost (u32) || t_cost (u32) ||
+//!     p_cost (u32) || salt (16) || nonce (12) || ciphertext
+//! ```
+//!
+//! The key tag is in the clear so the type of a key can be shown without its
+//! password. Hardware backed keypairs can not be encrypted and are rejected.
+//!
+//! Key bundles of the `backup` feature use the same encryption and KDF.
+use crate::{passphrase::Container, *};
+
+pub use crate::passphrase::{KdfParams, MAX_COST_FACTOR};
+
+/// Identifies the start of an encrypted keypair
+const ENCRYPTED_MAGIC: &[u8] = b"helium-encrypted-keypair";
+/// The current version of the encrypted keypair format
+pub const ENCRYPTED_VERSION: u8 = 1;
+const ENCRYPTED_KEYPAIR: Container = Container {
+    magic: ENCRYPTED_MAGIC,
+    version: ENCRYPTED_VERSION,
+    context_length: 1,
+    name: "encrypted keypair",
+};
+
+impl Keypair {
+    /// Returns the binary form of the keypair encrypted with the given
+    /// password, using the default KDF parameters
+    pub fn to_encrypted_bytes<R>(&self, password: &[u8], csprng: &mut R) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        self.to_encrypted_bytes_with_params(password, KdfParams::default(), csprng)
+    }
+
+    /// Like [`Keypair::to_encrypted_bytes`] but with the given KDF parameters
+    pub fn to_encrypted_bytes_with_params<R>(
+        &self,
+        password: &[u8],
+        params: KdfParams,
+        csprng: &mut R,
+    ) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let plaintext = match self {
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "tpm")]
+            Self::TPM(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => return Err(Error::not_permitted()),
+            _ => self.to_vec(),
+        };
+        ENCRYPTED_KEYPAIR.seal(
+            &[u8::from(self.key_tag())],
+            params,
+            password,
+            &plaintext,
+            csprng,
+        )
+    }
+
+    /// Decrypt a keypair encrypted with the given password. Fails if the
+    /// password is wrong or the encrypted keypair was tampered with.
+    pub fn from_encrypted_bytes(bytes: &[u8], password: &[u8]) -> Result<Keypair> {
+        let (key_tag, plaintext) = ENCRYPTED_KEYPAIR.open(bytes, password)?;
+        let key_tag = KeyTag::try_from(key_tag[0])?;
+        let keypair = Keypair::try_from(&plaintext[..])?;
+        if keypair.key_tag() != key_tag {
+            return Err(Error::invalid_keytype(u8::from(keypair.key_tag())));
+        }
+        Ok(keypair)
+    }
+
+    /// Returns the key tag of an encrypted keypair without decrypting it
+    pub fn encrypted_key_tag(bytes: &[u8]) -> Result<KeyTag> {
+        KeyTag::try_from(ENCRYPTED_KEYPAIR.context(bytes)?[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    // Keep tests fast, the KDF cost does not affect correctness
+    const PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            let keypair = Keypair::generate(key_tag, &mut OsRng);
+            let encrypted = keypair
+                .to_encrypted_bytes_with_params(b"password", PARAMS, &mut OsRng)
+                .expect("encrypted");
+            assert_eq!(
+                key_tag,
+                Keypair::encrypted_key_tag(&encrypted).expect("key tag")
+            );
+            assert_eq!(
+                keypair,
+                Keypair::from_encrypted_bytes(&encrypted, b"password").expect("keypair")
+            );
+            // The secret key is not in the clear
+            let secret = keypair.secret_to_vec();
+            assert!(!encrypted
+                .windows(secret.len())
+                .any(|window| window == &secret[..]));
+        }
+    }
+
+    #[test]
+    fn wrong_password() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let encrypted = keypair
+            .to_encrypted_bytes_with_params(b"password", PARAMS, &mut OsRng)
+            .expect("encrypted");
+        assert!(matches!(
+            Keypair::from_encrypted_bytes(&encrypted, b"wrong password"),
+            Err(Error::Encryption)
+        ));
+    }
+
+    #[test]
+    fn bounded_params() {
+        assert!(KdfParams::default().is_bounded());
+        assert!(PARAMS.is_bounded());
+        let params = KdfParams {
+            t_cost: 3 * MAX_COST_FACTOR + 1,
+            ..KdfParams::default()
+        };
+        assert!(!params.is_bounded());
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(keypair
+            .to_encrypted_bytes_with_params(b"password", params, &mut OsRng)
+            .is_err());
+    }
+
+    #[test]
+    fn tampered() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let encrypted = keypair
+            .to_encrypted_bytes_with_params(b"password", PARAMS, &mut OsRng)
+            .expect("encrypted");
+        // Changing the key tag, the KDF parameters or the ciphertext is
+        // detected
+        let key_tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::Ed25519,
+        };
+        let mut retagged = encrypted.clone();
+        retagged[ENCRYPTED_MAGIC.len() + 1] = u8::from(key_tag);
+        assert!(Keypair::from_encrypted_bytes(&retagged, b"password").is_err());
+        for index in [ENCRYPTED_MAGIC.len() + 9, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[index] ^= 1;
+            assert!(Keypair::from_encrypted_bytes(&tampered, b"password").is_err());
+        }
+        // Excessive KDF parameters are rejected before deriving a key
+        let mut expensive = encrypted.clone();
+        expensive[ENCRYPTED_MAGIC.len() + 2..ENCRYPTED_MAGIC.len() + 6]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            Keypair::from_encrypted_bytes(&expensive, b"password"),
+            Err(Error::Io(_))
+        ));
+        let mut versioned = encrypted;
+        versioned[ENCRYPTED_MAGIC.len()] = ENCRYPTED_VERSION + 1;
+        assert!(matches!(
+            Keypair::from_encrypted_bytes(&versioned, b"password"),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+//! Secure envelopes sign a payload with the sender's keypair and then encrypt
+//! the resulting signed message to a recipient's public key.
+//!
+//! Encryption uses ECIES over ecc_compact keys: an ephemeral keypair is
+//! generated for every envelope, the ECDH shared secret with the recipient is
+//! expanded with HKDF-SHA256 and the signed message is sealed with
+//! AES-256-GCM. The recipient public key is included in the signed data so a
+//! recipient can not re-encrypt a valid envelope to a third party as if the
+//! sender had addressed it to them.
+//!
+//! Opening an envelope always verifies the inner signature, so callers can not
+//! get at a payload without it having been authenticated.
+use crate::{
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use hkdf::Hkdf;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+/// The info string used when deriving the envelope encryption key
+const ENVELOPE_KDF_INFO: &[u8] = b"helium-secure-envelope";
+pub const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecureEnvelope {
+    /// The ephemeral public key used to derive the encryption key
+    pub ephemeral_key: PublicKey,
+    pub nonce: [u8; NONCE_LENGTH],
+    /// The encrypted signed message
+    pub ciphertext: Vec<u8>,
+}
+
+/// The authenticated contents of an opened secure envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenedEnvelope {
+    /// The public key of the sender that signed the payload
+    pub sender: PublicKey,
+    /// Seconds since the unix epoch at which the envelope was sealed
+    pub timestamp: u64,
+    pub payload: Vec<u8>,
+}
+
+impl SecureEnvelope {
+    /// Sign the given payload with the sender keypair and encrypt it to the
+    /// given recipient. The recipient must be an ecc_compact public key.
+    pub fn seal(sender: &Keypair, recipient: &PublicKey, payload: &[u8]) -> Result<Self> {
+        let signed = SignedMessage::seal(sender, &addressed_payload(recipient, payload))?;
+
+        let ephemeral = ecc_compact::Keypair::generate(recipient.network, &mut OsRng);
+        let shared_secret = ephemeral.ecdh(recipient)?;
+        let cipher = envelope_cipher(
+            shared_secret.raw_secret_bytes(),
+            &ephemeral.public_key,
+            recipient,
+        )?;
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let aad = envelope_aad(&ephemeral.public_key, recipient);
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &signed.to_vec(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| Error::encryption())?;
+
+        Ok(Self {
+            ephemeral_key: ephemeral.public_key,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypt the envelope with the given recipient keypair and verify the
+    /// signature of the sender. The payload is only returned if the envelope
+    /// was addressed to the recipient and the signature is valid.
+    pub fn open(&self, recipient: &Keypair) -> Result<OpenedEnvelope> {
+        let recipient_key = recipient.public_key();
+        let shared_secret = recipient.ecdh(&self.ephemeral_key)?;
+        let cipher = envelope_cipher(
+            shared_secret.raw_secret_bytes(),
+            &self.ephemeral_key,
+            recipient_key,
+        )?;
+        let aad = envelope_aad(&self.ephemeral_key, recipient_key);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| Error::encryption())?;
+
+        let signed = SignedMessage::try_from(&plaintext[..])?;
+        let addressed = signed.open()?;
+        let recipient_bytes = recipient_key.to_vec();
+        if !addressed.starts_with(&recipient_bytes) {
+            return Err(Error::encryption());
+        }
+        Ok(OpenedEnvelope {
+            payload: addressed[recipient_bytes.len()..].to_vec(),
+            sender: signed.public_key,
+            timestamp: signed.timestamp,
+        })
+    }
+
+    /// Convert the envelope to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+fn addressed_payload(recipient: &PublicKey, payload: &[u8]) -> Vec<u8> {
+    let mut result = recipient.to_vec();
+    result.extend_from_slice(payload);
+    result
+}
+
+fn envelope_aad(ephemeral_key: &PublicKey, recipient: &PublicKey) -> Vec<u8> {
+    let mut result = ephemeral_key.to_vec();
+    result.extend_from_slice(&recipient.to_vec());
+    result
+}
+
+fn envelope_cipher(
+    shared_secret: &[u8],
+    ephemeral_key: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<Aes256Gcm> {
+    let salt = envelope_aad(ephemeral_key, recipient);
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(ENVELOPE_KDF_INFO, &mut key)
+        .map_err(|_| Error::encryption())?;
+    Aes256Gcm::new_from_slice(&key).map_err(|_| Error::encryption())
+}
+
+impl WriteTo for SecureEnvelope {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        self.ephemeral_key.write_to(output)?;
+        output.write_all(&self.nonce)?;
+        write_u32_prefixed(output, &self.ciphertext)
+    }
+}
+
+impl ReadFrom for SecureEnvelope {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let ephemeral_key = PublicKey::read_from(input)?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        input.read_exact(&mut nonce)?;
+        let ciphertext = read_u32_prefixed(input)?;
+        Ok(Self {
+            ephemeral_key,
+            nonce,
+            ciphertext,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for SecureEnvelope {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(key_type: KeyType) -> Keypair {
+        Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type,
+            },
+            &mut OsRng,
+        )
+    }
+
+    #[test]
+    fn seal_open() {
+        let sender = keypair(KeyType::Ed25519);
+        let recipient = keypair(KeyType::EccCompact);
+        let envelope =
+            SecureEnvelope::seal(&sender, recipient.public_key(), b"hello world").expect("seal");
+
+        let decoded = SecureEnvelope::try_from(&envelope.to_vec()[..]).expect("decoded");
+        assert_eq!(envelope, decoded);
+
+        let opened = decoded.open(&recipient).expect("open");
+        assert_eq!(&opened.sender, sender.public_key());
+        assert_eq!(b"hello world".to_vec(), opened.payload);
+    }
+
+    #[test]
+    fn wrong_recipient() {
+        let sender = keypair(KeyType::EccCompact);
+        let recipient = keypair(KeyType::EccCompact);
+        let other = keypair(KeyType::EccCompact);
+        let envelope =
+            SecureEnvelope::seal(&sender, recipient.public_key(), b"hello world").expect("seal");
+        assert!(envelope.open(&other).is_err());
+    }
+
+    #[test]
+    fn tampered() {
+        let sender = keypair(KeyType::EccCompact);
+        let recipient = keypair(KeyType::EccCompact);
+        let mut envelope =
+            SecureEnvelope::seal(&sender, recipient.public_key(), b"hello world").expect("seal");
+        envelope.ciphertext[0] ^= 0xff;
+        assert!(envelope.open(&recipient).is_err());
+    }
+
+    #[test]
+    fn unsupported_recipient() {
+        let sender = keypair(KeyType::EccCompact);
+        let recipient = keypair(KeyType::Ed25519);
+        assert!(SecureEnvelope::seal(&sender, recipient.public_key(), b"hello world").is_err());
+    }
+}
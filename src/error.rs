@@ -16,12 +16,44 @@ pub enum Error {
     InvalidNetwork,
     #[error("io error")]
     Io(std::io::Error),
+    #[error("encryption error")]
+    Encryption,
+    #[error("stale message timestamp {0}")]
+    Stale(u64),
+    #[error("replayed message")]
+    Replayed,
+    #[error("expired at {0}")]
+    Expired(u64),
+    #[error("not valid before {0}")]
+    NotYetValid(u64),
+    #[error("not permitted")]
+    NotPermitted,
+    #[error("operation timed out")]
+    Timeout,
+    #[error("worker unavailable")]
+    WorkerUnavailable,
+    #[error("worker queue full")]
+    Overloaded,
+    #[error("connection closed")]
+    Closed,
+    #[error("invalid threshold participants")]
+    InvalidThreshold,
 
     #[cfg(feature = "ecc608")]
     #[cfg_attr(docsrs, doc(cfg(feature = "ecc608")))]
     #[error("ecc608 error")]
     Ecc608(#[from] ecc608_linux::Error),
 
+    #[cfg(feature = "ecc608")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ecc608")))]
+    #[error("ecc608 provisioning error")]
+    Ecc608Provision(#[from] crate::ecc608::provision::Error),
+
+    #[cfg(feature = "ecc608")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ecc608")))]
+    #[error("ecc608 certificate error")]
+    Ecc608Certificate(#[from] crate::ecc608::tng::Error),
+
     #[cfg(feature = "multisig")]
     #[cfg_attr(docsrs, doc(cfg(feature = "multisig")))]
     #[error("multisig error")]
@@ -31,6 +63,36 @@ pub enum Error {
     #[cfg_attr(docsrs, doc(cfg(feature = "tpm")))]
     #[error("TPM error")]
     TPM(#[from] crate::tpm::Error),
+
+    #[cfg(feature = "pkcs11")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "pkcs11")))]
+    #[error("PKCS#11 error")]
+    Pkcs11(#[from] crate::pkcs11::Error),
+
+    #[cfg(feature = "se050")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "se050")))]
+    #[error("SE050 error")]
+    Se050(#[from] crate::se050::Error),
+
+    #[cfg(feature = "aws-kms")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "aws-kms")))]
+    #[error("KMS error")]
+    Kms(#[from] crate::kms::Error),
+
+    #[cfg(feature = "azure-keyvault")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "azure-keyvault")))]
+    #[error("Key Vault error")]
+    KeyVault(#[from] crate::keyvault::Error),
+
+    #[cfg(feature = "vault")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "vault")))]
+    #[error("Vault error")]
+    Vault(#[from] crate::vault::Error),
+
+    #[cfg(feature = "vectors")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "vectors")))]
+    #[error("json error")]
+    Json(#[from] serde_json::Error),
 }
 
 #[derive(Error, Debug)]
@@ -45,6 +107,52 @@ pub enum DecodeError {
     NotCompact,
     #[error("missing type byte")]
     MissingType,
+    #[error("invalid magic bytes")]
+    Magic,
+    #[error("unsupported version {0}")]
+    Version(u8),
+    #[error("invalid derivation path {0}")]
+    Path(String),
+    #[error("invalid mnemonic")]
+    Mnemonic,
+    #[error("invalid pkcs8 document")]
+    Pkcs8,
+    #[error("invalid json web key")]
+    Jwk,
+    #[error("invalid openssh key")]
+    OpenSsh,
+    #[error("invalid cose key")]
+    Cose,
+    #[error("invalid multiformat key")]
+    Multiformat,
+    #[error("invalid keystore")]
+    Keystore,
+    #[error("invalid hex encoding")]
+    Hex,
+    #[error("invalid bech32 encoding")]
+    Bech32,
+    #[error("invalid solana keypair")]
+    Solana,
+}
+
+/// Broad classes of errors, used to decide how to react to an error without
+/// matching on every variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Malformed input that will never decode
+    Decode,
+    /// Invalid keys, signatures or messages
+    Crypto,
+    /// Failures reported by a hardware or remote key backend
+    Device,
+    /// Failures transferring a request to or a response from a hardware or
+    /// remote key backend, like I/O errors or checksum mismatches, which may
+    /// not recur when retried
+    Transport,
+    /// An operation that did not complete in time
+    Timeout,
+    /// A worker or queue that can not take on more work right now
+    Unavailable,
 }
 
 impl From<bs58::decode::Error> for Error {
@@ -68,6 +176,68 @@ impl From<std::io::Error> for Error {
 }
 
 impl Error {
+    /// Returns the class of this error
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::Decode(_) => ErrorClass::Decode,
+            // Backends report failures through the signature error with the
+            // backend error as its source
+            Self::Signature(err) => match std::error::Error::source(err) {
+                Some(source) => source
+                    .downcast_ref::<Error>()
+                    .map_or(ErrorClass::Device, Error::class),
+                None => ErrorClass::Crypto,
+            },
+            Self::EccCompact(_)
+            | Self::InvalidCurve
+            | Self::InvalidNetwork
+            | Self::Encryption
+            | Self::Stale(_)
+            | Self::Replayed
+            | Self::Expired(_)
+            | Self::NotYetValid(_)
+            | Self::NotPermitted
+            | Self::InvalidThreshold => ErrorClass::Crypto,
+            Self::Io(err) => match err.kind() {
+                std::io::ErrorKind::InvalidData
+                | std::io::ErrorKind::InvalidInput
+                | std::io::ErrorKind::UnexpectedEof => ErrorClass::Decode,
+                std::io::ErrorKind::TimedOut => ErrorClass::Timeout,
+                _ => ErrorClass::Transport,
+            },
+            Self::Timeout => ErrorClass::Timeout,
+            Self::WorkerUnavailable | Self::Overloaded | Self::Closed => ErrorClass::Unavailable,
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(
+                ecc608_linux::Error::Io { .. }
+                | ecc608_linux::Error::Timeout { .. }
+                | ecc608_linux::Error::Crc { .. },
+            ) => ErrorClass::Transport,
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(_) => ErrorClass::Device,
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608Provision(_) => ErrorClass::Device,
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608Certificate(_) => ErrorClass::Decode,
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => ErrorClass::Crypto,
+            #[cfg(feature = "tpm")]
+            Self::TPM(_) => ErrorClass::Device,
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => ErrorClass::Device,
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => ErrorClass::Device,
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => ErrorClass::Device,
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => ErrorClass::Device,
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => ErrorClass::Device,
+            #[cfg(feature = "vectors")]
+            Self::Json(_) => ErrorClass::Decode,
+        }
+    }
+
     pub fn invalid_curve() -> Error {
         Error::InvalidCurve
     }
@@ -76,6 +246,50 @@ impl Error {
         Error::InvalidNetwork
     }
 
+    pub fn encryption() -> Error {
+        Error::Encryption
+    }
+
+    pub fn stale(timestamp: u64) -> Error {
+        Error::Stale(timestamp)
+    }
+
+    pub fn replayed() -> Error {
+        Error::Replayed
+    }
+
+    pub fn expired(not_after: u64) -> Error {
+        Error::Expired(not_after)
+    }
+
+    pub fn not_yet_valid(not_before: u64) -> Error {
+        Error::NotYetValid(not_before)
+    }
+
+    pub fn not_permitted() -> Error {
+        Error::NotPermitted
+    }
+
+    pub fn timeout() -> Error {
+        Error::Timeout
+    }
+
+    pub fn worker_unavailable() -> Error {
+        Error::WorkerUnavailable
+    }
+
+    pub fn overloaded() -> Error {
+        Error::Overloaded
+    }
+
+    pub fn closed() -> Error {
+        Error::Closed
+    }
+
+    pub fn invalid_threshold() -> Error {
+        Error::InvalidThreshold
+    }
+
     pub fn invalid_keytype(v: u8) -> Error {
         Error::Decode(DecodeError::Type(v))
     }
@@ -91,4 +305,56 @@ impl Error {
     pub fn missing_keytype() -> Error {
         Error::Decode(DecodeError::MissingType)
     }
+
+    pub fn invalid_magic() -> Error {
+        Error::Decode(DecodeError::Magic)
+    }
+
+    pub fn invalid_version(v: u8) -> Error {
+        Error::Decode(DecodeError::Version(v))
+    }
+
+    pub fn invalid_path(v: &str) -> Error {
+        Error::Decode(DecodeError::Path(v.to_string()))
+    }
+
+    pub fn invalid_mnemonic() -> Error {
+        Error::Decode(DecodeError::Mnemonic)
+    }
+
+    pub fn invalid_pkcs8() -> Error {
+        Error::Decode(DecodeError::Pkcs8)
+    }
+
+    pub fn invalid_jwk() -> Error {
+        Error::Decode(DecodeError::Jwk)
+    }
+
+    pub fn invalid_openssh() -> Error {
+        Error::Decode(DecodeError::OpenSsh)
+    }
+
+    pub fn invalid_cose() -> Error {
+        Error::Decode(DecodeError::Cose)
+    }
+
+    pub fn invalid_multiformat() -> Error {
+        Error::Decode(DecodeError::Multiformat)
+    }
+
+    pub fn invalid_keystore() -> Error {
+        Error::Decode(DecodeError::Keystore)
+    }
+
+    pub fn invalid_hex() -> Error {
+        Error::Decode(DecodeError::Hex)
+    }
+
+    pub fn invalid_bech32() -> Error {
+        Error::Decode(DecodeError::Bech32)
+    }
+
+    pub fn invalid_solana() -> Error {
+        Error::Decode(DecodeError::Solana)
+    }
 }
@@ -0,0 +1,436 @@
+//! FROST threshold signatures over ed25519 keys.
+//!
+//! Implements the FROST(Ed25519, SHA-512) ciphersuite from [RFC 9591][RFC9591],
+//! which lets any `min_signers` of `max_signers` participants jointly produce
+//! a standard ed25519 signature that verifies with [`Verify::verify`] against
+//! the group public key. No participant ever learns the group secret.
+//!
+//! Participants get their [`KeyPackage`] from a trusted dealer, either for a
+//! fresh group key with [`generate_with_dealer`] or by [`split`]ting an
+//! existing ed25519 keypair. Signing then takes two rounds:
+//!
+//! 1. Every signer makes fresh nonces with [`commit`] and sends the
+//!    resulting [`SigningCommitments`] to a coordinator.
+//! 2. The coordinator sends a [`SigningPackage`] with the message and the
+//!    commitments of the chosen signers to those signers, who each [`sign`]
+//!    it and return a [`SignatureShare`]. The coordinator [`aggregate`]s the
+//!    shares into the signature.
+//!
+//! The round messages implement serde serialization for transport. Nonces
+//! must never be reused, so [`sign`] consumes them.
+//!
+//! [RFC9591]: https://www.rfc-editor.org/rfc/rfc9591
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, random_scalar, scalar_from_slice},
+    *,
+};
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+/// The RFC 9591 context string for FROST(Ed25519, SHA-512)
+pub(crate) const CONTEXT: &[u8] = b"FROST-ED25519-SHA512-v1";
+
+/// The secret share of the group key held by one participant
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyPackage {
+    /// The non-zero identifier of the participant
+    pub identifier: u16,
+    pub(crate) signing_share: [u8; 32],
+    pub group_public_key: PublicKey,
+    pub min_signers: u16,
+}
+
+/// The secret nonces of one signer for one signing session
+pub struct SigningNonces {
+    hiding: Scalar,
+    binding: Scalar,
+    commitments: SigningCommitments,
+}
+
+/// The round one message of a signer, committing to its nonces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningCommitments {
+    pub identifier: u16,
+    pub hiding: [u8; 32],
+    pub binding: [u8; 32],
+}
+
+/// The message to sign and the commitments of the chosen signers, sent by
+/// the coordinator to every chosen signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningPackage {
+    pub message: Vec<u8>,
+    pub commitments: Vec<SigningCommitments>,
+}
+
+/// The round two message of a signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureShare {
+    pub identifier: u16,
+    pub share: [u8; 32],
+}
+
+impl std::fmt::Debug for KeyPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("KeyPackage")
+            .field("identifier", &self.identifier)
+            .field("group_public_key", &self.group_public_key)
+            .field("min_signers", &self.min_signers)
+            .finish()
+    }
+}
+
+impl SigningNonces {
+    pub fn commitments(&self) -> &SigningCommitments {
+        &self.commitments
+    }
+}
+
+/// Generate a fresh group key on the given network and split it into
+/// `max_signers` key packages, any `min_signers` of which can sign
+pub fn generate_with_dealer<R>(
+    network: Network,
+    max_signers: u16,
+    min_signers: u16,
+    csprng: &mut R,
+) -> Result<(Vec<KeyPackage>, PublicKey)>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let secret = random_scalar(csprng);
+    let group_public_key = group_public_key(network, &base_mul(&secret))?;
+    let packages = deal(&secret, &group_public_key, max_signers, min_signers, csprng)?;
+    Ok((packages, group_public_key))
+}
+
+/// Split the given ed25519 keypair into `max_signers` key packages, any
+/// `min_signers` of which can sign for its public key. The keypair should be
+/// discarded afterwards.
+pub fn split<R>(
+    keypair: &ed25519::Keypair,
+    max_signers: u16,
+    min_signers: u16,
+    csprng: &mut R,
+) -> Result<Vec<KeyPackage>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    deal(
+        &keypair.secret_scalar(),
+        &keypair.public_key,
+        max_signers,
+        min_signers,
+        csprng,
+    )
+}
+
+/// Returns the ed25519 public key on the given network for the given group
+/// public key point
+pub(crate) fn group_public_key(network: Network, point: &EdwardsPoint) -> Result<PublicKey> {
+    let mut bytes = vec![u8::from(KeyTag {
+        network,
+        key_type: KeyType::Ed25519,
+    })];
+    bytes.extend_from_slice(point.compress().as_bytes());
+    PublicKey::try_from(&bytes[..])
+}
+
+/// Shamir split the given secret with a random polynomial of degree
+/// `min_signers - 1`
+fn deal<R>(
+    secret: &Scalar,
+    group_public_key: &PublicKey,
+    max_signers: u16,
+    min_signers: u16,
+    csprng: &mut R,
+) -> Result<Vec<KeyPackage>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    if min_signers < 2 || min_signers > max_signers {
+        return Err(Error::invalid_threshold());
+    }
+    let mut coefficients = vec![*secret];
+    coefficients.extend((1..min_signers).map(|_| random_scalar(csprng)));
+    Ok((1..=max_signers)
+        .map(|identifier| {
+            let x = Scalar::from(identifier as u64);
+            // Horner evaluation of the polynomial at x
+            let share = coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient);
+            KeyPackage {
+                identifier,
+                signing_share: share.to_bytes(),
+                group_public_key: group_public_key.clone(),
+                min_signers,
+            }
+        })
+        .collect())
+}
+
+/// Round one: make fresh nonces for one signing session. Keep the nonces
+/// secret and send the commitments to the coordinator.
+pub fn commit<R>(key_package: &KeyPackage, csprng: &mut R) -> (SigningNonces, SigningCommitments)
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let hiding = generate_nonce(&key_package.signing_share, csprng);
+    let binding = generate_nonce(&key_package.signing_share, csprng);
+    let commitments = SigningCommitments {
+        identifier: key_package.identifier,
+        hiding: base_mul(&hiding).compress().to_bytes(),
+        binding: base_mul(&binding).compress().to_bytes(),
+    };
+    (
+        SigningNonces {
+            hiding,
+            binding,
+            commitments,
+        },
+        commitments,
+    )
+}
+
+fn generate_nonce<R>(signing_share: &[u8; 32], csprng: &mut R) -> Scalar
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let mut random = [0u8; 32];
+    csprng.fill_bytes(&mut random);
+    hash_to_scalar(&[CONTEXT, b"nonce", &random, signing_share])
+}
+
+/// Round two: sign the given signing package with the nonces made for it in
+/// round one
+pub fn sign(
+    signing_package: &SigningPackage,
+    nonces: SigningNonces,
+    key_package: &KeyPackage,
+) -> Result<SignatureShare> {
+    if signing_package.commitments.len() < key_package.min_signers as usize
+        || !signing_package.commitments.contains(&nonces.commitments)
+        || nonces.commitments.identifier != key_package.identifier
+    {
+        return Err(Error::invalid_threshold());
+    }
+    let session = Session::new(signing_package, &key_package.group_public_key)?;
+    let signing_share = scalar_from_slice(&key_package.signing_share)?;
+    let lambda = session.lagrange_coefficient(key_package.identifier)?;
+    let rho = session.binding_factor(key_package.identifier)?;
+    let share = nonces.hiding + nonces.binding * rho + lambda * signing_share * session.challenge;
+    Ok(SignatureShare {
+        identifier: key_package.identifier,
+        share: share.to_bytes(),
+    })
+}
+
+/// Aggregate the signature shares for the given signing package into an
+/// ed25519 signature, which is verified against the group public key
+pub fn aggregate(
+    signing_package: &SigningPackage,
+    shares: &[SignatureShare],
+    group_public_key: &PublicKey,
+) -> Result<Vec<u8>> {
+    let session = Session::new(signing_package, group_public_key)?;
+    if shares.len() != signing_package.commitments.len() {
+        return Err(Error::invalid_threshold());
+    }
+    let mut z = Scalar::zero();
+    for commitments in &signing_package.commitments {
+        let share = shares
+            .iter()
+            .find(|share| share.identifier == commitments.identifier)
+            .ok_or_else(Error::invalid_threshold)?;
+        z += scalar_from_slice(&share.share)?;
+    }
+    let mut signature = session.group_commitment.compress().to_bytes().to_vec();
+    signature.extend_from_slice(z.as_bytes());
+    group_public_key.verify(&signing_package.message, &signature)?;
+    Ok(signature)
+}
+
+/// The values shared by all signers of one signing package
+struct Session<'a> {
+    commitments: Vec<&'a SigningCommitments>,
+    binding_factors: Vec<Scalar>,
+    group_commitment: EdwardsPoint,
+    challenge: Scalar,
+}
+
+impl<'a> Session<'a> {
+    fn new(signing_package: &'a SigningPackage, group_public_key: &PublicKey) -> Result<Self> {
+        let group_public_key: &ed25519::PublicKey = group_public_key.try_into()?;
+        let mut commitments: Vec<&SigningCommitments> =
+            signing_package.commitments.iter().collect();
+        commitments.sort_by_key(|commitments| commitments.identifier);
+        if commitments.is_empty()
+            || commitments[0].identifier == 0
+            || commitments
+                .windows(2)
+                .any(|pair| pair[0].identifier == pair[1].identifier)
+        {
+            return Err(Error::invalid_threshold());
+        }
+
+        let mut encoded_commitments = Sha512::new().chain_update(CONTEXT).chain_update(b"com");
+        for commitment in &commitments {
+            encoded_commitments.update(identifier_scalar(commitment.identifier).as_bytes());
+            encoded_commitments.update(commitment.hiding);
+            encoded_commitments.update(commitment.binding);
+        }
+        let encoded_commitments = encoded_commitments.finalize();
+        let message_hash = Sha512::new()
+            .chain_update(CONTEXT)
+            .chain_update(b"msg")
+            .chain_update(&signing_package.message)
+            .finalize();
+
+        let mut binding_factors = Vec::with_capacity(commitments.len());
+        let mut group_commitment = EdwardsPoint::default();
+        for commitment in &commitments {
+            let rho = hash_to_scalar(&[
+                CONTEXT,
+                b"rho",
+                group_public_key.as_ref(),
+                &message_hash,
+                &encoded_commitments,
+                identifier_scalar(commitment.identifier).as_bytes(),
+            ]);
+            group_commitment +=
+                decompress(&commitment.hiding)? + rho * decompress(&commitment.binding)?;
+            binding_factors.push(rho);
+        }
+        let challenge = hash_to_scalar(&[
+            group_commitment.compress().as_bytes(),
+            group_public_key.as_ref(),
+            &signing_package.message,
+        ]);
+        Ok(Self {
+            commitments,
+            binding_factors,
+            group_commitment,
+            challenge,
+        })
+    }
+
+    fn binding_factor(&self, identifier: u16) -> Result<Scalar> {
+        self.commitments
+            .iter()
+            .position(|commitment| commitment.identifier == identifier)
+            .map(|index| self.binding_factors[index])
+            .ok_or_else(Error::invalid_threshold)
+    }
+
+    /// Returns the Lagrange coefficient of the given identifier for
+    /// interpolating at zero over the identifiers of the signers
+    fn lagrange_coefficient(&self, identifier: u16) -> Result<Scalar> {
+        let x_i = identifier_scalar(identifier);
+        let mut numerator = Scalar::one();
+        let mut denominator = Scalar::one();
+        for commitment in &self.commitments {
+            if commitment.identifier == identifier {
+                continue;
+            }
+            let x_j = identifier_scalar(commitment.identifier);
+            numerator *= x_j;
+            denominator *= x_j - x_i;
+        }
+        if denominator == Scalar::zero() {
+            return Err(Error::invalid_threshold());
+        }
+        Ok(numerator * denominator.invert())
+    }
+}
+
+pub(crate) fn identifier_scalar(identifier: u16) -> Scalar {
+    Scalar::from(identifier as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn sign_with(packages: &[&KeyPackage], message: &[u8]) -> Result<Vec<u8>> {
+        let (nonces, commitments): (Vec<_>, Vec<_>) = packages
+            .iter()
+            .map(|package| commit(package, &mut OsRng))
+            .unzip();
+        let signing_package = SigningPackage {
+            message: message.to_vec(),
+            commitments,
+        };
+        let shares = packages
+            .iter()
+            .zip(nonces)
+            .map(|(package, nonces)| sign(&signing_package, nonces, package))
+            .collect::<Result<Vec<_>>>()?;
+        aggregate(&signing_package, &shares, &packages[0].group_public_key)
+    }
+
+    #[test]
+    fn threshold_sign() {
+        let (packages, group_public_key) =
+            generate_with_dealer(Network::MainNet, 5, 3, &mut OsRng).expect("dealer");
+        for signers in [[0, 1, 2], [1, 3, 4], [4, 0, 2]] {
+            let signers: Vec<&KeyPackage> = signers.iter().map(|i| &packages[*i]).collect();
+            let signature = sign_with(&signers, b"hello world").expect("signature");
+            assert!(group_public_key.verify(b"hello world", &signature).is_ok());
+        }
+        // Too few signers
+        assert!(sign_with(&[&packages[0], &packages[1]], b"hello world").is_err());
+    }
+
+    #[test]
+    fn split_keypair() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let packages = split(&keypair, 3, 2, &mut OsRng).expect("split");
+        assert_eq!(keypair.public_key, packages[0].group_public_key);
+        let signature =
+            sign_with(&[&packages[2], &packages[0]], b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn bad_share() {
+        let (packages, _) =
+            generate_with_dealer(Network::MainNet, 3, 2, &mut OsRng).expect("dealer");
+        let (nonces, commitments): (Vec<_>, Vec<_>) = packages[..2]
+            .iter()
+            .map(|package| commit(package, &mut OsRng))
+            .unzip();
+        let signing_package = SigningPackage {
+            message: b"hello world".to_vec(),
+            commitments,
+        };
+        let mut shares: Vec<SignatureShare> = packages[..2]
+            .iter()
+            .zip(nonces)
+            .map(|(package, nonces)| sign(&signing_package, nonces, package).expect("share"))
+            .collect();
+        shares[1].share = Scalar::one().to_bytes();
+        assert!(aggregate(&signing_package, &shares, &packages[0].group_public_key).is_err());
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let (packages, _) =
+            generate_with_dealer(Network::MainNet, 3, 2, &mut OsRng).expect("dealer");
+        let (_, commitments) = commit(&packages[0], &mut OsRng);
+        let signing_package = SigningPackage {
+            message: b"hello world".to_vec(),
+            commitments: vec![commitments],
+        };
+        let json = serde_json::to_string(&signing_package).expect("serialize");
+        assert_eq!(
+            signing_package,
+            serde_json::from_str(&json).expect("deserialize")
+        );
+    }
+}
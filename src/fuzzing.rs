@@ -0,0 +1,109 @@
+//! Entry points for fuzzing the parsers in this crate.
+//!
+//! Each function feeds arbitrary bytes to a parser and must not panic,
+//! whatever the input. The cargo-fuzz targets in the `fuzz` directory call
+//! these functions. Inputs that once made a target fail are kept in
+//! `fuzz/regressions/<target>` and replayed by the tests of this module.
+use crate::*;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Public keys of every key type to verify fuzzed signatures against
+    static ref PUBLIC_KEYS: Vec<PublicKey> = [KeyType::Ed25519, KeyType::EccCompact]
+        .iter()
+        .map(|key_type| {
+            let key_tag = KeyTag {
+                network: Network::MainNet,
+                key_type: *key_type,
+            };
+            // Unwrap ok here since both key types can be generated from a seed
+            Keypair::generate_deterministic(key_tag, b"fuzzing")
+                .unwrap()
+                .public_key()
+                .clone()
+        })
+        .collect();
+}
+
+/// Message used when verifying fuzzed signatures
+const MESSAGE: &[u8] = b"fuzz";
+
+/// Parse a keypair from its binary form
+pub fn keypair(data: &[u8]) {
+    if let Ok(keypair) = Keypair::try_from(data) {
+        let _ = keypair.to_vec();
+    }
+}
+
+/// Parse a public key from its binary form
+pub fn public_key(data: &[u8]) {
+    if let Ok(public_key) = PublicKey::try_from(data) {
+        let _ = public_key.to_string();
+    }
+}
+
+/// Parse a public key from its base58 form
+pub fn public_key_b58(data: &[u8]) {
+    if let Ok(encoded) = std::str::from_utf8(data) {
+        let _ = PublicKey::from_str(encoded);
+    }
+}
+
+/// Parse a signature, which is DER for ECC compact keys, and verify it
+pub fn signature(data: &[u8]) {
+    for public_key in PUBLIC_KEYS.iter() {
+        let _ = public_key.verify(MESSAGE, data);
+    }
+}
+
+/// Parse a multisig public key from the start of the data, and a multisig
+/// signature bundle from the rest, and verify the bundle
+#[cfg(feature = "multisig")]
+pub fn multisig(data: &[u8]) {
+    let (key, bundle) = data.split_at(data.len().min(multisig::PUBLIC_KEY_LENGTH));
+    if let Ok(public_key) = PublicKey::try_from(key) {
+        let _ = public_key.verify(MESSAGE, bundle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    fn replay(target: &str, f: fn(&[u8])) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fuzz/regressions")
+            .join(target);
+        for entry in fs::read_dir(dir).expect("regressions") {
+            let data = fs::read(entry.expect("entry").path()).expect("regression");
+            f(&data);
+        }
+    }
+
+    #[test]
+    fn keypair_regressions() {
+        replay("keypair", keypair);
+    }
+
+    #[test]
+    fn public_key_regressions() {
+        replay("public_key", public_key);
+    }
+
+    #[test]
+    fn public_key_b58_regressions() {
+        replay("public_key_b58", public_key_b58);
+    }
+
+    #[test]
+    fn signature_regressions() {
+        replay("signature", signature);
+    }
+
+    #[cfg(feature = "multisig")]
+    #[test]
+    fn multisig_regressions() {
+        replay("multisig", multisig);
+    }
+}
@@ -0,0 +1,61 @@
+//! Health of persistent hardware connections.
+//!
+//! Hardware backends keep a long lived connection to their device. When an
+//! operation fails, the backend reconnects and replays the operation once
+//! before giving up. Operations waiting for the connection in the meantime run
+//! against the new connection. The resulting [`Health`] is exposed by the
+//! `health` function of each backend.
+
+/// The health of a hardware connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// The connection has not been initialized yet
+    Uninitialized,
+    /// The last operation succeeded
+    Healthy,
+    /// The given number of consecutive operations failed, even after
+    /// reconnecting
+    Degraded(u32),
+}
+
+impl Health {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Self::Healthy)
+    }
+}
+
+/// Tracks consecutive failures of a connection
+#[derive(Debug, Default)]
+pub(crate) struct FailureCount(u32);
+
+impl FailureCount {
+    pub(crate) fn record<T, E>(&mut self, result: &std::result::Result<T, E>) {
+        self.0 = match result {
+            Ok(_) => 0,
+            Err(_) => self.0.saturating_add(1),
+        };
+    }
+
+    pub(crate) fn health(&self) -> Health {
+        match self.0 {
+            0 => Health::Healthy,
+            failures => Health::Degraded(failures),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_count() {
+        let mut failures = FailureCount::default();
+        assert_eq!(Health::Healthy, failures.health());
+        failures.record::<(), ()>(&Err(()));
+        failures.record::<(), ()>(&Err(()));
+        assert_eq!(Health::Degraded(2), failures.health());
+        failures.record::<(), ()>(&Ok(()));
+        assert!(failures.health().is_healthy());
+    }
+}
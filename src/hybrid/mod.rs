@@ -0,0 +1,322 @@
+//! Hybrid classical and post-quantum keypairs.
+//!
+//! A hybrid keypair bundles an ecc_compact or ed25519 keypair with a
+//! Dilithium keypair. Signing produces both signatures and verification
+//! requires both to be valid, so a hybrid signature stays secure as long as
+//! either scheme does. Since hybrid keypairs are just another key type,
+//! existing signing call sites migrate without changes.
+//!
+//! The binary forms concatenate the binary forms of both parts, each with its
+//! own key tag, after the hybrid key tag. Signatures are the classical
+//! signature with an unsigned varint length prefix, followed by the Dilithium
+//! signature.
+use crate::{dilithium::write_prefixed, *};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PublicKey {
+    classical: Box<public_key::PublicKey>,
+    pq: dilithium::PublicKey,
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    classical: Box<crate::Keypair>,
+    pq: dilithium::Keypair,
+}
+
+/// Both ecc_compact and ed25519 public keys are 33 bytes long
+const CLASSICAL_PUBLIC_KEY_LENGTH: usize = 33;
+
+pub const PUBLIC_KEY_LENGTH: usize = 1 + CLASSICAL_PUBLIC_KEY_LENGTH + dilithium::PUBLIC_KEY_LENGTH;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let mut result = vec![];
+        write_prefixed(&mut result, &self.classical.sign(msg)?)?;
+        result.extend_from_slice(&self.pq.sign(msg)?);
+        Ok(result)
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        let input = &input[1..];
+        let classical_len = match input.first().map(|tag| KeyTag::try_from(*tag)) {
+            Some(Ok(KeyTag {
+                key_type: KeyType::EccCompact,
+                ..
+            })) => ecc_compact::KEYPAIR_LENGTH,
+            Some(Ok(KeyTag {
+                key_type: KeyType::Ed25519,
+                ..
+            })) => ed25519::KEYPAIR_LENGTH,
+            Some(Ok(key_tag)) => return Err(Error::invalid_keytype(key_tag.into())),
+            Some(Err(err)) => return Err(err),
+            None => return Err(Error::missing_keytype()),
+        };
+        if input.len() < classical_len {
+            return Err(Error::invalid_curve());
+        }
+        let classical = crate::Keypair::try_from(&input[..classical_len])?;
+        let pq = dilithium::Keypair::try_from(&input[classical_len..])?;
+        Self::new(network, classical, pq)
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.classical.to_vec())?;
+        output.write_all(&self.pq.to_vec())
+    }
+}
+
+impl Keypair {
+    /// Bundle the given classical and Dilithium keypairs into a hybrid
+    /// keypair. The classical keypair must be a software ecc_compact or
+    /// ed25519 keypair.
+    pub fn new(
+        network: Network,
+        classical: crate::Keypair,
+        pq: dilithium::Keypair,
+    ) -> Result<Self> {
+        match &classical {
+            crate::Keypair::EccCompact(_) | crate::Keypair::Ed25519(_) => (),
+            _ => return Err(Error::invalid_curve()),
+        }
+        if classical.key_tag().network != network || pq.network != network {
+            return Err(Error::invalid_network());
+        }
+        let pq_public_key: &dilithium::PublicKey = (&pq.public_key).try_into()?;
+        let public_key = PublicKey {
+            classical: Box::new(classical.public_key().clone()),
+            pq: pq_public_key.clone(),
+        };
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            classical: Box::new(classical),
+            pq,
+        })
+    }
+
+    /// Generate a hybrid keypair with an ecc_compact classical keypair
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        // Unwrap ok here since the generated keypairs are valid for a hybrid
+        Self::generate_with(network, KeyType::EccCompact, csprng).unwrap()
+    }
+
+    /// Generate a hybrid keypair with a classical keypair of the given type,
+    /// which must be ecc_compact or ed25519. Only the classical keypair is
+    /// drawn from the given random number generator, the Dilithium keypair
+    /// always draws from the operating system one.
+    pub fn generate_with<R>(
+        network: Network,
+        classical_type: KeyType,
+        csprng: &mut R,
+    ) -> Result<Keypair>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let classical = crate::Keypair::generate(
+            KeyTag {
+                network,
+                key_type: classical_type,
+            },
+            csprng,
+        );
+        let pq = dilithium::Keypair::generate(network, csprng);
+        Self::new(network, classical, pq)
+    }
+
+    /// Hybrid keypairs can not be derived from entropy since their Dilithium
+    /// part can not, so this always fails
+    pub fn generate_from_entropy(_network: Network, _entropy: &[u8]) -> Result<Keypair> {
+        Err(Error::invalid_curve())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![];
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Hybrid,
+        }
+    }
+
+    /// Returns the secrets of the classical and Dilithium keypairs,
+    /// concatenated
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        let mut result = self.classical.secret_to_vec();
+        result.extend_from_slice(&self.pq.secret_to_vec());
+        result
+    }
+
+    pub fn classical(&self) -> &crate::Keypair {
+        &self.classical
+    }
+
+    pub fn pq(&self) -> &dilithium::Keypair {
+        &self.pq
+    }
+}
+
+impl PublicKey {
+    pub fn classical(&self) -> &public_key::PublicKey {
+        &self.classical
+    }
+
+    pub fn pq(&self) -> &dilithium::PublicKey {
+        &self.pq
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        // Classical signatures are short enough for their length prefix to be
+        // a single byte
+        let (classical, pq) = match signature.split_first() {
+            Some((len, rest)) if len & 0x80 == 0 && rest.len() >= *len as usize => {
+                rest.split_at(*len as usize)
+            }
+            _ => return Err(signature::Error::new().into()),
+        };
+        self.classical.verify(msg, classical)?;
+        self.pq.verify(msg, pq)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let classical = public_key::PublicKey::read_from(input)?;
+        match classical.key_type() {
+            KeyType::EccCompact | KeyType::Ed25519 => (),
+            key_type => return Err(Error::invalid_keytype(key_type.into())),
+        }
+        let pq = public_key::PublicKey::read_from(input)?;
+        let pq: &dilithium::PublicKey = (&pq).try_into()?;
+        Ok(PublicKey {
+            classical: Box::new(classical),
+            pq: pq.clone(),
+        })
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        self.classical.write_to(output)?;
+        public_key::PublicKey::for_network(self.classical.network, self.pq.clone()).write_to(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn sign_roundtrip() {
+        for classical_type in [KeyType::EccCompact, KeyType::Ed25519] {
+            let keypair = Keypair::generate_with(Network::MainNet, classical_type, &mut OsRng)
+                .expect("keypair");
+            let signature = keypair.sign(b"hello world").expect("signature");
+            assert!(keypair
+                .public_key
+                .verify(b"hello world", &signature)
+                .is_ok());
+            assert!(keypair
+                .public_key
+                .verify(b"hello there", &signature)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn requires_both() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = Keypair::generate(Network::MainNet, &mut OsRng);
+        let classical = keypair.classical().sign(b"hello world").expect("classical");
+        let other_pq = other.pq().sign(b"hello world").expect("pq");
+        let mut signature = vec![];
+        write_prefixed(&mut signature, &classical).expect("prefix");
+        signature.extend_from_slice(&other_pq);
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_err());
+        // A classical signature alone does not verify
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &classical)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate_with(Network::TestNet, KeyType::Ed25519, &mut OsRng)
+            .expect("keypair");
+        let bytes = keypair.to_vec();
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+
+        let public_key = &keypair.public_key;
+        let bytes = public_key.to_vec();
+        assert_eq!(PUBLIC_KEY_LENGTH, bytes.len());
+        assert_eq!(
+            public_key,
+            &public_key::PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+    }
+
+    #[test]
+    fn classical_key_type() {
+        let classical = crate::Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::Dilithium,
+            },
+            &mut OsRng,
+        );
+        let pq = dilithium::Keypair::generate(Network::MainNet, &mut OsRng);
+        assert!(Keypair::new(Network::MainNet, classical, pq).is_err());
+    }
+}
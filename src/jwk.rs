@@ -0,0 +1,205 @@
+//! JSON Web Key conversion of keys.
+//!
+//! Ed25519 keys convert to [RFC 8037][RFC8037] `OKP` keys on the `Ed25519`
+//! curve and ecc_compact keys to [RFC 7518][RFC7518] `EC` keys on the `P-256`
+//! curve. Public keys carry just the public parameters, keypairs the secret
+//! `d` parameter as well. A JSON Web Key has no notion of a Helium network, so
+//! the network is given when importing a key.
+//!
+//! [RFC8037]: https://www.rfc-editor.org/rfc/rfc8037
+//! [RFC7518]: https://www.rfc-editor.org/rfc/rfc7518#section-6.2
+use crate::*;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use serde::{Deserialize, Serialize};
+
+/// A JSON Web Key
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+}
+
+impl std::fmt::Debug for Jwk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Jwk")
+            .field("kty", &self.kty)
+            .field("crv", &self.crv)
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .finish()
+    }
+}
+
+impl Jwk {
+    fn new(kty: &str, crv: &str, x: &[u8], y: Option<&[u8]>) -> Self {
+        Self {
+            kty: kty.to_string(),
+            crv: crv.to_string(),
+            x: encode(x),
+            y: y.map(encode),
+            d: None,
+        }
+    }
+
+    /// Returns the key type of the key, checking the key type and curve
+    /// parameters
+    fn key_type(&self) -> Result<KeyType> {
+        match (self.kty.as_str(), self.crv.as_str()) {
+            ("OKP", "Ed25519") => Ok(KeyType::Ed25519),
+            ("EC", "P-256") => Ok(KeyType::EccCompact),
+            _ => Err(Error::invalid_jwk()),
+        }
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD).map_err(|_| Error::invalid_jwk())
+}
+
+impl PublicKey {
+    /// Returns the JSON Web Key of an ed25519 or ecc_compact public key
+    pub fn to_jwk(&self) -> Result<Jwk> {
+        match self.key_type() {
+            KeyType::Ed25519 => {
+                let public_key: &ed25519::PublicKey = self.try_into()?;
+                Ok(Jwk::new("OKP", "Ed25519", public_key.as_ref(), None))
+            }
+            KeyType::EccCompact => {
+                let public_key: &ecc_compact::PublicKey = self.try_into()?;
+                let point = public_key.0.to_encoded_point(false);
+                // Unwraps ok since an uncompressed point always has both
+                // coordinates
+                Ok(Jwk::new(
+                    "EC",
+                    "P-256",
+                    point.x().unwrap().as_slice(),
+                    Some(point.y().unwrap().as_slice()),
+                ))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Import an ed25519 or ecc_compact public key for the given network from
+    /// the given JSON Web Key. P-256 public keys that are not compactable are
+    /// rejected.
+    pub fn from_jwk(network: Network, jwk: &Jwk) -> Result<PublicKey> {
+        let key_type = jwk.key_type()?;
+        let mut bytes = vec![u8::from(KeyTag { network, key_type })];
+        match key_type {
+            KeyType::Ed25519 => {
+                if jwk.y.is_some() {
+                    return Err(Error::invalid_jwk());
+                }
+                bytes.extend_from_slice(&decode(&jwk.x)?);
+                PublicKey::try_from(&bytes[..])
+            }
+            _ => {
+                let x = decode(&jwk.x)?;
+                let y = decode(jwk.y.as_deref().ok_or_else(Error::invalid_jwk)?)?;
+                if x.len() != 32 || y.len() != 32 {
+                    return Err(Error::invalid_jwk());
+                }
+                let point = [&[0x04][..], &x[..], &y[..]].concat();
+                let public_key = ecc_compact::PublicKey::try_from(&point[..])?;
+                Ok(PublicKey::for_network(network, public_key))
+            }
+        }
+    }
+}
+
+impl Keypair {
+    /// Returns the JSON Web Key, including the secret key, of an ed25519 or
+    /// ecc_compact keypair
+    pub fn to_jwk(&self) -> Result<Jwk> {
+        let mut jwk = self.public_key().to_jwk()?;
+        jwk.d = Some(encode(&self.secret_to_vec()));
+        Ok(jwk)
+    }
+
+    /// Import an ed25519 or ecc_compact keypair for the given network from
+    /// the given JSON Web Key. The public parameters of the key must match
+    /// the secret key.
+    pub fn from_jwk(network: Network, jwk: &Jwk) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: jwk.key_type()?,
+        };
+        let d = decode(jwk.d.as_deref().ok_or_else(Error::invalid_jwk)?)?;
+        let keypair = Keypair::generate_from_entropy(key_tag, &d)?;
+        if keypair.public_key() != &PublicKey::from_jwk(network, jwk)? {
+            return Err(Error::invalid_jwk());
+        }
+        Ok(keypair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn ed25519_vector() {
+        // The example key from RFC 8037 appendix A.1
+        const JWK: &str = r#"{"kty":"OKP","crv":"Ed25519","d":"nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#;
+        let jwk: Jwk = serde_json::from_str(JWK).expect("jwk");
+        let keypair = Keypair::from_jwk(Network::MainNet, &jwk).expect("keypair");
+        assert_eq!(
+            hex!("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+            &keypair.secret_to_vec()[..]
+        );
+        assert_eq!(
+            hex!("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"),
+            &keypair.public_key().to_vec()[1..]
+        );
+        assert_eq!(jwk, keypair.to_jwk().expect("jwk"));
+
+        let public_jwk = keypair.public_key().to_jwk().expect("public jwk");
+        assert_eq!(None, public_jwk.d);
+        assert!(Keypair::from_jwk(Network::MainNet, &public_jwk).is_err());
+    }
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            let keypair = Keypair::generate(key_tag, &mut OsRng);
+            let json = serde_json::to_string(&keypair.to_jwk().expect("jwk")).expect("json");
+            let jwk: Jwk = serde_json::from_str(&json).expect("jwk");
+            assert_eq!(
+                keypair,
+                Keypair::from_jwk(Network::TestNet, &jwk).expect("keypair")
+            );
+            assert_eq!(
+                keypair.public_key(),
+                &PublicKey::from_jwk(Network::TestNet, &jwk).expect("public key")
+            );
+        }
+    }
+
+    #[test]
+    fn mismatched_keys() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let other = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let mut jwk = keypair.to_jwk().expect("jwk");
+        jwk.x = other.to_jwk().expect("jwk").x;
+        assert!(Keypair::from_jwk(Network::MainNet, &jwk).is_err());
+        jwk.crv = "P-384".to_string();
+        assert!(PublicKey::from_jwk(Network::MainNet, &jwk).is_err());
+    }
+}
@@ -1,9 +1,13 @@
 use crate::*;
-use std::ops::Deref;
+use sha2::Digest;
+
+/// Domain separator for deterministic key generation
+const DETERMINISTIC_KEYGEN_DOMAIN: &[u8] = b"helium-crypto-deterministic-keygen";
 
 /// Defines a trait for signing messages. Rather than the signature::Signer
 /// trait which deals with exact signature sizes, this trait allows for variable
-/// sized signatures, since the ECDSA signature is DER encoded.
+/// sized signatures, since the ECDSA signature is DER encoded. Use
+/// [`Keypair::sign_raw`] for fixed size ECDSA signatures.
 pub trait Sign {
     /// Sign the given message
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>>;
@@ -13,44 +17,196 @@ pub trait Sign {
 pub enum Keypair {
     Ed25519(ed25519::Keypair),
     EccCompact(ecc_compact::Keypair),
+    #[cfg(feature = "multisig")]
+    MultiSig(multisig::Keypair),
     #[cfg(feature = "ecc608")]
     Ecc608(ecc608::Keypair),
     #[cfg(feature = "tpm")]
     TPM(tpm::Keypair),
+    #[cfg(feature = "pkcs11")]
+    Pkcs11(pkcs11::Keypair),
+    #[cfg(feature = "se050")]
+    Se050(se050::Keypair),
+    #[cfg(feature = "aws-kms")]
+    Kms(kms::Keypair),
+    #[cfg(feature = "azure-keyvault")]
+    KeyVault(keyvault::Keypair),
+    #[cfg(feature = "vault")]
+    Vault(vault::Keypair),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(secp256k1::Keypair),
+    #[cfg(feature = "bls")]
+    Bls(bls12_381::Keypair),
+    #[cfg(feature = "x25519")]
+    X25519(x25519::Keypair),
+    #[cfg(feature = "ed448")]
+    Ed448(ed448::Keypair),
+    #[cfg(feature = "sr25519")]
+    Sr25519(sr25519::Keypair),
+    #[cfg(feature = "p384")]
+    EccP384(ecc_p384::Keypair),
+    #[cfg(feature = "pqc")]
+    Dilithium(dilithium::Keypair),
+    #[cfg(feature = "pqc")]
+    Hybrid(hybrid::Keypair),
+    #[cfg(feature = "pqc")]
+    Kyber(kyber::Keypair),
+}
+
+/// The shared secret of a key agreement, either from an ECDH or from a key
+/// encapsulation mechanism. Use [`SharedSecret::raw_secret_bytes`] as input to
+/// a key derivation function, whatever the kind of agreement.
+pub enum SharedSecret {
+    Ecdh(ecc_compact::SharedSecret),
+    #[cfg(feature = "p384")]
+    EcdhP384(ecc_p384::SharedSecret),
+    #[cfg(feature = "x25519")]
+    X25519(x25519::SharedSecret),
+    #[cfg(feature = "pqc")]
+    Kem(kyber::SharedSecret),
+}
+
+/// A streaming signer incrementally hashes a message before signing it, so
+/// large messages do not need to be held in memory. The resulting signature is
+/// identical to signing the full message with [`Sign::sign`].
+///
+/// Streaming signing is only supported for key types that sign a SHA-256
+/// digest of the message, which excludes ed25519. Ed25519 keypairs can hash
+/// large messages incrementally with [`Keypair::sign_prehashed`] instead.
+pub struct StreamSigner<'a> {
+    keypair: &'a Keypair,
+    digest: sha2::Sha256,
+}
+
+impl<'a> StreamSigner<'a> {
+    /// Add the given data to the message to be signed
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.digest.update(data)
+    }
+
+    /// Sign the message passed in through `update`
+    pub fn finalize(self) -> Result<Vec<u8>> {
+        match self.keypair {
+            Keypair::EccCompact(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "tpm")]
+            Keypair::TPM(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "pkcs11")]
+            Keypair::Pkcs11(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "se050")]
+            Keypair::Se050(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "aws-kms")]
+            Keypair::Kms(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "azure-keyvault")]
+            Keypair::KeyVault(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            #[cfg(feature = "vault")]
+            Keypair::Vault(keypair) => Ok(keypair.sign_digest(self.digest)?.to_vec()),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
 }
 
-pub struct SharedSecret(ecc_compact::SharedSecret);
+impl<'a> std::io::Write for StreamSigner<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
 impl Sign for Keypair {
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
-        match self {
+        telemetry::observe("sign", self.key_tag(), self.backend(), || match self {
             Self::Ed25519(keypair) => keypair.sign(msg),
             Self::EccCompact(keypair) => keypair.sign(msg),
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(keypair) => keypair.sign(msg),
             #[cfg(feature = "ecc608")]
             Self::Ecc608(keypair) => keypair.sign(msg),
             #[cfg(feature = "tpm")]
             Self::TPM(keypair) => keypair.sign(msg),
-        }
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => keypair.sign(msg),
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => keypair.sign(msg),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(keypair) => keypair.sign(msg),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(keypair) => keypair.sign(msg),
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) => keypair.sign(msg),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => keypair.sign(msg),
+            #[cfg(feature = "bls")]
+            Self::Bls(keypair) => keypair.sign(msg),
+            #[cfg(feature = "x25519")]
+            Self::X25519(_) => Err(Error::invalid_curve()),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => keypair.sign(msg),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => keypair.sign(msg),
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => keypair.sign(msg),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(keypair) => keypair.sign(msg),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(keypair) => keypair.sign(msg),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(_) => Err(Error::invalid_curve()),
+        })
     }
 }
 
 impl Keypair {
+    /// Generate a keypair of the given key type from the given random number
+    /// generator. The post-quantum Dilithium and Kyber keys, including the
+    /// Dilithium half of a hybrid keypair, always draw from the operating
+    /// system random number generator instead.
     pub fn generate<R>(key_tag: KeyTag, csprng: &mut R) -> Keypair
     where
         R: rand_core::CryptoRng + rand_core::RngCore,
     {
-        match key_tag.key_type {
+        let span = telemetry::span("generate", Some(key_tag), telemetry::BACKEND_SOFTWARE);
+        span.in_scope(|| match key_tag.key_type {
             KeyType::EccCompact => {
                 Self::EccCompact(ecc_compact::Keypair::generate(key_tag.network, csprng))
             }
             KeyType::Ed25519 => Self::Ed25519(ed25519::Keypair::generate(key_tag.network, csprng)),
             #[cfg(feature = "multisig")]
-            KeyType::MultiSig => panic!("not supported"),
-        }
+            KeyType::MultiSig => Self::MultiSig(multisig::Keypair::single(
+                ed25519::Keypair::generate(key_tag.network, csprng).into(),
+            )),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => {
+                Self::Secp256k1(secp256k1::Keypair::generate(key_tag.network, csprng))
+            }
+            #[cfg(feature = "bls")]
+            KeyType::Bls => Self::Bls(bls12_381::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => Self::X25519(x25519::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => Self::Ed448(ed448::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => Self::Sr25519(sr25519::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => Self::EccP384(ecc_p384::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => {
+                Self::Dilithium(dilithium::Keypair::generate(key_tag.network, csprng))
+            }
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => Self::Hybrid(hybrid::Keypair::generate(key_tag.network, csprng)),
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => Self::Kyber(kyber::Keypair::generate(key_tag.network, csprng)),
+        })
     }
 
     pub fn generate_from_entropy(key_tag: KeyTag, entropy: &[u8]) -> Result<Keypair> {
-        match key_tag.key_type {
+        let span = telemetry::span("generate", Some(key_tag), telemetry::BACKEND_SOFTWARE);
+        span.in_scope(|| match key_tag.key_type {
             KeyType::EccCompact => Ok(Self::EccCompact(
                 ecc_compact::Keypair::generate_from_entropy(key_tag.network, entropy)?,
             )),
@@ -59,18 +215,174 @@ impl Keypair {
                 entropy,
             )?)),
             #[cfg(feature = "multisig")]
-            KeyType::MultiSig => panic!("not supported"),
-        }
+            KeyType::MultiSig => Ok(Self::MultiSig(multisig::Keypair::single(
+                ed25519::Keypair::generate_from_entropy(key_tag.network, entropy)?.into(),
+            ))),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "bls")]
+            KeyType::Bls => Ok(Self::Bls(bls12_381::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => Ok(Self::X25519(x25519::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => Ok(Self::Ed448(ed448::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => Ok(Self::Sr25519(sr25519::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => Ok(Self::EccP384(ecc_p384::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => Ok(Self::Dilithium(dilithium::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => Ok(Self::Hybrid(hybrid::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => Ok(Self::Kyber(kyber::Keypair::generate_from_entropy(
+                key_tag.network,
+                entropy,
+            )?)),
+        })
+    }
+
+    /// Generate a keypair deterministically from the given seed. The same key
+    /// tag and seed always produce the same keypair, which makes this useful
+    /// for reproducible tests and cross implementation fixtures.
+    ///
+    /// Candidate secrets are drawn from a SHA-256 counter mode DRBG:
+    ///
+    /// ```text
+    /// block(i, 0) = SHA-256("helium-crypto-deterministic-keygen" || tag || seed || i)
+    /// block(i, j) = SHA-256("helium-crypto-deterministic-keygen" || tag || seed || i || j)
+    /// ```
+    ///
+    /// where `tag` is the key tag byte and `i` and `j` are big endian u32
+    /// counters starting at zero. Candidate `i` is the concatenation of
+    /// blocks `block(i, 0)`, `block(i, 1)` and so on, cut to the secret length
+    /// of the key type: 57 bytes for Ed448, 48 bytes for P-384 and 32 bytes
+    /// for every other key type, which is a single block. The first candidate
+    /// that forms a valid secret, as with [`Keypair::generate_from_entropy`],
+    /// is used. For most key types this is always the first candidate; ECC
+    /// compact keys skip candidates which are out of range or whose public
+    /// key is not compactable.
+    ///
+    /// Fails for key types which can not be generated from a seed, like the
+    /// post-quantum key types.
+    ///
+    /// NOTE: Only the seed is secret. Never use this with a low entropy seed
+    /// for keys that protect anything of value.
+    pub fn generate_deterministic(key_tag: KeyTag, seed: &[u8]) -> Result<Keypair> {
+        let length =
+            deterministic_secret_length(key_tag.key_type).ok_or_else(Error::invalid_curve)?;
+        (0u32..)
+            .find_map(|counter| {
+                let candidate = expand_seed(u8::from(key_tag), seed, counter, length);
+                Self::generate_from_entropy(key_tag, &candidate).ok()
+            })
+            .ok_or_else(Error::invalid_curve)
     }
 
     pub fn key_tag(&self) -> KeyTag {
         match self {
             Self::Ed25519(keypair) => keypair.key_tag(),
             Self::EccCompact(keypair) => keypair.key_tag(),
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(keypair) => keypair.key_tag(),
             #[cfg(feature = "ecc608")]
             Self::Ecc608(keypair) => keypair.key_tag(),
             #[cfg(feature = "tpm")]
             Self::TPM(keypair) => keypair.key_tag(),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => keypair.key_tag(),
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => keypair.key_tag(),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(keypair) => keypair.key_tag(),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(keypair) => keypair.key_tag(),
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) => keypair.key_tag(),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => keypair.key_tag(),
+            #[cfg(feature = "bls")]
+            Self::Bls(keypair) => keypair.key_tag(),
+            #[cfg(feature = "x25519")]
+            Self::X25519(keypair) => keypair.key_tag(),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => keypair.key_tag(),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => keypair.key_tag(),
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => keypair.key_tag(),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(keypair) => keypair.key_tag(),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(keypair) => keypair.key_tag(),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(keypair) => keypair.key_tag(),
+        }
+    }
+
+    /// Returns the name of the backend holding the private key, used to
+    /// label telemetry
+    pub(crate) fn backend(&self) -> &'static str {
+        match self {
+            Self::Ed25519(_) | Self::EccCompact(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => "multisig",
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(_) => "ecc608",
+            #[cfg(feature = "tpm")]
+            Self::TPM(_) => "tpm",
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => "pkcs11",
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => "se050",
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => "kms",
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => "keyvault",
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => "vault",
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "bls")]
+            Self::Bls(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "x25519")]
+            Self::X25519(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "ed448")]
+            Self::Ed448(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "p384")]
+            Self::EccP384(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(_) => telemetry::BACKEND_SOFTWARE,
+            #[cfg(feature = "pqc")]
+            Self::Kyber(_) => telemetry::BACKEND_SOFTWARE,
         }
     }
 
@@ -78,32 +390,269 @@ impl Keypair {
         match self {
             Self::Ed25519(keypair) => &keypair.public_key,
             Self::EccCompact(keypair) => &keypair.public_key,
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(keypair) => &keypair.public_key,
             #[cfg(feature = "ecc608")]
             Self::Ecc608(keypair) => &keypair.public_key,
             #[cfg(feature = "tpm")]
             Self::TPM(keypair) => &keypair.public_key,
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => &keypair.public_key,
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => &keypair.public_key,
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(keypair) => &keypair.public_key,
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(keypair) => &keypair.public_key,
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) => &keypair.public_key,
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => &keypair.public_key,
+            #[cfg(feature = "bls")]
+            Self::Bls(keypair) => &keypair.public_key,
+            #[cfg(feature = "x25519")]
+            Self::X25519(keypair) => &keypair.public_key,
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => &keypair.public_key,
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => &keypair.public_key,
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => &keypair.public_key,
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(keypair) => &keypair.public_key,
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(keypair) => &keypair.public_key,
+            #[cfg(feature = "pqc")]
+            Self::Kyber(keypair) => &keypair.public_key,
         }
     }
 
-    pub fn ecdh(&self, public_key: &PublicKey) -> Result<SharedSecret> {
+    /// Construct a streaming signer for this keypair. Returns an error if the
+    /// keypair does not support streaming signing.
+    pub fn signer(&self) -> Result<StreamSigner<'_>> {
         match self {
-            Self::EccCompact(keypair) => Ok(SharedSecret(keypair.ecdh(public_key)?)),
-            #[cfg(feature = "ecc608")]
-            Self::Ecc608(keypair) => Ok(SharedSecret(keypair.ecdh(public_key)?)),
+            Self::EccCompact(_) => (),
+            #[cfg(feature = "tpm")]
+            Self::TPM(_) => (),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => (),
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => (),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => (),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => (),
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) if keypair.key_tag().key_type == KeyType::EccCompact => (),
+            _ => return Err(Error::invalid_curve()),
+        }
+        Ok(StreamSigner {
+            keypair: self,
+            digest: sha2::Sha256::default(),
+        })
+    }
+
+    /// Sign the data read from the given reader using a streaming signer
+    pub fn sign_reader<R: std::io::Read>(&self, reader: &mut R) -> Result<Vec<u8>> {
+        let mut signer = self.signer()?;
+        std::io::copy(reader, &mut signer)?;
+        signer.finalize()
+    }
+
+    /// Sign a SHA-512 digest of a message using Ed25519ph, which lets large
+    /// messages be hashed incrementally before signing. Only ed25519
+    /// keypairs support this, and the resulting signature must be verified
+    /// with [`PublicKey::verify_prehashed`].
+    pub fn sign_prehashed(&self, prehashed: sha2::Sha512) -> Result<Vec<u8>> {
+        telemetry::observe("sign", self.key_tag(), self.backend(), || match self {
+            Self::Ed25519(keypair) => keypair.sign_prehashed(prehashed, None),
+            _ => Err(Error::invalid_curve()),
+        })
+    }
+
+    /// Sign the given message bound to the given context, so signatures made
+    /// for one protocol or message type can not be mistaken for another.
+    /// Contexts must be between 1 and 255 bytes long.
+    ///
+    /// Ed25519 keypairs sign with Ed25519ctx, Ed448 and sr25519 keypairs with
+    /// their native contexts, and ECDSA keypairs sign the message prefixed
+    /// with the context length and the context. Context signatures only verify
+    /// with [`PublicKey::verify_with_context`] and the same context.
+    #[cfg(feature = "context")]
+    pub fn sign_with_context(&self, context: &[u8], msg: &[u8]) -> Result<Vec<u8>> {
+        telemetry::observe("sign", self.key_tag(), self.backend(), || match self {
+            Self::Ed25519(keypair) => keypair.sign_with_context(context, msg),
+            Self::EccCompact(keypair) => keypair.sign_with_context(context, msg),
+            #[cfg(feature = "tpm")]
+            Self::TPM(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) => Ok(keypair
+                .sign_digest(ecc_compact::context_digest(context, msg)?)?
+                .to_vec()),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => keypair.sign_with_context(context, msg),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => Ok(keypair.sign_with_context(context, msg)),
+            _ => Err(Error::invalid_curve()),
+        })
+    }
+
+    /// Sign the given message, returning the fixed size raw `r || s` form of
+    /// the signature rather than the DER encoded form [`Sign::sign`] returns.
+    /// Only ecc_compact, TPM, PKCS#11, SE050, KMS and Key Vault keypairs, and
+    /// Vault keypairs with an ECDSA key, support this.
+    pub fn sign_raw(&self, msg: &[u8]) -> Result<[u8; ecc_compact::RAW_SIGNATURE_LENGTH]> {
+        telemetry::observe("sign", self.key_tag(), self.backend(), || match self {
+            Self::EccCompact(keypair) => keypair.sign_raw(msg),
             #[cfg(feature = "tpm")]
-            Self::TPM(keypair) => Ok(SharedSecret(keypair.ecdh(public_key)?)),
+            Self::TPM(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
+            #[cfg(feature = "vault")]
+            Self::Vault(keypair) => Ok(keypair
+                .sign_digest(sha2::Sha256::new_with_prefix(msg))?
+                .to_raw()),
             _ => Err(Error::invalid_curve()),
+        })
+    }
+
+    /// Sign the given message with a recoverable signature, from which
+    /// [`PublicKey::recover`] recovers the public key of this keypair. Only
+    /// software ECDSA keypairs support this.
+    pub fn sign_recoverable(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        telemetry::observe("sign", self.key_tag(), self.backend(), || match self {
+            Self::EccCompact(keypair) => keypair.sign_recoverable(msg),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => keypair.sign_recoverable(msg),
+            _ => Err(Error::invalid_curve()),
+        })
+    }
+
+    /// Derive the child keypair at the given SLIP-0010 path, using the secret
+    /// of this keypair as the seed. Only software ed25519, ecc_compact and
+    /// secp256k1 keypairs support this. Use [`derivation::ExtendedKey`] to
+    /// derive keypairs from any other seed.
+    #[cfg(feature = "derivation")]
+    pub fn derive_path(&self, path: &derivation::DerivationPath) -> Result<Keypair> {
+        match self {
+            Self::Ed25519(_) | Self::EccCompact(_) => (),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(_) => (),
+            _ => return Err(Error::invalid_curve()),
         }
+        let key_tag = self.key_tag();
+        derivation::ExtendedKey::from_seed(key_tag.key_type, &self.secret_to_vec())?
+            .derive_path(path)?
+            .to_keypair(key_tag.network)
+    }
+
+    pub fn ecdh(&self, public_key: &PublicKey) -> Result<SharedSecret> {
+        telemetry::observe("ecdh", self.key_tag(), self.backend(), || match self {
+            Self::EccCompact(keypair) => Ok(SharedSecret::Ecdh(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(keypair) => Ok(SharedSecret::Ecdh(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "tpm")]
+            Self::TPM(keypair) => Ok(SharedSecret::Ecdh(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(keypair) => Ok(SharedSecret::Ecdh(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "se050")]
+            Self::Se050(keypair) => Ok(SharedSecret::Ecdh(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "x25519")]
+            Self::X25519(keypair) => Ok(SharedSecret::X25519(keypair.ecdh(public_key)?)),
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => Ok(SharedSecret::EcdhP384(keypair.ecdh(public_key)?)),
+            _ => Err(Error::invalid_curve()),
+        })
+    }
+
+    /// Decapsulate the shared secret from a ciphertext made by
+    /// [`PublicKey::encapsulate`]. Only key encapsulation keypairs, like
+    /// Kyber ones, support this.
+    pub fn decapsulate(&self, ciphertext: &[u8]) -> Result<SharedSecret> {
+        telemetry::observe(
+            "decapsulate",
+            self.key_tag(),
+            self.backend(),
+            || match self {
+                #[cfg(feature = "pqc")]
+                Self::Kyber(keypair) => Ok(SharedSecret::Kem(keypair.decapsulate(ciphertext)?)),
+                _ => Err(Error::invalid_curve()),
+            },
+        )
     }
 
     pub fn to_vec(&self) -> Vec<u8> {
         match self {
             Self::Ed25519(keypair) => keypair.to_vec(),
             Self::EccCompact(keypair) => keypair.to_vec(),
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => panic!("not supported"),
             #[cfg(feature = "ecc608")]
             Self::Ecc608(_) => panic!("not supported"),
             #[cfg(feature = "tpm")]
             Self::TPM(_) => panic!("not supported"),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => panic!("not supported"),
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => panic!("not supported"),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => panic!("not supported"),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => panic!("not supported"),
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => panic!("not supported"),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => keypair.to_vec(),
+            #[cfg(feature = "bls")]
+            Self::Bls(keypair) => keypair.to_vec(),
+            #[cfg(feature = "x25519")]
+            Self::X25519(keypair) => keypair.to_vec(),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => keypair.to_vec(),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => keypair.to_vec(),
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => keypair.to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(keypair) => keypair.to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(keypair) => keypair.to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(keypair) => keypair.to_vec(),
         }
     }
 
@@ -111,14 +660,88 @@ impl Keypair {
         match self {
             Self::Ed25519(keypair) => keypair.secret_to_vec(),
             Self::EccCompact(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => panic!("not supported"),
             #[cfg(feature = "ecc608")]
             Self::Ecc608(_) => panic!("not supported"),
             #[cfg(feature = "tpm")]
             Self::TPM(_) => panic!("not supported"),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => panic!("not supported"),
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => panic!("not supported"),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => panic!("not supported"),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => panic!("not supported"),
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => panic!("not supported"),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "bls")]
+            Self::Bls(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "x25519")]
+            Self::X25519(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "p384")]
+            Self::EccP384(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(keypair) => keypair.secret_to_vec(),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(keypair) => keypair.secret_to_vec(),
         }
     }
 }
 
+/// Returns candidate `counter` of `length` bytes drawn from the SHA-256
+/// counter mode DRBG described in [`Keypair::generate_deterministic`] for the
+/// given tag byte and seed. This is the one expansion scheme for turning a
+/// seed into secret key material, and is also used by key types whose
+/// [`Keypair::generate_from_entropy`] accepts entropy shorter than their
+/// secret, with the key type byte as the tag.
+pub(crate) fn expand_seed(tag: u8, seed: &[u8], counter: u32, length: usize) -> Vec<u8> {
+    let block = |index: Option<u32>| {
+        let mut digest = sha2::Sha256::new()
+            .chain_update(DETERMINISTIC_KEYGEN_DOMAIN)
+            .chain_update([tag])
+            .chain_update(seed)
+            .chain_update(counter.to_be_bytes());
+        if let Some(index) = index {
+            digest.update(index.to_be_bytes());
+        }
+        digest.finalize()
+    };
+    let mut candidate = block(None).to_vec();
+    for index in 1.. {
+        if candidate.len() >= length {
+            break;
+        }
+        candidate.extend_from_slice(&block(Some(index)));
+    }
+    candidate.truncate(length);
+    candidate
+}
+
+/// Returns the length of the secret [`Keypair::generate_deterministic`]
+/// draws for the given key type, or `None` if keypairs of the key type can
+/// not be generated from a seed
+fn deterministic_secret_length(key_type: KeyType) -> Option<usize> {
+    match key_type {
+        #[cfg(feature = "ed448")]
+        KeyType::Ed448 => Some(ed448_rust::KEY_LENGTH),
+        #[cfg(feature = "p384")]
+        KeyType::EccP384 => Some(48),
+        #[cfg(feature = "pqc")]
+        KeyType::Dilithium | KeyType::Hybrid | KeyType::Kyber => None,
+        _ => Some(32),
+    }
+}
+
 impl From<ed25519::Keypair> for Keypair {
     fn from(keypair: ed25519::Keypair) -> Self {
         Self::Ed25519(keypair)
@@ -131,6 +754,13 @@ impl From<ecc_compact::Keypair> for Keypair {
     }
 }
 
+#[cfg(feature = "multisig")]
+impl From<multisig::Keypair> for Keypair {
+    fn from(keypair: multisig::Keypair) -> Self {
+        Self::MultiSig(keypair)
+    }
+}
+
 #[cfg(feature = "ecc608")]
 impl From<ecc608::Keypair> for Keypair {
     fn from(keypair: ecc608::Keypair) -> Self {
@@ -145,23 +775,140 @@ impl From<tpm::Keypair> for Keypair {
     }
 }
 
+#[cfg(feature = "pkcs11")]
+impl From<pkcs11::Keypair> for Keypair {
+    fn from(keypair: pkcs11::Keypair) -> Self {
+        Self::Pkcs11(keypair)
+    }
+}
+
+#[cfg(feature = "se050")]
+impl From<se050::Keypair> for Keypair {
+    fn from(keypair: se050::Keypair) -> Self {
+        Self::Se050(keypair)
+    }
+}
+
+#[cfg(feature = "aws-kms")]
+impl From<kms::Keypair> for Keypair {
+    fn from(keypair: kms::Keypair) -> Self {
+        Self::Kms(keypair)
+    }
+}
+
+#[cfg(feature = "azure-keyvault")]
+impl From<keyvault::Keypair> for Keypair {
+    fn from(keypair: keyvault::Keypair) -> Self {
+        Self::KeyVault(keypair)
+    }
+}
+
+#[cfg(feature = "vault")]
+impl From<vault::Keypair> for Keypair {
+    fn from(keypair: vault::Keypair) -> Self {
+        Self::Vault(keypair)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl From<secp256k1::Keypair> for Keypair {
+    fn from(keypair: secp256k1::Keypair) -> Self {
+        Self::Secp256k1(keypair)
+    }
+}
+
+#[cfg(feature = "bls")]
+impl From<bls12_381::Keypair> for Keypair {
+    fn from(keypair: bls12_381::Keypair) -> Self {
+        Self::Bls(keypair)
+    }
+}
+#[cfg(feature = "x25519")]
+impl From<x25519::Keypair> for Keypair {
+    fn from(keypair: x25519::Keypair) -> Self {
+        Self::X25519(keypair)
+    }
+}
+#[cfg(feature = "ed448")]
+impl From<ed448::Keypair> for Keypair {
+    fn from(keypair: ed448::Keypair) -> Self {
+        Self::Ed448(keypair)
+    }
+}
+#[cfg(feature = "sr25519")]
+impl From<sr25519::Keypair> for Keypair {
+    fn from(keypair: sr25519::Keypair) -> Self {
+        Self::Sr25519(keypair)
+    }
+}
+#[cfg(feature = "p384")]
+impl From<ecc_p384::Keypair> for Keypair {
+    fn from(keypair: ecc_p384::Keypair) -> Self {
+        Self::EccP384(keypair)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<dilithium::Keypair> for Keypair {
+    fn from(keypair: dilithium::Keypair) -> Self {
+        Self::Dilithium(keypair)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<hybrid::Keypair> for Keypair {
+    fn from(keypair: hybrid::Keypair) -> Self {
+        Self::Hybrid(keypair)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<kyber::Keypair> for Keypair {
+    fn from(keypair: kyber::Keypair) -> Self {
+        Self::Kyber(keypair)
+    }
+}
+
 impl TryFrom<&[u8]> for Keypair {
     type Error = Error;
 
     fn try_from(input: &[u8]) -> Result<Self> {
-        match KeyType::try_from(input[0])? {
+        let tag = *input.first().ok_or_else(Error::missing_keytype)?;
+        match KeyType::try_from(tag)? {
             KeyType::Ed25519 => Ok(ed25519::Keypair::try_from(input)?.into()),
             KeyType::EccCompact => Ok(ecc_compact::Keypair::try_from(input)?.into()),
             #[cfg(feature = "multisig")]
-            KeyType::MultiSig => Err(Error::invalid_keytype(input[0])),
+            KeyType::MultiSig => Err(Error::invalid_keytype(tag)),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Ok(secp256k1::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "bls")]
+            KeyType::Bls => Ok(bls12_381::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => Ok(x25519::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => Ok(ed448::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => Ok(sr25519::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => Ok(ecc_p384::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => Ok(dilithium::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => Ok(hybrid::Keypair::try_from(input)?.into()),
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => Ok(kyber::Keypair::try_from(input)?.into()),
         }
     }
 }
 
-impl Deref for SharedSecret {
-    type Target = ecc_compact::SharedSecret;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl SharedSecret {
+    pub fn raw_secret_bytes(&self) -> &[u8] {
+        match self {
+            Self::Ecdh(shared_secret) => shared_secret.raw_secret_bytes().as_slice(),
+            #[cfg(feature = "p384")]
+            Self::EcdhP384(shared_secret) => shared_secret.raw_secret_bytes().as_slice(),
+            #[cfg(feature = "x25519")]
+            Self::X25519(shared_secret) => shared_secret.raw_secret_bytes(),
+            #[cfg(feature = "pqc")]
+            Self::Kem(shared_secret) => shared_secret.raw_secret_bytes(),
+        }
     }
 }
 
@@ -260,6 +1007,188 @@ mod tests {
         sign_test_keypair(&Keypair::TPM(keypair));
     }
 
+    #[cfg(feature = "multisig")]
+    #[test]
+    fn sign_multisig() {
+        sign_test_tag(KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::MultiSig,
+        });
+    }
+
+    #[test]
+    fn stream_sign_ecc_compact() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let mut signer = keypair.signer().expect("signer");
+        signer.update(b"hello ");
+        signer.update(b"world");
+        let signature = signer.finalize().expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_ok());
+
+        let mut verifier = keypair.public_key().verifier().expect("verifier");
+        verifier.update(b"hello ");
+        verifier.update(b"world");
+        assert!(verifier.finalize(&signature).is_ok());
+
+        let signature = keypair
+            .sign_reader(&mut &b"hello world"[..])
+            .expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify_reader(&mut &b"hello world"[..], &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn generate_deterministic() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::MainNet,
+                key_type,
+            };
+            let keypair = Keypair::generate_deterministic(key_tag, b"seed").expect("keypair");
+            assert_eq!(
+                keypair,
+                Keypair::generate_deterministic(key_tag, b"seed").expect("keypair")
+            );
+            assert_ne!(
+                keypair,
+                Keypair::generate_deterministic(key_tag, b"other").expect("keypair")
+            );
+            assert_eq!(key_tag, keypair.key_tag());
+        }
+    }
+
+    #[test]
+    fn generate_deterministic_key_types() {
+        for key_type in (0u8..16).filter_map(|v| KeyType::try_from(v).ok()) {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            match Keypair::generate_deterministic(key_tag, b"seed") {
+                Ok(keypair) => {
+                    assert_eq!(key_tag, keypair.key_tag());
+                    assert_eq!(
+                        keypair,
+                        Keypair::generate_deterministic(key_tag, b"seed").expect("keypair")
+                    );
+                }
+                Err(_) => assert!(deterministic_secret_length(key_type).is_none()),
+            }
+        }
+    }
+
+    #[test]
+    fn stream_sign_ed25519() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(keypair.signer().is_err());
+        assert!(keypair.public_key().verifier().is_err());
+    }
+
+    #[test]
+    fn sign_prehashed() {
+        use sha2::Digest;
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let mut prehashed = sha2::Sha512::new();
+        prehashed.update(b"hello ");
+        prehashed.update(b"world");
+        let signature = keypair
+            .sign_prehashed(prehashed.clone())
+            .expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify_prehashed(prehashed, &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_err());
+
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        assert!(keypair.sign_prehashed(sha2::Sha512::new()).is_err());
+    }
+
+    #[cfg(feature = "context")]
+    #[test]
+    fn sign_with_context() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let keypair = Keypair::generate(
+                KeyTag {
+                    network: Network::MainNet,
+                    key_type,
+                },
+                &mut OsRng,
+            );
+            let signature = keypair
+                .sign_with_context(b"foo", b"hello world")
+                .expect("signature");
+            let public_key = keypair.public_key();
+            assert!(public_key
+                .verify_with_context(b"foo", b"hello world", &signature)
+                .is_ok());
+            assert!(public_key
+                .verify_with_context(b"bar", b"hello world", &signature)
+                .is_err());
+            assert!(public_key.verify(b"hello world", &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn sign_raw() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let signature = keypair.sign_raw(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify_raw(b"hello world", &signature)
+            .is_ok());
+
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(keypair.sign_raw(b"hello world").is_err());
+        assert!(keypair
+            .public_key()
+            .verify_raw(b"hello world", &[0u8; 64])
+            .is_err());
+    }
+
+    #[test]
+    fn recover() {
+        let key_tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccCompact,
+        };
+        let keypair = Keypair::generate(key_tag, &mut OsRng);
+        let signature = keypair.sign_recoverable(b"hello world").expect("signature");
+        assert_eq!(
+            keypair.public_key(),
+            &PublicKey::recover(key_tag, b"hello world", &signature).expect("recover")
+        );
+
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(keypair.sign_recoverable(b"hello world").is_err());
+    }
+
     #[test]
     fn ecdh_ecc_compact() {
         ecdh_test_tag(KeyTag {
@@ -268,6 +1197,47 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "p384")]
+    #[test]
+    fn ecdh_ecc_p384() {
+        ecdh_test_tag(KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccP384,
+        });
+    }
+
+    #[cfg(feature = "pqc")]
+    #[test]
+    fn encapsulate_kyber() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::Kyber,
+            },
+            &mut OsRng,
+        );
+        let (shared_secret, ciphertext) = keypair.public_key().encapsulate().expect("encapsulate");
+        let decapsulated = keypair.decapsulate(&ciphertext).expect("decapsulate");
+        assert_eq!(
+            shared_secret.raw_secret_bytes(),
+            decapsulated.raw_secret_bytes()
+        );
+        assert!(keypair.ecdh(keypair.public_key()).is_err());
+    }
+
+    #[test]
+    fn encapsulate_ecc_compact() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        assert!(keypair.public_key().encapsulate().is_err());
+        assert!(keypair.decapsulate(&[0u8; 32]).is_err());
+    }
+
     #[cfg(feature = "tpm")]
     #[test]
     fn ecdh_tpm() {
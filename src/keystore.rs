@@ -0,0 +1,320 @@
+//! Password protected keystore JSON for software keypairs.
+//!
+//! Keystores use the version 3 [Web3 Secret Storage][WEB3] format that
+//! Ethereum wallets read and write: the secret key is encrypted with
+//! AES-128-CTR under a key derived from the password with scrypt, and a
+//! Keccak-256 MAC over the ciphertext detects a wrong password. Keystores
+//! made with the PBKDF2 key derivation can be imported as well.
+//!
+//! The format stores only the 32 byte secret key, so ed25519, ecc_compact and
+//! secp256k1 keypairs are supported, and the key tag of the keypair is given
+//! when importing a keystore. Keystores made by Ethereum wallets import as
+//! secp256k1 keypairs.
+//!
+//! [WEB3]: https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/
+use crate::*;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// The version of the keystore format
+pub const KEYSTORE_VERSION: u32 = 3;
+/// The default scrypt cost parameter, as the base 2 logarithm of `n`
+pub const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const DERIVED_KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 32;
+const IV_LENGTH: usize = 16;
+const SECRET_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    #[serde(alias = "Crypto")]
+    crypto: Crypto,
+    id: String,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Crypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: KdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+/// The parameters of either of the scrypt and pbkdf2 key derivations
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+    dklen: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    r: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    c: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prf: Option<String>,
+    salt: String,
+}
+
+impl Keypair {
+    /// Returns the keystore JSON of an ed25519, ecc_compact or secp256k1
+    /// keypair, encrypted with the given password
+    pub fn to_keystore<R>(&self, password: &[u8], csprng: &mut R) -> Result<String>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        self.to_keystore_with_log_n(password, DEFAULT_SCRYPT_LOG_N, csprng)
+    }
+
+    /// Like [`Keypair::to_keystore`] but with the given scrypt cost parameter
+    pub fn to_keystore_with_log_n<R>(
+        &self,
+        password: &[u8],
+        log_n: u8,
+        csprng: &mut R,
+    ) -> Result<String>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        match self {
+            Self::Ed25519(_) | Self::EccCompact(_) => (),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(_) => (),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::invalid_curve()),
+        }
+        let mut salt = [0u8; SALT_LENGTH];
+        csprng.fill_bytes(&mut salt);
+        let mut iv = [0u8; IV_LENGTH];
+        csprng.fill_bytes(&mut iv);
+        let mut id = [0u8; 16];
+        csprng.fill_bytes(&mut id);
+
+        let kdfparams = KdfParams {
+            dklen: DERIVED_KEY_LENGTH,
+            n: Some(1 << log_n),
+            r: Some(SCRYPT_R),
+            p: Some(SCRYPT_P),
+            c: None,
+            prf: None,
+            salt: hex::encode(salt),
+        };
+        let derived_key = derive_key("scrypt", &kdfparams, password)?;
+        let mut ciphertext = self.secret_to_vec();
+        Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|_| Error::encryption())?
+            .apply_keystream(&mut ciphertext);
+
+        let keystore = Keystore {
+            crypto: Crypto {
+                cipher: "aes-128-ctr".to_string(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                mac: hex::encode(mac(&derived_key, &ciphertext)),
+                ciphertext: hex::encode(ciphertext),
+                kdf: "scrypt".to_string(),
+                kdfparams,
+            },
+            id: uuid(id),
+            version: KEYSTORE_VERSION,
+        };
+        serde_json::to_string(&keystore).map_err(|_| Error::invalid_keystore())
+    }
+
+    /// Import a keypair with the given key tag from the given keystore JSON
+    /// encrypted with the given password. Fails with an encryption error if
+    /// the password is wrong.
+    pub fn from_keystore(key_tag: KeyTag, json: &str, password: &[u8]) -> Result<Keypair> {
+        match key_tag.key_type {
+            KeyType::Ed25519 | KeyType::EccCompact => (),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => (),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::invalid_curve()),
+        }
+        let keystore: Keystore =
+            serde_json::from_str(json).map_err(|_| Error::invalid_keystore())?;
+        let crypto = keystore.crypto;
+        if keystore.version != KEYSTORE_VERSION || crypto.cipher != "aes-128-ctr" {
+            return Err(Error::invalid_keystore());
+        }
+        let iv = decode(&crypto.cipherparams.iv)?;
+        let mut secret = decode(&crypto.ciphertext)?;
+        let expected_mac = decode(&crypto.mac)?;
+        if iv.len() != IV_LENGTH || secret.len() != SECRET_LENGTH {
+            return Err(Error::invalid_keystore());
+        }
+
+        let derived_key = derive_key(&crypto.kdf, &crypto.kdfparams, password)?;
+        if mac(&derived_key, &secret)[..] != expected_mac[..] {
+            return Err(Error::encryption());
+        }
+        Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|_| Error::encryption())?
+            .apply_keystream(&mut secret);
+        Keypair::generate_from_entropy(key_tag, &secret)
+    }
+}
+
+fn derive_key(kdf: &str, params: &KdfParams, password: &[u8]) -> Result<Vec<u8>> {
+    if params.dklen != DERIVED_KEY_LENGTH {
+        return Err(Error::invalid_keystore());
+    }
+    let salt = decode(&params.salt)?;
+    let mut derived_key = vec![0u8; params.dklen];
+    match (kdf, params) {
+        (
+            "scrypt",
+            KdfParams {
+                n: Some(n),
+                r: Some(r),
+                p: Some(p),
+                ..
+            },
+        ) if n.is_power_of_two() => {
+            let log_n = n.trailing_zeros() as u8;
+            let scrypt_params = scrypt::Params::new(log_n, *r, *p, derived_key.len())
+                .map_err(|_| Error::invalid_keystore())?;
+            scrypt::scrypt(password, &salt, &scrypt_params, &mut derived_key)
+                .map_err(|_| Error::invalid_keystore())?;
+        }
+        (
+            "pbkdf2",
+            KdfParams {
+                c: Some(c),
+                prf: Some(prf),
+                ..
+            },
+        ) if prf == "hmac-sha256" => {
+            pbkdf2::pbkdf2_hmac::<Sha256>(password, &salt, *c, &mut derived_key);
+        }
+        _ => return Err(Error::invalid_keystore()),
+    }
+    Ok(derived_key)
+}
+
+/// The MAC over the ciphertext, keyed with the second half of the derived key
+fn mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    Keccak256::new()
+        .chain_update(&derived_key[16..32])
+        .chain_update(ciphertext)
+        .finalize()
+        .into()
+}
+
+/// Formats the given random bytes as a version 4 UUID
+fn uuid(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|_| Error::invalid_keystore())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    // Keep tests fast, the scrypt cost does not affect correctness
+    const LOG_N: u8 = 4;
+
+    #[test]
+    fn pbkdf2_vector() {
+        // The PBKDF2-SHA-256 test vector of the Web3 Secret Storage definition
+        const JSON: &str = r#"{
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 262144,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+        // The vector holds a secp256k1 secret key, but any 32 bytes are a
+        // valid ed25519 seed
+        let keypair =
+            Keypair::from_keystore(KeyTag::default(), JSON, b"testpassword").expect("keypair");
+        assert_eq!(
+            hex!("7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"),
+            &keypair.secret_to_vec()[..]
+        );
+        assert!(matches!(
+            Keypair::from_keystore(KeyTag::default(), JSON, b"wrongpassword"),
+            Err(Error::Encryption)
+        ));
+    }
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            let keypair = Keypair::generate(key_tag, &mut OsRng);
+            let json = keypair
+                .to_keystore_with_log_n(b"password", LOG_N, &mut OsRng)
+                .expect("keystore");
+            assert_eq!(
+                keypair,
+                Keypair::from_keystore(key_tag, &json, b"password").expect("keypair")
+            );
+            assert!(matches!(
+                Keypair::from_keystore(key_tag, &json, b"wrong password"),
+                Err(Error::Encryption)
+            ));
+        }
+    }
+
+    #[test]
+    fn format() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let json = keypair
+            .to_keystore_with_log_n(b"password", LOG_N, &mut OsRng)
+            .expect("keystore");
+        let value: serde_json::Value = serde_json::from_str(&json).expect("json");
+        assert_eq!(3, value["version"]);
+        assert_eq!("aes-128-ctr", value["crypto"]["cipher"]);
+        assert_eq!("scrypt", value["crypto"]["kdf"]);
+        assert_eq!(16, value["crypto"]["kdfparams"]["n"]);
+        let id = value["id"].as_str().expect("id");
+        assert_eq!(36, id.len());
+        assert_eq!(Some('4'), id.chars().nth(14));
+    }
+}
@@ -0,0 +1,295 @@
+//! Keypairs backed by a key in Azure Key Vault or Azure Managed HSM.
+//!
+//! The private key never leaves the vault. Messages are hashed with SHA-256
+//! in software and the digest is signed by the vault with `ES256`. The public
+//! key is fetched once when the keypair is opened, and signing is pinned to
+//! the key version it was fetched for, so rotating the key in the vault does
+//! not silently change the signing key. The key must be a P-256 `EC` or
+//! `EC-HSM` key whose public key is compactable, and the keypair is an
+//! ecc_compact keypair.
+//!
+//! Requests are authorized with an access token from an Azure credential,
+//! by default the standard credential chain of environment, managed identity
+//! and Azure CLI credentials. The token scope follows from the vault URL, so
+//! the same keypair works with vaults, managed HSMs and sovereign clouds.
+//!
+//! Like KMS keypairs, every keypair drives its async requests on its own
+//! single threaded runtime and blocks the calling thread, so it must not be
+//! used from within an async runtime; async callers should wrap the keypair
+//! in an `AsyncKeypair`.
+use crate::{
+    deadline,
+    ecc_compact::{self, Signature},
+    jwk::Jwk,
+    keypair, public_key, telemetry, KeyTag, KeyType as CrateKeyType, Network, Result,
+};
+use azure_core::auth::TokenCredential;
+use p256::ecdsa;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::runtime::{Builder, Runtime};
+
+/// The Key Vault REST API version used for requests
+const API_VERSION: &str = "7.4";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("key vault request failed")]
+    Http(#[from] reqwest::Error),
+
+    #[error("azure credential error")]
+    Credential(#[from] azure_core::Error),
+
+    #[error("invalid vault url: {0}")]
+    InvalidUrl(String),
+
+    #[error("unsupported key vault key: {0} {1}")]
+    UnsupportedKey(String, String),
+
+    #[error("invalid key vault signature encoding")]
+    InvalidSignature,
+}
+
+#[derive(Deserialize)]
+struct KeyBundle {
+    key: JsonWebKey,
+}
+
+/// A key as returned by the vault, the JSON Web Key of its public key along
+/// with the versioned key id
+#[derive(Deserialize)]
+struct JsonWebKey {
+    kid: String,
+    #[serde(flatten)]
+    jwk: Jwk,
+}
+
+#[derive(Deserialize)]
+struct KeyOperationResult {
+    value: String,
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    key_id: String,
+    scope: String,
+    client: reqwest::Client,
+    credential: Arc<dyn TokenCredential>,
+    runtime: Arc<Runtime>,
+    timeout: Option<Duration>,
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("key_id", &self.key_id)
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature = self.sign_digest(Sha256::new().chain_update(msg))?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl Keypair {
+    /// Opens the latest version of the key with the given name in the vault
+    /// or managed HSM at the given URL, like
+    /// `https://my-vault.vault.azure.net`, using the standard Azure
+    /// credential chain.
+    pub fn open(network: Network, vault_url: &str, key_name: &str) -> Result<Keypair> {
+        let credential = azure_identity::create_credential().map_err(Error::from)?;
+        Self::open_with_credential(network, credential, vault_url, key_name)
+    }
+
+    /// Opens the latest version of the key with the given name in the vault
+    /// or managed HSM at the given URL, using the given credential.
+    pub fn open_with_credential(
+        network: Network,
+        credential: Arc<dyn TokenCredential>,
+        vault_url: &str,
+        key_name: &str,
+    ) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: CrateKeyType::EccCompact,
+        };
+        telemetry::span("init", Some(key_tag), "keyvault").in_scope(|| {
+            let scope = scope(vault_url)?;
+            let runtime = Arc::new(Builder::new_current_thread().enable_all().build()?);
+            let client = reqwest::Client::new();
+            let url = format!(
+                "{}/keys/{}?api-version={}",
+                vault_url.trim_end_matches('/'),
+                key_name,
+                API_VERSION
+            );
+            let bundle: KeyBundle = runtime.block_on(async {
+                let token = credential.get_token(&[scope.as_str()]).await?;
+                let response = client
+                    .get(&url)
+                    .bearer_auth(token.token.secret())
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok::<_, Error>(response.json().await?)
+            })?;
+            Ok(Keypair {
+                network,
+                public_key: vault_public_key(network, bundle.key.jwk)?,
+                key_id: bundle.key.kid,
+                scope,
+                client,
+                credential,
+                runtime,
+                timeout: None,
+            })
+        })
+    }
+
+    /// Set the timeout for sign operations on this keypair. An operation
+    /// which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: CrateKeyType::EccCompact,
+        }
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        let url = format!("{}/sign?api-version={}", self.key_id, API_VERSION);
+        let body = serde_json::json!({
+            "alg": "ES256",
+            "value": base64::encode_config(digest.finalize(), base64::URL_SAFE_NO_PAD),
+        });
+        let scope = self.scope.clone();
+        let client = self.client.clone();
+        let credential = self.credential.clone();
+        let runtime = self.runtime.clone();
+        let result: KeyOperationResult = deadline::run(self.timeout, move || {
+            Ok(runtime.block_on(async {
+                let token = credential.get_token(&[scope.as_str()]).await?;
+                let response = client
+                    .post(&url)
+                    .bearer_auth(token.token.secret())
+                    .json(&body)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok::<_, Error>(response.json().await?)
+            })?)
+        })?;
+        let bytes = base64::decode_config(&result.value, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::InvalidSignature)?;
+        let signature = ecdsa::Signature::try_from(&bytes[..])?;
+        Ok(Signature(signature))
+    }
+}
+
+/// Returns the public key of the given vault key. Keys in managed HSMs and
+/// premium vaults are `EC-HSM` keys, which are plain `EC` keys as far as their
+/// public key goes.
+fn vault_public_key(network: Network, mut jwk: Jwk) -> Result<public_key::PublicKey> {
+    if jwk.kty == "EC-HSM" {
+        jwk.kty = "EC".to_string();
+    }
+    if jwk.kty != "EC" || jwk.crv != "P-256" {
+        return Err(Error::UnsupportedKey(jwk.kty, jwk.crv).into());
+    }
+    public_key::PublicKey::from_jwk(network, &jwk)
+}
+
+/// Returns the access token scope for the given vault URL, which is the
+/// vault URL without the vault name, so `https://my-vault.vault.azure.net`
+/// has the scope `https://vault.azure.net/.default`.
+fn scope(vault_url: &str) -> Result<String> {
+    let host = vault_url
+        .strip_prefix("https://")
+        .and_then(|rest| rest.split(|c| c == '/' || c == ':').next())
+        .ok_or_else(|| Error::InvalidUrl(vault_url.to_string()))?;
+    match host.split_once('.') {
+        Some((name, domain)) if !name.is_empty() && domain.contains('.') => {
+            Ok(format!("https://{}/.default", domain))
+        }
+        _ => Err(Error::InvalidUrl(vault_url.to_string()).into()),
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes() {
+        assert_eq!(
+            "https://vault.azure.net/.default",
+            scope("https://my-vault.vault.azure.net").expect("vault")
+        );
+        assert_eq!(
+            "https://managedhsm.azure.net/.default",
+            scope("https://my-hsm.managedhsm.azure.net/").expect("managed hsm")
+        );
+        assert_eq!(
+            "https://vault.azure.cn/.default",
+            scope("https://my-vault.vault.azure.cn:443/keys").expect("sovereign cloud")
+        );
+        assert!(scope("http://my-vault.vault.azure.net").is_err());
+        assert!(scope("https://localhost").is_err());
+    }
+
+    #[test]
+    fn key_bundle() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut rand::rngs::OsRng);
+        let jwk = keypair.public_key.to_jwk().expect("jwk");
+        let bundle = serde_json::json!({
+            "key": {
+                "kid": "https://my-vault.vault.azure.net/keys/signer/0123456789abcdef",
+                "kty": "EC-HSM",
+                "key_ops": ["sign", "verify"],
+                "crv": jwk.crv,
+                "x": jwk.x,
+                "y": jwk.y,
+            },
+            "attributes": {"enabled": true},
+        });
+        let bundle: KeyBundle = serde_json::from_value(bundle).expect("bundle");
+        assert_eq!(
+            "https://my-vault.vault.azure.net/keys/signer/0123456789abcdef",
+            bundle.key.kid
+        );
+        assert_eq!(
+            keypair.public_key,
+            vault_public_key(Network::MainNet, bundle.key.jwk.clone()).expect("public key")
+        );
+
+        let mut rsa = bundle.key.jwk;
+        rsa.kty = "RSA-HSM".to_string();
+        assert!(vault_public_key(Network::MainNet, rsa).is_err());
+    }
+}
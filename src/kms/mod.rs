@@ -0,0 +1,209 @@
+//! Keypairs backed by an asymmetric AWS KMS key.
+//!
+//! The private key never leaves KMS. Messages are hashed with SHA-256 in
+//! software and the digest is signed with `kms:Sign`, and the public key is
+//! fetched once with `kms:GetPublicKey` when the keypair is opened. The KMS
+//! key must be an `ECC_NIST_P256` signing key whose public key is
+//! compactable, and the keypair is an ecc_compact keypair.
+//!
+//! The KMS client is async, so every keypair drives it on its own single
+//! threaded runtime and blocks the calling thread for the duration of every
+//! operation, like other hardware keypairs. Calling it from within an async
+//! runtime panics; async callers should wrap the keypair in an
+//! `AsyncKeypair`, which runs it on a worker thread.
+use crate::{
+    deadline,
+    ecc_compact::{self, Signature},
+    keypair, public_key, telemetry, KeyTag, KeyType as CrateKeyType, Network, Result,
+};
+use aws_sdk_kms::{
+    primitives::Blob,
+    types::{KeySpec, KeyUsageType, MessageType, SigningAlgorithmSpec},
+    Client,
+};
+use p256::ecdsa;
+use sha2::{Digest, Sha256};
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::runtime::{Builder, Runtime};
+
+/// The DER prefix of a P-256 SubjectPublicKeyInfo, which is followed by the
+/// uncompressed SEC1 point
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01, 0x06, 0x08, 0x2A,
+    0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("kms request failed")]
+    Request(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("unsupported kms key: {0}")]
+    UnsupportedKey(String),
+
+    #[error("kms response without {0}")]
+    MissingField(&'static str),
+
+    #[error("invalid kms public key")]
+    InvalidPublicKey,
+}
+
+impl Error {
+    fn request<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        Self::Request(Box::new(err))
+    }
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    key_id: String,
+    client: Client,
+    runtime: Arc<Runtime>,
+    timeout: Option<Duration>,
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("key_id", &self.key_id)
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature = self.sign_digest(Sha256::new().chain_update(msg))?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl Keypair {
+    /// Opens the KMS key with the given key id, key ARN, alias name or alias
+    /// ARN, using the AWS configuration from the environment, like the
+    /// default credential chain and region.
+    pub fn open(network: Network, key_id: &str) -> Result<Keypair> {
+        let runtime = runtime()?;
+        let config = runtime.block_on(aws_config::load_defaults(
+            aws_config::BehaviorVersion::latest(),
+        ));
+        Self::open_with_client(network, Client::new(&config), key_id)
+    }
+
+    /// Opens the KMS key with the given key id, key ARN, alias name or alias
+    /// ARN using the given KMS client.
+    pub fn open_with_client(network: Network, client: Client, key_id: &str) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: CrateKeyType::EccCompact,
+        };
+        telemetry::span("init", Some(key_tag), "kms").in_scope(|| {
+            let runtime = Arc::new(runtime()?);
+            let response = runtime
+                .block_on(client.get_public_key().key_id(key_id).send())
+                .map_err(Error::request)?;
+            match (response.key_spec(), response.key_usage()) {
+                (Some(KeySpec::EccNistP256), Some(KeyUsageType::SignVerify)) => (),
+                (spec, usage) => {
+                    return Err(Error::UnsupportedKey(format!("{:?} {:?}", spec, usage)).into())
+                }
+            }
+            let spki = response
+                .public_key()
+                .ok_or(Error::MissingField("public key"))?;
+            let public_key = ecc_compact::PublicKey::try_from(spki_point(spki.as_ref())?)?;
+            Ok(Keypair {
+                network,
+                public_key: public_key::PublicKey::for_network(network, public_key),
+                key_id: key_id.to_string(),
+                client,
+                runtime,
+                timeout: None,
+            })
+        })
+    }
+
+    /// Set the timeout for sign operations on this keypair. An operation
+    /// which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: CrateKeyType::EccCompact,
+        }
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        let request = self
+            .client
+            .sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(digest.finalize().to_vec()))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256);
+        let runtime = self.runtime.clone();
+        let der = deadline::run(self.timeout, move || {
+            let response = runtime.block_on(request.send()).map_err(Error::request)?;
+            let signature = response
+                .signature()
+                .ok_or(Error::MissingField("signature"))?;
+            Ok(signature.as_ref().to_vec())
+        })?;
+        let signature = ecdsa::Signature::from_der(&der)?;
+        Ok(Signature(signature))
+    }
+}
+
+fn runtime() -> Result<Runtime> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+/// Returns the uncompressed SEC1 point of the given DER P-256
+/// SubjectPublicKeyInfo
+fn spki_point(spki: &[u8]) -> Result<&[u8]> {
+    match spki.strip_prefix(&P256_SPKI_PREFIX[..]) {
+        Some(point) if point.len() == 65 => Ok(point),
+        _ => Err(Error::InvalidPublicKey.into()),
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use rand::rngs::OsRng;
+    use std::convert::TryInto;
+
+    #[test]
+    fn spki_points() {
+        let keypair = ecc_compact::Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &ecc_compact::PublicKey =
+            (&keypair.public_key).try_into().expect("ecc_compact key");
+        let point = public_key.0.to_encoded_point(false);
+        let spki = [&P256_SPKI_PREFIX[..], point.as_bytes()].concat();
+        assert_eq!(point.as_bytes(), spki_point(&spki).expect("point"));
+        assert!(spki_point(&spki[..spki.len() - 1]).is_err());
+        assert!(spki_point(point.as_bytes()).is_err());
+    }
+}
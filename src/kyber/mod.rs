@@ -0,0 +1,244 @@
+//! Post-quantum ML-KEM (Kyber) keypairs.
+//!
+//! Keypairs use the Kyber768 parameter set and can only be used for key
+//! encapsulation, not for signing. A sender encapsulates a fresh shared secret
+//! to a public key with [`PublicKey::encapsulate`] and sends the resulting
+//! ciphertext, which the owner of the keypair decapsulates with
+//! [`Keypair::decapsulate`] to get the same shared secret.
+//!
+//! Binary forms carry the key bytes with the same unsigned varint length
+//! prefix as Dilithium keys. Key generation and encapsulation draw from the
+//! operating system random number generator.
+use crate::{
+    dilithium::{read_prefixed, varint_len, write_prefixed},
+    *,
+};
+use pqcrypto_kyber::kyber768;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _, SharedSecret as _};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Clone)]
+pub struct PublicKey(Box<kyber768::PublicKey>);
+
+pub struct SharedSecret(kyber768::SharedSecret);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: kyber768::SecretKey,
+}
+
+const RAW_PUBLIC_KEY_LENGTH: usize = kyber768::public_key_bytes();
+const RAW_SECRET_KEY_LENGTH: usize = kyber768::secret_key_bytes();
+
+pub const PUBLIC_KEY_LENGTH: usize = 1 + varint_len(RAW_PUBLIC_KEY_LENGTH) + RAW_PUBLIC_KEY_LENGTH;
+pub const KEYPAIR_LENGTH: usize = 1
+    + varint_len(RAW_SECRET_KEY_LENGTH)
+    + RAW_SECRET_KEY_LENGTH
+    + varint_len(RAW_PUBLIC_KEY_LENGTH)
+    + RAW_PUBLIC_KEY_LENGTH;
+pub const CIPHERTEXT_LENGTH: usize = kyber768::ciphertext_bytes();
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        let mut input = std::io::Cursor::new(&input[1..]);
+        let secret = read_prefixed(&mut input, RAW_SECRET_KEY_LENGTH)?;
+        let secret =
+            kyber768::SecretKey::from_bytes(&secret).map_err(|_| Error::invalid_curve())?;
+        let public_key = PublicKey::read_from(&mut input)?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            secret,
+        })
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        write_prefixed(output, self.secret.as_bytes())?;
+        // The public key is written without its key tag
+        let public_key: &PublicKey = (&self.public_key)
+            .try_into()
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+        public_key.write_to(output)
+    }
+}
+
+impl Keypair {
+    /// Generate a keypair. Kyber key generation always draws from the
+    /// operating system random number generator, so the given one is unused.
+    pub fn generate<R>(network: Network, _csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let (public_key, secret) = kyber768::keypair();
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(
+                network,
+                PublicKey(Box::new(public_key)),
+            ),
+            secret,
+        }
+    }
+
+    /// Kyber keypairs can not be derived from entropy, so this always fails
+    pub fn generate_from_entropy(_network: Network, _entropy: &[u8]) -> Result<Keypair> {
+        Err(Error::invalid_curve())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Kyber,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.as_bytes().to_vec()
+    }
+
+    /// Decapsulate the shared secret from a ciphertext made by
+    /// [`PublicKey::encapsulate`]
+    pub fn decapsulate(&self, ciphertext: &[u8]) -> Result<SharedSecret> {
+        let ciphertext =
+            kyber768::Ciphertext::from_bytes(ciphertext).map_err(|_| Error::invalid_curve())?;
+        Ok(SharedSecret(kyber768::decapsulate(
+            &ciphertext,
+            &self.secret,
+        )))
+    }
+}
+
+impl PublicKey {
+    /// Encapsulate a fresh shared secret to this public key. Returns the
+    /// shared secret and the ciphertext to send to the owner of the keypair.
+    pub fn encapsulate(&self) -> (SharedSecret, Vec<u8>) {
+        let (shared_secret, ciphertext) = kyber768::encapsulate(&self.0);
+        (SharedSecret(shared_secret), ciphertext.as_bytes().to_vec())
+    }
+}
+
+impl SharedSecret {
+    pub fn raw_secret_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl std::fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_tuple("PublicKey").finish()
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    /// Kyber keys can not verify signatures, so this always fails
+    fn verify(&self, _msg: &[u8], _signature: &[u8]) -> Result {
+        Err(Error::invalid_curve())
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let buf = read_prefixed(input, RAW_PUBLIC_KEY_LENGTH)?;
+        let public_key =
+            kyber768::PublicKey::from_bytes(&buf).map_err(|_| Error::invalid_curve())?;
+        Ok(PublicKey(Box::new(public_key)))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        write_prefixed(output, self.0.as_bytes())
+    }
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes() == other.0.as_bytes()
+    }
+}
+
+impl Eq for PublicKey {}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.0.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn encapsulate() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("kyber key");
+        let (shared_secret, ciphertext) = public_key.encapsulate();
+        assert_eq!(CIPHERTEXT_LENGTH, ciphertext.len());
+        let decapsulated = keypair.decapsulate(&ciphertext).expect("decapsulate");
+        assert_eq!(
+            shared_secret.raw_secret_bytes(),
+            decapsulated.raw_secret_bytes()
+        );
+        assert!(keypair.decapsulate(&ciphertext[1..]).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_vec();
+        assert_eq!(KEYPAIR_LENGTH, bytes.len());
+        assert_eq!(keypair, Keypair::try_from(&bytes[..]).expect("keypair"));
+        let bytes = keypair.public_key.to_vec();
+        assert_eq!(PUBLIC_KEY_LENGTH, bytes.len());
+        assert_eq!(
+            keypair.public_key,
+            public_key::PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Compatibility parsing and verification for historical ecc keys.
+//!
+//! Before the compact ecc encoding settled, some tooling wrote P-256 public
+//! keys as tagged SEC1 compressed or uncompressed points, and some keys were
+//! used that are not compactable: their x coordinate alone decodes to the
+//! negation of the actual public key, so their signatures do not verify
+//! against the strict [`PublicKey`] with the same encoding. Signatures were
+//! also not always DER encoded.
+//!
+//! A [`LegacyPublicKey`] accepts all of these encodings and verifies DER as
+//! well as fixed size `r || s` signatures, trying both candidate points for
+//! x-only keys. It is only meant for processing historical data; anything
+//! produced today should go through the strict [`PublicKey`] path.
+use crate::*;
+use p256::{
+    ecdsa,
+    elliptic_curve::{sec1::ToCompactEncodedPoint, DecompactPoint},
+    FieldBytes,
+};
+
+/// The length of an x-only key without its tag
+const X_ONLY_LENGTH: usize = 32;
+
+/// A leniently parsed historical ecc public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyPublicKey {
+    pub network: Network,
+    /// The points the encoding may stand for. X-only encodings have two.
+    candidates: Vec<p256::PublicKey>,
+}
+
+impl LegacyPublicKey {
+    /// Parse a key tag followed by an x-only, SEC1 compressed or SEC1
+    /// uncompressed P-256 point
+    pub fn from_bytes(input: &[u8]) -> Result<Self> {
+        let tag = *input.first().ok_or_else(Error::missing_keytype)?;
+        let key_tag = KeyTag::try_from(tag)?;
+        if key_tag.key_type != KeyType::EccCompact {
+            return Err(Error::invalid_keytype(tag));
+        }
+        let point = &input[1..];
+        let candidates = if point.len() == X_ONLY_LENGTH {
+            let affine: Option<p256::AffinePoint> =
+                p256::AffinePoint::decompact(FieldBytes::from_slice(point)).into();
+            let affine = affine.ok_or_else(Error::not_compact)?;
+            vec![
+                p256::PublicKey::from_affine(affine).map_err(Error::from)?,
+                p256::PublicKey::from_affine(-affine).map_err(Error::from)?,
+            ]
+        } else {
+            vec![p256::PublicKey::from_sec1_bytes(point).map_err(Error::from)?]
+        };
+        Ok(Self {
+            network: key_tag.network,
+            candidates,
+        })
+    }
+
+    /// Verify a DER or `r || s` encoded signature over the given message
+    /// against any point the key may stand for
+    pub fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        use signature::Verifier;
+        let signature = ecdsa::Signature::from_der(signature)
+            .or_else(|_| ecdsa::Signature::try_from(signature))?;
+        let verified = self.candidates.iter().any(|key| {
+            ecdsa::VerifyingKey::from(*key)
+                .verify(msg, &signature)
+                .is_ok()
+        });
+        if !verified {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Convert to a strict public key. Fails if the key is not compactable.
+    pub fn to_public_key(&self) -> Result<PublicKey> {
+        let key = self
+            .candidates
+            .iter()
+            .find(|key| key.as_affine().to_compact_encoded_point().is_some().into())
+            .ok_or_else(Error::not_compact)?;
+        Ok(PublicKey::for_network(
+            self.network,
+            ecc_compact::PublicKey(*key),
+        ))
+    }
+}
+
+impl TryFrom<&[u8]> for LegacyPublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        Self::from_bytes(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use rand::rngs::OsRng;
+    use signature::Signer;
+
+    fn tagged(network: Network, point: &[u8]) -> Vec<u8> {
+        let tag = KeyTag {
+            network,
+            key_type: KeyType::EccCompact,
+        };
+        let mut result = vec![u8::from(tag)];
+        result.extend_from_slice(point);
+        result
+    }
+
+    #[test]
+    fn sec1() {
+        let secret = ecdsa::SigningKey::random(&mut OsRng);
+        let public_key = p256::PublicKey::from(secret.verifying_key());
+        let signature: ecdsa::Signature = secret.sign(b"hello world");
+        for compress in [false, true] {
+            let encoded = public_key.as_affine().to_encoded_point(compress);
+            let key = LegacyPublicKey::from_bytes(&tagged(Network::TestNet, encoded.as_bytes()))
+                .expect("legacy key");
+            assert_eq!(Network::TestNet, key.network);
+            assert!(key
+                .verify(b"hello world", signature.to_der().as_bytes())
+                .is_ok());
+            assert!(key.verify(b"hello world", signature.as_ref()).is_ok());
+            assert!(key.verify(b"hello there", signature.as_ref()).is_err());
+        }
+    }
+
+    #[test]
+    fn non_compactable() {
+        let secret = std::iter::repeat_with(|| ecdsa::SigningKey::random(&mut OsRng))
+            .find(|secret| {
+                let key = p256::PublicKey::from(secret.verifying_key());
+                bool::from(key.as_affine().to_compact_encoded_point().is_none())
+            })
+            .expect("non compactable key");
+        let encoded = p256::PublicKey::from(secret.verifying_key())
+            .as_affine()
+            .to_encoded_point(false);
+        let x_only = tagged(Network::MainNet, &encoded.as_bytes()[1..33]);
+        let signature: ecdsa::Signature = secret.sign(b"hello world");
+        let signature = signature.to_der();
+
+        // The strict path decodes the x-only key to the wrong point
+        let strict = PublicKey::try_from(&x_only[..]).expect("strict key");
+        assert!(strict.verify(b"hello world", signature.as_bytes()).is_err());
+
+        let legacy = LegacyPublicKey::from_bytes(&x_only).expect("legacy key");
+        assert!(legacy.verify(b"hello world", signature.as_bytes()).is_ok());
+        assert_eq!(strict, legacy.to_public_key().expect("public key"));
+    }
+
+    #[test]
+    fn to_public_key() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let legacy = LegacyPublicKey::from_bytes(&keypair.public_key().to_vec()).expect("legacy");
+        assert_eq!(keypair.public_key(), &legacy.to_public_key().expect("key"));
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(legacy.verify(b"hello world", &signature).is_ok());
+    }
+
+    #[test]
+    fn wrong_type() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        assert!(LegacyPublicKey::from_bytes(&keypair.public_key().to_vec()).is_err());
+    }
+}
@@ -7,6 +7,108 @@
 //! ECC keypairs keys implement the strategy described in a [Victor Miller
 //! paper][JIVSOV] which compresses keys to just their X-coordinate.
 //!
+//! With the `secp256k1` feature, secp256k1 keypairs are also available for
+//! use with EVM and Bitcoin adjacent tooling.
+//!
+//! With the `bls` feature, BLS12-381 keypairs are available whose signatures
+//! over the same message can be aggregated into a single signature.
+//!
+//! With the `x25519` feature, x25519 keypairs are available for ECDH only.
+//!
+//! With the `ed448` feature, Ed448 keypairs are available for deployments that
+//! require a 224-bit security level.
+//!
+//! With the `p384` feature, NIST P-384 (secp384r1) keypairs are available for
+//! higher strength ECDSA.
+//!
+//! With the `pqc` feature, post-quantum ML-DSA (Dilithium) signing keypairs
+//! and ML-KEM (Kyber) key encapsulation keypairs are available, as well as
+//! hybrid keypairs which sign with both a classical and a Dilithium key.
+//!
+//! With the `sr25519` feature, sr25519 (Schnorrkel) keypairs are available for
+//! use with Substrate based chains.
+//!
+//! With the `context` feature, keypairs can sign messages bound to a context,
+//! using Ed25519ctx for ed25519 keys, so signatures made for one protocol can
+//! not be confused with those made for another.
+//!
+//! With the `derivation` feature, ed25519, ecc_compact and secp256k1 keypairs
+//! can be derived from a single seed by SLIP-0010 derivation paths.
+//!
+//! With the `mnemonic` feature, keypairs can be generated with and recovered
+//! from 12 or 24 word BIP39 phrases, including those made by Helium wallets.
+//!
+//! With the `vrf` feature, ed25519 keypairs can prove verifiable random
+//! function outputs.
+//!
+//! With the `frost` feature, any threshold of the holders of shares of an
+//! ed25519 key can jointly sign with FROST, producing a plain ed25519
+//! signature. With the `dkg` feature, the holders can generate their shares
+//! in a ceremony without a trusted dealer.
+//!
+//! With the `musig` feature, the holders of a set of ecc_compact or secp256k1
+//! keys can jointly make a single MuSig2 Schnorr signature under the aggregate
+//! public key of the set.
+//!
+//! With the `pkcs8` feature, ed25519 and ecc_compact keypairs and public keys
+//! can be exported to and imported from PKCS#8 and SPKI documents in DER or
+//! PEM form, as used by OpenSSL and HSM key import tools.
+//!
+//! With the `jwk` feature, ed25519 and ecc_compact keypairs and public keys
+//! convert to and from JSON Web Keys for use with JOSE based services.
+//!
+//! With the `ssh` feature, ed25519 and ecc_compact keypairs and public keys
+//! convert to and from OpenSSH keys.
+//!
+//! With the `cose` feature, ed25519 and ecc_compact public keys convert to
+//! and from CBOR encoded COSE_Key structures.
+//!
+//! With the `multiformats` feature, public keys convert to and from
+//! multibase encoded multicodec keys and did:key identifiers, and derive
+//! libp2p PeerIds.
+//!
+//! With the `bech32` feature, public keys convert to and from checksummed,
+//! case insensitive bech32 and bech32m strings with an application chosen
+//! prefix.
+//!
+//! With the `sqlx-postgres` feature, the binary form of public keys in
+//! [`PublicKeyBinary`] can be stored in Postgres with sqlx.
+//!
+//! With the `solana` feature, ed25519 keys convert to and from Solana keys,
+//! including the 64 byte secret form of Solana keypair files.
+//!
+//! With the `keystore` feature, ed25519, ecc_compact and secp256k1 keypairs
+//! can be exported to and imported from password protected Web3 Secret
+//! Storage (V3 keystore) JSON.
+//!
+//! With the `encrypted-keypair` feature, software keypairs can be written to
+//! disk encrypted with a password, using Argon2id and AES-256-GCM, instead of
+//! as their raw binary form.
+//!
+//! With the `cert` feature, keypairs, including hardware backed ones, can
+//! make self-signed X.509 certificates for mutual TLS, and PKCS#10
+//! certificate signing requests to enroll with a certificate authority.
+//! With the `pkcs12` feature, ed25519 and ecc_compact keypairs can be
+//! exported with their certificate chain as password protected PKCS#12
+//! bundles.
+//!
+//! With the `pkcs11` feature, keypairs can be backed by a P-256 key in a
+//! PKCS#11 token, like a network HSM, which signs and does ECDH without the
+//! private key leaving the token.
+//!
+//! With the `se050` feature, keypairs can be backed by a P-256 key in an NXP
+//! SE050 secure element on an I2C bus, which can also generate the key.
+//!
+//! With the `aws-kms` feature, keypairs can be backed by an asymmetric P-256
+//! AWS KMS key, which signs without the private key ever being on the host.
+//! With the `azure-keyvault` feature, keypairs can likewise be backed by a
+//! P-256 key in Azure Key Vault or Managed HSM, authorized through the
+//! standard Azure credential chain.
+//!
+//! With the `vault` feature, keypairs can be backed by an ed25519 or ECDSA
+//! P-256 key in the transit secrets engine of HashiCorp Vault, authenticated
+//! with a token or an AppRole login.
+//!
 //! The intended implemenation strategy in this crate allows for keypair
 //! implementations where the private key is based external to the software,
 //! such as an ECC608 chip or an HSM.
@@ -24,31 +126,192 @@
 pub mod ecc_compact;
 pub mod ed25519;
 
+#[cfg(feature = "secp256k1")]
+pub mod secp256k1;
+
+#[cfg(feature = "bls")]
+pub mod bls12_381;
+
+#[cfg(feature = "pqc")]
+pub mod dilithium;
+#[cfg(feature = "p384")]
+pub mod ecc_p384;
+#[cfg(feature = "ed448")]
+pub mod ed448;
+#[cfg(feature = "pqc")]
+pub mod hybrid;
+#[cfg(feature = "pqc")]
+pub mod kyber;
+#[cfg(feature = "sr25519")]
+pub mod sr25519;
+#[cfg(feature = "x25519")]
+pub mod x25519;
+
 #[cfg(feature = "ecc608")]
 pub mod ecc608;
 
 #[cfg(feature = "tpm")]
 pub mod tpm;
 
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
+#[cfg(feature = "se050")]
+pub mod se050;
+
+#[cfg(feature = "aws-kms")]
+pub mod kms;
+
+#[cfg(feature = "azure-keyvault")]
+pub mod keyvault;
+
+#[cfg(feature = "vault")]
+pub mod vault;
+
+#[cfg(any(feature = "ecc608", feature = "tpm", feature = "se050"))]
+pub mod health;
+
 #[cfg(feature = "multisig")]
 pub mod multisig;
 
 #[cfg(feature = "multisig")]
 pub use multihash;
 
+#[cfg(feature = "ecies")]
+pub mod envelope;
+
+#[cfg(feature = "backup")]
+pub mod backup;
+
+#[cfg(feature = "blind")]
+pub mod blind;
+
+#[cfg(feature = "ring-signature")]
+pub mod ring;
+
+#[cfg(feature = "adaptor")]
+pub mod adaptor;
+
+#[cfg(feature = "pake")]
+pub mod pake;
+
+#[cfg(feature = "x3dh")]
+pub mod x3dh;
+
+#[cfg(feature = "derivation")]
+pub mod derivation;
+
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+
+#[cfg(feature = "vrf")]
+pub mod vrf;
+
+#[cfg(feature = "frost")]
+pub mod frost;
+
+#[cfg(feature = "dkg")]
+pub mod dkg;
+
+#[cfg(feature = "musig")]
+pub mod musig;
+
+#[cfg(feature = "pkcs8")]
+pub mod pkcs8;
+
+#[cfg(feature = "jwk")]
+pub mod jwk;
+
+#[cfg(feature = "ssh")]
+pub mod ssh;
+
+#[cfg(feature = "cose")]
+pub mod cose;
+
+#[cfg(feature = "bech32")]
+pub mod bech32;
+
+#[cfg(feature = "multiformats")]
+pub mod multiformats;
+#[cfg(feature = "solana")]
+pub mod solana;
+
+#[cfg(feature = "keystore")]
+pub mod keystore;
+
+#[cfg(feature = "encrypted-keypair")]
+pub mod encrypted_keypair;
+
+#[cfg(feature = "cert")]
+pub mod cert;
+
+#[cfg(feature = "pkcs12")]
+pub mod pkcs12;
+
+#[cfg(feature = "async")]
+pub mod async_keypair;
+
+#[cfg(feature = "async")]
+pub mod scheduler;
+
+#[cfg(feature = "async")]
+pub mod verify_pool;
+
+#[cfg(feature = "vectors")]
+pub mod vectors;
+
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub mod attest;
+pub mod clock;
+pub mod conformance;
+pub mod delegation;
+pub mod detached;
 pub mod error;
+pub mod legacy;
+pub mod merkle;
+pub mod possession;
 pub mod public_key;
+pub mod public_key_binary;
+pub mod replay;
+pub mod retry;
+pub mod serde_keypair;
+pub mod signed_message;
+pub mod subkey;
+pub mod validity;
 
+#[cfg(any(
+    feature = "ecc608",
+    feature = "tpm",
+    feature = "pkcs11",
+    feature = "se050",
+    feature = "aws-kms",
+    feature = "azure-keyvault",
+    feature = "vault"
+))]
+mod deadline;
 mod keypair;
-pub use error::{Error, Result};
-pub use keypair::{Keypair, Sign};
-pub use public_key::{PublicKey, PublicKeySize, Verify};
+#[cfg(any(feature = "backup", feature = "encrypted-keypair"))]
+mod passphrase;
+mod swarm_key;
+mod tagged_signature;
+mod telemetry;
+pub use error::{Error, ErrorClass, Result};
+pub use keypair::{Keypair, Sign, StreamSigner};
+pub use public_key::{PublicKey, PublicKeySize, StreamVerifier, Verify};
+pub use public_key_binary::PublicKeyBinary;
+pub use signed_message::SignedMessage;
 use std::{
     convert::{From, TryFrom, TryInto},
     fmt,
     hash::Hash,
     str::FromStr,
 };
+pub use tagged_signature::Signature;
 
 /// Keys are generated for a given network. Supported networks are mainnet and
 /// testnet. The default network is mainnet.
@@ -74,6 +337,24 @@ pub enum KeyType {
     EccCompact,
     #[cfg(feature = "multisig")]
     MultiSig,
+    #[cfg(feature = "secp256k1")]
+    Secp256k1,
+    #[cfg(feature = "bls")]
+    Bls,
+    #[cfg(feature = "x25519")]
+    X25519,
+    #[cfg(feature = "ed448")]
+    Ed448,
+    #[cfg(feature = "sr25519")]
+    Sr25519,
+    #[cfg(feature = "p384")]
+    EccP384,
+    #[cfg(feature = "pqc")]
+    Dilithium,
+    #[cfg(feature = "pqc")]
+    Hybrid,
+    #[cfg(feature = "pqc")]
+    Kyber,
 }
 
 impl Copy for KeyType {}
@@ -110,6 +391,25 @@ impl From<KeyTag> for u8 {
     }
 }
 
+impl FromStr for KeyTag {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (network, key_type) = s
+            .split_once(KEYTAG_SEPARATOR)
+            .ok_or_else(|| Error::invalid_keytype_str(s))?;
+        Ok(KeyTag {
+            network: network.parse()?,
+            key_type: key_type.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for KeyTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        write!(f, "{}{}{}", self.network, KEYTAG_SEPARATOR, self.key_type)
+    }
+}
+
 impl TryFrom<u8> for Network {
     type Error = Error;
     fn try_from(v: u8) -> Result<Self> {
@@ -158,6 +458,24 @@ impl FromStr for KeyType {
             KEYTYPE_ECC_COMPACT_STR => Ok(Self::EccCompact),
             #[cfg(feature = "multisig")]
             KEYTYPE_MULTISIG_STR => Ok(Self::MultiSig),
+            #[cfg(feature = "secp256k1")]
+            KEYTYPE_SECP256K1_STR => Ok(Self::Secp256k1),
+            #[cfg(feature = "bls")]
+            KEYTYPE_BLS_STR => Ok(Self::Bls),
+            #[cfg(feature = "x25519")]
+            KEYTYPE_X25519_STR => Ok(Self::X25519),
+            #[cfg(feature = "ed448")]
+            KEYTYPE_ED448_STR => Ok(Self::Ed448),
+            #[cfg(feature = "sr25519")]
+            KEYTYPE_SR25519_STR => Ok(Self::Sr25519),
+            #[cfg(feature = "p384")]
+            KEYTYPE_ECC_P384_STR => Ok(Self::EccP384),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_DILITHIUM_STR => Ok(Self::Dilithium),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_HYBRID_STR => Ok(Self::Hybrid),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_KYBER_STR => Ok(Self::Kyber),
             _ => Err(Error::invalid_keytype_str(s)),
         }
     }
@@ -170,6 +488,24 @@ impl fmt::Display for KeyType {
             Self::EccCompact => KEYTYPE_ECC_COMPACT_STR,
             #[cfg(feature = "multisig")]
             Self::MultiSig => KEYTYPE_MULTISIG_STR,
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1 => KEYTYPE_SECP256K1_STR,
+            #[cfg(feature = "bls")]
+            Self::Bls => KEYTYPE_BLS_STR,
+            #[cfg(feature = "x25519")]
+            Self::X25519 => KEYTYPE_X25519_STR,
+            #[cfg(feature = "ed448")]
+            Self::Ed448 => KEYTYPE_ED448_STR,
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519 => KEYTYPE_SR25519_STR,
+            #[cfg(feature = "p384")]
+            Self::EccP384 => KEYTYPE_ECC_P384_STR,
+            #[cfg(feature = "pqc")]
+            Self::Dilithium => KEYTYPE_DILITHIUM_STR,
+            #[cfg(feature = "pqc")]
+            Self::Hybrid => KEYTYPE_HYBRID_STR,
+            #[cfg(feature = "pqc")]
+            Self::Kyber => KEYTYPE_KYBER_STR,
         })
     }
 }
@@ -182,6 +518,24 @@ impl TryFrom<u8> for KeyType {
             KEYTYPE_ECC_COMPACT => Ok(Self::EccCompact),
             #[cfg(feature = "multisig")]
             KEYTYPE_MULTISIG => Ok(Self::MultiSig),
+            #[cfg(feature = "secp256k1")]
+            KEYTYPE_SECP256K1 => Ok(Self::Secp256k1),
+            #[cfg(feature = "bls")]
+            KEYTYPE_BLS => Ok(Self::Bls),
+            #[cfg(feature = "x25519")]
+            KEYTYPE_X25519 => Ok(Self::X25519),
+            #[cfg(feature = "ed448")]
+            KEYTYPE_ED448 => Ok(Self::Ed448),
+            #[cfg(feature = "sr25519")]
+            KEYTYPE_SR25519 => Ok(Self::Sr25519),
+            #[cfg(feature = "p384")]
+            KEYTYPE_ECC_P384 => Ok(Self::EccP384),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_DILITHIUM => Ok(Self::Dilithium),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_HYBRID => Ok(Self::Hybrid),
+            #[cfg(feature = "pqc")]
+            KEYTYPE_KYBER => Ok(Self::Kyber),
             _ => Err(Error::invalid_keytype(v)),
         }
     }
@@ -194,6 +548,24 @@ impl From<KeyType> for u8 {
             KeyType::Ed25519 => KEYTYPE_ED25519,
             #[cfg(feature = "multisig")]
             KeyType::MultiSig => KEYTYPE_MULTISIG,
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => KEYTYPE_SECP256K1,
+            #[cfg(feature = "bls")]
+            KeyType::Bls => KEYTYPE_BLS,
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => KEYTYPE_X25519,
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => KEYTYPE_ED448,
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => KEYTYPE_SR25519,
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => KEYTYPE_ECC_P384,
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => KEYTYPE_DILITHIUM,
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => KEYTYPE_HYBRID,
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => KEYTYPE_KYBER,
         }
     }
 }
@@ -218,6 +590,42 @@ pub const KEYTYPE_ECC_COMPACT_STR: &str = "ecc_compact";
 pub const KEYTYPE_MULTISIG: u8 = 0x02;
 /// The string representation of the multisig pblic key type
 pub const KEYTYPE_MULTISIG_STR: &str = "multisig";
+/// The type tag for encoded secp256k1 keys
+pub const KEYTYPE_SECP256K1: u8 = 0x03;
+/// The string representation of the secp256k1 key type
+pub const KEYTYPE_SECP256K1_STR: &str = "secp256k1";
+/// The type tag for encoded BLS12-381 keys
+pub const KEYTYPE_BLS: u8 = 0x04;
+/// The string representation of the BLS12-381 key type
+pub const KEYTYPE_BLS_STR: &str = "bls12_381";
+/// The type tag for encoded x25519 keys
+pub const KEYTYPE_X25519: u8 = 0x05;
+/// The string representation of the x25519 key type
+pub const KEYTYPE_X25519_STR: &str = "x25519";
+/// The type tag for encoded Ed448 keys
+pub const KEYTYPE_ED448: u8 = 0x06;
+/// The string representation of the Ed448 key type
+pub const KEYTYPE_ED448_STR: &str = "ed448";
+/// The type tag for encoded P-384 keys
+pub const KEYTYPE_ECC_P384: u8 = 0x07;
+/// The string representation of the P-384 key type
+pub const KEYTYPE_ECC_P384_STR: &str = "ecc_p384";
+/// The type tag for encoded ML-DSA (Dilithium) keys
+pub const KEYTYPE_DILITHIUM: u8 = 0x08;
+/// The string representation of the ML-DSA (Dilithium) key type
+pub const KEYTYPE_DILITHIUM_STR: &str = "dilithium3";
+/// The type tag for encoded ML-KEM (Kyber) keys
+pub const KEYTYPE_KYBER: u8 = 0x09;
+/// The string representation of the ML-KEM (Kyber) key type
+pub const KEYTYPE_KYBER_STR: &str = "kyber768";
+/// The type tag for encoded hybrid classical and post-quantum keys
+pub const KEYTYPE_HYBRID: u8 = 0x0a;
+/// The string representation of the hybrid key type
+pub const KEYTYPE_HYBRID_STR: &str = "hybrid";
+/// The type tag for encoded sr25519 keys
+pub const KEYTYPE_SR25519: u8 = 0x0b;
+/// The string representation of the sr25519 key type
+pub const KEYTYPE_SR25519_STR: &str = "sr25519";
 
 // The type tag for mainnet keys.
 pub const NETTYPE_MAIN: u8 = 0x00;
@@ -227,6 +635,9 @@ pub const NETTYPE_TEST: u8 = 0x10;
 pub const NETTYPE_MAIN_STR: &str = "mainnet";
 /// The string representation of the testnet network type
 pub const NETTYPE_TEST_STR: &str = "testnet";
+/// Separates the network from the key type in the string representation of
+/// a key tag, as in `mainnet::ed25519`
+pub const KEYTAG_SEPARATOR: &str = "::";
 
 pub trait WriteTo {
     /// Convert the implementor into its binary form by writing to the given output.
@@ -239,3 +650,34 @@ pub trait ReadFrom {
     where
         Self: Sized;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_tag_str() {
+        for network in [Network::MainNet, Network::TestNet] {
+            for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+                let key_tag = KeyTag { network, key_type };
+                assert_eq!(key_tag, key_tag.to_string().parse().expect("key tag"));
+            }
+        }
+        assert_eq!(
+            "testnet::ecc_compact",
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::EccCompact,
+            }
+            .to_string()
+        );
+        for invalid in [
+            "mainnet",
+            "mainnet:ed25519",
+            "devnet::ed25519",
+            "mainnet::rsa",
+        ] {
+            assert!(invalid.parse::<KeyTag>().is_err(), "{}", invalid);
+        }
+    }
+}
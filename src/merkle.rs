@@ -0,0 +1,335 @@
+//! Merkle trees allow a large number of messages to be signed with a single
+//! signature over the root of the tree. Individual messages are then verified
+//! using an inclusion proof against the signed root.
+//!
+//! Leaves are hashed as `SHA-256(0x00 || message)` and interior nodes as
+//! `SHA-256(0x01 || left || right)`, so a leaf can never be confused with an
+//! interior node. A node without a sibling is promoted to the next level
+//! unchanged.
+use crate::*;
+use sha2::{Digest, Sha256};
+
+pub const HASH_LENGTH: usize = 32;
+pub type Hash = [u8; HASH_LENGTH];
+
+/// Domain separator prefixed to the signed bytes of a merkle root
+const MERKLE_ROOT_DOMAIN: &[u8] = b"helium-merkle-root";
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// All levels of the tree, starting with the leaf hashes and ending with
+    /// the level containing just the root
+    levels: Vec<Vec<Hash>>,
+}
+
+/// Proves that a message is included at a given index in a tree with a given
+/// number of leaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub index: u64,
+    pub leaf_count: u64,
+    /// The sibling hashes from the leaf level up to the root
+    pub siblings: Vec<Hash>,
+}
+
+/// A merkle root signed by a keypair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRoot {
+    pub root: Hash,
+    pub leaf_count: u64,
+    pub public_key: PublicKey,
+    pub signature: Vec<u8>,
+}
+
+fn leaf_hash(message: &[u8]) -> Hash {
+    let mut result = [0u8; HASH_LENGTH];
+    result.copy_from_slice(
+        &Sha256::new()
+            .chain_update([LEAF_PREFIX])
+            .chain_update(message)
+            .finalize(),
+    );
+    result
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut result = [0u8; HASH_LENGTH];
+    result.copy_from_slice(
+        &Sha256::new()
+            .chain_update([NODE_PREFIX])
+            .chain_update(left)
+            .chain_update(right)
+            .finalize(),
+    );
+    result
+}
+
+impl MerkleTree {
+    /// Construct a merkle tree over the given messages. Returns `None` if no
+    /// messages are given.
+    pub fn new<M: AsRef<[u8]>>(messages: &[M]) -> Option<Self> {
+        if messages.is_empty() {
+            return None;
+        }
+        let mut levels = vec![messages
+            .iter()
+            .map(|message| leaf_hash(message.as_ref()))
+            .collect::<Vec<Hash>>()];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Some(Self { levels })
+    }
+
+    /// The root hash of the tree
+    pub fn root(&self) -> Hash {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// The number of messages in the tree
+    pub fn leaf_count(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /// Construct the inclusion proof for the message at the given index.
+    /// Returns `None` if the index is out of range.
+    pub fn proof(&self, index: usize) -> Option<InclusionProof> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut siblings = vec![];
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            position /= 2;
+        }
+        Some(InclusionProof {
+            index: index as u64,
+            leaf_count: self.leaf_count(),
+            siblings,
+        })
+    }
+
+    /// Sign the root of the tree with the given keypair
+    pub fn sign(&self, keypair: &Keypair) -> Result<SignedRoot> {
+        let root = self.root();
+        let leaf_count = self.leaf_count();
+        Ok(SignedRoot {
+            root,
+            leaf_count,
+            public_key: keypair.public_key().clone(),
+            signature: keypair.sign(&root_signing_bytes(&root, leaf_count))?,
+        })
+    }
+}
+
+fn root_signing_bytes(root: &Hash, leaf_count: u64) -> Vec<u8> {
+    let mut result = MERKLE_ROOT_DOMAIN.to_vec();
+    result.extend_from_slice(&leaf_count.to_be_bytes());
+    result.extend_from_slice(root);
+    result
+}
+
+impl InclusionProof {
+    /// Compute the root hash implied by this proof for the given message.
+    /// Returns `None` if the proof is malformed.
+    pub fn root(&self, message: &[u8]) -> Option<Hash> {
+        if self.index >= self.leaf_count {
+            return None;
+        }
+        let mut hash = leaf_hash(message);
+        let mut siblings = self.siblings.iter();
+        let mut position = self.index;
+        let mut size = self.leaf_count;
+        while size > 1 {
+            if position % 2 == 1 {
+                hash = node_hash(siblings.next()?, &hash);
+            } else if position + 1 < size {
+                hash = node_hash(&hash, siblings.next()?);
+            }
+            position /= 2;
+            size = (size + 1) / 2;
+        }
+        if siblings.next().is_some() {
+            return None;
+        }
+        Some(hash)
+    }
+
+    /// Convert the proof to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl SignedRoot {
+    /// Verify the signature over the root
+    pub fn verify(&self) -> Result {
+        self.public_key.verify(
+            &root_signing_bytes(&self.root, self.leaf_count),
+            &self.signature,
+        )
+    }
+
+    /// Verify that the given message is included in the signed tree using the
+    /// given inclusion proof, and that the root signature is valid.
+    pub fn verify_inclusion(&self, message: &[u8], proof: &InclusionProof) -> Result {
+        if proof.leaf_count != self.leaf_count || proof.root(message) != Some(self.root) {
+            return Err(signature::Error::new().into());
+        }
+        self.verify()
+    }
+
+    /// Convert the signed root to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl WriteTo for InclusionProof {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.index.to_be_bytes())?;
+        output.write_all(&self.leaf_count.to_be_bytes())?;
+        output.write_all(&[self.siblings.len() as u8])?;
+        for sibling in &self.siblings {
+            output.write_all(sibling)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadFrom for InclusionProof {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; 8];
+        input.read_exact(&mut buf)?;
+        let index = u64::from_be_bytes(buf);
+        input.read_exact(&mut buf)?;
+        let leaf_count = u64::from_be_bytes(buf);
+        let mut count = [0u8];
+        input.read_exact(&mut count)?;
+        let mut siblings = Vec::with_capacity(count[0].into());
+        for _ in 0..count[0] {
+            let mut sibling = [0u8; HASH_LENGTH];
+            input.read_exact(&mut sibling)?;
+            siblings.push(sibling);
+        }
+        Ok(Self {
+            index,
+            leaf_count,
+            siblings,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for InclusionProof {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+impl WriteTo for SignedRoot {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.root)?;
+        output.write_all(&self.leaf_count.to_be_bytes())?;
+        self.public_key.write_to(output)?;
+        signed_message::write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for SignedRoot {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut root = [0u8; HASH_LENGTH];
+        input.read_exact(&mut root)?;
+        let mut buf = [0u8; 8];
+        input.read_exact(&mut buf)?;
+        let public_key = PublicKey::read_from(input)?;
+        let signature = signed_message::read_u32_prefixed(input)?;
+        Ok(Self {
+            root,
+            leaf_count: u64::from_be_bytes(buf),
+            public_key,
+            signature,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for SignedRoot {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn messages(n: usize) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|i| format!("reward {}", i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn inclusion() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        for n in 1..=9 {
+            let messages = messages(n);
+            let tree = MerkleTree::new(&messages).expect("tree");
+            let signed_root = tree.sign(&keypair).expect("signed root");
+            for (index, message) in messages.iter().enumerate() {
+                let proof = tree.proof(index).expect("proof");
+                let proof = InclusionProof::try_from(&proof.to_vec()[..]).expect("decoded proof");
+                assert!(signed_root.verify_inclusion(message, &proof).is_ok());
+                assert!(signed_root.verify_inclusion(b"bogus", &proof).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_index() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let messages = messages(4);
+        let tree = MerkleTree::new(&messages).expect("tree");
+        let signed_root = tree.sign(&keypair).expect("signed root");
+        let mut proof = tree.proof(1).expect("proof");
+        proof.index = 0;
+        assert!(signed_root.verify_inclusion(&messages[1], &proof).is_err());
+        assert!(tree.proof(4).is_none());
+    }
+
+    #[test]
+    fn signed_root_roundtrip() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let tree = MerkleTree::new(&messages(3)).expect("tree");
+        let signed_root = tree.sign(&keypair).expect("signed root");
+        let decoded = SignedRoot::try_from(&signed_root.to_vec()[..]).expect("decoded");
+        assert_eq!(signed_root, decoded);
+        assert!(decoded.verify().is_ok());
+        assert!(MerkleTree::new::<Vec<u8>>(&[]).is_none());
+    }
+}
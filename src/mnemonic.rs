@@ -0,0 +1,169 @@
+//! BIP39 mnemonic phrases for keypairs.
+//!
+//! Two derivations from a 12 or 24 word English phrase are supported:
+//!
+//! * [`Keypair::from_mnemonic`] follows [BIP39][BIP39]: the phrase and an
+//!   optional passphrase are stretched into a 64 byte seed, which is used as
+//!   the [SLIP-0010](crate::derivation) master seed for the key type.
+//!   [`Keypair::generate_with_mnemonic`] generates phrases for this
+//!   derivation.
+//! * [`Keypair::from_legacy_mnemonic`] recovers keypairs made by the Helium
+//!   wallets, which use the phrase entropy as the keypair entropy directly,
+//!   repeating the 16 bytes of entropy of a 12 word phrase twice. The legacy
+//!   mobile wallet wrote 12 word phrases with an all zero checksum, which are
+//!   accepted as well as phrases with a valid checksum.
+//!
+//! [BIP39]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+use crate::{derivation::ExtendedKey, *};
+use bip39::{Language, Mnemonic};
+use sha2::{Digest, Sha256};
+
+/// Returns the BIP39 seed for the given phrase and passphrase. The checksum of
+/// the phrase must be valid.
+pub fn to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64]> {
+    Ok(parse(phrase)?.to_seed(passphrase))
+}
+
+fn parse(phrase: &str) -> Result<Mnemonic> {
+    let mnemonic = Mnemonic::parse(phrase).map_err(|_| Error::invalid_mnemonic())?;
+    match mnemonic.word_count() {
+        12 | 24 => Ok(mnemonic),
+        _ => Err(Error::invalid_mnemonic()),
+    }
+}
+
+/// Returns the 32 bytes of keypair entropy the Helium wallets derive from the
+/// given phrase
+pub fn legacy_entropy(phrase: &str) -> Result<[u8; 32]> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if words.len() != 12 && words.len() != 24 {
+        return Err(Error::invalid_mnemonic());
+    }
+    // Every word encodes 11 bits of entropy followed by checksum bits
+    let mut bits = vec![0u8; words.len() * 11 / 8 + 1];
+    for (i, word) in words.iter().enumerate() {
+        let index = Language::English
+            .find_word(word)
+            .ok_or_else(Error::invalid_mnemonic)?;
+        for bit in 0..11 {
+            if index & (1 << (10 - bit)) != 0 {
+                let pos = i * 11 + bit;
+                bits[pos / 8] |= 0x80 >> (pos % 8);
+            }
+        }
+    }
+    let entropy_len = words.len() * 4 / 3;
+    let checksum_len = words.len() / 3;
+    let entropy = &bits[..entropy_len];
+    let checksum = bits[entropy_len] >> (8 - checksum_len);
+    let expected = Sha256::digest(entropy)[0] >> (8 - checksum_len);
+    if checksum != expected && !(words.len() == 12 && checksum == 0) {
+        return Err(Error::invalid_mnemonic());
+    }
+    let mut result = [0u8; 32];
+    for chunk in result.chunks_mut(entropy_len) {
+        chunk.copy_from_slice(entropy);
+    }
+    Ok(result)
+}
+
+impl Keypair {
+    /// Recover the keypair with the given key tag from the given BIP39 phrase
+    /// and passphrase, which may be empty
+    pub fn from_mnemonic(phrase: &str, passphrase: &str, key_tag: KeyTag) -> Result<Keypair> {
+        ExtendedKey::from_seed(key_tag.key_type, &to_seed(phrase, passphrase)?)?
+            .to_keypair(key_tag.network)
+    }
+
+    /// Generate a keypair with the given key tag along with the BIP39 phrase
+    /// of the given number of words, 12 or 24, that recovers it with
+    /// [`Keypair::from_mnemonic`] and the same passphrase
+    pub fn generate_with_mnemonic<R>(
+        key_tag: KeyTag,
+        word_count: usize,
+        passphrase: &str,
+        csprng: &mut R,
+    ) -> Result<(Keypair, String)>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut entropy = [0u8; 32];
+        let entropy = match word_count {
+            12 => &mut entropy[..16],
+            24 => &mut entropy[..],
+            _ => return Err(Error::invalid_mnemonic()),
+        };
+        csprng.fill_bytes(entropy);
+        let phrase = Mnemonic::from_entropy(entropy)
+            .map_err(|_| Error::invalid_mnemonic())?
+            .to_string();
+        let keypair = Self::from_mnemonic(&phrase, passphrase, key_tag)?;
+        Ok((keypair, phrase))
+    }
+
+    /// Recover the keypair with the given key tag from a phrase made by the
+    /// Helium wallets
+    pub fn from_legacy_mnemonic(phrase: &str, key_tag: KeyTag) -> Result<Keypair> {
+        Self::generate_from_entropy(key_tag, &legacy_entropy(phrase)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    const PHRASE: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn seed_vector() {
+        // BIP39 test vector for all zero entropy
+        assert_eq!(
+            hex!("c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"),
+            to_seed(PHRASE, "TREZOR").expect("seed")
+        );
+        let keypair = Keypair::from_mnemonic(PHRASE, "TREZOR", KeyTag::default()).expect("keypair");
+        assert_ne!(
+            keypair,
+            Keypair::from_mnemonic(PHRASE, "", KeyTag::default()).expect("keypair")
+        );
+    }
+
+    #[test]
+    fn generate_roundtrip() {
+        for (key_type, word_count) in [(KeyType::Ed25519, 12), (KeyType::EccCompact, 24)] {
+            let key_tag = KeyTag {
+                network: Network::MainNet,
+                key_type,
+            };
+            let (keypair, phrase) =
+                Keypair::generate_with_mnemonic(key_tag, word_count, "secret", &mut OsRng)
+                    .expect("keypair");
+            assert_eq!(word_count, phrase.split_whitespace().count());
+            assert_eq!(
+                keypair,
+                Keypair::from_mnemonic(&phrase, "secret", key_tag).expect("recovered")
+            );
+        }
+        assert!(Keypair::generate_with_mnemonic(KeyTag::default(), 15, "", &mut OsRng).is_err());
+    }
+
+    #[test]
+    fn legacy_checksum() {
+        let legacy = ["abandon"; 12].join(" ");
+        // The all zero checksum is only accepted for legacy phrases
+        assert!(to_seed(&legacy, "").is_err());
+        assert_eq!([0u8; 32], legacy_entropy(&legacy).expect("legacy"));
+        assert_eq!([0u8; 32], legacy_entropy(PHRASE).expect("valid"));
+        assert!(legacy_entropy(&["abandon"; 24].join(" ")).is_err());
+        assert!(legacy_entropy(&PHRASE.replace("about", "zoo")).is_err());
+
+        let keypair = Keypair::from_legacy_mnemonic(&legacy, KeyTag::default()).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::generate_from_entropy(KeyTag::default(), &[0u8; 32]).expect("entropy")
+        );
+    }
+}
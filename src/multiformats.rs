@@ -0,0 +1,300 @@
+//! Multibase, multicodec and libp2p PeerId encodings of public keys.
+//!
+//! Public keys encode as the base58btc [multibase][MULTIBASE] of their
+//! [multicodec][MULTICODEC] prefixed key bytes, which is the form used by
+//! [`did:key`][DIDKEY] identifiers. Ed25519 keys use the `ed25519-pub` codec
+//! and ecc_compact keys the `p256-pub` codec with the compressed SEC1 point.
+//!
+//! [PeerIds][PEERID] are derived from the libp2p protobuf encoding of the
+//! public key. Keys whose encoding is short enough, like ed25519 keys, are
+//! inlined in the PeerId with the identity multihash, so their public key can
+//! be recovered from the PeerId. Longer encodings, like those of ecc_compact
+//! keys, are hashed with sha2-256.
+//!
+//! Neither encoding has a notion of a Helium network, so the network is given
+//! when importing a key.
+//!
+//! [MULTIBASE]: https://github.com/multiformats/multibase
+//! [MULTICODEC]: https://github.com/multiformats/multicodec
+//! [DIDKEY]: https://w3c-ccg.github.io/did-method-key/
+//! [PEERID]: https://github.com/libp2p/specs/blob/master/peer-ids/peer-ids.md
+use crate::*;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::{Digest, Sha256};
+
+/// The multibase prefix for base58btc
+const BASE58BTC: char = 'z';
+
+/// The prefix of a did:key identifier
+const DID_KEY_PREFIX: &str = "did:key:";
+
+/// The varint encoded multicodec prefix of an ed25519 public key
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+/// The varint encoded multicodec prefix of a compressed P-256 public key
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+/// The varint encoded multicodec prefix of a compressed secp256k1 public key
+#[cfg(feature = "secp256k1")]
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+
+/// libp2p key type identifiers
+const LIBP2P_ED25519: u8 = 1;
+#[cfg(feature = "secp256k1")]
+const LIBP2P_SECP256K1: u8 = 2;
+const LIBP2P_ECDSA: u8 = 3;
+
+/// The multihash codes for the identity and sha2-256 hashes
+const MULTIHASH_IDENTITY: u8 = 0x00;
+const MULTIHASH_SHA2_256: u8 = 0x12;
+/// Protobuf encoded public keys up to this length are inlined in the PeerId
+const MAX_INLINE_KEY_LENGTH: usize = 42;
+
+/// The DER prefix of a SubjectPublicKeyInfo for an uncompressed P-256 point:
+/// the id-ecPublicKey and prime256v1 algorithm identifiers and the bit string
+/// header of the 65 byte point
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+impl PublicKey {
+    /// Returns the base58btc multibase encoding of the multicodec prefixed
+    /// bytes of the public key
+    pub fn to_multibase(&self) -> Result<String> {
+        let (codec, key) = match self.key_type() {
+            KeyType::Ed25519 => {
+                let public_key: &ed25519::PublicKey = self.try_into()?;
+                (ED25519_PUB, public_key.as_ref().to_vec())
+            }
+            KeyType::EccCompact => {
+                let public_key: &ecc_compact::PublicKey = self.try_into()?;
+                (
+                    P256_PUB,
+                    public_key.0.to_encoded_point(true).as_bytes().to_vec(),
+                )
+            }
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => (SECP256K1_PUB, self.to_vec()[1..].to_vec()),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::invalid_curve()),
+        };
+        let data = [&codec[..], &key[..]].concat();
+        Ok(format!("{}{}", BASE58BTC, bs58::encode(data).into_string()))
+    }
+
+    /// Import a public key for the given network from the given base58btc
+    /// multibase encoding of its multicodec prefixed bytes. P-256 public keys
+    /// that are not compactable are rejected.
+    pub fn from_multibase(network: Network, value: &str) -> Result<PublicKey> {
+        let encoded = value
+            .strip_prefix(BASE58BTC)
+            .ok_or_else(Error::invalid_multiformat)?;
+        let data = bs58::decode(encoded)
+            .into_vec()
+            .map_err(|_| Error::invalid_multiformat())?;
+        if data.len() < 2 {
+            return Err(Error::invalid_multiformat());
+        }
+        let (codec, key) = data.split_at(2);
+        let key_tag = |key_type| u8::from(KeyTag { network, key_type });
+        match codec {
+            c if c == ED25519_PUB && key.len() == 32 => {
+                PublicKey::try_from(&[&[key_tag(KeyType::Ed25519)][..], key].concat()[..])
+            }
+            c if c == P256_PUB && key.len() == 33 => {
+                let point = p256::PublicKey::from_sec1_bytes(key)?.to_encoded_point(false);
+                let public_key = ecc_compact::PublicKey::try_from(point.as_bytes())?;
+                Ok(PublicKey::for_network(network, public_key))
+            }
+            #[cfg(feature = "secp256k1")]
+            c if c == SECP256K1_PUB && key.len() == 33 => {
+                PublicKey::try_from(&[&[key_tag(KeyType::Secp256k1)][..], key].concat()[..])
+            }
+            _ => Err(Error::invalid_multiformat()),
+        }
+    }
+
+    /// Returns the did:key identifier of the public key
+    pub fn to_did_key(&self) -> Result<String> {
+        Ok(format!("{}{}", DID_KEY_PREFIX, self.to_multibase()?))
+    }
+
+    /// Import a mainnet public key from the given did:key identifier. A DID
+    /// URL fragment, like the verification method id `#z6Mk...`, is ignored.
+    pub fn try_from_did(did: &str) -> Result<PublicKey> {
+        Self::from_did(Network::default(), did)
+    }
+
+    /// Import a public key for the given network from the given did:key
+    /// identifier. A DID URL fragment is ignored.
+    pub fn from_did(network: Network, did: &str) -> Result<PublicKey> {
+        let identifier = did
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or_else(Error::invalid_multiformat)?;
+        let identifier = identifier.split('#').next().unwrap_or_default();
+        Self::from_multibase(network, identifier)
+    }
+
+    /// Returns the base58 encoded libp2p PeerId of the public key
+    pub fn to_peer_id(&self) -> Result<String> {
+        let encoded = self.to_libp2p_protobuf()?;
+        let multihash = if encoded.len() <= MAX_INLINE_KEY_LENGTH {
+            [&[MULTIHASH_IDENTITY, encoded.len() as u8][..], &encoded[..]].concat()
+        } else {
+            [&[MULTIHASH_SHA2_256, 32][..], &Sha256::digest(&encoded)[..]].concat()
+        };
+        Ok(bs58::encode(multihash).into_string())
+    }
+
+    /// Import a public key for the given network from the given base58
+    /// encoded libp2p PeerId. Only PeerIds that inline their public key, like
+    /// those of ed25519 keys, can be imported.
+    pub fn from_peer_id(network: Network, peer_id: &str) -> Result<PublicKey> {
+        let data = bs58::decode(peer_id)
+            .into_vec()
+            .map_err(|_| Error::invalid_multiformat())?;
+        let encoded = match &data[..] {
+            [MULTIHASH_IDENTITY, len, rest @ ..] if *len as usize == rest.len() => rest,
+            _ => return Err(Error::invalid_multiformat()),
+        };
+        let (key_type, key) = match encoded {
+            [0x08, key_type, 0x12, len, key @ ..] if *len as usize == key.len() => (*key_type, key),
+            _ => return Err(Error::invalid_multiformat()),
+        };
+        let key_tag = |key_type| u8::from(KeyTag { network, key_type });
+        match key_type {
+            LIBP2P_ED25519 if key.len() == 32 => {
+                PublicKey::try_from(&[&[key_tag(KeyType::Ed25519)][..], key].concat()[..])
+            }
+            #[cfg(feature = "secp256k1")]
+            LIBP2P_SECP256K1 if key.len() == 33 => {
+                PublicKey::try_from(&[&[key_tag(KeyType::Secp256k1)][..], key].concat()[..])
+            }
+            _ => Err(Error::invalid_multiformat()),
+        }
+    }
+
+    /// Returns the libp2p protobuf encoding of the public key, a message with
+    /// the key type in field 1 and the key data in field 2
+    fn to_libp2p_protobuf(&self) -> Result<Vec<u8>> {
+        let (key_type, data) = match self.key_type() {
+            KeyType::Ed25519 => {
+                let public_key: &ed25519::PublicKey = self.try_into()?;
+                (LIBP2P_ED25519, public_key.as_ref().to_vec())
+            }
+            KeyType::EccCompact => {
+                let public_key: &ecc_compact::PublicKey = self.try_into()?;
+                let point = public_key.0.to_encoded_point(false);
+                (
+                    LIBP2P_ECDSA,
+                    [&P256_SPKI_PREFIX[..], point.as_bytes()].concat(),
+                )
+            }
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => (LIBP2P_SECP256K1, self.to_vec()[1..].to_vec()),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::invalid_curve()),
+        };
+        // All supported key data is shorter than 128 bytes, so the length
+        // fits in a single byte varint
+        Ok([&[0x08, key_type, 0x12, data.len() as u8][..], &data[..]].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    fn ed25519_public_key() -> PublicKey {
+        // The public key of test 1 of RFC 8032 section 7.1
+        let key = hex!("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        PublicKey::try_from(&[&[u8::from(KeyTag::default())][..], &key[..]].concat()[..])
+            .expect("public key")
+    }
+
+    #[test]
+    fn ed25519_multibase() {
+        let public_key = ed25519_public_key();
+        let encoded = public_key.to_multibase().expect("multibase");
+        assert_eq!("z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw", encoded);
+        assert_eq!(
+            public_key,
+            PublicKey::from_multibase(Network::MainNet, &encoded).expect("decoded")
+        );
+    }
+
+    #[test]
+    fn ed25519_peer_id() {
+        let public_key = ed25519_public_key();
+        let peer_id = public_key.to_peer_id().expect("peer id");
+        assert_eq!(
+            "12D3KooWQK1wnefoLrcVHbbnf5tLzbopUd3K3bFAoJpA7YJgL5pV",
+            peer_id
+        );
+        assert_eq!(
+            public_key,
+            PublicKey::from_peer_id(Network::MainNet, &peer_id).expect("decoded")
+        );
+    }
+
+    #[test]
+    fn ecc_compact() {
+        let key_tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccCompact,
+        };
+        let public_key = Keypair::generate(key_tag, &mut OsRng).public_key().clone();
+        let encoded = public_key.to_multibase().expect("multibase");
+        assert!(encoded.starts_with("zDn"));
+        assert_eq!(
+            public_key,
+            PublicKey::from_multibase(Network::TestNet, &encoded).expect("decoded")
+        );
+
+        // P-256 PeerIds hash the key, so they are all the same length and
+        // can not be decoded back to a key
+        let peer_id = public_key.to_peer_id().expect("peer id");
+        assert!(peer_id.starts_with("Qm"));
+        assert!(PublicKey::from_peer_id(Network::TestNet, &peer_id).is_err());
+    }
+
+    #[test]
+    fn did_key() {
+        // The first ed25519 example of the did:key method specification
+        const DID: &str = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+        let public_key = PublicKey::try_from_did(DID).expect("public key");
+        assert_eq!(KeyType::Ed25519, public_key.key_type());
+        assert_eq!(Network::MainNet, public_key.network);
+        assert_eq!(DID, public_key.to_did_key().expect("did"));
+
+        let with_fragment = format!("{}#{}", DID, &DID[DID_KEY_PREFIX.len()..]);
+        assert_eq!(
+            public_key,
+            PublicKey::try_from_did(&with_fragment).expect("public key")
+        );
+        assert!(PublicKey::try_from_did(&DID[4..]).is_err());
+        assert!(PublicKey::try_from_did("did:web:example.com").is_err());
+
+        let key_tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccCompact,
+        };
+        let public_key = Keypair::generate(key_tag, &mut OsRng).public_key().clone();
+        let did = public_key.to_did_key().expect("did");
+        assert!(did.starts_with("did:key:zDn"));
+        assert_eq!(
+            public_key,
+            PublicKey::from_did(Network::TestNet, &did).expect("public key")
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let encoded = ed25519_public_key().to_multibase().expect("multibase");
+        assert!(PublicKey::from_multibase(Network::MainNet, &encoded[1..]).is_err());
+        assert!(PublicKey::from_multibase(Network::MainNet, &encoded[..20]).is_err());
+        assert!(PublicKey::from_multibase(Network::MainNet, "z").is_err());
+        assert!(PublicKey::from_peer_id(Network::MainNet, &encoded[1..]).is_err());
+    }
+}
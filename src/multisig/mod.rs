@@ -23,6 +23,14 @@ pub enum Error {
     KeyDigest,
     #[error("not a multisig member: {0}")]
     NotMember(public_key::PublicKey),
+    #[error("duplicate signature by member {0}")]
+    DuplicateSigner(u8),
+    #[error("unknown member {0}")]
+    UnknownSigner(u8),
+    #[error("signature not in canonical order")]
+    NotCanonical,
+    #[error("multisig nesting deeper than {0}")]
+    TooDeep(usize),
 }
 
 impl Error {
@@ -53,6 +61,22 @@ impl Error {
     pub fn not_member(public_key: public_key::PublicKey) -> crate::Error {
         Self::NotMember(public_key).into()
     }
+
+    pub fn duplicate_signer(index: u8) -> crate::Error {
+        Self::DuplicateSigner(index).into()
+    }
+
+    pub fn unknown_signer(index: u8) -> crate::Error {
+        Self::UnknownSigner(index).into()
+    }
+
+    pub fn not_canonical() -> crate::Error {
+        Self::NotCanonical.into()
+    }
+
+    pub fn too_deep(depth: usize) -> crate::Error {
+        Self::TooDeep(depth).into()
+    }
 }
 
 #[derive(Clone)]
@@ -63,6 +87,19 @@ pub struct PublicKey {
 }
 
 pub const PUBLIC_KEY_LENGTH: usize = 37;
+/// Members of a multisig key can be multisig keys themselves. Signatures are
+/// only verified up to this many levels of multisig keys, counting the
+/// outermost key.
+pub const MAX_DEPTH: usize = 4;
+
+/// A multisig keypair signs by collecting the signatures of its members. Not
+/// every member needs to be able to sign, as long as at least `m` of them can.
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    members: Vec<public_key::PublicKey>,
+    signers: Vec<(public_key::PublicKey, Box<dyn keypair::Sign + Send + Sync>)>,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Signature {
@@ -70,6 +107,22 @@ pub struct Signature {
     key_signatures: Vec<KeySignature>,
 }
 
+/// The signature of a single member, gathered from the member before it is
+/// combined into a multisig [`Signature`].
+///
+/// The binary form is the index of the member in the sorted member keys, the
+/// key type byte of the member and the signature of the member:
+///
+/// ```text
+/// index (1) || key type (1) || signature
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialSignature {
+    pub index: u8,
+    pub key_type: KeyType,
+    pub signature: Vec<u8>,
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 struct KeySignature {
     index: u8,
@@ -106,6 +159,195 @@ impl PublicKey {
     }
 }
 
+impl PartialSignature {
+    /// Construct the partial signature of the given member of the given
+    /// members. The members do not need to be sorted.
+    pub fn new(
+        members: &[public_key::PublicKey],
+        public_key: &public_key::PublicKey,
+        signature: Vec<u8>,
+    ) -> Result<Self> {
+        let mut members = members.to_owned();
+        public_key_sort(&mut members);
+        let KeySignature { index, signature } = KeySignature::new(&members, public_key, signature)?;
+        Ok(Self {
+            index,
+            key_type: public_key.key_type(),
+            signature,
+        })
+    }
+
+    /// Returns the member among the given sorted members that made this
+    /// partial signature
+    fn member<'a>(
+        &self,
+        members: &'a [public_key::PublicKey],
+    ) -> Result<&'a public_key::PublicKey> {
+        let member = members
+            .get(usize::from(self.index))
+            .ok_or_else(signature::Error::new)?;
+        if member.key_type() != self.key_type {
+            return Err(crate::Error::invalid_keytype(self.key_type.into()));
+        }
+        Ok(member)
+    }
+
+    /// Verify this partial signature over the given message by its member
+    /// of the given members
+    pub fn verify(&self, members: &[public_key::PublicKey], msg: &[u8]) -> Result {
+        let mut members = members.to_owned();
+        public_key_sort(&mut members);
+        self.member(&members)?.verify(msg, &self.signature)
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![self.index, self.key_type.into()];
+        result.extend_from_slice(&self.signature);
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for PartialSignature {
+    type Error = crate::Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        match input {
+            [index, key_type, signature @ ..] if !signature.is_empty() => Ok(Self {
+                index: *index,
+                key_type: KeyType::try_from(*key_type)?,
+                signature: signature.to_vec(),
+            }),
+            _ => Err(signature::Error::new().into()),
+        }
+    }
+}
+
+impl Keypair {
+    /// Construct a multisig keypair for the given `m` of the given member
+    /// public keys, without any signers. Add the members that can sign with
+    /// [`Keypair::with_signer`].
+    pub fn new(
+        network: Network,
+        m: u8,
+        hash: multihash::Code,
+        public_keys: &[public_key::PublicKey],
+    ) -> Result<Self> {
+        let public_key = PublicKey::generate(network, m, hash, public_keys)?;
+        let mut members = public_keys.to_owned();
+        public_key_sort(&mut members);
+        if usize::from(m) > members.len() {
+            return Err(Error::insufficient_keys(members.len(), m));
+        }
+        Ok(Self {
+            network,
+            public_key,
+            members,
+            signers: Vec::new(),
+        })
+    }
+
+    /// Construct an `m` of n multisig keypair from the given n member
+    /// keypairs, all of which can sign
+    pub fn from_keypairs(
+        network: Network,
+        m: u8,
+        hash: multihash::Code,
+        keypairs: Vec<crate::Keypair>,
+    ) -> Result<Self> {
+        let public_keys: Vec<public_key::PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect();
+        keypairs.into_iter().try_fold(
+            Self::new(network, m, hash, &public_keys)?,
+            |keypair, member| keypair.with_signer(member.public_key().clone(), Box::new(member)),
+        )
+    }
+
+    /// Construct a 1 of 1 multisig keypair for the given member keypair, as
+    /// [`crate::Keypair::generate`] does for the multisig key type
+    pub(crate) fn single(member: crate::Keypair) -> Self {
+        let network = member.key_tag().network;
+        // Unwrap ok since a single member always forms a valid key
+        Self::from_keypairs(network, 1, multihash::Code::Sha2_256, vec![member]).unwrap()
+    }
+
+    /// Add the given signer for the member with the given public key. The
+    /// signer can be a local keypair or a handle to a remote signer.
+    pub fn with_signer(
+        mut self,
+        public_key: public_key::PublicKey,
+        signer: Box<dyn keypair::Sign + Send + Sync>,
+    ) -> Result<Self> {
+        if !self.members.contains(&public_key) {
+            return Err(Error::not_member(public_key));
+        }
+        self.signers.retain(|(member, _)| member != &public_key);
+        self.signers.push((public_key, signer));
+        Ok(self)
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::MultiSig,
+        }
+    }
+
+    /// Returns the member public keys of this keypair
+    pub fn members(&self) -> &[public_key::PublicKey] {
+        &self.members
+    }
+
+    /// Combine the given partial signatures, gathered from the members, into
+    /// a multisig signature
+    pub fn combine(&self, partial_signatures: &[PartialSignature]) -> Result<Vec<u8>> {
+        Ok(Signature::from_partials(&self.public_key, &self.members, partial_signatures)?.to_vec())
+    }
+}
+
+impl keypair::Sign for Keypair {
+    /// Collects member signatures until `m` of them are gathered. Members that
+    /// fail to sign, like unreachable remote signers, are skipped.
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let m = to_multisig(&self.public_key)
+            .ok_or_else(Error::not_multisig)?
+            .m;
+        let signatures: Vec<(public_key::PublicKey, Vec<u8>)> = self
+            .signers
+            .iter()
+            .filter_map(|(public_key, signer)| {
+                signer
+                    .sign(msg)
+                    .ok()
+                    .map(|signature| (public_key.clone(), signature))
+            })
+            .take(usize::from(m))
+            .collect();
+        if signatures.len() < usize::from(m) {
+            return Err(Error::insufficient_signatures(signatures.len(), m));
+        }
+        Ok(Signature::new(&self.public_key, &self.members, &signatures)?.to_vec())
+    }
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keypair")
+            .field("network", &self.network)
+            .field("public_key", &self.public_key)
+            .field("members", &self.members)
+            .field("signers", &self.signers.len())
+            .finish()
+    }
+}
+
 impl Signature {
     pub fn new(
         public_key: &public_key::PublicKey,
@@ -131,10 +373,9 @@ impl Signature {
             }
             _ => (),
         }
-        let hash_type = Multihash::from_bytes(&public_key.keys_digest)
-            .and_then(|hash| multihash::Code::try_from(hash.code()))
-            .map_err(Error::multihash)?;
-        if public_key_digest(&network, &public_keys, &hash_type)? != public_key.keys_digest {
+        if public_key_digest(&network, &public_keys, &public_key.hash_type()?)?
+            != public_key.keys_digest
+        {
             return Err(Error::key_digest());
         }
 
@@ -144,6 +385,7 @@ impl Signature {
             key_signatures.push(key_signature);
         }
         key_signature_sort(&mut key_signatures);
+        check_key_signatures(&key_signatures, public_key.n)?;
 
         Ok(Self {
             public_keys,
@@ -151,14 +393,29 @@ impl Signature {
         })
     }
 
+    /// Construct a multisig signature from partial signatures gathered from
+    /// the members with the given keys
+    pub fn from_partials(
+        public_key: &public_key::PublicKey,
+        keys: &[public_key::PublicKey],
+        partial_signatures: &[PartialSignature],
+    ) -> Result<Self> {
+        let mut public_keys = keys.to_owned();
+        public_key_sort(&mut public_keys);
+        let signatures = partial_signatures
+            .iter()
+            .map(|partial_signature| {
+                let member = partial_signature.member(&public_keys)?;
+                Ok((member.clone(), partial_signature.signature.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(public_key, keys, &signatures)
+    }
+
     fn from_input<R: std::io::Read>(public_key: &PublicKey, input: &mut R) -> Result<Self> {
         let mut public_keys = Vec::with_capacity(public_key.n.into());
         for _ in 0..public_key.n {
-            let public_key = public_key::PublicKey::read_from(input)?;
-            if to_multisig(&public_key).is_some() {
-                return Err(crate::Error::invalid_keytype(KeyType::MultiSig.into()));
-            }
-            public_keys.push(public_key);
+            public_keys.push(public_key::PublicKey::read_from(input)?);
         }
         let mut key_signatures = Vec::with_capacity(public_key.m.into());
         loop {
@@ -170,7 +427,7 @@ impl Signature {
                 Err(err) => return Err(err),
             }
         }
-        key_signature_sort(&mut key_signatures);
+        check_key_signatures(&key_signatures, public_key.n)?;
 
         Ok(Self {
             public_keys,
@@ -178,14 +435,41 @@ impl Signature {
         })
     }
 
+    /// Check that the member keys of this signature are the sorted keys the
+    /// given multisig public key was generated from
+    fn check_public_keys(&self, public_key: &PublicKey) -> Result {
+        let mut sorted = self.public_keys.clone();
+        public_key_sort(&mut sorted);
+        if sorted != self.public_keys {
+            return Err(Error::not_canonical());
+        }
+        let network = match self.public_keys.first() {
+            Some(public_key) => public_key.network,
+            None => return Err(Error::insufficient_keys(0, public_key.n)),
+        };
+        if public_key_digest(&network, &self.public_keys, &public_key.hash_type()?)?
+            != public_key.keys_digest
+        {
+            return Err(Error::key_digest());
+        }
+        Ok(())
+    }
+
     /// Returns the number of key signatures that successfully verified the
-    /// given message
-    fn verify(&self, msg: &[u8]) -> u8 {
+    /// given message. Signatures by multisig members are verified at the
+    /// given depth.
+    fn verify(&self, msg: &[u8], depth: usize) -> u8 {
         self.key_signatures
             .iter()
             .filter(|key_signature| {
-                let public_key = &self.public_keys[key_signature.index as usize];
-                public_key.verify(msg, &key_signature.signature).is_ok()
+                self.public_keys
+                    .get(usize::from(key_signature.index))
+                    .map_or(false, |public_key| match to_multisig(public_key) {
+                        Some(multisig) => multisig
+                            .verify_at_depth(msg, &key_signature.signature, depth)
+                            .is_ok(),
+                        None => public_key.verify(msg, &key_signature.signature).is_ok(),
+                    })
             })
             .count() as u8
     }
@@ -204,9 +488,6 @@ impl KeySignature {
         public_key: &public_key::PublicKey,
         signature: Vec<u8>,
     ) -> Result<Self> {
-        if to_multisig(public_key).is_some() {
-            return Err(crate::Error::invalid_keytype(public_key.key_type().into()));
-        }
         let index = public_keys
             .iter()
             .position(|k| k == public_key)
@@ -228,9 +509,6 @@ fn public_key_digest(
         if key.network != *network {
             return Err(crate::Error::invalid_network());
         }
-        if key.key_type() == KeyType::MultiSig {
-            return Err(crate::Error::invalid_keytype(key.key_type().into()));
-        }
         keys_bin.extend_from_slice(&key.to_vec());
     }
     Ok(hash_type.digest(&keys_bin).to_bytes())
@@ -246,6 +524,26 @@ fn key_signature_sort(key_signatures: &mut Vec<KeySignature>) {
     key_signatures.dedup();
 }
 
+/// Key signatures must be ordered by strictly increasing member index, which
+/// rejects duplicate signers and makes the encoding of a signature by a given
+/// set of signers canonical
+fn check_key_signatures(key_signatures: &[KeySignature], n: u8) -> Result {
+    for pair in key_signatures.windows(2) {
+        if pair[0].index == pair[1].index {
+            return Err(Error::duplicate_signer(pair[0].index));
+        }
+        if pair[0].index > pair[1].index {
+            return Err(Error::not_canonical());
+        }
+    }
+    match key_signatures.last() {
+        Some(key_signature) if key_signature.index >= n => {
+            Err(Error::unknown_signer(key_signature.index))
+        }
+        _ => Ok(()),
+    }
+}
+
 fn to_multisig(public_key: &public_key::PublicKey) -> Option<&PublicKey> {
     match &public_key.inner {
         public_key::PublicKeyRepr::MultiSig(key) => Some(key),
@@ -253,6 +551,28 @@ fn to_multisig(public_key: &public_key::PublicKey) -> Option<&PublicKey> {
     }
 }
 
+impl PublicKey {
+    fn verify_at_depth(&self, msg: &[u8], signature: &[u8], depth: usize) -> Result {
+        if depth > MAX_DEPTH {
+            return Err(Error::too_deep(MAX_DEPTH));
+        }
+        let mut input = std::io::Cursor::new(signature);
+        let signature = Signature::from_input(self, &mut input)?;
+        signature.check_public_keys(self)?;
+        if signature.verify(msg, depth + 1) >= self.m {
+            return Ok(());
+        }
+        Err(signature::Error::new().into())
+    }
+
+    /// Returns the hash type the member keys digest was made with
+    fn hash_type(&self) -> Result<multihash::Code> {
+        Multihash::from_bytes(&self.keys_digest)
+            .and_then(|hash| multihash::Code::try_from(hash.code()))
+            .map_err(Error::multihash)
+    }
+}
+
 impl PublicKeySize for PublicKey {
     const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
 }
@@ -314,19 +634,31 @@ impl ReadFrom for PublicKey {
 }
 
 impl public_key::Verify for PublicKey {
+    /// Verify a multisig signature by any `m` or more of the `n` members. The
+    /// member keys in the signature must be the ones this key was generated
+    /// from, and every member may sign at most once. Multisig members are
+    /// verified the same way, up to [`MAX_DEPTH`] levels deep.
     fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
-        let mut input = std::io::Cursor::new(signature);
-        let signature = Signature::from_input(self, &mut input)?;
-        if signature.verify(msg) >= self.m {
-            return Ok(());
-        }
-        Err(signature::Error::new().into())
+        self.verify_at_depth(msg, signature, 1)
     }
 }
 
+// Key signatures are the member index and a one byte signature length
+// followed by the signature. Signatures of multisig members can be longer than
+// 255 bytes, in which case the length byte is zero and followed by a two byte
+// big endian length. Empty signatures never verify, so this is unambiguous.
 impl WriteTo for KeySignature {
     fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
-        output.write_all(&[self.index, self.signature.len() as u8])?;
+        match self.signature.len() {
+            len @ 1..=0xff => output.write_all(&[self.index, len as u8])?,
+            len => {
+                let len = u16::try_from(len).map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "signature too long")
+                })?;
+                output.write_all(&[self.index, 0])?;
+                output.write_all(&len.to_be_bytes())?;
+            }
+        }
         output.write_all(self.signature.as_ref())
     }
 }
@@ -336,7 +668,14 @@ impl ReadFrom for KeySignature {
         let mut buf = [0u8; 2];
         input.read_exact(&mut buf)?;
         let index = buf[0];
-        let mut signature = vec![0u8; usize::from(buf[1])];
+        let len = match buf[1] {
+            0 => {
+                input.read_exact(&mut buf)?;
+                usize::from(u16::from_be_bytes(buf))
+            }
+            len => usize::from(len),
+        };
+        let mut signature = vec![0u8; len];
         input.read_exact(&mut signature)?;
         Ok(Self { index, signature })
     }
@@ -455,4 +794,206 @@ mod tests {
             .verify(MSG, &signature.to_vec())
             .expect("verify success");
     }
+
+    #[test]
+    fn verify_signer_subsets() {
+        let keys = gen_keys(3);
+        let members = public_keys(&keys);
+        const MSG: &[u8] = b"hello world";
+
+        let pubkey =
+            super::PublicKey::generate(Network::MainNet, 2, multihash::Code::Sha2_256, &members)
+                .expect("multisig pubkey");
+        let signed = |signers: &[&crate::Keypair]| -> Vec<(public_key::PublicKey, Vec<u8>)> {
+            signers
+                .iter()
+                .map(|key| (key.public_key().clone(), key.sign(MSG).expect("signature")))
+                .collect()
+        };
+
+        // Any two or more members verify, in a canonical encoding
+        for signers in [
+            vec![&keys[0], &keys[1]],
+            vec![&keys[2], &keys[0]],
+            vec![&keys[1], &keys[2], &keys[0]],
+        ] {
+            let signature =
+                Signature::new(&pubkey, &members, &signed(&signers)).expect("signature");
+            assert!(pubkey.verify(MSG, &signature.to_vec()).is_ok());
+            let mut reversed = signers.clone();
+            reversed.reverse();
+            assert_eq!(
+                signature.to_vec(),
+                Signature::new(&pubkey, &members, &signed(&reversed))
+                    .expect("signature")
+                    .to_vec()
+            );
+        }
+
+        // A single member does not meet the threshold
+        let signature = Signature::new(&pubkey, &members, &signed(&[&keys[0]])).expect("signature");
+        assert!(pubkey.verify(MSG, &signature.to_vec()).is_err());
+
+        let signature =
+            Signature::new(&pubkey, &members, &signed(&[&keys[0], &keys[1]])).expect("signature");
+        let key_signature = |index: usize| KeySignature {
+            index: signature.key_signatures[index].index,
+            signature: signature.key_signatures[index].signature.clone(),
+        };
+
+        // The same member signing twice does not count twice
+        let duplicate = Signature {
+            public_keys: signature.public_keys.clone(),
+            key_signatures: vec![key_signature(0), key_signature(0)],
+        };
+        assert!(pubkey.verify(MSG, &duplicate.to_vec()).is_err());
+
+        // Signatures out of order are not canonical
+        let reordered = Signature {
+            public_keys: signature.public_keys.clone(),
+            key_signatures: vec![key_signature(1), key_signature(0)],
+        };
+        assert!(pubkey.verify(MSG, &reordered.to_vec()).is_err());
+
+        // Signatures by unknown members are rejected
+        let mut unknown = key_signature(1);
+        unknown.index = 3;
+        let unknown = Signature {
+            public_keys: signature.public_keys.clone(),
+            key_signatures: vec![key_signature(0), key_signature(1), unknown],
+        };
+        assert!(pubkey.verify(MSG, &unknown.to_vec()).is_err());
+
+        // Member keys have to be the ones the multisig key was made from
+        let outsiders = gen_keys(3);
+        let mut outsider_keys = public_keys(&outsiders);
+        public_key_sort(&mut outsider_keys);
+        let mut substituted = Signature {
+            public_keys: outsider_keys.clone(),
+            key_signatures: signed(&[&outsiders[0], &outsiders[1]])
+                .into_iter()
+                .map(|(public_key, signature)| {
+                    KeySignature::new(&outsider_keys, &public_key, signature)
+                        .expect("key signature")
+                })
+                .collect(),
+        };
+        key_signature_sort(&mut substituted.key_signatures);
+        assert!(pubkey.verify(MSG, &substituted.to_vec()).is_err());
+    }
+
+    #[test]
+    fn nested_multisig() {
+        const MSG: &[u8] = b"hello world";
+        // 2 of 3 departments, each 3 of 5 people
+        let departments: Vec<crate::Keypair> = (0..3)
+            .map(|_| {
+                Keypair::from_keypairs(Network::MainNet, 3, multihash::Code::Sha2_256, gen_keys(5))
+                    .expect("department")
+                    .into()
+            })
+            .collect();
+        let organization =
+            Keypair::from_keypairs(Network::MainNet, 2, multihash::Code::Sha2_256, departments)
+                .expect("organization");
+        let signature = organization.sign(MSG).expect("signature");
+        assert!(signature.len() > 0xff);
+        assert!(organization.public_key.verify(MSG, &signature).is_ok());
+        assert!(organization
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn nesting_depth() {
+        const MSG: &[u8] = b"hello world";
+        let nest = |depth: usize| {
+            let mut keypair = Keypair::single(gen_keys(1).remove(0));
+            for _ in 1..depth {
+                keypair = Keypair::single(keypair.into());
+            }
+            keypair
+        };
+        let keypair = nest(MAX_DEPTH);
+        let signature = keypair.sign(MSG).expect("signature");
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+
+        let keypair = nest(MAX_DEPTH + 1);
+        let signature = keypair.sign(MSG).expect("signature");
+        assert!(keypair.public_key.verify(MSG, &signature).is_err());
+    }
+
+    #[test]
+    fn partial_signatures() {
+        let keys = gen_keys(3);
+        let members = public_keys(&keys);
+        const MSG: &[u8] = b"hello world";
+
+        let keypair = Keypair::new(Network::MainNet, 2, multihash::Code::Sha2_256, &members)
+            .expect("multisig keypair");
+        let partial_signatures: Vec<PartialSignature> = keys[1..]
+            .iter()
+            .map(|key| {
+                let signature = key.sign(MSG).expect("signature");
+                let partial_signature =
+                    PartialSignature::new(&members, key.public_key(), signature)
+                        .expect("partial signature");
+                // Gathered over the wire in binary form
+                PartialSignature::try_from(&partial_signature.to_vec()[..])
+                    .expect("partial signature")
+            })
+            .collect();
+        for partial_signature in &partial_signatures {
+            assert!(partial_signature.verify(&members, MSG).is_ok());
+            assert!(partial_signature.verify(&members, b"hello there").is_err());
+        }
+        let signature = keypair.combine(&partial_signatures).expect("signature");
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+
+        let mut wrong_type = partial_signatures[0].clone();
+        wrong_type.key_type = match wrong_type.key_type {
+            KeyType::Ed25519 => KeyType::EccCompact,
+            _ => KeyType::Ed25519,
+        };
+        assert!(wrong_type.verify(&members, MSG).is_err());
+        assert!(keypair.combine(&[wrong_type]).is_err());
+        assert!(PartialSignature::try_from(&[0u8, 1][..]).is_err());
+    }
+
+    #[test]
+    fn keypair_sign() {
+        let keys = gen_keys(3);
+        let members = public_keys(&keys);
+        const MSG: &[u8] = b"hello world";
+
+        let keypair = Keypair::from_keypairs(Network::MainNet, 2, multihash::Code::Sha2_256, keys)
+            .expect("multisig keypair");
+        assert_eq!(KeyType::MultiSig, keypair.key_tag().key_type);
+        let signature = keypair.sign(MSG).expect("signature");
+        assert!(keypair.public_key.verify(MSG, &signature).is_ok());
+
+        // The combined public key roundtrips through its binary form
+        let bytes = keypair.public_key.to_vec();
+        assert_eq!(
+            keypair.public_key,
+            public_key::PublicKey::try_from(&bytes[..]).expect("multisig pubkey")
+        );
+
+        // Only one of two required members can sign
+        let mut keys = gen_keys(1);
+        let member = keys.remove(0);
+        let mut public_keys = members;
+        public_keys.push(member.public_key().clone());
+        let keypair = Keypair::new(Network::MainNet, 2, multihash::Code::Sha2_256, &public_keys)
+            .expect("multisig keypair")
+            .with_signer(member.public_key().clone(), Box::new(member))
+            .expect("member");
+        assert!(keypair.sign(MSG).is_err());
+
+        let outsider = gen_keys(1).remove(0);
+        assert!(keypair
+            .with_signer(outsider.public_key().clone(), Box::new(outsider))
+            .is_err());
+    }
 }
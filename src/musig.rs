@@ -0,0 +1,676 @@
+//! MuSig2 multi-signatures for ecc_compact and secp256k1 keys.
+//!
+//! Implements [BIP327][BIP327] MuSig2, which lets the holders of a set of
+//! keys jointly produce a single 64 byte Schnorr signature under the
+//! aggregate public key of the set. The signature takes no more space than a
+//! signature by a single key, and reveals nothing about the signers.
+//!
+//! For secp256k1 keys, signatures are [BIP340][BIP340] Schnorr signatures
+//! which also verify with [`secp256k1::verify_schnorr`]. ecc_compact keys use
+//! the same equations over P-256, with the compact form of a point standing
+//! in for the even Y form BIP340 uses, so aggregate public keys are ordinary
+//! ecc_compact public keys. Either way, signatures verify with [`verify`].
+//!
+//! Signing takes two rounds, after all signers have built the same
+//! [`KeyAggContext`] from the public keys of the set in the same order:
+//!
+//! 1. Every signer makes fresh nonces with [`nonce_gen`] and shares the
+//!    public nonce, which [`aggregate_nonces`] combines into the aggregate
+//!    nonce.
+//! 2. Every signer makes a partial signature over the message with
+//!    [`partial_sign`], and [`aggregate`] combines the partial signatures
+//!    into the signature.
+//!
+//! Nonces must never be reused, so [`partial_sign`] consumes them.
+//!
+//! [BIP327]: https://github.com/bitcoin/bips/blob/master/bip-0327.mediawiki
+//! [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+use crate::{secp256k1::tagged_hash, *};
+use p256::elliptic_curve::{
+    bigint::U256,
+    ff::{Field, PrimeField},
+    group::{Curve, Group},
+    ops::Reduce,
+    sec1::{FromEncodedPoint, ToCompactEncodedPoint, ToEncodedPoint},
+    subtle::Choice,
+    DecompactPoint, DecompressPoint,
+};
+
+/// Public nonces and aggregate nonces are two SEC1 compressed points
+pub const PUBLIC_NONCE_LENGTH: usize = 66;
+pub const PARTIAL_SIGNATURE_LENGTH: usize = 32;
+/// Signatures are the x coordinate of the nonce point followed by the
+/// big endian scalar
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// The ordered set of public keys signing together
+#[derive(Debug, Clone)]
+pub struct KeyAggContext {
+    key_type: KeyType,
+    keys: Vec<[u8; 33]>,
+    public_key: PublicKey,
+}
+
+/// The secret nonces of one signer for one signing session
+pub struct SecretNonce {
+    k1: [u8; 32],
+    k2: [u8; 32],
+    public_key: [u8; 33],
+}
+
+impl KeyAggContext {
+    /// Aggregate the given public keys. The keys must all be ecc_compact or
+    /// all secp256k1 keys on the same network. Every signer has to use the
+    /// same order of keys, so sort them first if they have no natural order.
+    pub fn new(public_keys: &[PublicKey]) -> Result<Self> {
+        let key_tag = public_keys
+            .first()
+            .ok_or_else(Error::invalid_curve)?
+            .key_tag();
+        for public_key in public_keys {
+            if public_key.key_type() != key_tag.key_type {
+                return Err(Error::invalid_curve());
+            }
+            if public_key.key_tag().network != key_tag.network {
+                return Err(Error::invalid_network());
+            }
+        }
+        let (keys, public_key) = match key_tag.key_type {
+            KeyType::EccCompact => key_agg_context::<p256::NistP256>(key_tag.network, public_keys),
+            KeyType::Secp256k1 => key_agg_context::<k256::Secp256k1>(key_tag.network, public_keys),
+            _ => Err(Error::invalid_curve()),
+        }?;
+        Ok(Self {
+            key_type: key_tag.key_type,
+            keys,
+            public_key,
+        })
+    }
+
+    /// Returns the aggregate public key signatures verify against
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+}
+
+/// Round one: make fresh nonces for one signing session with the given
+/// keypair. Keep the secret nonce and share the public nonce.
+pub fn nonce_gen<R>(
+    keypair: &Keypair,
+    csprng: &mut R,
+) -> Result<(SecretNonce, [u8; PUBLIC_NONCE_LENGTH])>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    match keypair.key_tag().key_type {
+        KeyType::EccCompact => nonce_gen_with::<p256::NistP256, R>(keypair, csprng),
+        KeyType::Secp256k1 => nonce_gen_with::<k256::Secp256k1, R>(keypair, csprng),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+/// Combine the public nonces of all signers into the aggregate nonce
+pub fn aggregate_nonces(
+    context: &KeyAggContext,
+    public_nonces: &[[u8; PUBLIC_NONCE_LENGTH]],
+) -> Result<[u8; PUBLIC_NONCE_LENGTH]> {
+    match context.key_type {
+        KeyType::EccCompact => aggregate_nonces_with::<p256::NistP256>(public_nonces),
+        KeyType::Secp256k1 => aggregate_nonces_with::<k256::Secp256k1>(public_nonces),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+/// Round two: make the partial signature of the given keypair over the
+/// given message with the nonce made for this session in round one
+pub fn partial_sign(
+    context: &KeyAggContext,
+    keypair: &Keypair,
+    secret_nonce: SecretNonce,
+    aggregate_nonce: &[u8; PUBLIC_NONCE_LENGTH],
+    msg: &[u8],
+) -> Result<[u8; PARTIAL_SIGNATURE_LENGTH]> {
+    match context.key_type {
+        KeyType::EccCompact => partial_sign_with::<p256::NistP256>(
+            context,
+            keypair,
+            secret_nonce,
+            aggregate_nonce,
+            msg,
+        ),
+        KeyType::Secp256k1 => partial_sign_with::<k256::Secp256k1>(
+            context,
+            keypair,
+            secret_nonce,
+            aggregate_nonce,
+            msg,
+        ),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+/// Combine the partial signatures of all signers into the signature, which
+/// is verified against the aggregate public key
+pub fn aggregate(
+    context: &KeyAggContext,
+    aggregate_nonce: &[u8; PUBLIC_NONCE_LENGTH],
+    msg: &[u8],
+    partial_signatures: &[[u8; PARTIAL_SIGNATURE_LENGTH]],
+) -> Result<[u8; SIGNATURE_LENGTH]> {
+    let signature = match context.key_type {
+        KeyType::EccCompact => {
+            aggregate_with::<p256::NistP256>(context, aggregate_nonce, msg, partial_signatures)
+        }
+        KeyType::Secp256k1 => {
+            aggregate_with::<k256::Secp256k1>(context, aggregate_nonce, msg, partial_signatures)
+        }
+        _ => Err(Error::invalid_curve()),
+    }?;
+    verify(&context.public_key, msg, &signature)?;
+    Ok(signature)
+}
+
+/// Verify the given Schnorr signature over the given message against the
+/// given ecc_compact or secp256k1 public key
+pub fn verify(public_key: &PublicKey, msg: &[u8], signature: &[u8]) -> Result {
+    match public_key.key_type() {
+        KeyType::EccCompact => verify_with::<p256::NistP256>(public_key, msg, signature),
+        KeyType::Secp256k1 => verify_with::<k256::Secp256k1>(public_key, msg, signature),
+        _ => Err(Error::invalid_curve()),
+    }
+}
+
+/// The curve operations MuSig2 needs from the curves of the supported key
+/// types
+trait MuSigCurve {
+    type Scalar: PrimeField;
+    type Point: Group<Scalar = Self::Scalar>;
+
+    /// Returns the SEC1 compressed form of the given point
+    fn encode(point: &Self::Point) -> Result<[u8; 33]>;
+    fn decode(bytes: &[u8]) -> Result<Self::Point>;
+    /// Whether the given point, rather than its negation, is the one an x
+    /// coordinate stands for
+    fn is_canonical(point: &Self::Point) -> bool;
+    /// Returns the canonical point with the given x coordinate
+    fn lift_x(x: &[u8]) -> Result<Self::Point>;
+    fn reduce(digest: [u8; 32]) -> Self::Scalar;
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point>;
+    fn secret_scalar(keypair: &Keypair) -> Result<Self::Scalar>;
+    /// Returns the public key of the canonical form of the given point
+    fn to_public_key(network: Network, point: &Self::Point) -> Result<PublicKey>;
+}
+
+impl MuSigCurve for p256::NistP256 {
+    type Scalar = p256::Scalar;
+    type Point = p256::ProjectivePoint;
+
+    fn encode(point: &Self::Point) -> Result<[u8; 33]> {
+        let encoded = point.to_affine().to_encoded_point(true);
+        <[u8; 33]>::try_from(encoded.as_bytes()).map_err(|_| Error::invalid_curve())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self::Point> {
+        if bytes.len() != 33 {
+            return Err(Error::invalid_curve());
+        }
+        let encoded = p256::EncodedPoint::from_bytes(bytes).map_err(|_| Error::invalid_curve())?;
+        let point: Option<p256::AffinePoint> =
+            p256::AffinePoint::from_encoded_point(&encoded).into();
+        point
+            .map(Self::Point::from)
+            .ok_or_else(Error::invalid_curve)
+    }
+
+    fn is_canonical(point: &Self::Point) -> bool {
+        point
+            .to_affine()
+            .to_compact_encoded_point()
+            .is_some()
+            .into()
+    }
+
+    fn lift_x(x: &[u8]) -> Result<Self::Point> {
+        if x.len() != 32 {
+            return Err(Error::invalid_curve());
+        }
+        let point: Option<p256::AffinePoint> =
+            p256::AffinePoint::decompact(p256::FieldBytes::from_slice(x)).into();
+        point.map(Self::Point::from).ok_or_else(Error::not_compact)
+    }
+
+    fn reduce(digest: [u8; 32]) -> Self::Scalar {
+        <p256::Scalar as Reduce<U256>>::from_be_bytes_reduced(p256::FieldBytes::from(digest))
+    }
+
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point> {
+        let public_key: &ecc_compact::PublicKey = public_key.try_into()?;
+        Ok(public_key.0.to_projective())
+    }
+
+    fn secret_scalar(keypair: &Keypair) -> Result<Self::Scalar> {
+        match keypair {
+            Keypair::EccCompact(keypair) => scalar_from_bytes::<Self>(&keypair.secret_to_vec()),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    fn to_public_key(network: Network, point: &Self::Point) -> Result<PublicKey> {
+        let point = if Self::is_canonical(point) {
+            *point
+        } else {
+            -*point
+        };
+        let public_key = p256::PublicKey::from_affine(point.to_affine())?;
+        Ok(PublicKey::for_network(
+            network,
+            ecc_compact::PublicKey(public_key),
+        ))
+    }
+}
+
+impl MuSigCurve for k256::Secp256k1 {
+    type Scalar = k256::Scalar;
+    type Point = k256::ProjectivePoint;
+
+    fn encode(point: &Self::Point) -> Result<[u8; 33]> {
+        let encoded = point.to_affine().to_encoded_point(true);
+        <[u8; 33]>::try_from(encoded.as_bytes()).map_err(|_| Error::invalid_curve())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self::Point> {
+        if bytes.len() != 33 {
+            return Err(Error::invalid_curve());
+        }
+        let encoded = k256::EncodedPoint::from_bytes(bytes).map_err(|_| Error::invalid_curve())?;
+        let point: Option<k256::AffinePoint> =
+            k256::AffinePoint::from_encoded_point(&encoded).into();
+        point
+            .map(Self::Point::from)
+            .ok_or_else(Error::invalid_curve)
+    }
+
+    fn is_canonical(point: &Self::Point) -> bool {
+        point.to_affine().to_encoded_point(true).as_bytes()[0] == 0x02
+    }
+
+    fn lift_x(x: &[u8]) -> Result<Self::Point> {
+        if x.len() != 32 {
+            return Err(Error::invalid_curve());
+        }
+        let point: Option<k256::AffinePoint> =
+            k256::AffinePoint::decompress(k256::FieldBytes::from_slice(x), Choice::from(0)).into();
+        point
+            .map(Self::Point::from)
+            .ok_or_else(Error::invalid_curve)
+    }
+
+    fn reduce(digest: [u8; 32]) -> Self::Scalar {
+        <k256::Scalar as Reduce<U256>>::from_be_bytes_reduced(k256::FieldBytes::from(digest))
+    }
+
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point> {
+        let public_key: &secp256k1::PublicKey = public_key.try_into()?;
+        Ok(public_key.0.to_projective())
+    }
+
+    fn secret_scalar(keypair: &Keypair) -> Result<Self::Scalar> {
+        match keypair {
+            Keypair::Secp256k1(keypair) => scalar_from_bytes::<Self>(&keypair.secret_to_vec()),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    fn to_public_key(network: Network, point: &Self::Point) -> Result<PublicKey> {
+        let point = if Self::is_canonical(point) {
+            *point
+        } else {
+            -*point
+        };
+        let public_key = k256::PublicKey::from_affine(point.to_affine())?;
+        Ok(PublicKey::for_network(
+            network,
+            secp256k1::PublicKey(public_key),
+        ))
+    }
+}
+
+fn scalar_from_bytes<C: MuSigCurve>(bytes: &[u8]) -> Result<C::Scalar> {
+    let mut repr = <C::Scalar as PrimeField>::Repr::default();
+    if bytes.len() != repr.as_ref().len() {
+        return Err(signature::Error::new().into());
+    }
+    repr.as_mut().copy_from_slice(bytes);
+    Option::from(C::Scalar::from_repr(repr)).ok_or_else(|| signature::Error::new().into())
+}
+
+fn scalar_to_bytes<C: MuSigCurve>(scalar: &C::Scalar) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    result.copy_from_slice(scalar.to_repr().as_ref());
+    result
+}
+
+fn x_bytes<C: MuSigCurve>(point: &C::Point) -> Result<[u8; PARTIAL_SIGNATURE_LENGTH]> {
+    let mut result = [0u8; 32];
+    result.copy_from_slice(&C::encode(point)?[1..]);
+    Ok(result)
+}
+
+/// Encodes the identity, which aggregate nonces can be, as all zeros
+fn encode_ext<C: MuSigCurve>(point: &C::Point) -> Result<[u8; 33]> {
+    if point.is_identity().into() {
+        return Ok([0u8; 33]);
+    }
+    C::encode(point)
+}
+
+fn decode_ext<C: MuSigCurve>(bytes: &[u8]) -> Result<C::Point> {
+    if bytes.iter().all(|byte| *byte == 0) {
+        return Ok(C::Point::identity());
+    }
+    C::decode(bytes)
+}
+
+fn challenge<C: MuSigCurve>(r: &[u8], q: &[u8], msg: &[u8]) -> C::Scalar {
+    C::reduce(tagged_hash(b"BIP0340/challenge", &[r, q, msg].concat()))
+}
+
+/// Returns the aggregate point of the given SEC1 compressed keys along with
+/// the coefficient of every key
+fn key_agg<C: MuSigCurve>(keys: &[[u8; 33]]) -> Result<(C::Point, Vec<C::Scalar>)> {
+    let list = tagged_hash(b"KeyAgg list", &keys.concat());
+    // The coefficient of the second distinct key is one, which saves a
+    // multiplication for the common two key case
+    let second = keys.iter().find(|key| *key != &keys[0]);
+    let coefficients: Vec<C::Scalar> = keys
+        .iter()
+        .map(|key| {
+            if Some(key) == second {
+                C::Scalar::one()
+            } else {
+                C::reduce(tagged_hash(
+                    b"KeyAgg coefficient",
+                    &[&list[..], &key[..]].concat(),
+                ))
+            }
+        })
+        .collect();
+    let mut q = C::Point::identity();
+    for (key, coefficient) in keys.iter().zip(&coefficients) {
+        q += C::decode(key)? * *coefficient;
+    }
+    if q.is_identity().into() {
+        return Err(Error::invalid_curve());
+    }
+    Ok((q, coefficients))
+}
+
+fn key_agg_context<C: MuSigCurve>(
+    network: Network,
+    public_keys: &[PublicKey],
+) -> Result<(Vec<[u8; 33]>, PublicKey)> {
+    let keys = public_keys
+        .iter()
+        .map(|public_key| C::encode(&C::public_key_point(public_key)?))
+        .collect::<Result<Vec<_>>>()?;
+    let (q, _) = key_agg::<C>(&keys)?;
+    Ok((keys, C::to_public_key(network, &q)?))
+}
+
+/// The values shared by all signers of one message
+struct Session<C: MuSigCurve> {
+    q: C::Point,
+    coefficients: Vec<C::Scalar>,
+    b: C::Scalar,
+    r: C::Point,
+    e: C::Scalar,
+}
+
+impl<C: MuSigCurve> Session<C> {
+    fn new(
+        keys: &[[u8; 33]],
+        aggregate_nonce: &[u8; PUBLIC_NONCE_LENGTH],
+        msg: &[u8],
+    ) -> Result<Self> {
+        let (q, coefficients) = key_agg::<C>(keys)?;
+        let q_x = x_bytes::<C>(&q)?;
+        let b = C::reduce(tagged_hash(
+            b"MuSig/noncecoef",
+            &[&aggregate_nonce[..], &q_x[..], msg].concat(),
+        ));
+        let r =
+            decode_ext::<C>(&aggregate_nonce[..33])? + decode_ext::<C>(&aggregate_nonce[33..])? * b;
+        let r = if r.is_identity().into() {
+            C::Point::generator()
+        } else {
+            r
+        };
+        let e = challenge::<C>(&x_bytes::<C>(&r)?, &q_x, msg);
+        Ok(Self {
+            q,
+            coefficients,
+            b,
+            r,
+            e,
+        })
+    }
+}
+
+fn nonce_gen_with<C: MuSigCurve, R>(
+    keypair: &Keypair,
+    csprng: &mut R,
+) -> Result<(SecretNonce, [u8; PUBLIC_NONCE_LENGTH])>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let secret = C::secret_scalar(keypair)?;
+    let public_key = C::encode(&(C::Point::generator() * secret))?;
+    // Mix the secret into the fresh randomness so a weak random number
+    // generator alone does not reveal the nonces
+    let mut rand = [0u8; 32];
+    csprng.fill_bytes(&mut rand);
+    let nonce = |i: u8| {
+        C::reduce(tagged_hash(
+            b"MuSig/nonce",
+            &[
+                &rand[..],
+                secret.to_repr().as_ref(),
+                &public_key[..],
+                &[i][..],
+            ]
+            .concat(),
+        ))
+    };
+    let (k1, k2) = (nonce(0), nonce(1));
+    let mut public_nonce = [0u8; 66];
+    public_nonce[..33].copy_from_slice(&C::encode(&(C::Point::generator() * k1))?);
+    public_nonce[33..].copy_from_slice(&C::encode(&(C::Point::generator() * k2))?);
+    Ok((
+        SecretNonce {
+            k1: scalar_to_bytes::<C>(&k1),
+            k2: scalar_to_bytes::<C>(&k2),
+            public_key,
+        },
+        public_nonce,
+    ))
+}
+
+fn aggregate_nonces_with<C: MuSigCurve>(
+    public_nonces: &[[u8; PUBLIC_NONCE_LENGTH]],
+) -> Result<[u8; PUBLIC_NONCE_LENGTH]> {
+    let mut r1 = C::Point::identity();
+    let mut r2 = C::Point::identity();
+    for public_nonce in public_nonces {
+        r1 += C::decode(&public_nonce[..33])?;
+        r2 += C::decode(&public_nonce[33..])?;
+    }
+    let mut result = [0u8; 66];
+    result[..33].copy_from_slice(&encode_ext::<C>(&r1)?);
+    result[33..].copy_from_slice(&encode_ext::<C>(&r2)?);
+    Ok(result)
+}
+
+fn partial_sign_with<C: MuSigCurve>(
+    context: &KeyAggContext,
+    keypair: &Keypair,
+    secret_nonce: SecretNonce,
+    aggregate_nonce: &[u8; PUBLIC_NONCE_LENGTH],
+    msg: &[u8],
+) -> Result<[u8; PARTIAL_SIGNATURE_LENGTH]> {
+    let secret = C::secret_scalar(keypair)?;
+    let public_key = C::encode(&(C::Point::generator() * secret))?;
+    if public_key != secret_nonce.public_key {
+        return Err(signature::Error::new().into());
+    }
+    let index = context
+        .keys
+        .iter()
+        .position(|key| key == &public_key)
+        .ok_or_else(signature::Error::new)?;
+    let session = Session::<C>::new(&context.keys, aggregate_nonce, msg)?;
+    let mut k1 = scalar_from_bytes::<C>(&secret_nonce.k1)?;
+    let mut k2 = scalar_from_bytes::<C>(&secret_nonce.k2)?;
+    if !C::is_canonical(&session.r) {
+        k1 = -k1;
+        k2 = -k2;
+    }
+    let d = if C::is_canonical(&session.q) {
+        secret
+    } else {
+        -secret
+    };
+    let s = k1 + session.b * k2 + session.e * session.coefficients[index] * d;
+    Ok(scalar_to_bytes::<C>(&s))
+}
+
+fn aggregate_with<C: MuSigCurve>(
+    context: &KeyAggContext,
+    aggregate_nonce: &[u8; PUBLIC_NONCE_LENGTH],
+    msg: &[u8],
+    partial_signatures: &[[u8; PARTIAL_SIGNATURE_LENGTH]],
+) -> Result<[u8; SIGNATURE_LENGTH]> {
+    let session = Session::<C>::new(&context.keys, aggregate_nonce, msg)?;
+    let mut s = C::Scalar::zero();
+    for partial_signature in partial_signatures {
+        s += scalar_from_bytes::<C>(partial_signature)?;
+    }
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&x_bytes::<C>(&session.r)?);
+    signature[32..].copy_from_slice(&scalar_to_bytes::<C>(&s));
+    Ok(signature)
+}
+
+fn verify_with<C: MuSigCurve>(public_key: &PublicKey, msg: &[u8], signature: &[u8]) -> Result {
+    if signature.len() != SIGNATURE_LENGTH {
+        return Err(signature::Error::new().into());
+    }
+    let q_x = x_bytes::<C>(&C::public_key_point(public_key)?)?;
+    let q = C::lift_x(&q_x)?;
+    let s = scalar_from_bytes::<C>(&signature[32..])?;
+    let e = challenge::<C>(&signature[..32], &q_x, msg);
+    let r = C::Point::generator() * s - q * e;
+    if r.is_identity().into() || !C::is_canonical(&r) || x_bytes::<C>(&r)?[..] != signature[..32] {
+        return Err(signature::Error::new().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn sign_with(
+        keypairs: &[Keypair],
+        msg: &[u8],
+    ) -> Result<(KeyAggContext, [u8; SIGNATURE_LENGTH])> {
+        let public_keys: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect();
+        let context = KeyAggContext::new(&public_keys)?;
+        let (secret_nonces, public_nonces): (Vec<_>, Vec<_>) = keypairs
+            .iter()
+            .map(|keypair| nonce_gen(keypair, &mut OsRng))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let aggregate_nonce = aggregate_nonces(&context, &public_nonces)?;
+        let partial_signatures = keypairs
+            .iter()
+            .zip(secret_nonces)
+            .map(|(keypair, secret_nonce)| {
+                partial_sign(&context, keypair, secret_nonce, &aggregate_nonce, msg)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let signature = aggregate(&context, &aggregate_nonce, msg, &partial_signatures)?;
+        Ok((context, signature))
+    }
+
+    fn keypairs(key_type: KeyType, count: usize) -> Vec<Keypair> {
+        let key_tag = KeyTag {
+            network: Network::MainNet,
+            key_type,
+        };
+        (0..count)
+            .map(|_| Keypair::generate(key_tag, &mut OsRng))
+            .collect()
+    }
+
+    #[test]
+    fn ecc_compact() {
+        let keypairs = keypairs(KeyType::EccCompact, 3);
+        let (context, signature) = sign_with(&keypairs, b"hello world").expect("signature");
+        assert_eq!(KeyType::EccCompact, context.public_key().key_type());
+        assert!(verify(context.public_key(), b"hello world", &signature).is_ok());
+        assert!(verify(context.public_key(), b"hello there", &signature).is_err());
+        assert!(verify(keypairs[0].public_key(), b"hello world", &signature).is_err());
+    }
+
+    #[test]
+    fn secp256k1_bip340() {
+        let keypairs = keypairs(KeyType::Secp256k1, 2);
+        let msg = tagged_hash(b"helium/test", b"hello world");
+        let (context, signature) = sign_with(&keypairs, &msg).expect("signature");
+        let public_key: &secp256k1::PublicKey =
+            context.public_key().try_into().expect("secp256k1 key");
+        assert!(public_key.verify_schnorr(&msg, &signature).is_ok());
+        assert!(verify(context.public_key(), &msg, &signature).is_ok());
+    }
+
+    #[test]
+    fn mixed_keys() {
+        let mut keypairs = keypairs(KeyType::EccCompact, 1);
+        keypairs.extend(self::keypairs(KeyType::Secp256k1, 1));
+        assert!(sign_with(&keypairs, b"hello world").is_err());
+    }
+
+    #[test]
+    fn missing_partial_signature() {
+        let keypairs = keypairs(KeyType::EccCompact, 2);
+        let public_keys: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect();
+        let context = KeyAggContext::new(&public_keys).expect("context");
+        let (secret_nonce, public_nonce) = nonce_gen(&keypairs[0], &mut OsRng).expect("nonce");
+        let (_, other_nonce) = nonce_gen(&keypairs[1], &mut OsRng).expect("nonce");
+        let aggregate_nonce =
+            aggregate_nonces(&context, &[public_nonce, other_nonce]).expect("aggregate nonce");
+        let partial_signature = partial_sign(
+            &context,
+            &keypairs[0],
+            secret_nonce,
+            &aggregate_nonce,
+            b"hello world",
+        )
+        .expect("partial signature");
+        assert!(aggregate(
+            &context,
+            &aggregate_nonce,
+            b"hello world",
+            &[partial_signature]
+        )
+        .is_err());
+    }
+}
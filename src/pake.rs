@@ -0,0 +1,360 @@
+//! Password authenticated key exchange from a short pairing code.
+//!
+//! This implements SPAKE2 over the ed25519 group, following RFC 9382, so two
+//! parties who share a short pairing code, like a hotspot and an operator app
+//! during onboarding, can establish an authenticated shared key over an
+//! untrusted channel. An attacker gets a single guess at the pairing code per
+//! protocol run and learns nothing that lets them test guesses offline.
+//!
+//! 1. Both sides [`Spake2::start`] with their [`Role`], the pairing code and
+//!    the identities of both parties, and exchange their
+//!    [`Spake2::message`]s.
+//! 2. Both sides [`Spake2::finish`] with the message of the other side to get
+//!    a [`Session`], then exchange and check their
+//!    [`Session::confirmation`]s. Only once the confirmation of the other
+//!    side checks out was the same pairing code used on both sides.
+//! 3. The device proves its [`PublicKey`] with [`Session::prove_identity`],
+//!    which the other side checks with [`Session::verify_identity`]. The
+//!    proof is bound to the session so it can not be replayed into another.
+//!
+//! This is the RFC 9382 `SPAKE2-edwards25519-SHA256-HKDF-HMAC-SHA256` suite
+//! with its `M` and `N` constants, transcript and key schedule, and no
+//! additional authenticated data. RFC 9382 leaves deriving the password
+//! scalar `w` to the application. Here it is the SHA-512 hash of a domain
+//! separator and the length prefixed identities and pairing code, reduced
+//! modulo the group order, so another implementation interoperates when it
+//! derives `w` the same way.
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, random_scalar},
+    *,
+};
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar, traits::IsIdentity};
+use hkdf::{
+    hmac::{Hmac, Mac},
+    Hkdf,
+};
+use sha2::{Digest, Sha256};
+
+/// The compressed RFC 9382 edwards25519 point `M` used by the initiator
+const SPAKE2_M: [u8; 32] = [
+    0xd0, 0x48, 0x03, 0x2c, 0x6e, 0xa0, 0xb6, 0xd6, 0x97, 0xdd, 0xc2, 0xe8, 0x6b, 0xda, 0x85, 0xa3,
+    0x3a, 0xda, 0xc9, 0x20, 0xf1, 0xbf, 0x18, 0xe1, 0xb0, 0xc6, 0xd1, 0x66, 0xa5, 0xce, 0xcd, 0xaf,
+];
+/// The compressed RFC 9382 edwards25519 point `N` used by the responder
+const SPAKE2_N: [u8; 32] = [
+    0xd3, 0xbf, 0xb5, 0x18, 0xf4, 0x4f, 0x34, 0x30, 0xf2, 0x9d, 0x0c, 0x92, 0xaf, 0x50, 0x38, 0x65,
+    0xa1, 0xed, 0x32, 0x81, 0xdc, 0x69, 0xb3, 0x5d, 0xd8, 0x68, 0xba, 0x85, 0xf8, 0x86, 0xc4, 0xab,
+];
+/// Domain separator for deriving the password scalar `w`
+const PAKE_PASSWORD_DOMAIN: &[u8] = b"helium-crypto-spake2-password";
+/// Domain separator prefixed to the signed bytes of an identity proof
+const PAKE_IDENTITY_DOMAIN: &[u8] = b"helium-crypto-spake2-identity";
+
+/// The side of the exchange a party is on. The two parties must take
+/// different roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The party starting the exchange, like an operator app
+    Initiator,
+    /// The party responding to the exchange, like a hotspot
+    Responder,
+}
+
+impl Role {
+    fn peer(self) -> Self {
+        match self {
+            Self::Initiator => Self::Responder,
+            Self::Responder => Self::Initiator,
+        }
+    }
+
+    /// The point this role blinds its message with
+    fn blinding_point(self) -> EdwardsPoint {
+        let point = match self {
+            Self::Initiator => &SPAKE2_M,
+            Self::Responder => &SPAKE2_N,
+        };
+        // Unwrap ok here since both constants are valid curve points
+        decompress(point).unwrap()
+    }
+}
+
+/// One side of a SPAKE2 exchange in progress
+pub struct Spake2 {
+    role: Role,
+    password: Scalar,
+    secret: Scalar,
+    message: [u8; 32],
+    initiator_id: Vec<u8>,
+    responder_id: Vec<u8>,
+}
+
+impl std::fmt::Debug for Spake2 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Spake2")
+            .field("role", &self.role)
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
+impl Spake2 {
+    /// Start an exchange in the given role. Both sides must use the same
+    /// pairing code and identities for the exchange to succeed.
+    pub fn start<R>(
+        role: Role,
+        pairing_code: &[u8],
+        initiator_id: &[u8],
+        responder_id: &[u8],
+        csprng: &mut R,
+    ) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut password_input = vec![];
+        append_prefixed(&mut password_input, initiator_id);
+        append_prefixed(&mut password_input, responder_id);
+        append_prefixed(&mut password_input, pairing_code);
+        let password = hash_to_scalar(&[PAKE_PASSWORD_DOMAIN, &password_input]);
+        let secret = random_scalar(csprng);
+        let message = (base_mul(&secret) + password * role.blinding_point())
+            .compress()
+            .to_bytes();
+        Self {
+            role,
+            password,
+            secret,
+            message,
+            initiator_id: initiator_id.to_vec(),
+            responder_id: responder_id.to_vec(),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// The message to send to the other side
+    pub fn message(&self) -> [u8; 32] {
+        self.message
+    }
+
+    /// Complete the exchange with the message received from the other side
+    pub fn finish(self, peer_message: &[u8]) -> Result<Session> {
+        let peer_role = self.role.peer();
+        let peer_point = decompress(peer_message)?;
+        let shared = (self.secret * (peer_point - self.password * peer_role.blinding_point()))
+            .mul_by_cofactor();
+        if shared.is_identity() {
+            return Err(Error::invalid_curve());
+        }
+        let (initiator_message, responder_message) = match self.role {
+            Role::Initiator => (&self.message[..], peer_message),
+            Role::Responder => (peer_message, &self.message[..]),
+        };
+        let mut transcript = vec![];
+        append_prefixed(&mut transcript, &self.initiator_id);
+        append_prefixed(&mut transcript, &self.responder_id);
+        append_prefixed(&mut transcript, initiator_message);
+        append_prefixed(&mut transcript, responder_message);
+        append_prefixed(&mut transcript, shared.compress().as_bytes());
+        // RFC 9382 encodes w as a big endian number
+        let mut password = self.password.to_bytes();
+        password.reverse();
+        append_prefixed(&mut transcript, &password);
+
+        let hash = Sha256::digest(&transcript);
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&hash[..16]);
+        let mut confirmation_keys = [0u8; 32];
+        Hkdf::<Sha256>::new(None, &hash[16..])
+            .expand(b"ConfirmationKeys", &mut confirmation_keys)
+            .map_err(|_| Error::encryption())?;
+        let mut initiator_key = [0u8; 16];
+        let mut responder_key = [0u8; 16];
+        initiator_key.copy_from_slice(&confirmation_keys[..16]);
+        responder_key.copy_from_slice(&confirmation_keys[16..]);
+        let (confirmation_key, peer_confirmation_key) = match self.role {
+            Role::Initiator => (initiator_key, responder_key),
+            Role::Responder => (responder_key, initiator_key),
+        };
+        Ok(Session {
+            role: self.role,
+            key,
+            confirmation_key,
+            peer_confirmation_key,
+            transcript,
+        })
+    }
+}
+
+/// An established, but not yet confirmed, SPAKE2 session
+pub struct Session {
+    role: Role,
+    key: [u8; 16],
+    confirmation_key: [u8; 16],
+    peer_confirmation_key: [u8; 16],
+    transcript: Vec<u8>,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Session").field("role", &self.role).finish()
+    }
+}
+
+/// A proof that a party holds the keypair of a public key, bound to a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityProof {
+    pub public_key: PublicKey,
+    pub signature: Vec<u8>,
+}
+
+impl Session {
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// The shared key. Only use it once the confirmation of the other side
+    /// has been verified.
+    pub fn key(&self) -> &[u8; 16] {
+        &self.key
+    }
+
+    /// The confirmation to send to the other side
+    pub fn confirmation(&self) -> Vec<u8> {
+        confirmation_mac(&self.confirmation_key, &self.transcript)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    /// Verify the confirmation received from the other side. This fails if
+    /// the two sides used different pairing codes or identities.
+    pub fn verify_confirmation(&self, confirmation: &[u8]) -> Result {
+        confirmation_mac(&self.peer_confirmation_key, &self.transcript)
+            .verify_slice(confirmation)
+            .map_err(|_| signature::Error::new().into())
+    }
+
+    /// Prove ownership of the given keypair to the other side of the session
+    pub fn prove_identity(&self, keypair: &Keypair) -> Result<IdentityProof> {
+        let signature = keypair.sign(&self.identity_bytes(self.role))?;
+        Ok(IdentityProof {
+            public_key: keypair.public_key().clone(),
+            signature,
+        })
+    }
+
+    /// Verify an identity proof made by the other side of the session
+    pub fn verify_identity(&self, proof: &IdentityProof) -> Result {
+        proof
+            .public_key
+            .verify(&self.identity_bytes(self.role.peer()), &proof.signature)
+    }
+
+    /// The bytes signed by the given role to prove its identity
+    fn identity_bytes(&self, role: Role) -> Vec<u8> {
+        let mut result = PAKE_IDENTITY_DOMAIN.to_vec();
+        result.push(match role {
+            Role::Initiator => 0,
+            Role::Responder => 1,
+        });
+        result.extend_from_slice(&Sha256::digest(&self.transcript));
+        result
+    }
+}
+
+fn confirmation_mac(key: &[u8; 16], transcript: &[u8]) -> Hmac<Sha256> {
+    // Unwrap ok here since HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(transcript);
+    mac
+}
+
+/// Appends the given part prefixed with its length as a little endian u64, as
+/// RFC 9382 does for its transcript
+fn append_prefixed(output: &mut Vec<u8>, part: &[u8]) {
+    output.extend_from_slice(&(part.len() as u64).to_le_bytes());
+    output.extend_from_slice(part);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn exchange(initiator_code: &[u8], responder_code: &[u8]) -> (Session, Session) {
+        let initiator = Spake2::start(
+            Role::Initiator,
+            initiator_code,
+            b"operator",
+            b"hotspot",
+            &mut OsRng,
+        );
+        let responder = Spake2::start(
+            Role::Responder,
+            responder_code,
+            b"operator",
+            b"hotspot",
+            &mut OsRng,
+        );
+        let initiator_message = initiator.message();
+        let responder_message = responder.message();
+        (
+            initiator.finish(&responder_message).expect("initiator"),
+            responder.finish(&initiator_message).expect("responder"),
+        )
+    }
+
+    #[test]
+    fn pairing() {
+        let (initiator, responder) = exchange(b"123456", b"123456");
+        assert!(responder
+            .verify_confirmation(&initiator.confirmation())
+            .is_ok());
+        assert!(initiator
+            .verify_confirmation(&responder.confirmation())
+            .is_ok());
+        assert_eq!(initiator.key(), responder.key());
+        // A confirmation can not be reflected back to its sender
+        assert!(initiator
+            .verify_confirmation(&initiator.confirmation())
+            .is_err());
+
+        let device = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let proof = responder.prove_identity(&device).expect("proof");
+        assert!(initiator.verify_identity(&proof).is_ok());
+        assert!(responder.verify_identity(&proof).is_err());
+    }
+
+    #[test]
+    fn blinding_points() {
+        for role in [Role::Initiator, Role::Responder] {
+            let point = role.blinding_point();
+            assert!(point.is_torsion_free());
+            assert!(!point.is_identity());
+        }
+    }
+
+    #[test]
+    fn wrong_code() {
+        let (initiator, responder) = exchange(b"123456", b"123457");
+        assert!(responder
+            .verify_confirmation(&initiator.confirmation())
+            .is_err());
+        assert!(initiator
+            .verify_confirmation(&responder.confirmation())
+            .is_err());
+        assert_ne!(initiator.key(), responder.key());
+    }
+
+    #[test]
+    fn proof_bound_to_session() {
+        let (initiator, _) = exchange(b"123456", b"123456");
+        let (_, other_responder) = exchange(b"123456", b"123456");
+        let device = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let proof = other_responder.prove_identity(&device).expect("proof");
+        assert!(initiator.verify_identity(&proof).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Passphrase encrypted containers shared by encrypted keypairs and key
+//! bundles.
+//!
+//! A container is encrypted with AES-256-GCM under a key derived from a
+//! passphrase with Argon2id. Its binary form is:
+//!
+//! ```text
+//! magic || version (u8) || context || m_cost (u32) || t_cost (u32) ||
+//!     p_cost (u32) || salt (16) || nonce (12) || ciphertext
+//! ```
+//!
+//! The fixed length context is in the clear, like the key tag of an encrypted
+//! keypair. The whole header is authenticated along with the ciphertext, so
+//! any tampering with the container, including its KDF parameters, makes it
+//! fail to decrypt.
+//!
+//! The KDF parameters are read before the header can be authenticated, so
+//! each is limited to [`MAX_COST_FACTOR`] times its default to keep a crafted
+//! container from exhausting memory or time before decryption fails.
+use crate::*;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Version};
+use std::io::{self, Read};
+
+/// How many times its default value each KDF parameter may be at most
+pub const MAX_COST_FACTOR: u32 = 4;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+/// The Argon2id cost parameters used to derive the encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory size in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The second recommended option of RFC 9106: 64 MiB of memory, 3
+    /// iterations and 4 lanes
+    fn default() -> Self {
+        Self {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 4,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Whether every parameter is at most [`MAX_COST_FACTOR`] times its
+    /// default
+    pub fn is_bounded(&self) -> bool {
+        let max = Self::default();
+        self.m_cost <= max.m_cost * MAX_COST_FACTOR
+            && self.t_cost <= max.t_cost * MAX_COST_FACTOR
+            && self.p_cost <= max.p_cost * MAX_COST_FACTOR
+    }
+}
+
+/// The format of a kind of container
+pub(crate) struct Container {
+    pub magic: &'static [u8],
+    pub version: u8,
+    /// The length of the cleartext context after the version
+    pub context_length: usize,
+    /// What the container holds, for error messages
+    pub name: &'static str,
+}
+
+impl Container {
+    /// Encrypt the given plaintext with the given passphrase, with the given
+    /// context in the clear
+    pub fn seal<R>(
+        &self,
+        context: &[u8],
+        params: KdfParams,
+        passphrase: &[u8],
+        plaintext: &[u8],
+        csprng: &mut R,
+    ) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        // Containers sealed with unbounded parameters could not be opened
+        if !params.is_bounded() || context.len() != self.context_length {
+            return Err(Error::encryption());
+        }
+        let mut salt = [0u8; SALT_LENGTH];
+        csprng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LENGTH];
+        csprng.fill_bytes(&mut nonce);
+        let mut result = self.magic.to_vec();
+        result.push(self.version);
+        result.extend_from_slice(context);
+        result.extend_from_slice(&params.m_cost.to_be_bytes());
+        result.extend_from_slice(&params.t_cost.to_be_bytes());
+        result.extend_from_slice(&params.p_cost.to_be_bytes());
+        result.extend_from_slice(&salt);
+        result.extend_from_slice(&nonce);
+
+        let ciphertext = cipher(passphrase, &salt, params)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &result,
+                },
+            )
+            .map_err(|_| Error::encryption())?;
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
+    }
+
+    /// Decrypt the given container with the given passphrase, returning its
+    /// context and plaintext. Fails if the passphrase is wrong or the
+    /// container was tampered with.
+    pub fn open<'a>(&self, bytes: &'a [u8], passphrase: &[u8]) -> Result<(&'a [u8], Vec<u8>)> {
+        let mut input = io::Cursor::new(bytes);
+        let (context, params) = self.read_header(&mut input)?;
+        let mut salt = [0u8; SALT_LENGTH];
+        input.read_exact(&mut salt)?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        input.read_exact(&mut nonce)?;
+
+        let (header, ciphertext) = bytes.split_at(input.position() as usize);
+        let plaintext = cipher(passphrase, &salt, params)?
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| Error::encryption())?;
+        Ok((context, plaintext))
+    }
+
+    /// Returns the context of the given container without decrypting it
+    pub fn context<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8]> {
+        self.read_header(&mut io::Cursor::new(bytes))
+            .map(|(context, _)| context)
+    }
+
+    fn read_header<'a>(&self, input: &mut io::Cursor<&'a [u8]>) -> Result<(&'a [u8], KdfParams)> {
+        let mut magic = vec![0u8; self.magic.len()];
+        input.read_exact(&mut magic)?;
+        if magic != self.magic {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} magic not found", self.name),
+            )
+            .into());
+        }
+        let mut version = [0u8; 1];
+        input.read_exact(&mut version)?;
+        if version[0] != self.version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported {} version {}", self.name, version[0]),
+            )
+            .into());
+        }
+        let start = input.position() as usize;
+        let bytes: &'a [u8] = *input.get_ref();
+        let context = bytes
+            .get(start..start + self.context_length)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        input.set_position((start + self.context_length) as u64);
+        let mut read_u32 = || -> Result<u32> {
+            let mut buf = [0u8; 4];
+            input.read_exact(&mut buf)?;
+            Ok(u32::from_be_bytes(buf))
+        };
+        let params = KdfParams {
+            m_cost: read_u32()?,
+            t_cost: read_u32()?,
+            p_cost: read_u32()?,
+        };
+        if !params.is_bounded() {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidData, "KDF parameters too large").into(),
+            );
+        }
+        Ok((context, params))
+    }
+}
+
+fn cipher(passphrase: &[u8], salt: &[u8], params: KdfParams) -> Result<Aes256Gcm> {
+    let params = argon2::Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|_| Error::encryption())?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|_| Error::encryption())?;
+    Aes256Gcm::new_from_slice(&key).map_err(|_| Error::encryption())
+}
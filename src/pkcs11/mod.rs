@@ -0,0 +1,268 @@
+//! Keypairs backed by a key in a PKCS#11 token, like a network HSM.
+//!
+//! The private key never leaves the token. Messages are hashed with SHA-256
+//! in software and the digest is signed by the token with `CKM_ECDSA`, and
+//! ECDH is done by the token with `CKM_ECDH1_DERIVE`. Like ECC608 and TPM
+//! keys, the key must be a P-256 key whose public key is compactable, and the
+//! keypair is an ecc_compact keypair.
+//!
+//! A PKCS#11 module can only be initialized once per process, so modules are
+//! loaded once and shared by all keypairs opened from them. Every keypair has
+//! its own logged in session.
+use crate::{
+    deadline,
+    ecc_compact::{self, Signature},
+    keypair, public_key, telemetry, KeyTag, KeyType as CrateKeyType, Network, Result,
+};
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::{
+        elliptic_curve::{EcKdf, Ecdh1DeriveParams},
+        Mechanism,
+    },
+    object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    slot::Slot,
+    types::AuthPin,
+};
+use lazy_static::lazy_static;
+use p256::ecdsa;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("pkcs11 error")]
+    Cryptoki(#[from] cryptoki::error::Error),
+
+    #[error("no {0} key labeled {1}")]
+    KeyNotFound(&'static str, String),
+
+    #[error("invalid ec point")]
+    InvalidPoint,
+}
+
+lazy_static! {
+    /// The loaded and initialized PKCS#11 modules by path
+    static ref MODULES: Mutex<HashMap<PathBuf, Pkcs11>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the module at the given path, loading and initializing it if this
+/// is the first use of the module
+fn module(path: &Path) -> Result<Pkcs11> {
+    let mut modules = MODULES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(module) = modules.get(path) {
+        return Ok(module.clone());
+    }
+    let module = Pkcs11::new(path).map_err(Error::from)?;
+    module
+        .initialize(CInitializeArgs::OsThreads)
+        .map_err(Error::from)?;
+    modules.insert(path.to_path_buf(), module.clone());
+    Ok(module)
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    label: String,
+    session: Arc<Mutex<Session>>,
+    private_key: ObjectHandle,
+    timeout: Option<Duration>,
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("label", &self.label)
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature = self.sign_digest(Sha256::new().chain_update(msg))?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl Keypair {
+    /// Opens the key pair with the given label in the token in the given
+    /// slot, logging in to the token with the given user PIN. The PKCS#11
+    /// module at the given path is loaded on first use.
+    pub fn open<P: AsRef<Path>>(
+        network: Network,
+        module_path: P,
+        slot: u64,
+        pin: &str,
+        label: &str,
+    ) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: CrateKeyType::EccCompact,
+        };
+        telemetry::span("init", Some(key_tag), "pkcs11").in_scope(|| {
+            let module = module(module_path.as_ref())?;
+            let slot = Slot::try_from(slot).map_err(Error::from)?;
+            let session = module.open_rw_session(slot).map_err(Error::from)?;
+            session
+                .login(UserType::User, Some(&AuthPin::new(pin.to_string())))
+                .map_err(Error::from)?;
+            let private_key = find_key(&session, ObjectClass::PRIVATE_KEY, label)?;
+            let public_key = find_key(&session, ObjectClass::PUBLIC_KEY, label)?;
+            let point = match session
+                .get_attributes(public_key, &[AttributeType::EcPoint])
+                .map_err(Error::from)?
+                .pop()
+            {
+                Some(Attribute::EcPoint(point)) => point,
+                _ => return Err(Error::InvalidPoint.into()),
+            };
+            let public_key = ecc_compact::PublicKey::try_from(ec_point(&point)?)?;
+            Ok(Keypair {
+                network,
+                public_key: public_key::PublicKey::for_network(network, public_key),
+                label: label.to_string(),
+                session: Arc::new(Mutex::new(session)),
+                private_key,
+                timeout: None,
+            })
+        })
+    }
+
+    /// Set the timeout for sign and ecdh operations on this keypair. An
+    /// operation which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: CrateKeyType::EccCompact,
+        }
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        let session = self.session.clone();
+        let private_key = self.private_key;
+        let bytes = deadline::run(self.timeout, move || {
+            Ok(lock(&session)
+                .sign(&Mechanism::Ecdsa, private_key, &digest.finalize())
+                .map_err(Error::from)?)
+        })?;
+        let signature = ecdsa::Signature::try_from(&bytes[..])?;
+        Ok(Signature(signature))
+    }
+
+    pub fn ecdh<'a, C>(&self, public_key: C) -> Result<ecc_compact::SharedSecret>
+    where
+        C: TryInto<&'a ecc_compact::PublicKey, Error = crate::Error>,
+    {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let key = public_key.try_into()?;
+        let point = key.0.to_encoded_point(false).as_bytes().to_vec();
+        let session = self.session.clone();
+        let private_key = self.private_key;
+        let shared_secret_bytes = deadline::run(self.timeout, move || {
+            let session = lock(&session);
+            let params = Ecdh1DeriveParams::new(EcKdf::null(), &point);
+            // Derive the shared secret as a session object that can be read
+            // back, and destroy it right after
+            let template = [
+                Attribute::Class(ObjectClass::SECRET_KEY),
+                Attribute::KeyType(KeyType::GENERIC_SECRET),
+                Attribute::ValueLen(32u64.into()),
+                Attribute::Token(false),
+                Attribute::Sensitive(false),
+                Attribute::Extractable(true),
+            ];
+            let secret = session
+                .derive_key(&Mechanism::Ecdh1Derive(params), private_key, &template)
+                .map_err(Error::from)?;
+            let value = session.get_attributes(secret, &[AttributeType::Value]);
+            let _ = session.destroy_object(secret);
+            match value.map_err(Error::from)?.pop() {
+                Some(Attribute::Value(value)) if value.len() == 32 => Ok(value),
+                _ => Err(Error::InvalidPoint.into()),
+            }
+        })?;
+        Ok(ecc_compact::SharedSecret(p256::ecdh::SharedSecret::from(
+            *p256::FieldBytes::from_slice(&shared_secret_bytes),
+        )))
+    }
+}
+
+/// Locks the given session. A panic while holding the lock leaves the session
+/// itself intact, so a poisoned lock is recovered.
+fn lock(session: &Mutex<Session>) -> std::sync::MutexGuard<'_, Session> {
+    session.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn find_key(session: &Session, class: ObjectClass, label: &str) -> Result<ObjectHandle> {
+    let kind = if class == ObjectClass::PRIVATE_KEY {
+        "private"
+    } else {
+        "public"
+    };
+    session
+        .find_objects(&[
+            Attribute::Class(class),
+            Attribute::KeyType(KeyType::EC),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ])
+        .map_err(Error::from)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::KeyNotFound(kind, label.to_string()).into())
+}
+
+/// Returns the uncompressed SEC1 point of the given `CKA_EC_POINT` value.
+/// Tokens are meant to wrap the point in a DER octet string, but some return
+/// the bare point.
+fn ec_point(value: &[u8]) -> Result<&[u8]> {
+    match value {
+        [0x04, 0x41, point @ ..] if point.len() == 65 => Ok(point),
+        [0x04, ..] if value.len() == 65 => Ok(value),
+        _ => Err(Error::InvalidPoint.into()),
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ec_points() {
+        let point = [&[0x04u8][..], &[0x11; 64]].concat();
+        let wrapped = [&[0x04u8, 0x41][..], &point].concat();
+        assert_eq!(&point[..], ec_point(&wrapped).expect("wrapped point"));
+        assert_eq!(&point[..], ec_point(&point).expect("bare point"));
+        assert!(ec_point(&point[..33]).is_err());
+        assert!(ec_point(&wrapped[..60]).is_err());
+    }
+}
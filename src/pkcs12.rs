@@ -0,0 +1,293 @@
+//! PKCS#12 export of keypairs with their certificate chain.
+//!
+//! A bundle holds the certificate chain, unencrypted, and the PKCS#8 secret
+//! key of the keypair, encrypted with PBES2 using PBKDF2-HMAC-SHA256 and
+//! AES-256-CBC. The bundle is authenticated with an HMAC-SHA256 keyed by the
+//! [RFC 7292][RFC7292] key derivation. The key and the leaf certificate share
+//! a local key id so importers pair them up. This is the modern encryption
+//! profile that current OpenSSL and Windows releases read.
+//!
+//! Windows imports ecc_compact keys only, while other tools import ed25519
+//! keys as well. Hardware backed keypairs can not be exported.
+//!
+//! [RFC7292]: https://www.rfc-editor.org/rfc/rfc7292
+use crate::{
+    cert::{integer, sequence, set, tlv, TAG_OCTET_STRING, TAG_OID},
+    *,
+};
+use cbc::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+
+/// The default number of iterations of both the key encryption and the MAC
+/// key derivations
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+const SALT_LENGTH: usize = 16;
+const IV_LENGTH: usize = 16;
+
+const OID_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_PBES2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+const OID_PBKDF2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+const OID_HMAC_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+const OID_AES256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+const OID_SHROUDED_KEY_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x02,
+];
+const OID_CERT_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x03,
+];
+const OID_X509_CERTIFICATE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x16, 0x01];
+const OID_LOCAL_KEY_ID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x15];
+
+const TAG_NULL: &[u8] = &[0x05, 0x00];
+const TAG_EXPLICIT: u8 = 0xa0;
+/// The RFC 7292 key derivation id for MAC keys
+const MAC_KEY_ID: u8 = 3;
+
+/// Export the given keypair and its DER encoded certificate chain, leaf
+/// certificate first, as a PKCS#12 bundle encrypted with the given password
+pub fn export<R>(
+    keypair: &Keypair,
+    chain: &[Vec<u8>],
+    password: &str,
+    csprng: &mut R,
+) -> Result<Vec<u8>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    export_with_iterations(keypair, chain, password, DEFAULT_ITERATIONS, csprng)
+}
+
+/// Like [`export`] but with the given number of key derivation iterations
+pub fn export_with_iterations<R>(
+    keypair: &Keypair,
+    chain: &[Vec<u8>],
+    password: &str,
+    iterations: u32,
+    csprng: &mut R,
+) -> Result<Vec<u8>>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let private_key_info = keypair.to_pkcs8_der()?;
+    // Pair the key with the leaf certificate, or with nothing if there is no
+    // certificate
+    let local_key_id = Sha256::digest(chain.first().map(Vec::as_slice).unwrap_or_default());
+    let attributes = set(&[&sequence(&[
+        &tlv(TAG_OID, OID_LOCAL_KEY_ID),
+        &set(&[&tlv(TAG_OCTET_STRING, &local_key_id)]),
+    ])]);
+
+    let cert_bags: Vec<Vec<u8>> = chain
+        .iter()
+        .enumerate()
+        .map(|(index, certificate)| {
+            let cert_bag = tlv(
+                TAG_EXPLICIT,
+                &sequence(&[
+                    &tlv(TAG_OID, OID_X509_CERTIFICATE),
+                    &tlv(TAG_EXPLICIT, &tlv(TAG_OCTET_STRING, certificate)),
+                ]),
+            );
+            let bag_attributes = if index == 0 { &attributes[..] } else { &[] };
+            sequence(&[&tlv(TAG_OID, OID_CERT_BAG), &cert_bag, bag_attributes])
+        })
+        .collect();
+
+    let mut salt = [0u8; SALT_LENGTH];
+    csprng.fill_bytes(&mut salt);
+    let mut iv = [0u8; IV_LENGTH];
+    csprng.fill_bytes(&mut iv);
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut key);
+    let encrypted_key = Aes256CbcEnc::new_from_slices(&key, &iv)
+        .map_err(|_| Error::encryption())?
+        .encrypt_padded_vec_mut::<Pkcs7>(&private_key_info);
+    let encryption_algorithm = sequence(&[
+        &tlv(TAG_OID, OID_PBES2),
+        &sequence(&[
+            &sequence(&[
+                &tlv(TAG_OID, OID_PBKDF2),
+                &sequence(&[
+                    &tlv(TAG_OCTET_STRING, &salt),
+                    &integer(&iterations.to_be_bytes()),
+                    &sequence(&[&tlv(TAG_OID, OID_HMAC_WITH_SHA256), TAG_NULL]),
+                ]),
+            ]),
+            &sequence(&[&tlv(TAG_OID, OID_AES256_CBC), &tlv(TAG_OCTET_STRING, &iv)]),
+        ]),
+    ]);
+    let key_bag = sequence(&[
+        &tlv(TAG_OID, OID_SHROUDED_KEY_BAG),
+        &tlv(
+            TAG_EXPLICIT,
+            &sequence(&[
+                &encryption_algorithm,
+                &tlv(TAG_OCTET_STRING, &encrypted_key),
+            ]),
+        ),
+        &attributes,
+    ]);
+
+    let cert_contents = sequence(&cert_bags.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    let auth_safe = sequence(&[&data(&cert_contents), &data(&sequence(&[&key_bag]))]);
+
+    let mut mac_salt = [0u8; SALT_LENGTH];
+    csprng.fill_bytes(&mut mac_salt);
+    let mac_key = derive_key(
+        &bmp_password(password),
+        &mac_salt,
+        MAC_KEY_ID,
+        iterations,
+        32,
+    );
+    let mac = Hmac::<Sha256>::new_from_slice(&mac_key)
+        .map_err(|_| Error::encryption())?
+        .chain_update(&auth_safe)
+        .finalize()
+        .into_bytes();
+    let mac_data = sequence(&[
+        &sequence(&[
+            &sequence(&[&tlv(TAG_OID, OID_SHA256), TAG_NULL]),
+            &tlv(TAG_OCTET_STRING, &mac),
+        ]),
+        &tlv(TAG_OCTET_STRING, &mac_salt),
+        &integer(&iterations.to_be_bytes()),
+    ]);
+
+    Ok(sequence(&[
+        // Version 3
+        &integer(&[0x03]),
+        &data(&auth_safe),
+        &mac_data,
+    ]))
+}
+
+/// Wraps the given content in a data ContentInfo
+fn data(content: &[u8]) -> Vec<u8> {
+    sequence(&[
+        &tlv(TAG_OID, OID_DATA),
+        &tlv(TAG_EXPLICIT, &tlv(TAG_OCTET_STRING, content)),
+    ])
+}
+
+/// Returns the password as a null terminated big endian UTF-16 string, as
+/// the RFC 7292 key derivation expects
+fn bmp_password(password: &str) -> Vec<u8> {
+    password
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_be_bytes)
+        .collect()
+}
+
+/// The RFC 7292 appendix B key derivation with SHA-256
+fn derive_key(password: &[u8], salt: &[u8], id: u8, iterations: u32, len: usize) -> Vec<u8> {
+    const U: usize = 32;
+    const V: usize = 64;
+    // Repeats the given bytes to fill a whole number of V byte blocks
+    let fill = |bytes: &[u8]| -> Vec<u8> {
+        let len = (bytes.len() + V - 1) / V * V;
+        bytes.iter().copied().cycle().take(len).collect()
+    };
+    let diversifier = [id; V];
+    let mut input = [fill(salt), fill(password)].concat();
+    let mut result = Vec::with_capacity(len + U);
+    while result.len() < len {
+        let mut block = Sha256::new()
+            .chain_update(diversifier)
+            .chain_update(&input)
+            .finalize();
+        for _ in 1..iterations {
+            block = Sha256::digest(block);
+        }
+        result.extend_from_slice(&block);
+
+        // Add the block, repeated to V bytes, plus one to every V byte chunk
+        // of the input
+        let addend: Vec<u8> = block.iter().copied().cycle().take(V).collect();
+        for chunk in input.chunks_mut(V) {
+            let mut carry = 1u16;
+            for (byte, add) in chunk.iter_mut().zip(&addend).rev() {
+                let sum = *byte as u16 + *add as u16 + carry;
+                *byte = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+    }
+    result.truncate(len);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cert::{self_signed, CertParams};
+    use crate::validity::Validity;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn key_derivation() {
+        assert_eq!(
+            hex!("b3fad7cf164bd8922ee6be4677ff6714fe10e05ae66d895515380e7f7de4a92b"),
+            &derive_key(
+                &bmp_password("password"),
+                &hex!("0102030405060708"),
+                MAC_KEY_ID,
+                1000,
+                32
+            )[..]
+        );
+        // Longer than one hash output, which exercises the input update
+        assert_eq!(
+            hex!(
+                "89ba2bd5a6a189691b3e79a1793eafd779301559b6558e3db91f23ba06986060bb94cefb89a84011"
+            ),
+            &derive_key(
+                &bmp_password(""),
+                &hex!("0102030405060708"),
+                MAC_KEY_ID,
+                1,
+                40
+            )[..]
+        );
+    }
+
+    #[test]
+    fn bmp() {
+        assert_eq!(hex!("0061006200e40000"), &bmp_password("abä")[..]);
+    }
+
+    #[test]
+    fn export_bundle() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let certificate = self_signed(
+            &keypair,
+            keypair.public_key(),
+            &CertParams::new(Validity::new(1_700_000_000, 1_800_000_000)),
+        )
+        .expect("certificate");
+        let bundle =
+            export_with_iterations(&keypair, &[certificate.clone()], "password", 10, &mut OsRng)
+                .expect("bundle");
+        // The certificate is in the clear, the secret key is not
+        assert!(bundle
+            .windows(certificate.len())
+            .any(|window| window == &certificate[..]));
+        let secret = keypair.secret_to_vec();
+        assert!(!bundle
+            .windows(secret.len())
+            .any(|window| window == &secret[..]));
+        assert_eq!(&[0x02, 0x01, 0x03], &bundle[4..7]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Proof of possession of a keypair.
+//!
+//! A server that needs to know a device holds the keypair for a claimed
+//! [`PublicKey`] issues a fresh [`Challenge`] naming itself as the audience.
+//! The device checks the audience and answers with a [`Response`] signing the
+//! whole challenge along with its public key, and the server verifies the
+//! response against the challenge it issued and the claimed key.
+//!
+//! Signatures are domain separated, so a response can not be passed off as a
+//! signature over any other message, and are bound to the audience, nonce and
+//! validity window of the challenge, so a response can not be relayed to
+//! another server or replayed after the challenge expires. Servers must only
+//! accept one response per challenge.
+use crate::{
+    clock::Clock,
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    validity::Validity,
+    *,
+};
+use std::io;
+
+/// Domain separator prefixed to the signed bytes of a possession response
+const POSSESSION_DOMAIN: &[u8] = b"helium-proof-of-possession";
+
+/// A challenge issued by a server to a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// Identifies the server that issued the challenge
+    pub audience: Vec<u8>,
+    pub nonce: [u8; 32],
+    /// The window in which a response is accepted
+    pub validity: Validity,
+}
+
+impl Challenge {
+    /// Issue a challenge for the given audience which can be answered for the
+    /// given number of seconds from the current time of the given clock
+    pub fn new<R, C>(audience: &[u8], secs: u64, clock: &C, csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+        C: Clock,
+    {
+        Self::new_with_validity(audience, Validity::starting_at(clock.now(), secs), csprng)
+    }
+
+    /// Issue a challenge for the given audience with the given validity window
+    pub fn new_with_validity<R>(audience: &[u8], validity: Validity, csprng: &mut R) -> Self
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut nonce = [0u8; 32];
+        csprng.fill_bytes(&mut nonce);
+        Self {
+            audience: audience.to_vec(),
+            nonce,
+            validity,
+        }
+    }
+
+    /// Answer the challenge with the given keypair. Fails if the challenge
+    /// was not issued for the expected audience.
+    pub fn respond(&self, expected_audience: &[u8], keypair: &Keypair) -> Result<Response> {
+        if self.audience != expected_audience {
+            return Err(Error::not_permitted());
+        }
+        let public_key = keypair.public_key().clone();
+        let signature = keypair.sign(&signing_bytes(self, &public_key)?)?;
+        Ok(Response {
+            public_key,
+            signature,
+        })
+    }
+
+    /// Verify that the given response proves possession of the keypair for
+    /// the claimed public key, at the current time of the given clock
+    pub fn verify<C: Clock>(&self, claimed: &PublicKey, response: &Response, clock: &C) -> Result {
+        self.verify_at(claimed, response, clock.now())
+    }
+
+    /// Like [`Challenge::verify`] but at the given current time
+    pub fn verify_at(&self, claimed: &PublicKey, response: &Response, now: u64) -> Result {
+        if &response.public_key != claimed {
+            return Err(signature::Error::new().into());
+        }
+        claimed.verify(&signing_bytes(self, claimed)?, &response.signature)?;
+        self.validity.check(now)
+    }
+
+    /// Convert the challenge to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl WriteTo for Challenge {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        write_u32_prefixed(output, &self.audience)?;
+        output.write_all(&self.nonce)?;
+        output.write_all(&self.validity.not_before.to_be_bytes())?;
+        output.write_all(&self.validity.not_after.to_be_bytes())
+    }
+}
+
+impl ReadFrom for Challenge {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let audience = read_u32_prefixed(input)?;
+        let mut nonce = [0u8; 32];
+        input.read_exact(&mut nonce)?;
+        let mut not_before = [0u8; 8];
+        input.read_exact(&mut not_before)?;
+        let mut not_after = [0u8; 8];
+        input.read_exact(&mut not_after)?;
+        Ok(Self {
+            audience,
+            nonce,
+            validity: Validity::new(
+                u64::from_be_bytes(not_before),
+                u64::from_be_bytes(not_after),
+            ),
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Challenge {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// The answer of a device to a challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub public_key: PublicKey,
+    pub signature: Vec<u8>,
+}
+
+impl Response {
+    /// Convert the response to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl WriteTo for Response {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        self.public_key.write_to(output)?;
+        write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for Response {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        Ok(Self {
+            public_key: PublicKey::read_from(input)?,
+            signature: read_u32_prefixed(input)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Response {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// Returns the bytes a response to the given challenge is calculated over
+fn signing_bytes(challenge: &Challenge, public_key: &PublicKey) -> io::Result<Vec<u8>> {
+    let mut result = POSSESSION_DOMAIN.to_vec();
+    challenge.write_to(&mut result)?;
+    public_key.write_to(&mut result)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn possession() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let clock = ManualClock::new(1000);
+        let challenge = Challenge::new(b"onboarding", 60, &clock, &mut OsRng);
+        let response = challenge
+            .respond(b"onboarding", &keypair)
+            .expect("response");
+        assert!(challenge
+            .verify(keypair.public_key(), &response, &clock)
+            .is_ok());
+
+        clock.advance(61);
+        assert!(matches!(
+            challenge.verify(keypair.public_key(), &response, &clock),
+            Err(Error::Expired(1060))
+        ));
+    }
+
+    #[test]
+    fn wrong_claim() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let other = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let challenge =
+            Challenge::new_with_validity(b"onboarding", Validity::new(0, 60), &mut OsRng);
+        let response = challenge
+            .respond(b"onboarding", &keypair)
+            .expect("response");
+        assert!(challenge
+            .verify_at(other.public_key(), &response, 30)
+            .is_err());
+
+        // A response carrying the claimed key is still signed by another key
+        let forged = Response {
+            public_key: other.public_key().clone(),
+            signature: response.signature.clone(),
+        };
+        assert!(challenge
+            .verify_at(other.public_key(), &forged, 30)
+            .is_err());
+    }
+
+    #[test]
+    fn bound_to_challenge() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let validity = Validity::new(0, 60);
+        let challenge = Challenge::new_with_validity(b"onboarding", validity, &mut OsRng);
+        let other = Challenge::new_with_validity(b"onboarding", validity, &mut OsRng);
+        let response = challenge
+            .respond(b"onboarding", &keypair)
+            .expect("response");
+        assert!(other
+            .verify_at(keypair.public_key(), &response, 30)
+            .is_err());
+
+        let mut relayed = challenge.clone();
+        relayed.audience = b"elsewhere".to_vec();
+        assert!(matches!(
+            relayed.respond(b"onboarding", &keypair),
+            Err(Error::NotPermitted)
+        ));
+        assert!(relayed
+            .verify_at(keypair.public_key(), &response, 30)
+            .is_err());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let challenge =
+            Challenge::new_with_validity(b"onboarding", Validity::new(0, 60), &mut OsRng);
+        let response = challenge
+            .respond(b"onboarding", &keypair)
+            .expect("response");
+        assert_eq!(
+            challenge,
+            Challenge::try_from(&challenge.to_vec()[..]).expect("challenge")
+        );
+        assert_eq!(
+            response,
+            Response::try_from(&response.to_vec()[..]).expect("response")
+        );
+    }
+}
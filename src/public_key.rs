@@ -14,6 +14,68 @@ pub trait Verify {
     fn verify(&self, msg: &[u8], signature: &[u8]) -> Result;
 }
 
+/// A streaming verifier incrementally hashes a message before verifying a
+/// signature against it, so large messages do not need to be held in memory.
+///
+/// Streaming verification is supported for the ECDSA key types, which sign a
+/// digest of the message: ecc_compact and secp256k1 keys with SHA-256 and
+/// P-384 keys with SHA-384. See [`PublicKey::verifier`] for the key types
+/// that do not support it.
+pub struct StreamVerifier<'a> {
+    inner: StreamVerifierRepr<'a>,
+}
+
+enum StreamVerifierRepr<'a> {
+    EccCompact(&'a ecc_compact::PublicKey, sha2::Sha256),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(&'a secp256k1::PublicKey, sha2::Sha256),
+    #[cfg(feature = "p384")]
+    EccP384(&'a ecc_p384::PublicKey, sha2::Sha384),
+}
+
+impl<'a> StreamVerifier<'a> {
+    /// Add the given data to the message to be verified
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        match &mut self.inner {
+            StreamVerifierRepr::EccCompact(_, digest) => digest.update(data),
+            #[cfg(feature = "secp256k1")]
+            StreamVerifierRepr::Secp256k1(_, digest) => digest.update(data),
+            #[cfg(feature = "p384")]
+            StreamVerifierRepr::EccP384(_, digest) => digest.update(data),
+        }
+    }
+
+    /// Verify the given signature against the message passed in through
+    /// `update`
+    pub fn finalize(self, signature: &[u8]) -> Result {
+        match self.inner {
+            StreamVerifierRepr::EccCompact(public_key, digest) => {
+                public_key.verify_digest(digest, signature)
+            }
+            #[cfg(feature = "secp256k1")]
+            StreamVerifierRepr::Secp256k1(public_key, digest) => {
+                public_key.verify_digest(digest, signature)
+            }
+            #[cfg(feature = "p384")]
+            StreamVerifierRepr::EccP384(public_key, digest) => {
+                public_key.verify_digest(digest, signature)
+            }
+        }
+    }
+}
+
+impl<'a> std::io::Write for StreamVerifier<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// The public key byte length is the underlying public key length all key types
 /// with an extra type byte prefixed.
 pub trait PublicKeySize {
@@ -48,6 +110,24 @@ pub(crate) enum PublicKeyRepr {
     Ed25519(ed25519::PublicKey),
     #[cfg(feature = "multisig")]
     MultiSig(multisig::PublicKey),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(secp256k1::PublicKey),
+    #[cfg(feature = "bls")]
+    Bls(bls12_381::PublicKey),
+    #[cfg(feature = "x25519")]
+    X25519(x25519::PublicKey),
+    #[cfg(feature = "ed448")]
+    Ed448(ed448::PublicKey),
+    #[cfg(feature = "sr25519")]
+    Sr25519(sr25519::PublicKey),
+    #[cfg(feature = "p384")]
+    EccP384(ecc_p384::PublicKey),
+    #[cfg(feature = "pqc")]
+    Dilithium(dilithium::PublicKey),
+    #[cfg(feature = "pqc")]
+    Hybrid(hybrid::PublicKey),
+    #[cfg(feature = "pqc")]
+    Kyber(kyber::PublicKey),
 }
 
 impl Eq for PublicKeyRepr {}
@@ -107,6 +187,24 @@ impl TryFrom<&[u8]> for PublicKeyRepr {
             KeyType::Ed25519 => Ok(Self::Ed25519(ed25519::PublicKey::try_from(bytes)?)),
             #[cfg(feature = "multisig")]
             KeyType::MultiSig => Ok(Self::MultiSig(multisig::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Ok(Self::Secp256k1(secp256k1::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "bls")]
+            KeyType::Bls => Ok(Self::Bls(bls12_381::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => Ok(Self::X25519(x25519::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => Ok(Self::Ed448(ed448::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => Ok(Self::Sr25519(sr25519::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => Ok(Self::EccP384(ecc_p384::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => Ok(Self::Dilithium(dilithium::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => Ok(Self::Hybrid(hybrid::PublicKey::try_from(bytes)?)),
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => Ok(Self::Kyber(kyber::PublicKey::try_from(bytes)?)),
         }
     }
 }
@@ -128,6 +226,24 @@ impl ReadFrom for PublicKey {
             KeyType::Ed25519 => PublicKeyRepr::Ed25519(ed25519::PublicKey::read_from(input)?),
             #[cfg(feature = "multisig")]
             KeyType::MultiSig => PublicKeyRepr::MultiSig(multisig::PublicKey::read_from(input)?),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => PublicKeyRepr::Secp256k1(secp256k1::PublicKey::read_from(input)?),
+            #[cfg(feature = "bls")]
+            KeyType::Bls => PublicKeyRepr::Bls(bls12_381::PublicKey::read_from(input)?),
+            #[cfg(feature = "x25519")]
+            KeyType::X25519 => PublicKeyRepr::X25519(x25519::PublicKey::read_from(input)?),
+            #[cfg(feature = "ed448")]
+            KeyType::Ed448 => PublicKeyRepr::Ed448(ed448::PublicKey::read_from(input)?),
+            #[cfg(feature = "sr25519")]
+            KeyType::Sr25519 => PublicKeyRepr::Sr25519(sr25519::PublicKey::read_from(input)?),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => PublicKeyRepr::EccP384(ecc_p384::PublicKey::read_from(input)?),
+            #[cfg(feature = "pqc")]
+            KeyType::Dilithium => PublicKeyRepr::Dilithium(dilithium::PublicKey::read_from(input)?),
+            #[cfg(feature = "pqc")]
+            KeyType::Hybrid => PublicKeyRepr::Hybrid(hybrid::PublicKey::read_from(input)?),
+            #[cfg(feature = "pqc")]
+            KeyType::Kyber => PublicKeyRepr::Kyber(kyber::PublicKey::read_from(input)?),
         };
         Ok(Self {
             network: key_tag.network,
@@ -143,6 +259,24 @@ impl WriteTo for PublicKeyRepr {
             Self::Ed25519(key) => key.write_to(output),
             #[cfg(feature = "multisig")]
             Self::MultiSig(key) => key.write_to(output),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => key.write_to(output),
+            #[cfg(feature = "bls")]
+            Self::Bls(key) => key.write_to(output),
+            #[cfg(feature = "x25519")]
+            Self::X25519(key) => key.write_to(output),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(key) => key.write_to(output),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(key) => key.write_to(output),
+            #[cfg(feature = "p384")]
+            Self::EccP384(key) => key.write_to(output),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(key) => key.write_to(output),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(key) => key.write_to(output),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(key) => key.write_to(output),
         }
     }
 }
@@ -166,9 +300,71 @@ impl From<multisig::PublicKey> for PublicKeyRepr {
     }
 }
 
+#[cfg(feature = "secp256k1")]
+impl From<secp256k1::PublicKey> for PublicKeyRepr {
+    fn from(v: secp256k1::PublicKey) -> Self {
+        Self::Secp256k1(v)
+    }
+}
+
+#[cfg(feature = "bls")]
+impl From<bls12_381::PublicKey> for PublicKeyRepr {
+    fn from(v: bls12_381::PublicKey) -> Self {
+        Self::Bls(v)
+    }
+}
+
+#[cfg(feature = "x25519")]
+impl From<x25519::PublicKey> for PublicKeyRepr {
+    fn from(v: x25519::PublicKey) -> Self {
+        Self::X25519(v)
+    }
+}
+#[cfg(feature = "ed448")]
+impl From<ed448::PublicKey> for PublicKeyRepr {
+    fn from(v: ed448::PublicKey) -> Self {
+        Self::Ed448(v)
+    }
+}
+#[cfg(feature = "sr25519")]
+impl From<sr25519::PublicKey> for PublicKeyRepr {
+    fn from(v: sr25519::PublicKey) -> Self {
+        Self::Sr25519(v)
+    }
+}
+#[cfg(feature = "p384")]
+impl From<ecc_p384::PublicKey> for PublicKeyRepr {
+    fn from(v: ecc_p384::PublicKey) -> Self {
+        Self::EccP384(v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<dilithium::PublicKey> for PublicKeyRepr {
+    fn from(v: dilithium::PublicKey) -> Self {
+        Self::Dilithium(v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<hybrid::PublicKey> for PublicKeyRepr {
+    fn from(v: hybrid::PublicKey) -> Self {
+        Self::Hybrid(v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<kyber::PublicKey> for PublicKeyRepr {
+    fn from(v: kyber::PublicKey) -> Self {
+        Self::Kyber(v)
+    }
+}
+
 impl Verify for PublicKey {
     fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
-        self.inner.verify(msg, signature)
+        telemetry::observe(
+            "verify",
+            self.key_tag(),
+            telemetry::BACKEND_SOFTWARE,
+            || self.inner.verify(msg, signature),
+        )
     }
 }
 
@@ -179,6 +375,24 @@ impl Verify for PublicKeyRepr {
             Self::EccCompact(key) => key.verify(msg, signature),
             #[cfg(feature = "multisig")]
             Self::MultiSig(key) => key.verify(msg, signature),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => key.verify(msg, signature),
+            #[cfg(feature = "bls")]
+            Self::Bls(key) => key.verify(msg, signature),
+            #[cfg(feature = "x25519")]
+            Self::X25519(key) => key.verify(msg, signature),
+            #[cfg(feature = "ed448")]
+            Self::Ed448(key) => key.verify(msg, signature),
+            #[cfg(feature = "sr25519")]
+            Self::Sr25519(key) => key.verify(msg, signature),
+            #[cfg(feature = "p384")]
+            Self::EccP384(key) => key.verify(msg, signature),
+            #[cfg(feature = "pqc")]
+            Self::Dilithium(key) => key.verify(msg, signature),
+            #[cfg(feature = "pqc")]
+            Self::Hybrid(key) => key.verify(msg, signature),
+            #[cfg(feature = "pqc")]
+            Self::Kyber(key) => key.verify(msg, signature),
         }
     }
 }
@@ -215,6 +429,154 @@ impl<'a> TryFrom<&'a PublicKey> for &'a ed25519::PublicKey {
     }
 }
 
+#[cfg(feature = "secp256k1")]
+impl From<secp256k1::PublicKey> for PublicKey {
+    fn from(v: secp256k1::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl<'a> TryFrom<&'a PublicKey> for &'a secp256k1::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Secp256k1(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "bls")]
+impl From<bls12_381::PublicKey> for PublicKey {
+    fn from(v: bls12_381::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+
+#[cfg(feature = "bls")]
+impl<'a> TryFrom<&'a PublicKey> for &'a bls12_381::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Bls(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "x25519")]
+impl From<x25519::PublicKey> for PublicKey {
+    fn from(v: x25519::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "ed448")]
+impl From<ed448::PublicKey> for PublicKey {
+    fn from(v: ed448::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "sr25519")]
+impl From<sr25519::PublicKey> for PublicKey {
+    fn from(v: sr25519::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "p384")]
+impl From<ecc_p384::PublicKey> for PublicKey {
+    fn from(v: ecc_p384::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<dilithium::PublicKey> for PublicKey {
+    fn from(v: dilithium::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<hybrid::PublicKey> for PublicKey {
+    fn from(v: hybrid::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+#[cfg(feature = "pqc")]
+impl From<kyber::PublicKey> for PublicKey {
+    fn from(v: kyber::PublicKey) -> Self {
+        Self::for_network(Network::MainNet, v)
+    }
+}
+
+#[cfg(feature = "x25519")]
+impl<'a> TryFrom<&'a PublicKey> for &'a x25519::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::X25519(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "ed448")]
+impl<'a> TryFrom<&'a PublicKey> for &'a ed448::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Ed448(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "sr25519")]
+impl<'a> TryFrom<&'a PublicKey> for &'a sr25519::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Sr25519(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "p384")]
+impl<'a> TryFrom<&'a PublicKey> for &'a ecc_p384::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::EccP384(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "pqc")]
+impl<'a> TryFrom<&'a PublicKey> for &'a dilithium::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Dilithium(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "pqc")]
+impl<'a> TryFrom<&'a PublicKey> for &'a hybrid::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Hybrid(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+#[cfg(feature = "pqc")]
+impl<'a> TryFrom<&'a PublicKey> for &'a kyber::PublicKey {
+    type Error = Error;
+    fn try_from(v: &'a PublicKey) -> Result<Self> {
+        match &v.inner {
+            PublicKeyRepr::Kyber(public_key) => Ok(public_key),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
 impl std::str::FromStr for PublicKey {
     type Err = Error;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -235,17 +597,34 @@ impl std::fmt::Display for PublicKey {
     }
 }
 
+/// Formats the tagged binary form of the public key as lower case hex, as an
+/// alternative to the base58 [`Display`](std::fmt::Display) form
+impl std::fmt::LowerHex for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        for byte in self.to_vec() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 use serde::{
     de::{self, Visitor},
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
+/// Public keys serialize as their base58 string in human readable formats,
+/// and as their tagged binary form in other formats.
 impl Serialize for PublicKey {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            serializer.serialize_bytes(&self.to_vec())
+        }
     }
 }
 
@@ -260,7 +639,7 @@ impl<'de> Deserialize<'de> for PublicKey {
             type Value = PublicKey;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("base58 public key")
+                formatter.write_str("base58 public key or public key bytes")
             }
 
             fn visit_str<E>(self, value: &str) -> std::result::Result<PublicKey, E>
@@ -271,9 +650,86 @@ impl<'de> Deserialize<'de> for PublicKey {
                     .map_err(|_| de::Error::custom("invalid public key"))?;
                 Ok(key)
             }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<PublicKey, E>
+            where
+                E: de::Error,
+            {
+                PublicKey::try_from(value).map_err(|_| de::Error::custom("invalid public key"))
+            }
         }
 
-        deserializer.deserialize_str(PublicKeyVisitor)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(PublicKeyVisitor)
+        } else {
+            deserializer.deserialize_bytes(PublicKeyVisitor)
+        }
+    }
+}
+
+/// Serde support for public keys as hex strings.
+///
+/// Public keys serialize as their base58 string by default. Fields for APIs
+/// that expect hex encoded keys can opt in to the hex encoding of the tagged
+/// binary form with
+///
+/// ```ignore
+/// #[serde(with = "helium_crypto::public_key::serde_hex")]
+/// public_key: PublicKey,
+/// ```
+///
+/// Like the default encoding, public keys serialize as their binary form in
+/// formats that are not human readable.
+pub mod serde_hex {
+    use super::*;
+
+    pub fn serialize<S>(
+        public_key: &PublicKey,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&public_key.to_hex())
+        } else {
+            serializer.serialize_bytes(&public_key.to_vec())
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<PublicKey, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct HexVisitor;
+
+        impl<'de> Visitor<'de> for HexVisitor {
+            type Value = PublicKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("hex public key or public key bytes")
+            }
+
+            fn visit_str<E>(self, value: &str) -> std::result::Result<PublicKey, E>
+            where
+                E: de::Error,
+            {
+                PublicKey::from_hex(value).map_err(|_| de::Error::custom("invalid public key"))
+            }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<PublicKey, E>
+            where
+                E: de::Error,
+            {
+                PublicKey::try_from(value).map_err(|_| de::Error::custom("invalid public key"))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HexVisitor)
+        } else {
+            deserializer.deserialize_bytes(HexVisitor)
+        }
     }
 }
 
@@ -299,6 +755,45 @@ impl PublicKey {
         result
     }
 
+    /// Convert a public key to the lower case hex encoding of its binary form,
+    /// including the key tag
+    pub fn to_hex(&self) -> String {
+        format!("{:x}", self)
+    }
+
+    /// Construct a public key from the hex encoding of its binary form,
+    /// including the key tag. Both lower and upper case digits are accepted.
+    pub fn from_hex(s: &str) -> Result<Self> {
+        let digits = s
+            .chars()
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(Error::invalid_hex)?;
+        if digits.len() % 2 != 0 {
+            return Err(Error::invalid_hex());
+        }
+        let bytes: Vec<u8> = digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect();
+        Self::try_from(&bytes[..])
+    }
+
+    /// Encapsulate a fresh shared secret to this public key. Returns the
+    /// shared secret and the ciphertext to send to the owner of the keypair,
+    /// who gets the same shared secret with [`Keypair::decapsulate`]. Only
+    /// key encapsulation keys, like Kyber ones, support this.
+    pub fn encapsulate(&self) -> Result<(keypair::SharedSecret, Vec<u8>)> {
+        match &self.inner {
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Kyber(public_key) => {
+                let (shared_secret, ciphertext) = public_key.encapsulate();
+                Ok((keypair::SharedSecret::Kem(shared_secret), ciphertext))
+            }
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
     /// Get the type for this key
     pub fn key_type(&self) -> KeyType {
         match self.inner {
@@ -306,6 +801,24 @@ impl PublicKey {
             PublicKeyRepr::Ed25519(..) => KeyType::Ed25519,
             #[cfg(feature = "multisig")]
             PublicKeyRepr::MultiSig(..) => KeyType::MultiSig,
+            #[cfg(feature = "secp256k1")]
+            PublicKeyRepr::Secp256k1(..) => KeyType::Secp256k1,
+            #[cfg(feature = "bls")]
+            PublicKeyRepr::Bls(..) => KeyType::Bls,
+            #[cfg(feature = "x25519")]
+            PublicKeyRepr::X25519(..) => KeyType::X25519,
+            #[cfg(feature = "ed448")]
+            PublicKeyRepr::Ed448(..) => KeyType::Ed448,
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(..) => KeyType::Sr25519,
+            #[cfg(feature = "p384")]
+            PublicKeyRepr::EccP384(..) => KeyType::EccP384,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Dilithium(..) => KeyType::Dilithium,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Hybrid(..) => KeyType::Hybrid,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Kyber(..) => KeyType::Kyber,
         }
     }
 
@@ -317,12 +830,146 @@ impl PublicKey {
         }
     }
 
+    /// Construct a streaming verifier for this public key.
+    ///
+    /// Fails with an invalid curve error for key types that do not support
+    /// streaming verification:
+    ///
+    /// - ed25519 and Ed448 signatures hash the nonce commitment `R` from the
+    ///   signature ahead of the message, so the message can not be hashed
+    ///   before the signature is known. Sign large ed25519 messages with
+    ///   [`Keypair::sign_prehashed`] and verify them with
+    ///   [`PublicKey::verify_prehashed`] instead.
+    /// - multisig, BLS, sr25519 and post-quantum signatures are computed over
+    ///   the full message.
+    /// - x25519 and Kyber keys do not sign.
+    pub fn verifier(&self) -> Result<StreamVerifier<'_>> {
+        let inner = match &self.inner {
+            PublicKeyRepr::EccCompact(public_key) => {
+                StreamVerifierRepr::EccCompact(public_key, sha2::Sha256::default())
+            }
+            #[cfg(feature = "secp256k1")]
+            PublicKeyRepr::Secp256k1(public_key) => {
+                StreamVerifierRepr::Secp256k1(public_key, sha2::Sha256::default())
+            }
+            #[cfg(feature = "p384")]
+            PublicKeyRepr::EccP384(public_key) => {
+                StreamVerifierRepr::EccP384(public_key, sha2::Sha384::default())
+            }
+            _ => return Err(Error::invalid_curve()),
+        };
+        Ok(StreamVerifier { inner })
+    }
+
+    /// Verify the given signature against the data read from the given reader
+    /// using a streaming verifier
+    pub fn verify_reader<R: std::io::Read>(&self, reader: &mut R, signature: &[u8]) -> Result {
+        let mut verifier = self.verifier()?;
+        std::io::copy(reader, &mut verifier)?;
+        verifier.finalize(signature)
+    }
+
+    /// Verify a signature made with [`Keypair::sign_with_context`] over the
+    /// given message and with the given context
+    #[cfg(feature = "context")]
+    pub fn verify_with_context(&self, context: &[u8], msg: &[u8], signature: &[u8]) -> Result {
+        match &self.inner {
+            PublicKeyRepr::Ed25519(public_key) => {
+                public_key.verify_with_context(context, msg, signature)
+            }
+            PublicKeyRepr::EccCompact(public_key) => {
+                public_key.verify_with_context(context, msg, signature)
+            }
+            #[cfg(feature = "ed448")]
+            PublicKeyRepr::Ed448(public_key) => {
+                public_key.verify_with_context(context, msg, signature)
+            }
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(public_key) => {
+                public_key.verify_with_context(context, msg, signature)
+            }
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Verify the given fixed size raw `r || s` signature, as made by
+    /// [`Keypair::sign_raw`], over the given message. Only ecc_compact keys
+    /// support this.
+    pub fn verify_raw(&self, msg: &[u8], signature: &[u8]) -> Result {
+        match &self.inner {
+            PublicKeyRepr::EccCompact(public_key) => public_key.verify_raw(msg, signature),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Verify the given signature like [`Verify::verify`], but reject
+    /// malleable signatures, like high-S ECDSA signatures or non-canonical
+    /// encodings, so signatures can be used as unique identifiers. Only
+    /// ecc_compact, ed25519 and secp256k1 keys support strict verification.
+    pub fn verify_strict(&self, msg: &[u8], signature: &[u8]) -> Result {
+        match &self.inner {
+            PublicKeyRepr::EccCompact(public_key) => public_key.verify_strict(msg, signature),
+            PublicKeyRepr::Ed25519(public_key) => public_key.verify_strict(msg, signature),
+            #[cfg(feature = "secp256k1")]
+            PublicKeyRepr::Secp256k1(public_key) => public_key.verify_strict(msg, signature),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Recover the public key with the given key tag that made the given
+    /// recoverable signature, as made by [`Keypair::sign_recoverable`], over
+    /// the given message
+    pub fn recover(key_tag: KeyTag, msg: &[u8], signature: &[u8]) -> Result<Self> {
+        match key_tag.key_type {
+            KeyType::EccCompact => Ok(Self::for_network(
+                key_tag.network,
+                ecc_compact::PublicKey::recover(msg, signature)?,
+            )),
+            #[cfg(feature = "secp256k1")]
+            KeyType::Secp256k1 => Ok(Self::for_network(
+                key_tag.network,
+                secp256k1::PublicKey::recover(msg, signature)?,
+            )),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
+    /// Verify an Ed25519ph signature made with [`Keypair::sign_prehashed`]
+    /// against a SHA-512 digest of the message. Only ed25519 keys support
+    /// this.
+    pub fn verify_prehashed(&self, prehashed: sha2::Sha512, signature: &[u8]) -> Result {
+        match &self.inner {
+            PublicKeyRepr::Ed25519(public_key) => {
+                public_key.verify_prehashed(prehashed, None, signature)
+            }
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+
     pub fn public_key_size(&self) -> usize {
         match self.inner {
             PublicKeyRepr::EccCompact(..) => ecc_compact::PublicKey::PUBLIC_KEY_SIZE,
             PublicKeyRepr::Ed25519(..) => ed25519::PublicKey::PUBLIC_KEY_SIZE,
             #[cfg(feature = "multisig")]
             PublicKeyRepr::MultiSig(..) => multisig::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "secp256k1")]
+            PublicKeyRepr::Secp256k1(..) => secp256k1::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "bls")]
+            PublicKeyRepr::Bls(..) => bls12_381::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "x25519")]
+            PublicKeyRepr::X25519(..) => x25519::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "ed448")]
+            PublicKeyRepr::Ed448(..) => ed448::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "sr25519")]
+            PublicKeyRepr::Sr25519(..) => sr25519::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "p384")]
+            PublicKeyRepr::EccP384(..) => ecc_p384::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Dilithium(..) => dilithium::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Hybrid(..) => hybrid::PublicKey::PUBLIC_KEY_SIZE,
+            #[cfg(feature = "pqc")]
+            PublicKeyRepr::Kyber(..) => kyber::PublicKey::PUBLIC_KEY_SIZE,
         }
     }
 }
@@ -433,5 +1080,35 @@ mod tests {
         let serialized = serde_json::to_string(&orig_pub_key).unwrap();
         let deserialized = serde_json::from_str(&serialized).unwrap();
         assert_eq!(orig_pub_key, deserialized);
+
+        let serialized = bincode::serialize(&orig_pub_key).unwrap();
+        // Length prefixed tagged bytes
+        assert_eq!(&orig_pub_key.to_vec()[..], &serialized[8..]);
+        let deserialized: PublicKey = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(orig_pub_key, deserialized);
+    }
+
+    #[test]
+    fn hex() {
+        let public_key = parse_pubkey(&DEFAULT_BYTES);
+        const HEX: &str = "008f23e96ab6bbff48c8923cac831dc97111bcf33dba9f5a8539c00f9d93551af1";
+        assert_eq!(HEX, public_key.to_hex());
+        assert_eq!(HEX, format!("{:x}", public_key));
+        assert_eq!(public_key, PublicKey::from_hex(HEX).expect("public key"));
+        assert_eq!(
+            public_key,
+            PublicKey::from_hex(&HEX.to_uppercase()).expect("public key")
+        );
+        assert!(PublicKey::from_hex(&HEX[1..]).is_err());
+        assert!(PublicKey::from_hex(&HEX.replace('f', "g")).is_err());
+        assert!(PublicKey::from_hex(&format!("+{}", &HEX[1..])).is_err());
+
+        let mut json = vec![];
+        serde_hex::serialize(&public_key, &mut serde_json::Serializer::new(&mut json))
+            .expect("json");
+        assert_eq!(format!("\"{}\"", HEX).as_bytes(), &json[..]);
+        let deserialized = serde_hex::deserialize(&mut serde_json::Deserializer::from_slice(&json))
+            .expect("deserialized");
+        assert_eq!(public_key, deserialized);
     }
 }
@@ -0,0 +1,184 @@
+//! The tagged binary form of a public key, without decoding it.
+//!
+//! Services that store and compare public keys, but rarely verify with them,
+//! can hold a [`PublicKeyBinary`] instead of a [`PublicKey`]. Converting a
+//! public key to its binary form is cheap, and formatting, parsing and
+//! (de)serializing a binary form never decodes the key. The key is only
+//! validated when the binary form is converted back into a [`PublicKey`].
+use crate::*;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// The tagged binary form of a public key. It displays and parses as the same
+/// base58 string as the public key it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "sqlx-postgres", derive(sqlx::Type), sqlx(transparent))]
+pub struct PublicKeyBinary(Vec<u8>);
+
+impl PublicKeyBinary {
+    /// Returns the tagged binary form
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<PublicKey> for PublicKeyBinary {
+    fn from(public_key: PublicKey) -> Self {
+        Self(public_key.to_vec())
+    }
+}
+
+impl From<&PublicKey> for PublicKeyBinary {
+    fn from(public_key: &PublicKey) -> Self {
+        Self(public_key.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for PublicKeyBinary {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for PublicKeyBinary {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl From<PublicKeyBinary> for Vec<u8> {
+    fn from(binary: PublicKeyBinary) -> Self {
+        binary.0
+    }
+}
+
+impl TryFrom<PublicKeyBinary> for PublicKey {
+    type Error = Error;
+    fn try_from(binary: PublicKeyBinary) -> Result<Self> {
+        Self::try_from(&binary)
+    }
+}
+
+impl TryFrom<&PublicKeyBinary> for PublicKey {
+    type Error = Error;
+    fn try_from(binary: &PublicKeyBinary) -> Result<Self> {
+        Self::try_from(&binary.0[..])
+    }
+}
+
+impl AsRef<[u8]> for PublicKeyBinary {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl FromStr for PublicKeyBinary {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut data = bs58::decode(s).with_check(Some(0)).into_vec()?;
+        data.remove(0);
+        Ok(Self(data))
+    }
+}
+
+impl fmt::Display for PublicKeyBinary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        // prefix the base58 version
+        let data = [&[0u8][..], &self.0].concat();
+        f.write_str(&bs58::encode(&data).with_check().into_string())
+    }
+}
+
+/// Binary forms serialize like public keys, as their base58 string in human
+/// readable formats and as their bytes in other formats.
+impl Serialize for PublicKeyBinary {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKeyBinary {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PublicKeyBinaryVisitor;
+
+        impl<'de> Visitor<'de> for PublicKeyBinaryVisitor {
+            type Value = PublicKeyBinary;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("base58 public key or public key bytes")
+            }
+
+            fn visit_str<E>(self, value: &str) -> std::result::Result<PublicKeyBinary, E>
+            where
+                E: de::Error,
+            {
+                PublicKeyBinary::from_str(value)
+                    .map_err(|_| de::Error::custom("invalid public key"))
+            }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<PublicKeyBinary, E>
+            where
+                E: de::Error,
+            {
+                Ok(PublicKeyBinary::from(value))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(PublicKeyBinaryVisitor)
+        } else {
+            deserializer.deserialize_bytes(PublicKeyBinaryVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "13WvV82S7QN3VMzMSieiGxvuaPKknMtf213E5JwPnboDkUfesKw";
+
+    #[test]
+    fn roundtrip() {
+        let public_key: PublicKey = PUBKEY.parse().expect("public key");
+        let binary = PublicKeyBinary::from(&public_key);
+        assert_eq!(&public_key.to_vec()[..], binary.as_bytes());
+        assert_eq!(PUBKEY, binary.to_string());
+        assert_eq!(binary, PUBKEY.parse().expect("binary"));
+        assert_eq!(public_key, PublicKey::try_from(binary).expect("public key"));
+    }
+
+    #[test]
+    fn serde() {
+        let binary: PublicKeyBinary = PUBKEY.parse().expect("binary");
+        let json = serde_json::to_string(&binary).expect("json");
+        assert_eq!(format!("\"{}\"", PUBKEY), json);
+        assert_eq!(
+            binary,
+            serde_json::from_str::<PublicKeyBinary>(&json).expect("binary")
+        );
+    }
+
+    #[test]
+    fn unvalidated() {
+        // Binary forms of invalid keys only fail when converted to a key
+        let binary = PublicKeyBinary::from(vec![0x01, 0x02, 0x03]);
+        let parsed: PublicKeyBinary = binary.to_string().parse().expect("binary");
+        assert_eq!(binary, parsed);
+        assert!(PublicKey::try_from(&parsed).is_err());
+        assert!("not base58".parse::<PublicKeyBinary>().is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Replay protection for signed messages.
+//!
+//! A [`ReplayGuard`] only accepts a signed message if its timestamp is within
+//! a freshness window around the current time and the message has not been
+//! seen before. Seen messages are tracked by nonce in a [`NonceStore`], which
+//! only needs to remember a nonce until it falls outside the freshness window.
+//!
+//! The current time is read from a [`Clock`], the system time by default.
+//!
+//! The nonce of a signed message is the digest of its signed contents, so
+//! re-encoding a signature does not get a replayed message past the guard.
+use crate::{
+    clock::{Clock, SystemClock},
+    *,
+};
+use std::{collections::HashMap, sync::Mutex};
+
+/// Stores nonces that have been seen until they expire. Implementations are
+/// expected to be shareable between threads, so persistent or distributed
+/// stores can be plugged in.
+pub trait NonceStore {
+    /// Record the given nonce as seen until the given expiry time (in seconds
+    /// since the unix epoch). Returns false if the nonce was already present
+    /// and has not yet expired at the given current time.
+    fn insert(&self, nonce: &[u8], expires_at: u64, now: u64) -> Result<bool>;
+}
+
+/// An in-memory nonce store. Expired nonces are pruned on insert.
+#[derive(Debug, Default)]
+pub struct MemoryNonceStore {
+    nonces: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl MemoryNonceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of nonces currently tracked
+    pub fn len(&self) -> usize {
+        self.nonces.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn insert(&self, nonce: &[u8], expires_at: u64, now: u64) -> Result<bool> {
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.retain(|_, expiry| *expiry >= now);
+        if nonces.contains_key(nonce) {
+            return Ok(false);
+        }
+        nonces.insert(nonce.to_vec(), expires_at);
+        Ok(true)
+    }
+}
+
+/// Enforces freshness and uniqueness of signed messages.
+#[derive(Debug)]
+pub struct ReplayGuard<S: NonceStore, C: Clock = SystemClock> {
+    /// The maximum age, in seconds, of an accepted message
+    pub max_age: u64,
+    /// The maximum number of seconds a message timestamp may be ahead of the
+    /// current time to allow for clock skew between signer and verifier
+    pub max_skew: u64,
+    store: S,
+    clock: C,
+}
+
+impl<S: NonceStore> ReplayGuard<S> {
+    pub fn new(store: S, max_age: u64, max_skew: u64) -> Self {
+        Self {
+            max_age,
+            max_skew,
+            store,
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<S: NonceStore, C: Clock> ReplayGuard<S, C> {
+    /// Replace the clock the current time is read from
+    pub fn with_clock<T: Clock>(self, clock: T) -> ReplayGuard<S, T> {
+        ReplayGuard {
+            max_age: self.max_age,
+            max_skew: self.max_skew,
+            store: self.store,
+            clock,
+        }
+    }
+
+    /// Returns a reference to the nonce store used by this guard
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Verify the given signed message and check that it is fresh and has not
+    /// been seen before, using the current time of the guard's clock. Returns
+    /// the payload of the message if all checks pass.
+    pub fn open<'a>(&self, message: &'a SignedMessage) -> Result<&'a [u8]> {
+        self.open_at(message, self.clock.now())
+    }
+
+    /// Like [`ReplayGuard::open`] but at the given current time
+    pub fn open_at<'a>(&self, message: &'a SignedMessage, now: u64) -> Result<&'a [u8]> {
+        let payload = message.open()?;
+        self.check_nonce_at(&message.digest(), message.timestamp, now)?;
+        Ok(payload)
+    }
+
+    /// Check that the given nonce with the given timestamp is fresh and has
+    /// not been seen before, using the current time of the guard's clock. This
+    /// can be used for protocols that carry their own nonces.
+    pub fn check_nonce(&self, nonce: &[u8], timestamp: u64) -> Result {
+        self.check_nonce_at(nonce, timestamp, self.clock.now())
+    }
+
+    /// Like [`ReplayGuard::check_nonce`] but at the given current time
+    pub fn check_nonce_at(&self, nonce: &[u8], timestamp: u64, now: u64) -> Result {
+        if timestamp.saturating_add(self.max_age) < now
+            || timestamp > now.saturating_add(self.max_skew)
+        {
+            return Err(Error::stale(timestamp));
+        }
+        // The nonce needs to be remembered until the timestamp falls out of
+        // the freshness window, after which the timestamp check rejects it.
+        let expires_at = timestamp.saturating_add(self.max_age);
+        if !self.store.insert(nonce, expires_at, now)? {
+            return Err(Error::replayed());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn replay() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let guard = ReplayGuard::new(MemoryNonceStore::new(), 60, 5);
+        let message = SignedMessage::seal_at(&keypair, b"hello world", 1000).expect("message");
+
+        assert!(guard.open_at(&message, 1010).is_ok());
+        assert!(matches!(
+            guard.open_at(&message, 1020),
+            Err(Error::Replayed)
+        ));
+        // A different message with the same payload is accepted
+        let other = SignedMessage::seal_at(&keypair, b"hello world", 1001).expect("message");
+        assert!(guard.open_at(&other, 1020).is_ok());
+    }
+
+    #[test]
+    fn freshness() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let guard = ReplayGuard::new(MemoryNonceStore::new(), 60, 5);
+        let message = SignedMessage::seal_at(&keypair, b"hello world", 1000).expect("message");
+
+        assert!(matches!(
+            guard.open_at(&message, 1061),
+            Err(Error::Stale(1000))
+        ));
+        assert!(matches!(
+            guard.open_at(&message, 994),
+            Err(Error::Stale(1000))
+        ));
+        assert!(guard.open_at(&message, 995).is_ok());
+    }
+
+    #[test]
+    fn clock() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let clock = ManualClock::new(1000);
+        let guard = ReplayGuard::new(MemoryNonceStore::new(), 60, 5).with_clock(clock.clone());
+        let message = SignedMessage::seal_at(&keypair, b"hello world", 1000).expect("message");
+        let other = SignedMessage::seal_at(&keypair, b"hello world", 1001).expect("message");
+
+        assert!(guard.open(&message).is_ok());
+        clock.advance(62);
+        assert!(matches!(guard.open(&other), Err(Error::Stale(1001))));
+    }
+
+    #[test]
+    fn prune() {
+        let guard = ReplayGuard::new(MemoryNonceStore::new(), 60, 5);
+        guard.check_nonce_at(b"one", 1000, 1000).expect("fresh");
+        guard.check_nonce_at(b"two", 1100, 1100).expect("fresh");
+        assert_eq!(1, guard.store().len());
+    }
+}
@@ -0,0 +1,246 @@
+//! Retrying of keypair operations.
+//!
+//! Hardware and remote backed keypairs can fail transiently, for example when
+//! an I2C bus is briefly busy. A [`RetryPolicy`] describes how often and how
+//! quickly to retry an operation, and which [`ErrorClass`]es are worth
+//! retrying at all. A [`RetryKeypair`] applies a policy to every sign and ecdh
+//! operation of a keypair, whatever its backend.
+use crate::{keypair::SharedSecret, *};
+use std::{thread, time::Duration};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// The delay before the first retry. Each following retry doubles the
+    /// delay, up to the maximum backoff.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// The error classes that are retried
+    pub retryable: Vec<ErrorClass>,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts with a backoff starting at 50ms, retrying transport,
+    /// timeout and unavailable errors. Errors reported by a backend itself
+    /// are not retried.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            retryable: vec![
+                ErrorClass::Transport,
+                ErrorClass::Timeout,
+                ErrorClass::Unavailable,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_retryable(mut self, retryable: &[ErrorClass]) -> Self {
+        self.retryable = retryable.to_vec();
+        self
+    }
+
+    /// Returns whether the given error is retried by this policy
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        self.retryable.contains(&err.class())
+    }
+
+    /// Returns the delay before the given retry, counting from 1
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+
+    /// Run the given operation, retrying retryable errors until it succeeds
+    /// or the maximum number of attempts is reached. The last error is
+    /// returned if all attempts fail.
+    pub fn run<T, F>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(err) if attempt < self.max_attempts && self.is_retryable(&err) => {
+                    thread::sleep(self.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A keypair whose operations are retried according to a retry policy.
+#[derive(Debug, PartialEq)]
+pub struct RetryKeypair {
+    keypair: Keypair,
+    policy: RetryPolicy,
+}
+
+impl RetryKeypair {
+    pub fn new(keypair: Keypair, policy: RetryPolicy) -> Self {
+        Self { keypair, policy }
+    }
+
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.keypair.key_tag()
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        self.keypair.public_key()
+    }
+
+    pub fn ecdh(&self, public_key: &PublicKey) -> Result<SharedSecret> {
+        self.policy.run(|| self.keypair.ecdh(public_key))
+    }
+
+    pub fn into_inner(self) -> Keypair {
+        self.keypair
+    }
+}
+
+impl Sign for RetryKeypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.policy.run(|| self.keypair.sign(msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::default().with_backoff(Duration::from_millis(1), Duration::from_millis(2))
+    }
+
+    #[test]
+    fn retries_retryable() {
+        let mut attempts = 0;
+        let result = policy().run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(Error::timeout())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(3, result.expect("result"));
+    }
+
+    #[test]
+    fn gives_up() {
+        let mut attempts = 0;
+        let result: Result<()> = policy().with_max_attempts(2).run(|| {
+            attempts += 1;
+            Err(Error::overloaded())
+        });
+        assert!(matches!(result, Err(Error::Overloaded)));
+        assert_eq!(2, attempts);
+
+        attempts = 0;
+        let result: Result<()> = policy().run(|| {
+            attempts += 1;
+            Err(Error::invalid_curve())
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+    }
+
+    #[test]
+    fn retries_transport_only() {
+        // A failure reported by a backend itself, surfaced through the signer
+        let device = || -> Error {
+            signature::Error::from_source(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "execution error",
+            ))
+            .into()
+        };
+        let transport = || -> Error {
+            signature::Error::from_source(Error::from(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "bus error",
+            )))
+            .into()
+        };
+        assert_eq!(ErrorClass::Device, device().class());
+        assert_eq!(ErrorClass::Transport, transport().class());
+
+        let mut attempts = 0;
+        let result: Result<()> = policy().run(|| {
+            attempts += 1;
+            Err(device())
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+
+        attempts = 0;
+        let result: Result<()> = policy().run(|| {
+            attempts += 1;
+            Err(transport())
+        });
+        assert!(result.is_err());
+        assert_eq!(3, attempts);
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(50));
+        assert_eq!(Duration::from_millis(10), policy.backoff(1));
+        assert_eq!(Duration::from_millis(20), policy.backoff(2));
+        assert_eq!(Duration::from_millis(40), policy.backoff(3));
+        assert_eq!(Duration::from_millis(50), policy.backoff(4));
+        assert_eq!(Duration::from_millis(50), policy.backoff(64));
+    }
+
+    #[test]
+    fn sign() {
+        let keypair = RetryKeypair::new(
+            Keypair::generate(KeyTag::default(), &mut OsRng),
+            RetryPolicy::default(),
+        );
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_ok());
+    }
+}
@@ -0,0 +1,635 @@
+//! Ring signatures over ed25519 and ecc_compact keys.
+//!
+//! A ring signature proves that the signer holds the secret key for one of a
+//! set (the ring) of public keys, without revealing which one. All keys in a
+//! ring have to be of the same key type, so hotspots can sign with their
+//! ecc_compact keys among other hotspots.
+//!
+//! Signatures can optionally be made linkable within a given scope (for
+//! example a dispute round identifier). A linkable signature carries a key
+//! image which is the same for every signature by the same signer in the same
+//! scope, so double signing can be detected with [`RingSignature::is_linked`]
+//! while the signer still stays anonymous. This follows the LSAG construction
+//! by Liu, Wei and Wong. Without a scope the signature is a plain, unlinkable
+//! AOS ring signature.
+use crate::{ed25519::curve, *};
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar};
+use p256::elliptic_curve::{
+    bigint::U256,
+    ff::PrimeField,
+    group::Curve,
+    ops::Reduce,
+    sec1::{FromEncodedPoint, ToEncodedPoint},
+};
+use sha2::{Digest, Sha256};
+use std::ops::{Add, Mul, Sub};
+
+/// Domain separator used for the ring signature challenges
+const RING_CHALLENGE_DOMAIN: &[u8] = b"helium-ring-challenge";
+/// Domain separator used for the key image base points
+const RING_KEY_IMAGE_DOMAIN: &[u8] = b"helium-ring-key-image";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSignature {
+    /// The initial challenge
+    pub challenge: [u8; 32],
+    /// One response per ring member, in ring order
+    pub responses: Vec<[u8; 32]>,
+    /// The key image of the signer if the signature is linkable, 32 bytes for
+    /// ed25519 rings and 33 bytes for ecc_compact rings
+    pub key_image: Option<Vec<u8>>,
+}
+
+/// Keypairs that can sign as a member of a ring of public keys of their key
+/// type
+pub trait RingKeypair {
+    fn ring_sign<R>(
+        &self,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<RingSignature>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore;
+}
+
+impl RingKeypair for ed25519::Keypair {
+    fn ring_sign<R>(
+        &self,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<RingSignature>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        sign_with::<Edwards25519, R>(
+            &self.public_key,
+            self.secret_scalar(),
+            ring,
+            scope,
+            msg,
+            csprng,
+        )
+    }
+}
+
+impl RingKeypair for ecc_compact::Keypair {
+    fn ring_sign<R>(
+        &self,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<RingSignature>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        sign_with::<p256::NistP256, R>(
+            &self.public_key,
+            *self.secret_scalar(),
+            ring,
+            scope,
+            msg,
+            csprng,
+        )
+    }
+}
+
+impl RingKeypair for Keypair {
+    fn ring_sign<R>(
+        &self,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<RingSignature>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        match self {
+            Self::Ed25519(keypair) => keypair.ring_sign(ring, scope, msg, csprng),
+            Self::EccCompact(keypair) => keypair.ring_sign(ring, scope, msg, csprng),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
+/// The curve operations ring signatures need from the curves of the
+/// supported key types
+trait RingCurve {
+    type Scalar: Copy
+        + PartialEq
+        + Add<Output = Self::Scalar>
+        + Sub<Output = Self::Scalar>
+        + Mul<Output = Self::Scalar>;
+    type Point: Copy + Add<Output = Self::Point> + Mul<Self::Scalar, Output = Self::Point>;
+
+    fn zero() -> Self::Scalar;
+    fn random_scalar<R>(csprng: &mut R) -> Self::Scalar
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore;
+    fn hash_to_scalar(parts: &[&[u8]]) -> Self::Scalar;
+    /// Hash the given parts to a point with unknown discrete logarithm
+    fn hash_to_point(parts: &[&[u8]]) -> Self::Point;
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point;
+    fn encode(point: &Self::Point) -> Vec<u8>;
+    /// Decode a key image, rejecting points outside the prime order subgroup
+    fn decode_key_image(bytes: &[u8]) -> Result<Self::Point>;
+    fn scalar_to_bytes(scalar: &Self::Scalar) -> [u8; 32];
+    fn scalar_from_slice(bytes: &[u8]) -> Result<Self::Scalar>;
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point>;
+}
+
+struct Edwards25519;
+
+impl RingCurve for Edwards25519 {
+    type Scalar = Scalar;
+    type Point = EdwardsPoint;
+
+    fn zero() -> Self::Scalar {
+        Scalar::zero()
+    }
+
+    fn random_scalar<R>(csprng: &mut R) -> Self::Scalar
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        curve::random_scalar(csprng)
+    }
+
+    fn hash_to_scalar(parts: &[&[u8]]) -> Self::Scalar {
+        curve::hash_to_scalar(parts)
+    }
+
+    fn hash_to_point(parts: &[&[u8]]) -> Self::Point {
+        curve::hash_to_point(parts)
+    }
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point {
+        curve::base_mul(scalar)
+    }
+
+    fn encode(point: &Self::Point) -> Vec<u8> {
+        point.compress().as_bytes().to_vec()
+    }
+
+    fn decode_key_image(bytes: &[u8]) -> Result<Self::Point> {
+        let point = curve::decompress(bytes)?;
+        if point.is_small_order() || !point.is_torsion_free() {
+            return Err(signature::Error::new().into());
+        }
+        Ok(point)
+    }
+
+    fn scalar_to_bytes(scalar: &Self::Scalar) -> [u8; 32] {
+        scalar.to_bytes()
+    }
+
+    fn scalar_from_slice(bytes: &[u8]) -> Result<Self::Scalar> {
+        curve::scalar_from_slice(bytes)
+    }
+
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point> {
+        curve::public_key_point(public_key)
+    }
+}
+
+impl RingCurve for p256::NistP256 {
+    type Scalar = p256::Scalar;
+    type Point = p256::ProjectivePoint;
+
+    fn zero() -> Self::Scalar {
+        p256::Scalar::ZERO
+    }
+
+    fn random_scalar<R>(csprng: &mut R) -> Self::Scalar
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        *p256::NonZeroScalar::random(csprng)
+    }
+
+    fn hash_to_scalar(parts: &[&[u8]]) -> Self::Scalar {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        <p256::Scalar as Reduce<U256>>::from_be_bytes_reduced(hasher.finalize())
+    }
+
+    fn hash_to_point(parts: &[&[u8]]) -> Self::Point {
+        // Try and increment over x coordinates. The curve has cofactor one, so
+        // every point found is in the group.
+        let mut counter: u32 = 0;
+        loop {
+            let mut hasher = Sha256::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.update(counter.to_be_bytes());
+            let mut bytes = [0x02; 33];
+            bytes[1..].copy_from_slice(&hasher.finalize());
+            if let Ok(point) = Self::decode_key_image(&bytes) {
+                return point;
+            }
+            counter += 1;
+        }
+    }
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point {
+        p256::ProjectivePoint::GENERATOR * scalar
+    }
+
+    fn encode(point: &Self::Point) -> Vec<u8> {
+        point.to_affine().to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    fn decode_key_image(bytes: &[u8]) -> Result<Self::Point> {
+        // The identity has no 33 byte encoding
+        if bytes.len() != 33 {
+            return Err(Error::invalid_curve());
+        }
+        let encoded = p256::EncodedPoint::from_bytes(bytes).map_err(|_| Error::invalid_curve())?;
+        let point: Option<p256::AffinePoint> =
+            p256::AffinePoint::from_encoded_point(&encoded).into();
+        point
+            .map(Self::Point::from)
+            .ok_or_else(Error::invalid_curve)
+    }
+
+    fn scalar_to_bytes(scalar: &Self::Scalar) -> [u8; 32] {
+        scalar.to_bytes().into()
+    }
+
+    fn scalar_from_slice(bytes: &[u8]) -> Result<Self::Scalar> {
+        if bytes.len() != 32 {
+            return Err(signature::Error::new().into());
+        }
+        let scalar: Option<p256::Scalar> =
+            p256::Scalar::from_repr(p256::FieldBytes::clone_from_slice(bytes)).into();
+        scalar.ok_or_else(|| signature::Error::new().into())
+    }
+
+    fn public_key_point(public_key: &PublicKey) -> Result<Self::Point> {
+        let public_key: &ecc_compact::PublicKey = public_key.try_into()?;
+        Ok(public_key.0.to_projective())
+    }
+}
+
+/// The precomputed ring and scope used to calculate challenges
+struct Ring<'a, C: RingCurve> {
+    points: Vec<C::Point>,
+    scope: Option<&'a [u8]>,
+    prefix: Vec<u8>,
+}
+
+impl<'a, C: RingCurve> Ring<'a, C> {
+    fn new(
+        ring: &[PublicKey],
+        scope: Option<&'a [u8]>,
+        key_image: Option<&C::Point>,
+        msg: &[u8],
+    ) -> Result<Self> {
+        if ring.is_empty() {
+            return Err(signature::Error::new().into());
+        }
+        let points = ring
+            .iter()
+            .map(C::public_key_point)
+            .collect::<Result<Vec<C::Point>>>()?;
+        let mut prefix = RING_CHALLENGE_DOMAIN.to_vec();
+        for point in &points {
+            prefix.extend_from_slice(&C::encode(point));
+        }
+        if let Some(key_image) = key_image {
+            prefix.extend_from_slice(&C::encode(key_image));
+        }
+        prefix.extend_from_slice(msg);
+        Ok(Self {
+            points,
+            scope,
+            prefix,
+        })
+    }
+
+    /// Returns the base point for the key image of the given member
+    fn image_base(&self, index: usize) -> Option<C::Point> {
+        self.scope.map(|scope| {
+            C::hash_to_point(&[
+                RING_KEY_IMAGE_DOMAIN,
+                scope,
+                &C::encode(&self.points[index]),
+            ])
+        })
+    }
+
+    fn challenge(&self, l: &C::Point, r: Option<&C::Point>) -> C::Scalar {
+        let r = r.map(C::encode);
+        C::hash_to_scalar(&[
+            &self.prefix[..],
+            &C::encode(l),
+            r.as_deref().unwrap_or_default(),
+        ])
+    }
+
+    /// Compute the commitments for the member at the given index from its
+    /// response and challenge.
+    fn commitments(
+        &self,
+        index: usize,
+        response: &C::Scalar,
+        challenge: &C::Scalar,
+        key_image: Option<&C::Point>,
+    ) -> (C::Point, Option<C::Point>) {
+        let l = C::base_mul(response) + self.points[index] * *challenge;
+        let r = self
+            .image_base(index)
+            .zip(key_image)
+            .map(|(base, key_image)| base * *response + *key_image * *challenge);
+        (l, r)
+    }
+}
+
+fn sign_with<C: RingCurve, R>(
+    public_key: &PublicKey,
+    secret: C::Scalar,
+    ring: &[PublicKey],
+    scope: Option<&[u8]>,
+    msg: &[u8],
+    csprng: &mut R,
+) -> Result<RingSignature>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    let signer = ring
+        .iter()
+        .position(|member| member == public_key)
+        .ok_or_else(|| Error::from(signature::Error::new()))?;
+    let image_base = Ring::<C>::new(ring, scope, None, msg)?.image_base(signer);
+    let key_image = image_base.map(|base| base * secret);
+    let ring = Ring::<C>::new(ring, scope, key_image.as_ref(), msg)?;
+    let n = ring.points.len();
+
+    let alpha = C::random_scalar(csprng);
+    let mut challenges = vec![C::zero(); n];
+    let mut responses = vec![C::zero(); n];
+    challenges[(signer + 1) % n] = ring.challenge(
+        &C::base_mul(&alpha),
+        image_base.map(|base| base * alpha).as_ref(),
+    );
+    let mut i = (signer + 1) % n;
+    while i != signer {
+        responses[i] = C::random_scalar(csprng);
+        let (l, r) = ring.commitments(i, &responses[i], &challenges[i], key_image.as_ref());
+        challenges[(i + 1) % n] = ring.challenge(&l, r.as_ref());
+        i = (i + 1) % n;
+    }
+    responses[signer] = alpha - challenges[signer] * secret;
+
+    Ok(RingSignature {
+        challenge: C::scalar_to_bytes(&challenges[0]),
+        responses: responses.iter().map(C::scalar_to_bytes).collect(),
+        key_image: key_image.map(|key_image| C::encode(&key_image)),
+    })
+}
+
+impl RingSignature {
+    /// Sign the given message with the given keypair as a member of the given
+    /// ring. If a scope is given the signature is linkable to other signatures
+    /// by the same keypair in the same scope.
+    pub fn sign<K, R>(
+        keypair: &K,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+        csprng: &mut R,
+    ) -> Result<Self>
+    where
+        K: RingKeypair,
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        keypair.ring_sign(ring, scope, msg, csprng)
+    }
+
+    /// Verify the signature over the given message for the given ring and
+    /// scope.
+    pub fn verify(&self, ring: &[PublicKey], scope: Option<&[u8]>, msg: &[u8]) -> Result {
+        match ring.first().map(PublicKey::key_type) {
+            Some(KeyType::Ed25519) => self.verify_with::<Edwards25519>(ring, scope, msg),
+            Some(KeyType::EccCompact) => self.verify_with::<p256::NistP256>(ring, scope, msg),
+            Some(_) => Err(Error::invalid_curve()),
+            None => Err(signature::Error::new().into()),
+        }
+    }
+
+    fn verify_with<C: RingCurve>(
+        &self,
+        ring: &[PublicKey],
+        scope: Option<&[u8]>,
+        msg: &[u8],
+    ) -> Result {
+        if ring.len() != self.responses.len() || scope.is_some() != self.key_image.is_some() {
+            return Err(signature::Error::new().into());
+        }
+        let key_image = self
+            .key_image
+            .as_deref()
+            .map(C::decode_key_image)
+            .transpose()?;
+        let ring = Ring::<C>::new(ring, scope, key_image.as_ref(), msg)?;
+        let initial = C::scalar_from_slice(&self.challenge)?;
+        let mut challenge = initial;
+        for (i, response) in self.responses.iter().enumerate() {
+            let response = C::scalar_from_slice(response)?;
+            let (l, r) = ring.commitments(i, &response, &challenge, key_image.as_ref());
+            challenge = ring.challenge(&l, r.as_ref());
+        }
+        if challenge != initial {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Returns true if both signatures are linkable and were made by the same
+    /// signer. Only signatures verified against the same scope should be
+    /// compared.
+    pub fn is_linked(&self, other: &RingSignature) -> bool {
+        matches!((&self.key_image, &other.key_image), (Some(a), Some(b)) if a == b)
+    }
+
+    /// Convert the signature to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl WriteTo for RingSignature {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let count = u16::try_from(self.responses.len())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "ring too large"))?;
+        output.write_all(&count.to_be_bytes())?;
+        output.write_all(&self.challenge)?;
+        for response in &self.responses {
+            output.write_all(response)?;
+        }
+        // The flag gives the size of the key image: 1 for ed25519 and 2 for
+        // ecc_compact rings
+        match &self.key_image {
+            Some(key_image) => {
+                let flag = match key_image.len() {
+                    32 => 1,
+                    33 => 2,
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "invalid key image",
+                        ))
+                    }
+                };
+                output.write_all(&[flag])?;
+                output.write_all(key_image)
+            }
+            None => output.write_all(&[0]),
+        }
+    }
+}
+
+impl ReadFrom for RingSignature {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut count = [0u8; 2];
+        input.read_exact(&mut count)?;
+        let mut challenge = [0u8; 32];
+        input.read_exact(&mut challenge)?;
+        let mut responses = vec![];
+        for _ in 0..u16::from_be_bytes(count) {
+            let mut response = [0u8; 32];
+            input.read_exact(&mut response)?;
+            responses.push(response);
+        }
+        let mut flag = [0u8];
+        input.read_exact(&mut flag)?;
+        let key_image = match flag[0] {
+            0 => None,
+            1 | 2 => {
+                let mut key_image = vec![0u8; 31 + flag[0] as usize];
+                input.read_exact(&mut key_image)?;
+                Some(key_image)
+            }
+            _ => return Err(signature::Error::new().into()),
+        };
+        Ok(Self {
+            challenge,
+            responses,
+            key_image,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for RingSignature {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn keypairs(n: usize) -> Vec<ed25519::Keypair> {
+        (0..n)
+            .map(|_| ed25519::Keypair::generate(Network::MainNet, &mut OsRng))
+            .collect()
+    }
+
+    fn ring(keypairs: &[ed25519::Keypair]) -> Vec<PublicKey> {
+        keypairs
+            .iter()
+            .map(|keypair| keypair.public_key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn unlinkable() {
+        let keypairs = keypairs(4);
+        let ring = ring(&keypairs);
+        for keypair in &keypairs {
+            let signature =
+                RingSignature::sign(keypair, &ring, None, b"dispute", &mut OsRng).expect("sign");
+            let decoded = RingSignature::try_from(&signature.to_vec()[..]).expect("decoded");
+            assert_eq!(signature, decoded);
+            assert!(decoded.verify(&ring, None, b"dispute").is_ok());
+            assert!(decoded.verify(&ring, None, b"other").is_err());
+            assert!(decoded.verify(&ring[1..], None, b"dispute").is_err());
+        }
+    }
+
+    #[test]
+    fn linkable() {
+        let keypairs = keypairs(3);
+        let ring = ring(&keypairs);
+        let scope = Some(&b"round 1"[..]);
+        let first = RingSignature::sign(&keypairs[1], &ring, scope, b"a", &mut OsRng).expect("a");
+        let second = RingSignature::sign(&keypairs[1], &ring, scope, b"b", &mut OsRng).expect("b");
+        let other = RingSignature::sign(&keypairs[2], &ring, scope, b"a", &mut OsRng).expect("c");
+        assert!(first.verify(&ring, scope, b"a").is_ok());
+        assert!(second.verify(&ring, scope, b"b").is_ok());
+        assert!(other.verify(&ring, scope, b"a").is_ok());
+        assert!(first.verify(&ring, Some(b"round 2"), b"a").is_err());
+        assert!(first.is_linked(&second));
+        assert!(!first.is_linked(&other));
+
+        let next_round =
+            RingSignature::sign(&keypairs[1], &ring, Some(b"round 2"), b"a", &mut OsRng)
+                .expect("next");
+        assert!(!first.is_linked(&next_round));
+    }
+
+    #[test]
+    fn not_a_member() {
+        let keypairs = keypairs(3);
+        let ring = ring(&keypairs[..2]);
+        assert!(RingSignature::sign(&keypairs[2], &ring, None, b"msg", &mut OsRng).is_err());
+    }
+
+    #[test]
+    fn ecc_compact() {
+        let keypairs: Vec<Keypair> = (0..3)
+            .map(|_| {
+                let key_tag = KeyTag {
+                    network: Network::MainNet,
+                    key_type: KeyType::EccCompact,
+                };
+                Keypair::generate(key_tag, &mut OsRng)
+            })
+            .collect();
+        let ring: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect();
+        let scope = Some(&b"round 1"[..]);
+        let first = RingSignature::sign(&keypairs[0], &ring, scope, b"a", &mut OsRng).expect("a");
+        let second = RingSignature::sign(&keypairs[0], &ring, scope, b"b", &mut OsRng).expect("b");
+        let decoded = RingSignature::try_from(&first.to_vec()[..]).expect("decoded");
+        assert_eq!(first, decoded);
+        assert!(decoded.verify(&ring, scope, b"a").is_ok());
+        assert!(second.verify(&ring, scope, b"b").is_ok());
+        assert!(first.verify(&ring, scope, b"b").is_err());
+        assert!(first.is_linked(&second));
+
+        // Rings must not mix key types
+        let mut mixed = ring.clone();
+        mixed[1] = ed25519::Keypair::generate(Network::MainNet, &mut OsRng).public_key;
+        assert!(RingSignature::sign(&keypairs[0], &mixed, None, b"a", &mut OsRng).is_err());
+    }
+}
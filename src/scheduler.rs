@@ -0,0 +1,285 @@
+//! Priority aware scheduling of signing operations.
+//!
+//! A single hardware keypair can only sign one message at a time. A
+//! [`SigningScheduler`] queues signing requests for a shared keypair and runs
+//! them on a worker thread in order of [`Priority`], so latency critical
+//! requests are not starved by bulk signing. Within a priority, requests with
+//! the earliest deadline run first, followed by requests without a deadline in
+//! the order they were submitted.
+//!
+//! A request whose deadline passes while it is queued is not signed and
+//! fails with [`Error::Timeout`].
+use crate::*;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Instant,
+};
+use tokio::sync::oneshot;
+
+/// The priority class of a signing request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Batch work which can wait, like reward signing
+    Bulk,
+    Normal,
+    /// Time sensitive work, like proof of coverage
+    Critical,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+struct Request {
+    priority: Priority,
+    deadline: Option<Instant>,
+    sequence: u64,
+    msg: Vec<u8>,
+    sender: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+impl Request {
+    fn run(self, keypair: &Keypair) {
+        // Skip the work if the caller has given up on the result
+        if self.sender.is_closed() {
+            return;
+        }
+        let result = match self.deadline {
+            Some(deadline) if deadline <= Instant::now() => Err(Error::timeout()),
+            _ => keypair.sign(&self.msg),
+        };
+        // Send errors mean the caller has gone away, which is fine to ignore
+        let _ = self.sender.send(result);
+    }
+}
+
+/// Requests are ordered so that the greatest request is the next one to run
+impl Ord for Request {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| match (self.deadline, other.deadline) {
+                (Some(deadline), Some(other)) => other.cmp(&deadline),
+                (Some(_), None) => Ordering::Greater,
+                (None, Some(_)) => Ordering::Less,
+                (None, None) => Ordering::Equal,
+            })
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Request {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Request {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Request {}
+
+#[derive(Default)]
+struct Queue {
+    requests: BinaryHeap<Request>,
+    sequence: u64,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits for the next request to run. Returns `None` once the scheduler
+    /// is closed and all queued requests have run.
+    fn next(&self) -> Option<Request> {
+        let mut queue = self.lock();
+        loop {
+            if let Some(request) = queue.requests.pop() {
+                return Some(request);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self
+                .available
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// Schedules signing requests for a keypair by priority and deadline.
+///
+/// The worker thread exits once the scheduler is dropped and all queued
+/// requests have run.
+pub struct SigningScheduler {
+    key_tag: KeyTag,
+    public_key: PublicKey,
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for SigningScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("SigningScheduler")
+            .field("tag", &self.key_tag)
+            .field("public", &self.public_key)
+            .field("queue_len", &self.queue_len())
+            .finish()
+    }
+}
+
+impl SigningScheduler {
+    /// Move the given keypair onto a new worker thread
+    pub fn new(keypair: Keypair) -> Result<Self> {
+        let key_tag = keypair.key_tag();
+        let public_key = keypair.public_key().clone();
+        let shared = Arc::new(Shared::default());
+        let worker_shared = shared.clone();
+        thread::Builder::new()
+            .name(format!("helium-crypto-scheduler-{}", key_tag.key_type))
+            .spawn(move || {
+                while let Some(request) = worker_shared.next() {
+                    request.run(&keypair);
+                }
+            })?;
+        Ok(Self {
+            key_tag,
+            public_key,
+            shared,
+        })
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.key_tag
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the number of requests waiting in the queue
+    pub fn queue_len(&self) -> usize {
+        self.shared.lock().requests.len()
+    }
+
+    /// Sign the given message with the given priority. If a deadline is given
+    /// and passes before the message is signed, a timeout error is returned.
+    pub async fn sign(
+        &self,
+        msg: &[u8],
+        priority: Priority,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<u8>> {
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut queue = self.shared.lock();
+            let sequence = queue.sequence;
+            queue.sequence += 1;
+            queue.requests.push(Request {
+                priority,
+                deadline,
+                sequence,
+                msg: msg.to_vec(),
+                sender,
+            });
+        }
+        self.shared.available.notify_one();
+        let result = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), receiver)
+                .await
+                .map_err(|_| Error::timeout())?,
+            None => receiver.await,
+        };
+        result.map_err(|_| Error::worker_unavailable())?
+    }
+}
+
+impl Drop for SigningScheduler {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::time::Duration;
+
+    fn request(priority: Priority, deadline: Option<Instant>, sequence: u64) -> Request {
+        let (sender, _) = oneshot::channel();
+        Request {
+            priority,
+            deadline,
+            sequence,
+            msg: vec![],
+            sender,
+        }
+    }
+
+    #[test]
+    fn order() {
+        let now = Instant::now();
+        let mut requests = BinaryHeap::new();
+        requests.push(request(Priority::Bulk, None, 0));
+        requests.push(request(Priority::Normal, None, 1));
+        requests.push(request(
+            Priority::Normal,
+            Some(now + Duration::from_secs(2)),
+            2,
+        ));
+        requests.push(request(Priority::Critical, None, 3));
+        requests.push(request(
+            Priority::Normal,
+            Some(now + Duration::from_secs(1)),
+            4,
+        ));
+        requests.push(request(Priority::Normal, None, 5));
+        let order: Vec<u64> = std::iter::from_fn(|| requests.pop())
+            .map(|request| request.sequence)
+            .collect();
+        assert_eq!(vec![3, 4, 2, 1, 5, 0], order);
+    }
+
+    #[tokio::test]
+    async fn sign() {
+        let scheduler = SigningScheduler::new(Keypair::generate(KeyTag::default(), &mut OsRng))
+            .expect("scheduler");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let signature = scheduler
+            .sign(b"hello world", Priority::Critical, Some(deadline))
+            .await
+            .expect("signature");
+        assert!(scheduler
+            .public_key()
+            .verify(b"hello world", &signature)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn expired_deadline() {
+        let scheduler = SigningScheduler::new(Keypair::generate(KeyTag::default(), &mut OsRng))
+            .expect("scheduler");
+        let result = scheduler
+            .sign(b"hello world", Priority::Bulk, Some(Instant::now()))
+            .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+}
@@ -0,0 +1,370 @@
+//! The SE050 IoT applet commands used by the keypair.
+//!
+//! Commands are ISO 7816 APDUs with the applet class byte. Their arguments
+//! are BER-TLVs tagged `TAG_1`, `TAG_2`, and so on, and so are the values in
+//! their responses. Keys live in secure objects addressed by a 32 bit object
+//! id, and EC keys refer to a curve that has to be created in the applet
+//! before first use.
+use super::{t1::T1, Error};
+use std::io::{Read, Write};
+
+const CLA: u8 = 0x80;
+const INS_WRITE: u8 = 0x01;
+const INS_READ: u8 = 0x02;
+const INS_CRYPTO: u8 = 0x03;
+const INS_MGMT: u8 = 0x04;
+
+const P1_DEFAULT: u8 = 0x00;
+const P1_EC: u8 = 0x01;
+const P1_KEY_PAIR: u8 = 0x60;
+const P1_CURVE: u8 = 0x0B;
+const P1_SIGNATURE: u8 = 0x0C;
+
+const P2_DEFAULT: u8 = 0x00;
+const P2_CREATE: u8 = 0x04;
+const P2_SIGN: u8 = 0x09;
+const P2_DH: u8 = 0x0F;
+const P2_LIST: u8 = 0x25;
+const P2_EXIST: u8 = 0x27;
+const P2_DELETE_OBJECT: u8 = 0x28;
+const P2_PARAM: u8 = 0x40;
+
+const TAG_1: u8 = 0x41;
+const TAG_2: u8 = 0x42;
+const TAG_3: u8 = 0x43;
+
+const RESULT_SUCCESS: u8 = 0x01;
+const SET_INDICATOR_SET: u8 = 0x01;
+const SW_OK: u16 = 0x9000;
+
+const CURVE_NIST_P256: u8 = 0x03;
+const ALGO_ECDSA_SHA_256: u8 = 0x21;
+
+const CURVE_PARAM_A: u8 = 0x01;
+const CURVE_PARAM_B: u8 = 0x02;
+const CURVE_PARAM_G: u8 = 0x04;
+const CURVE_PARAM_N: u8 = 0x08;
+const CURVE_PARAM_PRIME: u8 = 0x10;
+
+/// The application id of the IoT applet
+const APPLET_AID: [u8; 16] = [
+    0xA0, 0x00, 0x00, 0x03, 0x96, 0x54, 0x53, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The domain parameters of the NIST P-256 curve
+const P256_PARAMS: [(u8, &[u8]); 5] = [
+    (
+        CURVE_PARAM_A,
+        &[
+            0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFC,
+        ],
+    ),
+    (
+        CURVE_PARAM_B,
+        &[
+            0x5A, 0xC6, 0x35, 0xD8, 0xAA, 0x3A, 0x93, 0xE7, 0xB3, 0xEB, 0xBD, 0x55, 0x76, 0x98,
+            0x86, 0xBC, 0x65, 0x1D, 0x06, 0xB0, 0xCC, 0x53, 0xB0, 0xF6, 0x3B, 0xCE, 0x3C, 0x3E,
+            0x27, 0xD2, 0x60, 0x4B,
+        ],
+    ),
+    (
+        CURVE_PARAM_G,
+        &[
+            0x04, 0x6B, 0x17, 0xD1, 0xF2, 0xE1, 0x2C, 0x42, 0x47, 0xF8, 0xBC, 0xE6, 0xE5, 0x63,
+            0xA4, 0x40, 0xF2, 0x77, 0x03, 0x7D, 0x81, 0x2D, 0xEB, 0x33, 0xA0, 0xF4, 0xA1, 0x39,
+            0x45, 0xD8, 0x98, 0xC2, 0x96, 0x4F, 0xE3, 0x42, 0xE2, 0xFE, 0x1A, 0x7F, 0x9B, 0x8E,
+            0xE7, 0xEB, 0x4A, 0x7C, 0x0F, 0x9E, 0x16, 0x2B, 0xCE, 0x33, 0x57, 0x6B, 0x31, 0x5E,
+            0xCE, 0xCB, 0xB6, 0x40, 0x68, 0x37, 0xBF, 0x51, 0xF5,
+        ],
+    ),
+    (
+        CURVE_PARAM_N,
+        &[
+            0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xBC, 0xE6, 0xFA, 0xAD, 0xA7, 0x17, 0x9E, 0x84, 0xF3, 0xB9, 0xCA, 0xC2,
+            0xFC, 0x63, 0x25, 0x51,
+        ],
+    ),
+    (
+        CURVE_PARAM_PRIME,
+        &[
+            0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFF,
+        ],
+    ),
+];
+
+/// The length of an uncompressed SEC1 P-256 point
+const POINT_LENGTH: usize = 65;
+
+/// Appends the given value to the given buffer as a BER-TLV with the given
+/// tag
+fn push_tlv(buf: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    buf.push(tag);
+    match value.len() {
+        len if len < 0x80 => buf.push(len as u8),
+        len if len <= 0xFF => buf.extend_from_slice(&[0x81, len as u8]),
+        len => {
+            buf.push(0x82);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    buf.extend_from_slice(value);
+}
+
+/// Returns the value of the first BER-TLV with the given tag in the given
+/// response data
+fn find_tlv(data: &[u8], tag: u8) -> Result<&[u8], Error> {
+    let mut rest = data;
+    while let [found, first, tail @ ..] = rest {
+        let (len, tail) = match (*first, tail) {
+            (len, tail) if len < 0x80 => (len as usize, tail),
+            (0x81, [len, tail @ ..]) => (*len as usize, tail),
+            (0x82, [hi, lo, tail @ ..]) => (u16::from_be_bytes([*hi, *lo]) as usize, tail),
+            _ => break,
+        };
+        if tail.len() < len {
+            break;
+        }
+        if *found == tag {
+            return Ok(&tail[..len]);
+        }
+        rest = &tail[len..];
+    }
+    Err(Error::Protocol("missing response value"))
+}
+
+/// Encodes a command APDU expecting response data, using the extended length
+/// form only when the command data does not fit the short form
+fn command(ins: u8, p1: u8, p2: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![CLA, ins, p1, p2];
+    match data.len() {
+        0 => apdu.push(0x00),
+        len if len <= 0xFF => {
+            apdu.push(len as u8);
+            apdu.extend_from_slice(data);
+            apdu.push(0x00);
+        }
+        len => {
+            apdu.push(0x00);
+            apdu.extend_from_slice(&(len as u16).to_be_bytes());
+            apdu.extend_from_slice(data);
+            apdu.extend_from_slice(&[0x00, 0x00]);
+        }
+    }
+    apdu
+}
+
+/// A session with the IoT applet of an SE050
+pub(crate) struct Se050<T> {
+    t1: T1<T>,
+}
+
+impl<T: Read + Write> Se050<T> {
+    /// Resets the interface of the secure element on the given bus and
+    /// selects the IoT applet
+    pub(crate) fn open(bus: T) -> Result<Self, Error> {
+        let mut t1 = T1::new(bus);
+        t1.soft_reset()?;
+        let mut se050 = Self { t1 };
+        let mut select = vec![0x00, 0xA4, 0x04, 0x00, APPLET_AID.len() as u8];
+        select.extend_from_slice(&APPLET_AID);
+        select.push(0x00);
+        se050.exchange(&select)?;
+        Ok(se050)
+    }
+
+    fn exchange(&mut self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut response = self.t1.transceive(apdu)?;
+        if response.len() < 2 {
+            return Err(Error::Protocol("truncated response"));
+        }
+        let sw = response.split_off(response.len() - 2);
+        match u16::from_be_bytes([sw[0], sw[1]]) {
+            SW_OK => Ok(response),
+            sw => Err(Error::Status(sw)),
+        }
+    }
+
+    fn transmit(
+        &mut self,
+        ins: u8,
+        p1: u8,
+        p2: u8,
+        tlvs: &[(u8, &[u8])],
+    ) -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        for (tag, value) in tlvs {
+            push_tlv(&mut data, *tag, value);
+        }
+        self.exchange(&command(ins, p1, p2, &data))
+    }
+
+    /// Returns whether a secure object with the given id exists
+    pub(crate) fn object_exists(&mut self, id: u32) -> Result<bool, Error> {
+        let response = self.transmit(
+            INS_MGMT,
+            P1_DEFAULT,
+            P2_EXIST,
+            &[(TAG_1, &id.to_be_bytes())],
+        )?;
+        Ok(find_tlv(&response, TAG_1)? == [RESULT_SUCCESS])
+    }
+
+    pub(crate) fn delete_object(&mut self, id: u32) -> Result<(), Error> {
+        self.transmit(
+            INS_MGMT,
+            P1_DEFAULT,
+            P2_DELETE_OBJECT,
+            &[(TAG_1, &id.to_be_bytes())],
+        )?;
+        Ok(())
+    }
+
+    /// Creates the P-256 curve in the applet unless it was created before
+    fn ensure_curve(&mut self) -> Result<(), Error> {
+        let response = self.transmit(INS_READ, P1_CURVE, P2_LIST, &[])?;
+        let curves = find_tlv(&response, TAG_1)?;
+        if curves.get(CURVE_NIST_P256 as usize - 1) == Some(&SET_INDICATOR_SET) {
+            return Ok(());
+        }
+        self.transmit(
+            INS_WRITE,
+            P1_CURVE,
+            P2_CREATE,
+            &[(TAG_1, &[CURVE_NIST_P256])],
+        )?;
+        for (param, value) in P256_PARAMS.iter() {
+            self.transmit(
+                INS_WRITE,
+                P1_CURVE,
+                P2_PARAM,
+                &[
+                    (TAG_1, &[CURVE_NIST_P256]),
+                    (TAG_2, &[*param]),
+                    (TAG_3, value),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Generates a P-256 key pair in a new secure object with the given id
+    pub(crate) fn generate_p256(&mut self, id: u32) -> Result<(), Error> {
+        self.ensure_curve()?;
+        self.transmit(
+            INS_WRITE,
+            P1_EC | P1_KEY_PAIR,
+            P2_DEFAULT,
+            &[(TAG_1, &id.to_be_bytes()), (TAG_2, &[CURVE_NIST_P256])],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the uncompressed SEC1 public key point of the key in the
+    /// secure object with the given id
+    pub(crate) fn public_key(&mut self, id: u32) -> Result<Vec<u8>, Error> {
+        let response = self.transmit(
+            INS_READ,
+            P1_DEFAULT,
+            P2_DEFAULT,
+            &[(TAG_1, &id.to_be_bytes())],
+        )?;
+        // Depending on the applet version the point is returned bare or
+        // wrapped in a SubjectPublicKeyInfo, where it comes last
+        let value = find_tlv(&response, TAG_1)?;
+        match value.len().checked_sub(POINT_LENGTH) {
+            Some(start) if value[start] == 0x04 => Ok(value[start..].to_vec()),
+            _ => Err(Error::Protocol("invalid public key")),
+        }
+    }
+
+    /// Signs the given SHA-256 digest with the key in the secure object with
+    /// the given id, returning the DER encoded signature
+    pub(crate) fn sign(&mut self, id: u32, digest: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = self.transmit(
+            INS_CRYPTO,
+            P1_SIGNATURE,
+            P2_SIGN,
+            &[
+                (TAG_1, &id.to_be_bytes()),
+                (TAG_2, &[ALGO_ECDSA_SHA_256]),
+                (TAG_3, digest),
+            ],
+        )?;
+        Ok(find_tlv(&response, TAG_1)?.to_vec())
+    }
+
+    /// Returns the x coordinate of the shared point of the key in the secure
+    /// object with the given id and the given uncompressed SEC1 point
+    pub(crate) fn ecdh(&mut self, id: u32, point: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = self.transmit(
+            INS_CRYPTO,
+            P1_EC,
+            P2_DH,
+            &[(TAG_1, &id.to_be_bytes()), (TAG_2, point)],
+        )?;
+        Ok(find_tlv(&response, TAG_1)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::se050::t1::tests::MockBus;
+
+    #[test]
+    fn tlvs() {
+        let mut buf = vec![];
+        push_tlv(&mut buf, TAG_1, &[0x01, 0x02]);
+        push_tlv(&mut buf, TAG_2, &[0xAA; 0x80]);
+        push_tlv(&mut buf, TAG_3, &[0xBB; 0x100]);
+        assert_eq!(&[TAG_1, 0x02, 0x01, 0x02, TAG_2, 0x81, 0x80], &buf[..7]);
+        assert_eq!(&[TAG_3, 0x82, 0x01, 0x00], &buf[0x87..0x8B]);
+        assert_eq!(&[0x01, 0x02], find_tlv(&buf, TAG_1).expect("tag 1"));
+        assert_eq!(0x80, find_tlv(&buf, TAG_2).expect("tag 2").len());
+        assert_eq!(0x100, find_tlv(&buf, TAG_3).expect("tag 3").len());
+        assert!(find_tlv(&buf, 0x44).is_err());
+        assert!(find_tlv(&buf[..0x90], TAG_3).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            vec![CLA, INS_READ, 0x0B, 0x25, 0x00],
+            command(INS_READ, 0x0B, 0x25, &[])
+        );
+        assert_eq!(
+            vec![CLA, INS_MGMT, 0x00, 0x27, 0x02, TAG_1, 0x00, 0x00],
+            command(INS_MGMT, 0x00, 0x27, &[TAG_1, 0x00])
+        );
+        let extended = command(INS_WRITE, 0x00, 0x00, &[0xAA; 0x100]);
+        assert_eq!(&[0x00, 0x01, 0x00], &extended[4..7]);
+        assert_eq!(&[0x00, 0x00], &extended[extended.len() - 2..]);
+        assert_eq!(4 + 3 + 0x100 + 2, extended.len());
+    }
+
+    #[test]
+    fn session() {
+        let mut bus = MockBus::default();
+        bus.respond(0xEF, &[]);
+        bus.respond(0x00, &[0x90, 0x00]);
+        bus.respond(0x40, &[TAG_1, 0x01, RESULT_SUCCESS, 0x90, 0x00]);
+        bus.respond(0x00, &[0x6A, 0x80]);
+        let mut se050 = Se050::open(bus).expect("session");
+        assert!(se050.object_exists(0x1234_5678).expect("exists"));
+        assert!(matches!(
+            se050.public_key(0x1234_5678),
+            Err(Error::Status(0x6A80))
+        ));
+        let written = &se050.t1.bus().written;
+        // The object id is sent big endian in the first tag
+        assert_eq!(
+            &[CLA, INS_MGMT, P1_DEFAULT, P2_EXIST, 0x06, TAG_1, 0x04, 0x12, 0x34, 0x56, 0x78],
+            &written[2][3..14]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Keypairs backed by a key in an NXP SE050 secure element.
+//!
+//! The SE050 is talked to over I2C using the T=1 over I2C block protocol and
+//! the commands of its IoT applet. Keys are P-256 keys held in secure objects
+//! addressed by a 32 bit object id. Messages are hashed with SHA-256 in
+//! software and the digest is signed by the secure element, and ECDH is done
+//! by the secure element as well. Like ECC608 keys, the key must have a
+//! compactable public key, and the keypair is an ecc_compact keypair.
+//!
+//! Like the ECC608, a single global connection to the secure element is
+//! shared by all keypairs, and must be opened with [`init`] before use.
+use crate::{
+    deadline,
+    ecc_compact::{self, IsCompactable, Signature},
+    health::{FailureCount, Health},
+    keypair, public_key, telemetry, KeyTag, KeyType as CrateKeyType, Network, Result,
+};
+use i2c_linux::I2c;
+use lazy_static::lazy_static;
+use p256::ecdsa;
+use sha2::{Digest, Sha256};
+use std::{
+    convert::{TryFrom, TryInto},
+    fs::File,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use thiserror::Error;
+
+mod apdu;
+mod t1;
+
+use apdu::Se050;
+
+/// The default I2C address of the SE050
+pub const DEFAULT_ADDRESS: u16 = 0x48;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+
+    #[error("frame checksum mismatch")]
+    Crc,
+
+    #[error("protocol error: {0}")]
+    Protocol(&'static str),
+
+    #[error("command failed with status {0:#06x}")]
+    Status(u16),
+
+    #[error("secure object {0:#010x} already exists")]
+    ObjectExists(u32),
+}
+
+/// The global SE050 along with what is needed to reopen it
+struct Connection {
+    se050: Se050<I2c<File>>,
+    path: String,
+    address: u16,
+    failures: FailureCount,
+}
+
+impl Connection {
+    fn reopen(&mut self) -> Result {
+        self.se050 = open(&self.path, self.address)?;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref SE050: Mutex<Option<Connection>> = Mutex::new(None);
+}
+
+/// Opens the I2C bus at the given path, resets the secure element at the
+/// given address and selects its IoT applet
+fn open(path: &str, address: u16) -> std::result::Result<Se050<I2c<File>>, Error> {
+    let mut bus = I2c::from_path(path)?;
+    bus.smbus_set_slave_address(address, false)?;
+    Se050::open(bus)
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    object_id: u32,
+    timeout: Option<Duration>,
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("object_id", &self.object_id)
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature = self.sign_digest(Sha256::new().chain_update(msg))?;
+        Ok(signature.to_vec())
+    }
+}
+
+/// Opens the global SE050 on the I2C bus at the given path, for example
+/// `/dev/i2c-1`, with the secure element at the given address, usually
+/// [`DEFAULT_ADDRESS`].
+pub fn init(path: &str, address: u16) -> Result {
+    init_with_timeout(path, address, None)
+}
+
+/// Initializes the global SE050 like [`init`], failing with a timeout error
+/// if the SE050 can not be opened in the given time.
+pub fn init_with_timeout(path: &str, address: u16, timeout: Option<Duration>) -> Result {
+    if connection().is_some() {
+        return Ok(());
+    }
+    let open_path = path.to_string();
+    let se050 = telemetry::span("init", None, "se050")
+        .in_scope(|| deadline::run(timeout, move || Ok(open(&open_path, address)?)))?;
+    connection().get_or_insert_with(|| Connection {
+        se050,
+        path: path.to_string(),
+        address,
+        failures: FailureCount::default(),
+    });
+    Ok(())
+}
+
+/// Returns the health of the global SE050 connection
+pub fn health() -> Health {
+    connection()
+        .as_ref()
+        .map_or(Health::Uninitialized, |connection| {
+            connection.failures.health()
+        })
+}
+
+/// Reopens the global SE050 using the path and address it was initialized
+/// with. Fails with a closed error if the SE050 was not initialized.
+pub fn reconnect() -> Result {
+    connection()
+        .as_mut()
+        .ok_or_else(crate::Error::closed)?
+        .reopen()
+}
+
+/// Closes the global SE050 once any in-flight operation completes. Later
+/// operations fail with a closed error until the SE050 is initialized again.
+pub fn shutdown() {
+    *connection() = None;
+}
+
+/// Locks the global SE050 connection. A panic while holding the lock leaves
+/// the connection itself intact, so a poisoned lock is recovered.
+fn connection() -> MutexGuard<'static, Option<Connection>> {
+    SE050.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs the given operation on the global SE050. If the operation fails, the
+/// SE050 is reset and the operation is replayed once.
+fn supervised<F, R>(f: F) -> Result<R>
+where
+    F: Fn(&mut Se050<I2c<File>>) -> std::result::Result<R, Error>,
+{
+    let mut connection = connection();
+    let connection = connection.as_mut().ok_or_else(crate::Error::closed)?;
+    let mut result = f(&mut connection.se050);
+    if result.is_err() && connection.reopen().is_ok() {
+        result = f(&mut connection.se050);
+    }
+    connection.failures.record(&result);
+    Ok(result?)
+}
+
+impl Keypair {
+    /// Constructs a keypair from the P-256 key in the secure object with the
+    /// given id.
+    ///
+    /// NOTE: The init function _must_ have been called once, before using
+    /// this function.
+    pub fn from_object(network: Network, object_id: u32) -> Result<Keypair> {
+        let point = supervised(|se050| se050.public_key(object_id))?;
+        let public_key = ecc_compact::PublicKey::try_from(&point[..])?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            object_id,
+            timeout: None,
+        })
+    }
+
+    /// Generates a new P-256 key in a secure object with the given id and
+    /// constructs a keypair from it. Keys whose public key is not compactable
+    /// are discarded and generated again. Fails if the secure object already
+    /// exists, so an existing key is never replaced.
+    ///
+    /// NOTE: The init function _must_ have been called once, before using
+    /// this function.
+    pub fn generate(network: Network, object_id: u32) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: CrateKeyType::EccCompact,
+        };
+        telemetry::span("generate", Some(key_tag), "se050").in_scope(|| -> Result {
+            let mut connection = connection();
+            let se050 = &mut connection.as_mut().ok_or_else(crate::Error::closed)?.se050;
+            if se050.object_exists(object_id)? {
+                return Err(Error::ObjectExists(object_id).into());
+            }
+            loop {
+                se050.generate_p256(object_id)?;
+                let point = se050.public_key(object_id)?;
+                if p256::PublicKey::from_sec1_bytes(&point)?.is_compactable() {
+                    return Ok(());
+                }
+                se050.delete_object(object_id)?;
+            }
+        })?;
+        Self::from_object(network, object_id)
+    }
+
+    /// Set the timeout for sign and ecdh operations on this keypair. An
+    /// operation which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: CrateKeyType::EccCompact,
+        }
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        let object_id = self.object_id;
+        let digest = digest.finalize();
+        let der = deadline::run(self.timeout, move || {
+            supervised(|se050| se050.sign(object_id, &digest))
+        })?;
+        let signature = ecdsa::Signature::from_der(&der)?;
+        Ok(Signature(signature))
+    }
+
+    pub fn ecdh<'a, C>(&self, public_key: C) -> Result<ecc_compact::SharedSecret>
+    where
+        C: TryInto<&'a ecc_compact::PublicKey, Error = crate::Error>,
+    {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let key = public_key.try_into()?;
+        let point = key.0.to_encoded_point(false).as_bytes().to_vec();
+        let object_id = self.object_id;
+        let shared_secret_bytes = deadline::run(self.timeout, move || {
+            supervised(|se050| se050.ecdh(object_id, &point))
+        })?;
+        if shared_secret_bytes.len() != 32 {
+            return Err(Error::Protocol("invalid shared secret").into());
+        }
+        Ok(ecc_compact::SharedSecret(p256::ecdh::SharedSecret::from(
+            *p256::FieldBytes::from_slice(&shared_secret_bytes),
+        )))
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
+    }
+}
@@ -0,0 +1,318 @@
+//! The T=1 over I2C block protocol the SE050 speaks.
+//!
+//! Every frame is `NAD || PCB || LEN || INF || CRC`, where the CRC is the
+//! CRC-16/X-25 of the rest of the frame sent least significant byte first.
+//! APDUs longer than the information field size of the secure element are
+//! sent as a chain of I-blocks, each acknowledged with an R-block, and long
+//! responses are received the same way. The secure element NACKs reads on
+//! the bus while it is busy, so reads are polled, and it asks for more time
+//! with waiting time extension S-blocks.
+use super::Error;
+use std::{
+    io::{Read, Write},
+    thread,
+    time::Duration,
+};
+
+/// Node address of frames from the host to the secure element
+const NAD_HOST: u8 = 0x5A;
+/// Node address of frames from the secure element to the host
+const NAD_SE: u8 = 0xA5;
+
+const PCB_I_SEQ: u8 = 0x40;
+const PCB_I_MORE: u8 = 0x20;
+const PCB_R: u8 = 0x80;
+const PCB_R_SEQ: u8 = 0x10;
+const PCB_R_EDC_ERROR: u8 = 0x01;
+const PCB_S_REQUEST: u8 = 0xC0;
+const PCB_S_RESPONSE: u8 = 0xE0;
+const S_WTX: u8 = 0x03;
+const S_SOFT_RESET: u8 = 0x0F;
+
+/// The information field size to use until the ATR says otherwise
+const DEFAULT_IFSC: usize = 254;
+const MAX_RETRANSMISSIONS: usize = 3;
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Give up on a response after this many polls, about two seconds. Waiting
+/// time extensions start a new round of polls.
+const MAX_POLLS: usize = 2000;
+
+/// Returns the CRC-16/X-25 of the given data
+pub(crate) fn crc(data: &[u8]) -> u16 {
+    let crc = data.iter().fold(0xFFFFu16, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            }
+        })
+    });
+    crc ^ 0xFFFF
+}
+
+fn frame(pcb: u8, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![NAD_HOST, pcb, data.len() as u8];
+    result.extend_from_slice(data);
+    let crc = crc(&result);
+    result.extend_from_slice(&crc.to_le_bytes());
+    result
+}
+
+/// A T=1 session over the given bus
+pub(crate) struct T1<T> {
+    bus: T,
+    send_seq: bool,
+    recv_seq: bool,
+    ifsc: usize,
+}
+
+impl<T: Read + Write> T1<T> {
+    pub(crate) fn new(bus: T) -> Self {
+        Self {
+            bus,
+            send_seq: false,
+            recv_seq: false,
+            ifsc: DEFAULT_IFSC,
+        }
+    }
+
+    /// Resets the secure element interface and both sequence numbers, and
+    /// returns the answer to reset
+    pub(crate) fn soft_reset(&mut self) -> Result<Vec<u8>, Error> {
+        let (pcb, atr) = self.exchange(frame(PCB_S_REQUEST | S_SOFT_RESET, &[]))?;
+        if pcb != PCB_S_RESPONSE | S_SOFT_RESET {
+            return Err(Error::Protocol("unexpected soft reset response"));
+        }
+        self.send_seq = false;
+        self.recv_seq = false;
+        // The ATR holds the protocol version, the vendor id, and the length
+        // of the link layer parameters, which start with the block waiting
+        // time and the information field size
+        if let [_, _, _, _, _, _, len, _, _, hi, lo, ..] = atr[..] {
+            if len >= 4 && lo > 0 {
+                self.ifsc = usize::from(u16::from_be_bytes([hi, lo])).min(DEFAULT_IFSC);
+            }
+        }
+        Ok(atr)
+    }
+
+    /// Sends the given APDU and returns the response APDU
+    pub(crate) fn transceive(&mut self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+        let chunks: Vec<&[u8]> = apdu.chunks(self.ifsc).collect();
+        let mut block = (0, vec![]);
+        for (index, chunk) in chunks.iter().enumerate() {
+            let more = index + 1 < chunks.len();
+            let mut pcb = if self.send_seq { PCB_I_SEQ } else { 0 };
+            if more {
+                pcb |= PCB_I_MORE;
+            }
+            let mut retransmissions = 0;
+            block = loop {
+                let (pcb, data) = self.exchange(frame(pcb, chunk))?;
+                // An R-block acknowledges a chained block by asking for the
+                // next one, anything else asks for a retransmission
+                let is_r_block = pcb & 0xC0 == PCB_R;
+                let acknowledged = is_r_block
+                    && pcb & PCB_R_EDC_ERROR == 0
+                    && (pcb & PCB_R_SEQ != 0) != self.send_seq;
+                if !is_r_block || (more && acknowledged) {
+                    break (pcb, data);
+                }
+                retransmissions += 1;
+                if retransmissions > MAX_RETRANSMISSIONS {
+                    return Err(Error::Protocol("too many retransmissions"));
+                }
+            };
+            self.send_seq = !self.send_seq;
+        }
+
+        let mut response = vec![];
+        loop {
+            let (pcb, data) = block;
+            if pcb & 0x80 != 0 {
+                return Err(Error::Protocol("expected an I-block"));
+            }
+            if (pcb & PCB_I_SEQ != 0) != self.recv_seq {
+                return Err(Error::Protocol("unexpected sequence number"));
+            }
+            self.recv_seq = !self.recv_seq;
+            response.extend_from_slice(&data);
+            if pcb & PCB_I_MORE == 0 {
+                return Ok(response);
+            }
+            block = self.exchange(frame(self.r_block(), &[]))?;
+        }
+    }
+
+    /// The PCB of an R-block asking for the next block from the secure element
+    fn r_block(&self) -> u8 {
+        if self.recv_seq {
+            PCB_R | PCB_R_SEQ
+        } else {
+            PCB_R
+        }
+    }
+
+    /// Writes the given frame and reads the answering block, granting any
+    /// waiting time extensions and asking for corrupted blocks to be sent
+    /// again
+    fn exchange(&mut self, frame: Vec<u8>) -> Result<(u8, Vec<u8>), Error> {
+        let mut frame = frame;
+        for _ in 0..=MAX_RETRANSMISSIONS {
+            self.bus.write_all(&frame)?;
+            loop {
+                match self.read_block() {
+                    Ok((pcb, data)) if pcb == PCB_S_REQUEST | S_WTX => {
+                        self.bus
+                            .write_all(&self::frame(PCB_S_RESPONSE | S_WTX, &data))?;
+                    }
+                    Ok(block) => return Ok(block),
+                    Err(Error::Crc) => {
+                        frame = self::frame(self.r_block() | PCB_R_EDC_ERROR, &[]);
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Err(Error::Protocol("too many retransmissions"))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn bus(&self) -> &T {
+        &self.bus
+    }
+
+    fn read_block(&mut self) -> Result<(u8, Vec<u8>), Error> {
+        let mut header = [0u8; 3];
+        let mut polls = 0;
+        loop {
+            match self.bus.read_exact(&mut header) {
+                Ok(()) if header[0] == NAD_SE => break,
+                _ if polls < MAX_POLLS => {
+                    polls += 1;
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Ok(()) => return Err(Error::Protocol("invalid node address")),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let mut rest = vec![0u8; header[2] as usize + 2];
+        self.bus.read_exact(&mut rest)?;
+        let (data, received_crc) = rest.split_at(header[2] as usize);
+        if crc(&[&header[..], data].concat()).to_le_bytes() != received_crc {
+            return Err(Error::Crc);
+        }
+        Ok((header[1], data.to_vec()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A bus that records written frames and reads back scripted frames
+    #[derive(Default)]
+    pub(crate) struct MockBus {
+        pub(crate) written: Vec<Vec<u8>>,
+        pub(crate) readable: VecDeque<u8>,
+    }
+
+    impl MockBus {
+        /// Queue a frame from the secure element
+        pub(crate) fn respond(&mut self, pcb: u8, data: &[u8]) {
+            let mut block = frame(pcb, data);
+            block[0] = NAD_SE;
+            let crc = crc(&block[..block.len() - 2]);
+            let len = block.len();
+            block[len - 2..].copy_from_slice(&crc.to_le_bytes());
+            self.readable.extend(block);
+        }
+    }
+
+    impl Read for MockBus {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.readable.len() < buf.len() {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            for byte in buf.iter_mut() {
+                *byte = self.readable.pop_front().unwrap_or_default();
+            }
+            Ok(buf.len())
+        }
+    }
+
+    impl Write for MockBus {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn crc_check() {
+        assert_eq!(0x906E, crc(b"123456789"));
+        assert_eq!(
+            vec![NAD_HOST, 0xCF, 0x00, 0x37, 0x7F],
+            frame(PCB_S_REQUEST | S_SOFT_RESET, &[])
+        );
+    }
+
+    #[test]
+    fn soft_reset() {
+        let mut bus = MockBus::default();
+        bus.respond(
+            PCB_S_RESPONSE | S_SOFT_RESET,
+            &[
+                0x01, 0xA0, 0x00, 0x00, 0x03, 0x96, 0x04, 0x03, 0xE8, 0x00, 0x10, 0x02, 0x0B,
+            ],
+        );
+        let mut t1 = T1::new(bus);
+        t1.soft_reset().expect("atr");
+        assert_eq!(16, t1.ifsc);
+    }
+
+    #[test]
+    fn chained() {
+        let mut bus = MockBus::default();
+        // The chained command is acknowledged, a waiting time extension is
+        // requested, and the response is chained too
+        bus.respond(PCB_R | PCB_R_SEQ, &[]);
+        bus.respond(PCB_S_REQUEST | S_WTX, &[0x01]);
+        bus.respond(PCB_I_MORE, &[1, 2]);
+        bus.respond(PCB_I_SEQ, &[3, 4]);
+        let mut t1 = T1::new(bus);
+        t1.ifsc = 4;
+        assert_eq!(
+            vec![1, 2, 3, 4],
+            t1.transceive(&[0, 1, 2, 3, 4, 5]).expect("response")
+        );
+        let written = &t1.bus.written;
+        assert_eq!(4, written.len());
+        assert_eq!(PCB_I_MORE, written[0][1]);
+        assert_eq!(&[0, 1, 2, 3], &written[0][3..7]);
+        assert_eq!(PCB_I_SEQ, written[1][1]);
+        assert_eq!(PCB_S_RESPONSE | S_WTX, written[2][1]);
+        assert_eq!(PCB_R | PCB_R_SEQ, written[3][1]);
+        assert!(!t1.send_seq && !t1.recv_seq);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut bus = MockBus::default();
+        bus.respond(0, &[0x90, 0x00]);
+        let len = bus.readable.len();
+        bus.readable[len - 1] ^= 1;
+        bus.respond(0, &[0x90, 0x00]);
+        let mut t1 = T1::new(bus);
+        assert_eq!(vec![0x90, 0x00], t1.transceive(&[0]).expect("response"));
+        // The corrupted block was asked for again
+        assert_eq!(PCB_R | PCB_R_EDC_ERROR, t1.bus.written[1][1]);
+    }
+}
@@ -0,0 +1,474 @@
+//! secp256k1 keypairs.
+//!
+//! Keypairs sign with ECDSA over SHA-256 by default, with DER encoded
+//! signatures like ecc_compact keys. They can also make [BIP340][BIP340]
+//! Schnorr signatures over 32 byte messages with
+//! [`Keypair::sign_schnorr`], which verify against the x-only form of the
+//! public key, for Taproot style interoperability. Use [`tagged_hash`] to
+//! derive the 32 byte message as BIP340 based protocols do.
+//!
+//! [BIP340]: https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+use crate::*;
+use k256::{ecdsa, elliptic_curve::sec1::ToEncodedPoint, schnorr};
+use sha2::{Digest, Sha256};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Debug, Clone)]
+pub struct PublicKey(pub(crate) k256::PublicKey);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Signature(pub(crate) ecdsa::Signature);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: k256::ecdsa::SigningKey,
+}
+
+pub const KEYPAIR_LENGTH: usize = 33;
+/// Public keys are SEC1 compressed points prefixed with the key tag
+pub const PUBLIC_KEY_LENGTH: usize = 34;
+/// Recoverable signatures are the fixed size `r || s` signature followed by a
+/// one byte recovery id
+pub const RECOVERABLE_SIGNATURE_LENGTH: usize = 65;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        use signature::Signer;
+        let signature = self.try_sign(msg)?;
+        Ok(signature.to_vec())
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        let secret =
+            k256::SecretKey::from_be_bytes(&input[1..usize::min(input.len(), KEYPAIR_LENGTH)])?;
+        let public_key =
+            public_key::PublicKey::for_network(network, PublicKey(secret.public_key()));
+        Ok(Keypair {
+            network,
+            public_key,
+            secret: k256::ecdsa::SigningKey::from(secret),
+        })
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.secret.to_bytes())
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let secret = k256::SecretKey::random(&mut *csprng);
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(secret.public_key())),
+            secret: k256::ecdsa::SigningKey::from(secret),
+        }
+    }
+
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let secret = k256::SecretKey::from_be_bytes(entropy)?;
+        Ok(Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(secret.public_key())),
+            secret: k256::ecdsa::SigningKey::from(secret),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Secp256k1,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.to_bytes().as_slice().to_vec()
+    }
+
+    /// Returns the secret scalar of this keypair
+    pub(crate) fn secret_scalar(&self) -> k256::NonZeroScalar {
+        // Unwrap ok since the signing key always holds a valid secret
+        k256::SecretKey::from_be_bytes(&self.secret.to_bytes())
+            .unwrap()
+            .to_nonzero_scalar()
+    }
+
+    /// Sign the given message with a recoverable signature, from which
+    /// [`PublicKey::recover`] recovers the public key of this keypair, so the
+    /// public key does not need to be sent along with the signature.
+    pub fn sign_recoverable(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&self.secret, msg)?;
+        let public_key: &PublicKey = (&self.public_key).try_into()?;
+        for recovery_id in 0..2u8 {
+            let mut result = signature.as_ref().to_vec();
+            result.push(recovery_id);
+            match PublicKey::recover(msg, &result) {
+                Ok(recovered) if &recovered == public_key => return Ok(result),
+                _ => continue,
+            }
+        }
+        Err(signature::Error::new().into())
+    }
+
+    /// Make a BIP340 Schnorr signature over the given 32 byte message, using
+    /// fresh auxiliary randomness from the given random number generator
+    pub fn sign_schnorr<R>(&self, msg: &[u8; 32], csprng: &mut R) -> Result<Vec<u8>>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut aux_rand = [0u8; 32];
+        csprng.fill_bytes(&mut aux_rand);
+        self.sign_schnorr_with_aux(msg, &aux_rand)
+    }
+
+    /// Make a BIP340 Schnorr signature over the given 32 byte message with
+    /// the given auxiliary randomness
+    pub fn sign_schnorr_with_aux(&self, msg: &[u8; 32], aux_rand: &[u8; 32]) -> Result<Vec<u8>> {
+        let secret = schnorr::SigningKey::from_bytes(&self.secret.to_bytes())?;
+        let signature = secret.try_sign_prehashed(*msg, aux_rand)?;
+        Ok(signature.as_ref().to_vec())
+    }
+}
+
+impl signature::Signature for Signature {
+    fn from_bytes(input: &[u8]) -> std::result::Result<Self, signature::Error> {
+        Ok(Signature(signature::Signature::from_bytes(input)?))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        let signature: ecdsa::Signature = signature::Signer::try_sign(&self.secret, msg)?;
+        Ok(Signature(signature))
+    }
+}
+
+impl Signature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Signature(signature::Signature::from_bytes(bytes)?))
+    }
+
+    /// Convert to the DER encoded form used for signatures
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.to_der().as_bytes().to_vec()
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        use signature::Verifier;
+        let signature = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        Ok(ecdsa::VerifyingKey::from(self.0).verify(msg, &signature)?)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        Ok(PublicKey(k256::PublicKey::from_sec1_bytes(&buf)?))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let encoded = self.0.as_affine().to_encoded_point(true);
+        output.write_all(encoded.as_bytes())
+    }
+}
+
+/// Half the order of the secp256k1 group. Signatures with an `s` above this
+/// are high-S, and have an equally valid low-S twin.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+impl PublicKey {
+    /// Verify the given DER encoded signature like [`Verify::verify`], but
+    /// reject signatures that are high-S or not canonically DER encoded, so
+    /// a valid signature can not be altered into another valid signature.
+    /// Signatures made by [`Keypair`] are always low-S.
+    pub fn verify_strict(&self, msg: &[u8], signature: &[u8]) -> Result {
+        let parsed = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        let (_, s) = parsed.split_scalars();
+        if parsed.to_der().as_bytes() != signature || s.to_bytes().as_slice() > &HALF_ORDER[..] {
+            return Err(signature::Error::new().into());
+        }
+        self.verify(msg, signature)
+    }
+
+    /// Recover the public key that made the given recoverable signature over
+    /// the given message with [`Keypair::sign_recoverable`]
+    pub fn recover(msg: &[u8], signature: &[u8]) -> Result<Self> {
+        if signature.len() != RECOVERABLE_SIGNATURE_LENGTH {
+            return Err(signature::Error::new().into());
+        }
+        let recovery_id = ecdsa::recoverable::Id::new(signature[64])?;
+        let signature = ecdsa::recoverable::Signature::new(
+            &ecdsa::Signature::try_from(&signature[..64])?,
+            recovery_id,
+        )?;
+        let verifying_key =
+            signature.recover_verifying_key_from_digest(Sha256::new_with_prefix(msg))?;
+        Ok(PublicKey(k256::PublicKey::from(&verifying_key)))
+    }
+
+    /// Returns the BIP340 x-only form of the public key
+    pub fn x_only(&self) -> [u8; 32] {
+        let encoded = self.0.as_affine().to_encoded_point(true);
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&encoded.as_bytes()[1..]);
+        result
+    }
+
+    /// Verify the given DER encoded signature against the incrementally
+    /// computed SHA-256 digest of a message.
+    pub fn verify_digest(&self, digest: Sha256, signature: &[u8]) -> Result {
+        use signature::DigestVerifier;
+        let signature = ecdsa::Signature::from_der(signature).map_err(Error::from)?;
+        Ok(ecdsa::VerifyingKey::from(self.0).verify_digest(digest, &signature)?)
+    }
+
+    /// Verify a BIP340 Schnorr signature over the given 32 byte message
+    pub fn verify_schnorr(&self, msg: &[u8; 32], signature: &[u8]) -> Result {
+        verify_schnorr(&self.x_only(), msg, signature)
+    }
+}
+
+/// Verify a BIP340 Schnorr signature over the given 32 byte message against
+/// the given x-only public key
+pub fn verify_schnorr(x_only: &[u8], msg: &[u8; 32], signature: &[u8]) -> Result {
+    let public_key = schnorr::VerifyingKey::from_bytes(x_only)?;
+    let signature = schnorr::Signature::try_from(signature)?;
+    Ok(public_key.verify_prehashed(msg, &signature)?)
+}
+
+/// Returns the BIP340 tagged hash of the given message:
+/// `SHA-256(SHA-256(tag) || SHA-256(tag) || msg)`
+pub fn tagged_hash(tag: &[u8], msg: &[u8]) -> [u8; 32] {
+    let tag = Sha256::digest(tag);
+    Sha256::new()
+        .chain_update(tag)
+        .chain_update(tag)
+        .chain_update(msg)
+        .finalize()
+        .into()
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let encoded = self.0.as_affine().to_encoded_point(true);
+        state.write(encoded.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keypair, PublicKey, TryFrom, TryInto};
+    use crate::{KeyTag, KeyType, Network, Sign, Verify};
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn verify_reader() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let mut verifier = keypair.public_key.verifier().expect("verifier");
+        verifier.update(b"hello ");
+        verifier.update(b"world");
+        assert!(verifier.finalize(&signature).is_ok());
+        assert!(keypair
+            .public_key
+            .verify_reader(&mut &b"hello there"[..], &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        let bytes = keypair.to_vec();
+        assert_eq!(
+            keypair,
+            super::Keypair::try_from(&bytes[..]).expect("keypair")
+        );
+    }
+
+    #[test]
+    fn public_key_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key = &keypair.public_key;
+        assert_eq!(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::Secp256k1,
+            },
+            public_key.key_tag()
+        );
+        let bytes = public_key.to_vec();
+        assert_eq!(super::PUBLIC_KEY_LENGTH, bytes.len());
+        assert_eq!(
+            public_key,
+            &crate::PublicKey::try_from(&bytes[..]).expect("public key")
+        );
+        let decoded: crate::PublicKey = public_key.to_string().parse().expect("b58 public key");
+        assert_eq!(public_key, &decoded);
+    }
+
+    #[test]
+    fn recover() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("secp256k1 key");
+        let signature = keypair.sign_recoverable(b"hello world").expect("signature");
+        assert_eq!(super::RECOVERABLE_SIGNATURE_LENGTH, signature.len());
+        assert_eq!(
+            public_key,
+            &PublicKey::recover(b"hello world", &signature).expect("recover")
+        );
+        assert_ne!(
+            Some(public_key),
+            PublicKey::recover(b"hello there", &signature).ok().as_ref()
+        );
+    }
+
+    #[test]
+    fn verify_strict() {
+        use super::ecdsa;
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("secp256k1 key");
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(public_key.verify_strict(b"hello world", &signature).is_ok());
+
+        let parsed = ecdsa::Signature::from_der(&signature).expect("der");
+        let (r, s) = parsed.split_scalars();
+        let twin = ecdsa::Signature::from_scalars(r.to_bytes(), (-*s).to_bytes()).expect("twin");
+        assert!(public_key
+            .verify_strict(b"hello world", twin.to_der().as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn schnorr_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("secp256k1 key");
+        let msg = super::tagged_hash(b"helium/test", b"hello world");
+        let signature = keypair.sign_schnorr(&msg, &mut OsRng).expect("signature");
+        assert_eq!(64, signature.len());
+        assert!(public_key.verify_schnorr(&msg, &signature).is_ok());
+        let other = super::tagged_hash(b"helium/test", b"hello there");
+        assert!(public_key.verify_schnorr(&other, &signature).is_err());
+        // Schnorr and ECDSA signatures are not interchangeable
+        assert!(keypair.public_key.verify(&msg, &signature).is_err());
+    }
+
+    #[test]
+    fn schnorr_vector() {
+        // Test vector 0 from BIP340
+        let keypair = Keypair::generate_from_entropy(
+            Network::MainNet,
+            &hex!("0000000000000000000000000000000000000000000000000000000000000003"),
+        )
+        .expect("keypair");
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("secp256k1 key");
+        assert_eq!(
+            hex!("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"),
+            public_key.x_only()
+        );
+        let signature = keypair
+            .sign_schnorr_with_aux(&[0u8; 32], &[0u8; 32])
+            .expect("signature");
+        assert_eq!(
+            hex!("e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0").to_vec(),
+            signature
+        );
+    }
+
+    #[test]
+    fn invalid_point() {
+        let mut bytes = Keypair::generate(Network::MainNet, &mut OsRng)
+            .public_key
+            .to_vec();
+        // 0x05 is not a valid SEC1 point tag
+        bytes[1] = 0x05;
+        assert!(PublicKey::try_from(&bytes[..]).is_err());
+    }
+}
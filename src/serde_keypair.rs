@@ -0,0 +1,126 @@
+//! Opt-in serde support for keypairs.
+//!
+//! [`Keypair`] does not implement `Serialize` so that secret keys are not
+//! written out by accident. Fields that should hold a keypair opt in with
+//!
+//! ```ignore
+//! #[serde(with = "helium_crypto::serde_keypair")]
+//! keypair: Keypair,
+//! ```
+//!
+//! Keypairs serialize as the base58 check encoding of their binary form in
+//! human readable formats, and as their binary form in other formats. Keypairs
+//! whose secret key is not held in software, like hardware or multisig
+//! keypairs, can not be serialized.
+use crate::*;
+use serde::{
+    de::{self, Visitor},
+    ser, Deserializer, Serializer,
+};
+
+pub fn serialize<S>(keypair: &Keypair, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let bytes = match keypair {
+        #[cfg(feature = "multisig")]
+        Keypair::MultiSig(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "ecc608")]
+        Keypair::Ecc608(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "tpm")]
+        Keypair::TPM(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "pkcs11")]
+        Keypair::Pkcs11(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "se050")]
+        Keypair::Se050(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "aws-kms")]
+        Keypair::Kms(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "azure-keyvault")]
+        Keypair::KeyVault(_) => return Err(ser::Error::custom("keypair not serializable")),
+        #[cfg(feature = "vault")]
+        Keypair::Vault(_) => return Err(ser::Error::custom("keypair not serializable")),
+        _ => keypair.to_vec(),
+    };
+    if serializer.is_human_readable() {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&bytes);
+        serializer.serialize_str(&bs58::encode(&data).with_check().into_string())
+    } else {
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Keypair, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct KeypairVisitor;
+
+    impl<'de> Visitor<'de> for KeypairVisitor {
+        type Value = Keypair;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("base58 keypair or keypair bytes")
+        }
+
+        fn visit_str<E>(self, value: &str) -> std::result::Result<Keypair, E>
+        where
+            E: de::Error,
+        {
+            let data = bs58::decode(value)
+                .with_check(Some(0))
+                .into_vec()
+                .map_err(|_| de::Error::custom("invalid keypair"))?;
+            self.visit_bytes(&data[1..])
+        }
+
+        fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<Keypair, E>
+        where
+            E: de::Error,
+        {
+            Keypair::try_from(value).map_err(|_| de::Error::custom("invalid keypair"))
+        }
+    }
+
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(KeypairVisitor)
+    } else {
+        deserializer.deserialize_bytes(KeypairVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn roundtrip() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let key_tag = KeyTag {
+                network: Network::TestNet,
+                key_type,
+            };
+            let keypair = Keypair::generate(key_tag, &mut OsRng);
+
+            let mut json = vec![];
+            serialize(&keypair, &mut serde_json::Serializer::new(&mut json)).expect("json");
+            let decoded =
+                deserialize(&mut serde_json::Deserializer::from_slice(&json)).expect("decoded");
+            assert_eq!(keypair, decoded);
+
+            let mut binary = vec![];
+            serialize(
+                &keypair,
+                &mut bincode::Serializer::new(&mut binary, bincode::DefaultOptions::new()),
+            )
+            .expect("binary");
+            let decoded = deserialize(&mut bincode::Deserializer::from_slice(
+                &binary,
+                bincode::DefaultOptions::new(),
+            ))
+            .expect("decoded");
+            assert_eq!(keypair, decoded);
+        }
+    }
+}
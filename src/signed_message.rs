@@ -0,0 +1,430 @@
+//! Signed messages bundle a payload with the public key that signed it, the
+//! time it was signed at and the resulting signature.
+//!
+//! The signature covers the public key, the timestamp and the payload in their
+//! canonical binary form, so none of them can be swapped out without
+//! invalidating the message.
+//!
+//! A [`MultiSignedMessage`] allows any number of keypairs to progressively
+//! attach their signature to the same payload. Verification is done against a
+//! [`VerifyPolicy`] which lists the expected signers and how many of them need
+//! to have signed.
+use crate::{clock::unix_timestamp, *};
+use std::{collections::HashSet, io};
+
+/// Domain separator prefixed to the signed bytes of a signed message
+const SIGNED_MESSAGE_DOMAIN: &[u8] = b"helium-signed-message";
+/// Domain separator prefixed to the signed bytes of a multi signed message
+const MULTI_SIGNED_MESSAGE_DOMAIN: &[u8] = b"helium-multi-signed-message";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedMessage {
+    /// The public key of the keypair that sealed the message
+    pub public_key: PublicKey,
+    /// The signed payload
+    pub payload: Vec<u8>,
+    /// The signature over the public key, timestamp and payload
+    pub signature: Vec<u8>,
+    /// Seconds since the unix epoch at which the message was sealed
+    pub timestamp: u64,
+}
+
+impl SignedMessage {
+    /// Seal the given payload by signing it with the given keypair, using the
+    /// current system time as the message timestamp.
+    pub fn seal(keypair: &Keypair, payload: &[u8]) -> Result<Self> {
+        Self::seal_at(keypair, payload, unix_timestamp())
+    }
+
+    /// Seal the given payload by signing it with the given keypair at the
+    /// given timestamp (in seconds since the unix epoch).
+    pub fn seal_at(keypair: &Keypair, payload: &[u8], timestamp: u64) -> Result<Self> {
+        let public_key = keypair.public_key().clone();
+        let signature = keypair.sign(&signing_bytes(&public_key, timestamp, payload))?;
+        Ok(Self {
+            public_key,
+            payload: payload.to_vec(),
+            signature,
+            timestamp,
+        })
+    }
+
+    /// Verify the signature of the message and return the payload if it is
+    /// valid.
+    pub fn open(&self) -> Result<&[u8]> {
+        self.verify()?;
+        Ok(&self.payload)
+    }
+
+    /// Verify the signature of the message against the included public key
+    pub fn verify(&self) -> Result {
+        self.public_key.verify(
+            &signing_bytes(&self.public_key, self.timestamp, &self.payload),
+            &self.signature,
+        )
+    }
+
+    /// Returns the SHA-256 digest of the signed contents of the message. The
+    /// digest does not include the signature, so it identifies the message
+    /// regardless of signature malleability.
+    pub fn digest(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(&signing_bytes(
+            &self.public_key,
+            self.timestamp,
+            &self.payload,
+        ));
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&digest);
+        result
+    }
+
+    /// Convert the signed message to its canonical binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+/// A payload signed by any number of signers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiSignedMessage {
+    /// The signed payload
+    pub payload: Vec<u8>,
+    /// Seconds since the unix epoch at which the message was created
+    pub timestamp: u64,
+    /// The public keys and signatures of the signers that have signed so far
+    pub signatures: Vec<(PublicKey, Vec<u8>)>,
+}
+
+/// Describes which signatures are required for a multi signed message to be
+/// considered valid. Only signatures by keys listed in the policy count
+/// towards it, and every key counts at most once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyPolicy {
+    /// Every listed key must have signed
+    All(Vec<PublicKey>),
+    /// At least one of the listed keys must have signed
+    Any(Vec<PublicKey>),
+    /// At least the given number of listed keys must have signed
+    Threshold(usize, Vec<PublicKey>),
+}
+
+impl VerifyPolicy {
+    /// Returns the distinct listed keys, since a key listed more than once can
+    /// still only sign once
+    fn keys(&self) -> HashSet<&PublicKey> {
+        match self {
+            Self::All(keys) | Self::Any(keys) | Self::Threshold(_, keys) => keys.iter().collect(),
+        }
+    }
+
+    fn required(&self) -> usize {
+        match self {
+            Self::All(_) => self.keys().len(),
+            Self::Any(_) => 1,
+            Self::Threshold(threshold, _) => *threshold,
+        }
+    }
+}
+
+impl MultiSignedMessage {
+    /// Construct an unsigned message for the given payload using the current
+    /// system time as the message timestamp.
+    pub fn new(payload: &[u8]) -> Self {
+        Self::new_at(payload, unix_timestamp())
+    }
+
+    /// Construct an unsigned message for the given payload at the given
+    /// timestamp (in seconds since the unix epoch).
+    pub fn new_at(payload: &[u8], timestamp: u64) -> Self {
+        Self {
+            payload: payload.to_vec(),
+            timestamp,
+            signatures: vec![],
+        }
+    }
+
+    /// Attach a signature by the given keypair. A previous signature by the
+    /// same keypair is replaced.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result {
+        let public_key = keypair.public_key();
+        let signature = keypair.sign(&self.signing_bytes())?;
+        self.signatures.retain(|(key, _)| key != public_key);
+        self.signatures.push((public_key.clone(), signature));
+        self.signatures.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(())
+    }
+
+    /// Returns the public keys of all attached signatures that are valid
+    pub fn valid_signers(&self) -> Vec<&PublicKey> {
+        let msg = self.signing_bytes();
+        self.signatures
+            .iter()
+            .filter(|(public_key, signature)| public_key.verify(&msg, signature).is_ok())
+            .map(|(public_key, _)| public_key)
+            .collect()
+    }
+
+    /// Verify the attached signatures against the given policy
+    pub fn verify(&self, policy: &VerifyPolicy) -> Result {
+        let valid_signers = self.valid_signers();
+        let signed = policy
+            .keys()
+            .into_iter()
+            .filter(|public_key| valid_signers.contains(public_key))
+            .count();
+        if signed == 0 || signed < policy.required() {
+            return Err(signature::Error::new().into());
+        }
+        Ok(())
+    }
+
+    /// Verify the attached signatures against the given policy and return the
+    /// payload if the policy is met.
+    pub fn open(&self, policy: &VerifyPolicy) -> Result<&[u8]> {
+        self.verify(policy)?;
+        Ok(&self.payload)
+    }
+
+    /// Convert the message to its canonical binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut result = MULTI_SIGNED_MESSAGE_DOMAIN.to_vec();
+        result.extend_from_slice(&self.timestamp.to_be_bytes());
+        result.extend_from_slice(&self.payload);
+        result
+    }
+}
+
+/// Returns the bytes the signature in a signed message is calculated over.
+fn signing_bytes(public_key: &PublicKey, timestamp: u64, payload: &[u8]) -> Vec<u8> {
+    let mut result = SIGNED_MESSAGE_DOMAIN.to_vec();
+    result.extend_from_slice(&public_key.to_vec());
+    result.extend_from_slice(&timestamp.to_be_bytes());
+    result.extend_from_slice(payload);
+    result
+}
+
+/// Writes the given data prefixed with its length as a big endian u32.
+pub(crate) fn write_u32_prefixed<W: io::Write>(output: &mut W, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "data too large"))?;
+    output.write_all(&len.to_be_bytes())?;
+    output.write_all(data)
+}
+
+/// Reads data prefixed with its length as a big endian u32. The data is read
+/// incrementally so a bogus length can not cause a large allocation.
+pub(crate) fn read_u32_prefixed<R: io::Read>(input: &mut R) -> Result<Vec<u8>> {
+    let mut len_buf = [0u8; 4];
+    input.read_exact(&mut len_buf)?;
+    let len = u32::from_be_bytes(len_buf);
+    let mut result = Vec::new();
+    input.take(u64::from(len)).read_to_end(&mut result)?;
+    if result.len() != len as usize {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(result)
+}
+
+impl WriteTo for SignedMessage {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        self.public_key.write_to(output)?;
+        output.write_all(&self.timestamp.to_be_bytes())?;
+        write_u32_prefixed(output, &self.payload)?;
+        write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for SignedMessage {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let public_key = PublicKey::read_from(input)?;
+        let mut timestamp_buf = [0u8; 8];
+        input.read_exact(&mut timestamp_buf)?;
+        let payload = read_u32_prefixed(input)?;
+        let signature = read_u32_prefixed(input)?;
+        Ok(Self {
+            public_key,
+            payload,
+            signature,
+            timestamp: u64::from_be_bytes(timestamp_buf),
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for SignedMessage {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+impl WriteTo for MultiSignedMessage {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.timestamp.to_be_bytes())?;
+        write_u32_prefixed(output, &self.payload)?;
+        let count = u16::try_from(self.signatures.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many signatures"))?;
+        output.write_all(&count.to_be_bytes())?;
+        for (public_key, signature) in &self.signatures {
+            public_key.write_to(output)?;
+            write_u32_prefixed(output, signature)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadFrom for MultiSignedMessage {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut timestamp_buf = [0u8; 8];
+        input.read_exact(&mut timestamp_buf)?;
+        let payload = read_u32_prefixed(input)?;
+        let mut count_buf = [0u8; 2];
+        input.read_exact(&mut count_buf)?;
+        let mut signatures = vec![];
+        for _ in 0..u16::from_be_bytes(count_buf) {
+            let public_key = PublicKey::read_from(input)?;
+            let signature = read_u32_prefixed(input)?;
+            signatures.push((public_key, signature));
+        }
+        Ok(Self {
+            payload,
+            timestamp: u64::from_be_bytes(timestamp_buf),
+            signatures,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for MultiSignedMessage {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn seal_open(key_type: KeyType) {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type,
+            },
+            &mut OsRng,
+        );
+        let message = SignedMessage::seal(&keypair, b"hello world").expect("signed message");
+        assert_eq!(&message.public_key, keypair.public_key());
+        assert_eq!(b"hello world", message.open().expect("open"));
+
+        let decoded = SignedMessage::try_from(&message.to_vec()[..]).expect("decoded message");
+        assert_eq!(message, decoded);
+        assert!(decoded.verify().is_ok());
+    }
+
+    #[test]
+    fn seal_open_ed25519() {
+        seal_open(KeyType::Ed25519);
+    }
+
+    #[test]
+    fn seal_open_ecc_compact() {
+        seal_open(KeyType::EccCompact);
+    }
+
+    #[test]
+    fn tampered() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let message = SignedMessage::seal_at(&keypair, b"hello world", 1000).expect("message");
+
+        let mut tampered = message.clone();
+        tampered.payload = b"hello there".to_vec();
+        assert!(tampered.open().is_err());
+
+        let mut tampered = message;
+        tampered.timestamp += 1;
+        assert!(tampered.open().is_err());
+    }
+
+    #[test]
+    fn multi_signed() {
+        let keypairs: Vec<Keypair> = (0..3)
+            .map(|_| Keypair::generate(KeyTag::default(), &mut OsRng))
+            .collect();
+        let keys: Vec<PublicKey> = keypairs
+            .iter()
+            .map(|keypair| keypair.public_key().clone())
+            .collect();
+        let outsider = Keypair::generate(KeyTag::default(), &mut OsRng);
+
+        let mut message = MultiSignedMessage::new(b"hello world");
+        assert!(message.verify(&VerifyPolicy::Any(keys.clone())).is_err());
+
+        message.sign(&keypairs[0]).expect("sign");
+        message.sign(&outsider).expect("sign");
+        // Signing twice does not count twice
+        message.sign(&keypairs[0]).expect("sign");
+        assert!(message.verify(&VerifyPolicy::Any(keys.clone())).is_ok());
+        assert!(message
+            .verify(&VerifyPolicy::Threshold(2, keys.clone()))
+            .is_err());
+
+        message.sign(&keypairs[1]).expect("sign");
+        let decoded = MultiSignedMessage::try_from(&message.to_vec()[..]).expect("decoded");
+        assert_eq!(message, decoded);
+        assert!(decoded
+            .verify(&VerifyPolicy::Threshold(2, keys.clone()))
+            .is_ok());
+        assert!(decoded.verify(&VerifyPolicy::All(keys.clone())).is_err());
+
+        message.sign(&keypairs[2]).expect("sign");
+        assert_eq!(
+            b"hello world",
+            message.open(&VerifyPolicy::All(keys)).expect("open")
+        );
+    }
+
+    #[test]
+    fn multi_signed_duplicate_keys() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let other = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let key = keypair.public_key().clone();
+        let mut message = MultiSignedMessage::new(b"hello world");
+        message.sign(&keypair).expect("sign");
+
+        // A key listed twice only counts once towards a threshold
+        let duplicated = vec![key.clone(), key.clone(), other.public_key().clone()];
+        assert!(message
+            .verify(&VerifyPolicy::Threshold(2, duplicated.clone()))
+            .is_err());
+        assert!(message
+            .verify(&VerifyPolicy::Threshold(1, duplicated))
+            .is_ok());
+        assert!(message
+            .verify(&VerifyPolicy::All(vec![key.clone(), key]))
+            .is_ok());
+    }
+
+    #[test]
+    fn truncated() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let message = SignedMessage::seal(&keypair, b"hello world").expect("message");
+        let bytes = message.to_vec();
+        assert!(SignedMessage::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversions between ed25519 keys and Solana keys.
+//!
+//! Solana accounts are plain ed25519 keys, so ed25519 public keys convert to
+//! and from Solana `Pubkey`s and ed25519 keypairs to and from Solana
+//! `Keypair`s. Keypairs also convert to and from the 64 byte secret form
+//! Solana uses in keypair files, which is the secret key followed by the
+//! public key.
+//!
+//! Solana keys have no notion of a Helium network, so keys converted from
+//! Solana keys are mainnet keys unless a network is given.
+use crate::*;
+use solana_sdk::{pubkey::Pubkey, signer::keypair::Keypair as SolanaKeypair};
+
+/// The length of the Solana secret form of a keypair
+pub const SOLANA_KEYPAIR_LENGTH: usize = 64;
+
+impl ed25519::Keypair {
+    /// Returns the Solana secret form of the keypair, the secret key followed
+    /// by the public key
+    pub fn to_solana_bytes(&self) -> [u8; SOLANA_KEYPAIR_LENGTH] {
+        let mut result = [0u8; SOLANA_KEYPAIR_LENGTH];
+        result.copy_from_slice(&self.to_vec()[1..]);
+        result
+    }
+
+    /// Import a keypair for the given network from its Solana secret form.
+    /// The public key half must match the secret key.
+    pub fn from_solana_bytes(network: Network, bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != SOLANA_KEYPAIR_LENGTH {
+            return Err(Error::invalid_solana());
+        }
+        let keypair = Self::generate_from_entropy(network, &bytes[..32])?;
+        if keypair.to_vec()[1..] != bytes[..] {
+            return Err(Error::invalid_solana());
+        }
+        Ok(keypair)
+    }
+}
+
+impl From<&ed25519::PublicKey> for Pubkey {
+    fn from(public_key: &ed25519::PublicKey) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(public_key.as_ref());
+        Pubkey::new_from_array(bytes)
+    }
+}
+
+impl TryFrom<&Pubkey> for ed25519::PublicKey {
+    type Error = Error;
+
+    /// Fails for addresses that are not on the curve, like program derived
+    /// addresses
+    fn try_from(pubkey: &Pubkey) -> Result<Self> {
+        Self::read_from(&mut pubkey.as_ref())
+    }
+}
+
+impl TryFrom<&PublicKey> for Pubkey {
+    type Error = Error;
+
+    fn try_from(public_key: &PublicKey) -> Result<Self> {
+        let public_key: &ed25519::PublicKey = public_key.try_into()?;
+        Ok(Pubkey::from(public_key))
+    }
+}
+
+impl TryFrom<&Pubkey> for PublicKey {
+    type Error = Error;
+
+    fn try_from(pubkey: &Pubkey) -> Result<Self> {
+        ed25519::PublicKey::try_from(pubkey).map(PublicKey::from)
+    }
+}
+
+impl TryFrom<&ed25519::Keypair> for SolanaKeypair {
+    type Error = Error;
+
+    fn try_from(keypair: &ed25519::Keypair) -> Result<Self> {
+        SolanaKeypair::from_bytes(&keypair.to_solana_bytes()).map_err(|_| Error::invalid_solana())
+    }
+}
+
+impl TryFrom<&SolanaKeypair> for ed25519::Keypair {
+    type Error = Error;
+
+    fn try_from(keypair: &SolanaKeypair) -> Result<Self> {
+        Self::from_solana_bytes(Network::MainNet, &keypair.to_bytes())
+    }
+}
+
+impl TryFrom<&Keypair> for SolanaKeypair {
+    type Error = Error;
+
+    fn try_from(keypair: &Keypair) -> Result<Self> {
+        match keypair {
+            Keypair::Ed25519(keypair) => SolanaKeypair::try_from(keypair),
+            _ => Err(Error::invalid_curve()),
+        }
+    }
+}
+
+impl TryFrom<&SolanaKeypair> for Keypair {
+    type Error = Error;
+
+    fn try_from(keypair: &SolanaKeypair) -> Result<Self> {
+        ed25519::Keypair::try_from(keypair).map(Keypair::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use solana_sdk::signer::Signer;
+
+    #[test]
+    fn keypair_roundtrip() {
+        let keypair = ed25519::Keypair::generate(Network::TestNet, &mut OsRng);
+        let solana = SolanaKeypair::try_from(&keypair).expect("solana keypair");
+        assert_eq!(&solana.to_bytes(), &keypair.to_solana_bytes());
+        assert_eq!(
+            solana.pubkey(),
+            Pubkey::try_from(&keypair.public_key).expect("pubkey")
+        );
+        assert_eq!(
+            keypair,
+            ed25519::Keypair::from_solana_bytes(Network::TestNet, &solana.to_bytes())
+                .expect("keypair")
+        );
+
+        let solana = SolanaKeypair::new();
+        let keypair = Keypair::try_from(&solana).expect("keypair");
+        assert_eq!(Network::MainNet, keypair.public_key().network);
+        assert_eq!(
+            keypair.public_key(),
+            &PublicKey::try_from(&solana.pubkey()).expect("public key")
+        );
+        // Signatures made with either key verify with the other
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(solana_sdk::signature::Signature::try_from(&signature[..])
+            .expect("solana signature")
+            .verify(solana.pubkey().as_ref(), b"hello world"));
+        let signature = solana.sign_message(b"hello world");
+        assert!(keypair
+            .public_key()
+            .verify(b"hello world", signature.as_ref())
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = ed25519::Keypair::generate(Network::MainNet, &mut OsRng);
+        // A public key that does not belong to the secret key
+        let mut bytes = keypair.to_solana_bytes();
+        bytes[32..].copy_from_slice(&other.to_solana_bytes()[32..]);
+        assert!(ed25519::Keypair::from_solana_bytes(Network::MainNet, &bytes).is_err());
+        assert!(ed25519::Keypair::from_solana_bytes(Network::MainNet, &bytes[..32]).is_err());
+
+        let ecc_compact = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        assert!(matches!(
+            SolanaKeypair::try_from(&ecc_compact),
+            Err(Error::InvalidCurve)
+        ));
+        assert!(Pubkey::try_from(ecc_compact.public_key()).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! sr25519 (Schnorrkel) keypairs.
+//!
+//! sr25519 signatures are Schnorr signatures over the Ristretto group, as used
+//! by Substrate based chains. Every signature is bound to a signing context.
+//! The [`Sign`] and [`Verify`] traits use the [`SIGNING_CONTEXT`] Substrate
+//! uses, while [`Keypair::sign_with_context`] and
+//! [`PublicKey::verify_with_context`] take any other context.
+//!
+//! Keypairs derived from entropy expand the 32 byte entropy as a mini secret
+//! key the way Substrate does, so they match Substrate keys derived from the
+//! same seed.
+use crate::*;
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+/// The signing context used by Substrate, and by [`Sign`] and [`Verify`]
+pub const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey(schnorrkel::PublicKey);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: schnorrkel::Keypair,
+}
+
+pub const KEYPAIR_LENGTH: usize = schnorrkel::SECRET_KEY_LENGTH + 1;
+pub const PUBLIC_KEY_LENGTH: usize = schnorrkel::PUBLIC_KEY_LENGTH + 1;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.sign_with_context(SIGNING_CONTEXT, msg))
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        let secret =
+            schnorrkel::SecretKey::from_bytes(&input[1..usize::min(input.len(), KEYPAIR_LENGTH)])
+                .map_err(|_| Error::invalid_curve())?;
+        Ok(Self::from_secret(network, secret.to_keypair()))
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.secret.secret.to_bytes())
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        Self::from_secret(network, schnorrkel::Keypair::generate_with(csprng))
+    }
+
+    /// Derive a keypair from the given 32 bytes of entropy, used as a mini
+    /// secret key
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let secret =
+            schnorrkel::MiniSecretKey::from_bytes(entropy).map_err(|_| Error::invalid_curve())?;
+        Ok(Self::from_secret(
+            network,
+            secret.expand_to_keypair(schnorrkel::ExpansionMode::Ed25519),
+        ))
+    }
+
+    fn from_secret(network: Network, secret: schnorrkel::Keypair) -> Self {
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, PublicKey(secret.public)),
+            secret,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::Sr25519,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.secret.to_bytes().to_vec()
+    }
+
+    /// Sign the given message in the given signing context
+    pub fn sign_with_context(&self, context: &[u8], msg: &[u8]) -> Vec<u8> {
+        let context = schnorrkel::signing_context(context);
+        self.secret.sign(context.bytes(msg)).to_bytes().to_vec()
+    }
+}
+
+impl PublicKey {
+    /// Verify a signature over the given message in the given signing context
+    pub fn verify_with_context(&self, context: &[u8], msg: &[u8], signature: &[u8]) -> Result {
+        let signature =
+            schnorrkel::Signature::from_bytes(signature).map_err(|_| signature::Error::new())?;
+        let context = schnorrkel::signing_context(context);
+        self.0
+            .verify(context.bytes(msg), &signature)
+            .map_err(|_| signature::Error::new().into())
+    }
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    fn verify(&self, msg: &[u8], signature: &[u8]) -> Result {
+        self.verify_with_context(SIGNING_CONTEXT, msg, signature)
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        let public_key =
+            schnorrkel::PublicKey::from_bytes(&buf).map_err(|_| Error::invalid_curve())?;
+        Ok(PublicKey(public_key))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&self.0.to_bytes())
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn sign_roundtrip() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello there", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn signing_context() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let public_key: &PublicKey = (&keypair.public_key).try_into().expect("sr25519 key");
+        let signature = keypair.sign_with_context(b"helium", b"hello world");
+        assert!(public_key
+            .verify_with_context(b"helium", b"hello world", &signature)
+            .is_ok());
+        assert!(keypair
+            .public_key
+            .verify(b"hello world", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair =
+            Keypair::generate_from_entropy(Network::TestNet, &[7u8; 32]).expect("keypair");
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_vec()[..]).expect("keypair")
+        );
+        let public_key = &keypair.public_key;
+        assert_eq!(PUBLIC_KEY_LENGTH, public_key.to_vec().len());
+        let decoded: public_key::PublicKey = public_key.to_string().parse().expect("b58");
+        assert_eq!(public_key, &decoded);
+    }
+}
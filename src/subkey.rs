@@ -0,0 +1,377 @@
+//! Short lived signing subkeys certified by a master key.
+//!
+//! Signing every packet with a hardware backed keypair puts the secure
+//! element in the hot path. Instead a master keypair can certify a software
+//! [`Subkey`] once per session with a compact [`SubkeyCertificate`] binding
+//! the subkey to a validity window and the operations it may sign for.
+//! Verifiers check a subkey signature by checking the certificate against
+//! the master key, or against a [`DelegationChain`] ending in the master key
+//! when the master key is itself delegated to.
+//!
+//! The certificate does not include the master key, which the verifier is
+//! expected to know, to keep it small enough to send along with every
+//! message.
+use crate::{
+    clock::Clock,
+    delegation::DelegationChain,
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    validity::Validity,
+    *,
+};
+use std::io;
+
+/// Domain separator prefixed to the signed bytes of a subkey certificate
+const SUBKEY_DOMAIN: &[u8] = b"helium-subkey-certificate";
+
+/// A certificate by a master key for a subkey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubkeyCertificate {
+    pub subkey: PublicKey,
+    pub validity: Validity,
+    /// The operations the subkey may sign for. Empty allows any operation.
+    pub operations: Vec<String>,
+    /// The signature by the master key over the subkey, validity and
+    /// operations
+    pub signature: Vec<u8>,
+}
+
+impl SubkeyCertificate {
+    /// Certify the given subkey with the given master keypair
+    pub fn issue(
+        master: &Keypair,
+        subkey: &PublicKey,
+        validity: Validity,
+        operations: &[&str],
+    ) -> Result<Self> {
+        let operations: Vec<String> = operations.iter().map(|op| op.to_string()).collect();
+        let msg = signing_bytes(master.public_key(), subkey, &validity, &operations)?;
+        let signature = master.sign(&msg)?;
+        Ok(Self {
+            subkey: subkey.clone(),
+            validity,
+            operations,
+            signature,
+        })
+    }
+
+    pub fn allows_operation(&self, operation: &str) -> bool {
+        self.operations.is_empty() || self.operations.iter().any(|op| op == operation)
+    }
+
+    /// Verify that the certificate was issued by the given master key for
+    /// the given operation, at the current time of the given clock. Returns
+    /// the certified subkey.
+    pub fn verify<C: Clock>(
+        &self,
+        master: &PublicKey,
+        operation: &str,
+        clock: &C,
+    ) -> Result<&PublicKey> {
+        self.verify_at(master, operation, clock.now())
+    }
+
+    /// Like [`SubkeyCertificate::verify`] but at the given current time
+    pub fn verify_at(&self, master: &PublicKey, operation: &str, now: u64) -> Result<&PublicKey> {
+        let msg = signing_bytes(master, &self.subkey, &self.validity, &self.operations)?;
+        master.verify(&msg, &self.signature)?;
+        self.validity.check(now)?;
+        if !self.allows_operation(operation) {
+            return Err(Error::not_permitted());
+        }
+        Ok(&self.subkey)
+    }
+
+    /// Verify that the given chain delegates the given operation from the
+    /// given root key to the master key, and that the certificate was issued
+    /// by that master key for the operation. Returns the certified subkey.
+    pub fn verify_chain<C: Clock>(
+        &self,
+        chain: &DelegationChain,
+        root: &PublicKey,
+        operation: &str,
+        clock: &C,
+    ) -> Result<&PublicKey> {
+        let now = clock.now();
+        let master = chain.verify_at(root, operation, now)?;
+        self.verify_at(master, operation, now)
+    }
+
+    /// Verify that the given signature over the given message is by a subkey
+    /// certified by the given master key for the given operation
+    pub fn verify_message<C: Clock>(
+        &self,
+        master: &PublicKey,
+        operation: &str,
+        msg: &[u8],
+        signature: &[u8],
+        clock: &C,
+    ) -> Result {
+        self.verify(master, operation, clock)?
+            .verify(msg, signature)
+    }
+
+    /// Convert the certificate to its binary form. Fails if there are more
+    /// than 255 operations.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_to(&mut result)?;
+        Ok(result)
+    }
+}
+
+impl WriteTo for SubkeyCertificate {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        self.subkey.write_to(output)?;
+        write_body(output, &self.validity, &self.operations)?;
+        write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for SubkeyCertificate {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let subkey = PublicKey::read_from(input)?;
+        let mut not_before = [0u8; 8];
+        input.read_exact(&mut not_before)?;
+        let mut not_after = [0u8; 8];
+        input.read_exact(&mut not_after)?;
+        let mut count = [0u8; 1];
+        input.read_exact(&mut count)?;
+        let mut operations = vec![];
+        for _ in 0..count[0] {
+            let operation = String::from_utf8(read_u32_prefixed(input)?)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            operations.push(operation);
+        }
+        Ok(Self {
+            subkey,
+            validity: Validity::new(
+                u64::from_be_bytes(not_before),
+                u64::from_be_bytes(not_after),
+            ),
+            operations,
+            signature: read_u32_prefixed(input)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for SubkeyCertificate {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// A software signing keypair together with its certificate
+#[derive(Debug)]
+pub struct Subkey {
+    keypair: Keypair,
+    certificate: SubkeyCertificate,
+}
+
+impl Subkey {
+    /// Generate an ed25519 subkey on the network of the given master keypair
+    /// and certify it for the given number of seconds from the current time
+    /// of the given clock
+    pub fn generate<R, C>(
+        master: &Keypair,
+        secs: u64,
+        operations: &[&str],
+        clock: &C,
+        csprng: &mut R,
+    ) -> Result<Self>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+        C: Clock,
+    {
+        let validity = Validity::starting_at(clock.now(), secs);
+        Self::generate_with_validity(master, validity, operations, csprng)
+    }
+
+    /// Like [`Subkey::generate`] but with the given validity window
+    pub fn generate_with_validity<R>(
+        master: &Keypair,
+        validity: Validity,
+        operations: &[&str],
+        csprng: &mut R,
+    ) -> Result<Self>
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let key_tag = KeyTag {
+            network: master.public_key().network,
+            key_type: KeyType::Ed25519,
+        };
+        let keypair = Keypair::generate(key_tag, csprng);
+        let certificate =
+            SubkeyCertificate::issue(master, keypair.public_key(), validity, operations)?;
+        Ok(Self {
+            keypair,
+            certificate,
+        })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        self.keypair.public_key()
+    }
+
+    pub fn certificate(&self) -> &SubkeyCertificate {
+        &self.certificate
+    }
+
+    /// Returns whether the subkey is still valid at the current time of the
+    /// given clock, so callers know when to generate a new one
+    pub fn is_valid<C: Clock>(&self, clock: &C) -> bool {
+        self.certificate.validity.check(clock.now()).is_ok()
+    }
+}
+
+impl Sign for Subkey {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.keypair.sign(msg)
+    }
+}
+
+/// Returns the bytes the signature of a subkey certificate is calculated
+/// over
+fn signing_bytes(
+    master: &PublicKey,
+    subkey: &PublicKey,
+    validity: &Validity,
+    operations: &[String],
+) -> io::Result<Vec<u8>> {
+    let mut result = SUBKEY_DOMAIN.to_vec();
+    master.write_to(&mut result)?;
+    subkey.write_to(&mut result)?;
+    write_body(&mut result, validity, operations)?;
+    Ok(result)
+}
+
+fn write_body<W: io::Write>(
+    output: &mut W,
+    validity: &Validity,
+    operations: &[String],
+) -> io::Result<()> {
+    output.write_all(&validity.not_before.to_be_bytes())?;
+    output.write_all(&validity.not_after.to_be_bytes())?;
+    let count = u8::try_from(operations.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many operations"))?;
+    output.write_all(&[count])?;
+    for operation in operations {
+        write_u32_prefixed(output, operation.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        delegation::{Constraints, Delegation},
+    };
+    use rand::rngs::OsRng;
+
+    fn keypair() -> Keypair {
+        Keypair::generate(KeyTag::default(), &mut OsRng)
+    }
+
+    #[test]
+    fn sign() {
+        let master = keypair();
+        let clock = ManualClock::new(1000);
+        let subkey =
+            Subkey::generate(&master, 60, &["packet"], &clock, &mut OsRng).expect("subkey");
+        let signature = subkey.sign(b"hello world").expect("signature");
+        let certificate = subkey.certificate();
+        assert!(certificate
+            .verify_message(
+                master.public_key(),
+                "packet",
+                b"hello world",
+                &signature,
+                &clock
+            )
+            .is_ok());
+        assert!(matches!(
+            certificate.verify(master.public_key(), "burn", &clock),
+            Err(Error::NotPermitted)
+        ));
+        assert!(certificate
+            .verify(keypair().public_key(), "packet", &clock)
+            .is_err());
+
+        clock.advance(61);
+        assert!(!subkey.is_valid(&clock));
+        assert!(matches!(
+            certificate.verify(master.public_key(), "packet", &clock),
+            Err(Error::Expired(1060))
+        ));
+    }
+
+    #[test]
+    fn tampered() {
+        let master = keypair();
+        let subkey = Subkey::generate_with_validity(
+            &master,
+            Validity::new(1000, 1060),
+            &["packet"],
+            &mut OsRng,
+        )
+        .expect("subkey");
+        let mut certificate = subkey.certificate().clone();
+        certificate.validity.not_after = 2000;
+        assert!(certificate
+            .verify_at(master.public_key(), "packet", 1030)
+            .is_err());
+        let mut certificate = subkey.certificate().clone();
+        certificate.operations.clear();
+        assert!(certificate
+            .verify_at(master.public_key(), "packet", 1030)
+            .is_err());
+    }
+
+    #[test]
+    fn chain() {
+        let (root, master) = (keypair(), keypair());
+        let chain = DelegationChain::new(vec![Delegation::issue(
+            &root,
+            master.public_key(),
+            Constraints::default().with_operations(&["packet"]),
+        )
+        .expect("delegation")]);
+        let clock = ManualClock::new(1000);
+        let subkey = Subkey::generate(&master, 60, &[], &clock, &mut OsRng).expect("subkey");
+        let certificate = subkey.certificate();
+        assert_eq!(
+            subkey.public_key(),
+            certificate
+                .verify_chain(&chain, root.public_key(), "packet", &clock)
+                .expect("verify")
+        );
+        // The chain narrows what the subkey certificate allows
+        assert!(matches!(
+            certificate.verify_chain(&chain, root.public_key(), "burn", &clock),
+            Err(Error::NotPermitted)
+        ));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let master = keypair();
+        let subkey = Subkey::generate_with_validity(
+            &master,
+            Validity::new(1000, 1060),
+            &["packet", "beacon"],
+            &mut OsRng,
+        )
+        .expect("subkey");
+        let certificate = subkey.certificate();
+        assert_eq!(
+            certificate,
+            &SubkeyCertificate::try_from(&certificate.to_vec().expect("bytes")[..])
+                .expect("certificate")
+        );
+    }
+}
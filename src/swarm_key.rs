@@ -0,0 +1,153 @@
+//! Loading and saving keypairs as Helium `swarm_key` files.
+//!
+//! A `swarm_key` file, as used by Helium miners and gateways, holds just the
+//! binary form of a keypair. Since the file holds the secret key it is
+//! written readable by its owner only, and it is replaced atomically so a
+//! crash while saving never leaves a truncated key behind.
+use crate::*;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+impl Keypair {
+    /// Load a keypair from the `swarm_key` file at the given path
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Keypair> {
+        Keypair::try_from(&fs::read(path)?[..])
+    }
+
+    /// Save the keypair as a `swarm_key` file at the given path, replacing
+    /// any existing file. On unix the file is only readable and writable by
+    /// its owner. Hardware backed keypairs have no binary form and are
+    /// rejected.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result {
+        let data = match self {
+            #[cfg(feature = "multisig")]
+            Self::MultiSig(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "ecc608")]
+            Self::Ecc608(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "tpm")]
+            Self::TPM(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "pkcs11")]
+            Self::Pkcs11(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "se050")]
+            Self::Se050(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "aws-kms")]
+            Self::Kms(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "azure-keyvault")]
+            Self::KeyVault(_) => return Err(Error::not_permitted()),
+            #[cfg(feature = "vault")]
+            Self::Vault(_) => return Err(Error::not_permitted()),
+            _ => self.to_vec(),
+        };
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        // Write to a temporary file next to the target, so the rename below
+        // stays on one file system and is atomic
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(format!(".tmp-{}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+        // A temporary file left behind by an earlier crash would make the
+        // exclusive create below fail
+        let _ = fs::remove_file(&tmp_path);
+
+        let result = write_private(&tmp_path, &data).and_then(|_| fs::rename(&tmp_path, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result?;
+        sync_parent(path);
+        Ok(())
+    }
+}
+
+/// Write the given data to a new file at the given path that only its owner
+/// can read, and flush it to disk
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Flush the directory entry of a renamed file to disk. This is best effort
+/// since not every platform can open a directory.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("helium-crypto-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_load() {
+        let path = temp_path("swarm-key");
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let other = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let result = keypair
+            .save(&path)
+            .and_then(|_| Keypair::load(&path))
+            .and_then(|loaded| {
+                // Saving again replaces the existing file
+                other.save(&path)?;
+                Ok((loaded, Keypair::load(&path)?, fs::metadata(&path)?))
+            });
+        let _ = fs::remove_file(&path);
+
+        let (loaded, replaced, _metadata) = result.expect("swarm key");
+        assert_eq!(keypair, loaded);
+        assert_eq!(other, replaced);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(0o600, _metadata.permissions().mode() & 0o777);
+        }
+    }
+
+    #[test]
+    fn load_invalid() {
+        let path = temp_path("swarm-key-invalid");
+        fs::write(&path, b"not a key").expect("write file");
+        let result = Keypair::load(&path);
+        let _ = fs::remove_file(&path);
+        assert!(result.is_err());
+        assert!(matches!(
+            Keypair::load(temp_path("swarm-key-missing")),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Signatures tagged with the type of key that made them.
+//!
+//! A raw signature does not say which kind of key it is for, so it is easy to
+//! pair it with the wrong public key. A [`Signature`] carries the key type,
+//! and optionally the network, of the signing key along with the signature
+//! bytes, and verifying it against a public key of a different type or network
+//! fails before the signature is even parsed.
+//!
+//! The binary form of a signature is a tag byte followed by the signature
+//! bytes. The tag byte is the key tag of the signing key, with all network
+//! bits set when the signature has no network.
+use crate::*;
+
+/// The network bits of the tag byte of a signature without a network
+const NETTYPE_NONE: u8 = 0xF0;
+
+/// A signature along with the type and optional network of the signing key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The type of the key that made the signature
+    pub key_type: KeyType,
+    /// The network of the key that made the signature, if known
+    pub network: Option<Network>,
+    /// The signature bytes as returned by [`Sign::sign`]
+    pub bytes: Vec<u8>,
+}
+
+impl Signature {
+    /// Returns the binary form of the signature
+    pub fn to_vec(&self) -> Vec<u8> {
+        let network = self.network.map(u8::from).unwrap_or(NETTYPE_NONE);
+        let mut result = vec![network | u8::from(self.key_type)];
+        result.extend_from_slice(&self.bytes);
+        result
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let (tag, bytes) = input.split_first().ok_or_else(Error::missing_keytype)?;
+        let network = if tag & 0xF0 == NETTYPE_NONE {
+            None
+        } else {
+            Some(Network::try_from(*tag)?)
+        };
+        Ok(Self {
+            key_type: KeyType::try_from(*tag)?,
+            network,
+            bytes: bytes.to_vec(),
+        })
+    }
+}
+
+impl Keypair {
+    /// Sign the given message, tagging the signature with the key tag of the
+    /// keypair
+    pub fn sign_tagged(&self, msg: &[u8]) -> Result<Signature> {
+        let key_tag = self.key_tag();
+        Ok(Signature {
+            key_type: key_tag.key_type,
+            network: Some(key_tag.network),
+            bytes: self.sign(msg)?,
+        })
+    }
+}
+
+impl PublicKey {
+    /// Verify the given tagged signature over the given message. Fails with
+    /// an invalid curve error if the signature was made by a different type
+    /// of key, and with an invalid network error if it was made by a key for
+    /// a different network.
+    pub fn verify_signature(&self, msg: &[u8], signature: &Signature) -> Result {
+        if signature.key_type != self.key_type() {
+            return Err(Error::invalid_curve());
+        }
+        if signature
+            .network
+            .map_or(false, |network| network != self.network)
+        {
+            return Err(Error::invalid_network());
+        }
+        self.verify(msg, &signature.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn roundtrip() {
+        let keypair = Keypair::generate(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let signature = keypair.sign_tagged(b"hello world").expect("signature");
+        assert_eq!(u8::from(keypair.key_tag()), signature.to_vec()[0]);
+        assert_eq!(
+            signature,
+            Signature::try_from(&signature.to_vec()[..]).expect("signature")
+        );
+        assert!(keypair
+            .public_key()
+            .verify_signature(b"hello world", &signature)
+            .is_ok());
+
+        let untagged = Signature {
+            network: None,
+            ..signature
+        };
+        let bytes = untagged.to_vec();
+        assert_eq!(NETTYPE_NONE, bytes[0] & 0xF0);
+        assert_eq!(
+            untagged,
+            Signature::try_from(&bytes[..]).expect("signature")
+        );
+        assert!(keypair
+            .public_key()
+            .verify_signature(b"hello world", &untagged)
+            .is_ok());
+    }
+
+    #[test]
+    fn mismatched() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature = keypair.sign_tagged(b"hello world").expect("signature");
+        let public_key = keypair.public_key();
+        assert!(matches!(
+            public_key.verify_signature(
+                b"hello world",
+                &Signature {
+                    key_type: KeyType::EccCompact,
+                    ..signature.clone()
+                }
+            ),
+            Err(Error::InvalidCurve)
+        ));
+        assert!(matches!(
+            public_key.verify_signature(
+                b"hello world",
+                &Signature {
+                    network: Some(Network::TestNet),
+                    ..signature.clone()
+                }
+            ),
+            Err(Error::InvalidNetwork)
+        ));
+        assert!(public_key
+            .verify_signature(b"goodbye world", &signature)
+            .is_err());
+        assert!(Signature::try_from(&[][..]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Telemetry for crypto operations.
+//!
+//! With the `metrics` feature enabled, sign, verify and ecdh operations are
+//! counted and timed through the [`metrics`](https://docs.rs/metrics) facade:
+//!
+//! * `helium_crypto_operations_total` counts operations
+//! * `helium_crypto_operation_duration_seconds` records operation latency
+//!
+//! Both are labeled with the operation, the key type, the backend holding the
+//! key and the result, which is either `ok` or the [`ErrorClass`] of the
+//! failure.
+//!
+//! With the `tracing` feature enabled, key generation, sign, verify, ecdh and
+//! hardware initialization run in a `helium_crypto` debug span carrying the
+//! same labels and the key network. Spans never carry key material.
+//!
+//! Without either feature no telemetry is recorded.
+use crate::*;
+
+#[cfg(feature = "metrics")]
+const OPERATIONS_TOTAL: &str = "helium_crypto_operations_total";
+#[cfg(feature = "metrics")]
+const OPERATION_DURATION: &str = "helium_crypto_operation_duration_seconds";
+
+/// The backend label for software keys
+pub(crate) const BACKEND_SOFTWARE: &str = "software";
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Stands in for a tracing span when the `tracing` feature is disabled
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        f()
+    }
+}
+
+/// Construct the span for the given operation. The key tag is optional since
+/// it is not known for some operations, like hardware initialization.
+pub(crate) fn span(op: &'static str, key_tag: Option<KeyTag>, backend: &'static str) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::debug_span!(
+            "helium_crypto",
+            op,
+            backend,
+            key_type = tracing::field::Empty,
+            network = tracing::field::Empty,
+            result = tracing::field::Empty,
+        );
+        if let Some(key_tag) = key_tag {
+            span.record("key_type", &tracing::field::display(key_tag.key_type));
+            span.record("network", &tracing::field::display(key_tag.network));
+        }
+        span
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (op, key_tag, backend);
+        Span
+    }
+}
+
+/// Run the given operation in a span, recording its outcome and latency for
+/// the given key tag and backend.
+pub(crate) fn observe<T, F>(
+    op: &'static str,
+    key_tag: KeyTag,
+    backend: &'static str,
+    f: F,
+) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let span = span(op, Some(key_tag), backend);
+    span.in_scope(|| {
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+        let result = f();
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("op", op.to_string()),
+                ("key_type", key_tag.key_type.to_string()),
+                ("backend", backend.to_string()),
+                ("result", result_label(&result).to_string()),
+            ];
+            metrics::increment_counter!(OPERATIONS_TOTAL, &labels);
+            metrics::histogram!(OPERATION_DURATION, start.elapsed(), &labels);
+        }
+        #[cfg(feature = "tracing")]
+        {
+            span.record("result", &result_label(&result));
+            if let Err(err) = &result {
+                tracing::debug!(error = %err, "operation failed");
+            }
+        }
+        result
+    })
+}
+
+#[cfg(any(feature = "metrics", feature = "tracing", test))]
+fn result_label<T>(result: &Result<T>) -> &'static str {
+    match result.as_ref().map_err(Error::class) {
+        Ok(_) => "ok",
+        Err(ErrorClass::Decode) => "decode",
+        Err(ErrorClass::Crypto) => "crypto",
+        Err(ErrorClass::Device) => "device",
+        Err(ErrorClass::Transport) => "transport",
+        Err(ErrorClass::Timeout) => "timeout",
+        Err(ErrorClass::Unavailable) => "unavailable",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_labels() {
+        assert_eq!("ok", result_label(&Ok(())));
+        assert_eq!("timeout", result_label::<()>(&Err(Error::timeout())));
+        assert_eq!(
+            "crypto",
+            result_label::<()>(&Err(signature::Error::new().into()))
+        );
+        assert_eq!(
+            42,
+            observe("sign", KeyTag::default(), BACKEND_SOFTWARE, || Ok(42)).expect("result")
+        );
+    }
+}
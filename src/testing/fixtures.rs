@@ -0,0 +1,195 @@
+//! Well known keypairs for tests.
+//!
+//! **INSECURE**: the secrets of these keypairs are derived from a published
+//! seed, so anyone can sign with them. Never use them outside of tests.
+//!
+//! The ed25519 and ecc_compact key types, and the secp256k1, Ed448 and P-384
+//! key types when their features are enabled, have a fixture on every
+//! network with its keypair, its b58 encoded public key and a sample
+//! signature over [`MESSAGE`]. The keypairs are derived with
+//! [`Keypair::generate_deterministic`] from [`SEED`], so they are the same in
+//! every run and on every machine, and a failure involving one of them can be
+//! reproduced.
+//!
+//! The other key types have no fixtures: x25519 and Kyber keys can not sign,
+//! the post-quantum keys can not be derived from a seed, and multisig, BLS
+//! and sr25519 fixtures are not published. Deriving keypairs of the key types
+//! that can be derived from [`SEED`] the same way still gives reproducible
+//! keypairs.
+use crate::*;
+
+/// The seed all fixture keypairs are derived from
+pub const SEED: &[u8] = b"helium-crypto insecure test fixture";
+/// The message signed by the sample signature of every fixture
+pub const MESSAGE: &[u8] = b"helium-crypto test fixture message";
+
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub key_tag: KeyTag,
+    /// The b58 encoded public key of the fixture keypair
+    pub public_key: &'static str,
+    /// A hex encoded signature over [`MESSAGE`] by the fixture keypair
+    pub signature: &'static str,
+}
+
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed25519,
+        },
+        public_key: "13SdLHkiBZHCZDHnWpjUNeAe6yXnDm7dgJr1Yee5HXLNrKVmAtN",
+        signature: "eddfe0e240924d5f204d65dad9be24c30cddc68a8d086947df1eea832c4b34f2890e0e0cae1a11daff008a8d752747fc173b786c40c8ad0ef236765eab995f09",
+    },
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        },
+        public_key: "11Xf8b1nUbWEXfUBpzCustykC65QDFsi971xV7yB78z3oxR3NN5",
+        signature: "3045022100b51e135662e8eed289e4bd7271d21ac66cb5ea0eb87736ed8afbd70358fe3e9b0220088393c1b6c3c04b2042528697a821c71dcbe3c0b14e6bec5913de0d632d15c7",
+    },
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::Ed25519,
+        },
+        public_key: "1b5pSEixX4Qmo59exMpfHVH1m61rwGWsLPBmquxTiM5rBCxkMSo",
+        signature: "be1a5fe1311589e4e23b8d651816076d4c43fbc0894eca8592c86615a26c41e562490f72e49b92fc8b75221d31194344f66f7b0851e46cef0221d946459ee804",
+    },
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccCompact,
+        },
+        public_key: "1a1ksXngfGPysiDYZ8njT7SY9nsMdX3BjEKXbL19wrsZnWNt4SQ",
+        signature: "3045022100ccb9fe1e73791542e2b0dc92d8f5f83723a683e9d4439528eaec00e0472c1d500220683b4ace4b6037525cd2e1e50dd58c6811938096833c49e381626b40bc0854d6",
+    },
+    #[cfg(feature = "secp256k1")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Secp256k1,
+        },
+        public_key: "1SqHNuMQQtwPVR6uY9DYLrzEfSE2u2ajSWPeRNAZmzVjGc98PuWS",
+        signature: "30450221009273539a0488d118bfaa1c038d6a8f04d7c47d50e3ecfb1da9957e297d11078502204f94ad60baf0e96f48e556648bde12c022839e46d3d9c9adfd13a6a53e2bf352",
+    },
+    #[cfg(feature = "secp256k1")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::Secp256k1,
+        },
+        public_key: "13p7C8thrhxq8KBMiD853zHkui9imkr16fYNHtUnHBif9nskA3GaA",
+        signature: "3045022100ed405d6eecb139e98b64c90718dee32e798a7ea25dadb7fa22f3350cb804fb7d022011036eb57d8fadc266cfd94c545fa97fbb52e8d1489db628e4f2ea9d307932ea",
+    },
+    #[cfg(feature = "ed448")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::Ed448,
+        },
+        public_key: "1NZhCGwjCAUkjtmhgXvHZ4kKFFTEshXbVMqfyjJeuNqWFd6pdXpb7zUcL6pa5MHVGpbtFCpaZr6VeLoqpQFQG",
+        signature: "46abbb22bc929d455e722f6b35dc883485e7ce27f20411e7dbbe7b7e98eff2b0d74e1833107d2b8c515033c07304f4ebfe2cd0b2ceafd6ab0031d265aa5c9c538f67821a68f92bba0643f6534997ee590fe86b9649f385279b13a4f53a929967994470368787abda273819e0779c5e623f00",
+    },
+    #[cfg(feature = "ed448")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::Ed448,
+        },
+        public_key: "12KfqfPnqPuxyQXKXL2ofybeUH82yNFDPhBNCk35CfkRxk7wPJ1GRxM9caPHGmpoqPADAcTCxpL9qCzaUeGN3b",
+        signature: "6e01196d62f9bf3c1b7519845b2a5226d67ce1dc3c83f6a99c2af8c3f8669e6834366e2d18f7b33227a65a81492e7e4ae7c5c269a2d7aa33005a3200d0a07a34a508e20b7d3a15bf082f9a1d4c483cf465919ce5709d19bca47adcb1bbc3385f058e48c660dad1a77c7e6597dfe344522c00",
+    },
+    #[cfg(feature = "p384")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccP384,
+        },
+        public_key: "1Zon347p6CP46jbkLA9AbeyazenhHAaP7nEkiCuV7Lc6UkZTZDDoPcGwkSLG7jvwRNkxsHsqpy",
+        signature: "3065023022a9dcfea402e390eb1ee57f77e83360946f8c42ff5752cabd0844588f980e98d96bc4846c3ba54e335b54e96a7de9bd023100b12c92010da748cbc90579e3dce96e4edd5a8c5a0fca7c862b7df33fd4310ff724d2340181d8c121262e6f1da6e8079e",
+    },
+    #[cfg(feature = "p384")]
+    Fixture {
+        key_tag: KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccP384,
+        },
+        public_key: "12rbPhjMesdA4eMZCUeFGSqNf9j3UcsV6oxQ1LUxvvWX2gCq294nDemEaWpp7vR2mQdNJPoAmDw",
+        signature: "3065023100934350a21d665e21b64fc90b5803f0560715a1e21e1548220357a4fed5b0226d451edea1748a21988610669895f56d9d023003840f456da8f00418d03bc920d446b6fe73c24066f12917ec3ee48da6f1da031e8d2bda3090b2738fcdf04d0f85d5a1",
+    },
+];
+
+impl Fixture {
+    /// Returns the insecure fixture keypair
+    pub fn keypair(&self) -> Keypair {
+        // Unwrap ok here since fixtures only exist for key types which can be
+        // generated from a seed
+        Keypair::generate_deterministic(self.key_tag, SEED).unwrap()
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        // Unwrap ok here since the fixture public keys are checked in tests
+        self.public_key.parse().unwrap()
+    }
+
+    pub fn signature(&self) -> Vec<u8> {
+        // Unwrap ok here since the fixture signatures are checked in tests
+        hex::decode(self.signature).unwrap()
+    }
+}
+
+/// Returns the fixture for the given key tag, if there is one
+pub fn fixture(key_tag: KeyTag) -> Option<&'static Fixture> {
+    FIXTURES.iter().find(|fixture| fixture.key_tag == key_tag)
+}
+
+/// Returns the insecure fixture keypair for the given key tag, if there is a
+/// fixture for it
+pub fn keypair(key_tag: KeyTag) -> Option<Keypair> {
+    fixture(key_tag).map(Fixture::keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures() {
+        for fixture in FIXTURES.iter() {
+            let keypair = fixture.keypair();
+            assert_eq!(fixture.public_key, keypair.public_key().to_string());
+            assert_eq!(&fixture.public_key(), keypair.public_key());
+            assert!(keypair
+                .public_key()
+                .verify(MESSAGE, &fixture.signature())
+                .is_ok());
+            // EdDSA signatures are deterministic
+            let eddsa = match fixture.key_tag.key_type {
+                KeyType::Ed25519 => true,
+                #[cfg(feature = "ed448")]
+                KeyType::Ed448 => true,
+                _ => false,
+            };
+            if eddsa {
+                assert_eq!(fixture.signature(), keypair.sign(MESSAGE).expect("sign"));
+            }
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let key_tag = KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::EccCompact,
+        };
+        assert_eq!(key_tag, keypair(key_tag).expect("fixture").key_tag());
+        #[cfg(feature = "x25519")]
+        assert!(fixture(KeyTag {
+            network: Network::TestNet,
+            key_type: KeyType::X25519,
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,394 @@
+//! A harness which runs the same suite of checks against any key backend.
+//!
+//! The suite is the [`conformance`] checks plus public key serialization and
+//! ecdh checks.
+//!
+//! Backends implement [`BackendUnderTest`] to hand out the keypair to test.
+//! [`from_env`] selects a backend from the environment so the same test
+//! binary can run against software keys in CI and against real hardware, or
+//! a simulator like swtpm, on a test rig:
+//!
+//! * `HELIUM_CRYPTO_BACKEND` selects `software` (the default), `ecc608`,
+//!   `tpm`, `pkcs11`, `se050`, `kms`, `keyvault` or `vault`.
+//! * `HELIUM_CRYPTO_ECC608_PATH`, `HELIUM_CRYPTO_ECC608_ADDRESS` and
+//!   `HELIUM_CRYPTO_ECC608_SLOT` configure the ecc608 backend, defaulting to
+//!   `/dev/i2c-1`, `0x60` and slot 0.
+//! * `HELIUM_CRYPTO_TPM_KEY_PATH` configures the tpm backend, defaulting to
+//!   `HS/SRK/MinerKey`. The TPM itself, real or swtpm, is selected through
+//!   the usual TSS2 FAPI configuration.
+//! * `HELIUM_CRYPTO_PKCS11_MODULE`, `HELIUM_CRYPTO_PKCS11_SLOT`,
+//!   `HELIUM_CRYPTO_PKCS11_PIN` and `HELIUM_CRYPTO_PKCS11_LABEL` configure the
+//!   pkcs11 backend. The module path is required, the others default to slot
+//!   0, PIN `1234` and label `helium`, which suits a SoftHSM test token.
+//! * `HELIUM_CRYPTO_SE050_PATH`, `HELIUM_CRYPTO_SE050_ADDRESS` and
+//!   `HELIUM_CRYPTO_SE050_OBJECT_ID` configure the se050 backend, defaulting
+//!   to `/dev/i2c-1`, `0x48` and object id `0x10000000`.
+//! * `HELIUM_CRYPTO_KMS_KEY_ID` selects the key of the kms backend and is
+//!   required. AWS credentials and region come from the usual AWS
+//!   environment.
+//! * `HELIUM_CRYPTO_KEYVAULT_URL` and `HELIUM_CRYPTO_KEYVAULT_KEY` select the
+//!   vault and key name of the keyvault backend and are required. Azure
+//!   credentials come from the standard Azure credential chain.
+//! * `HELIUM_CRYPTO_VAULT_ADDR`, `HELIUM_CRYPTO_VAULT_TOKEN` and
+//!   `HELIUM_CRYPTO_VAULT_KEY` configure the vault backend and are required.
+//!   The transit mount defaults to `transit` and is set with
+//!   `HELIUM_CRYPTO_VAULT_MOUNT`.
+use crate::*;
+
+pub const BACKEND_ENV: &str = "HELIUM_CRYPTO_BACKEND";
+
+/// A key backend to run the harness against
+pub trait BackendUnderTest {
+    /// The name of the backend, used in reports
+    fn name(&self) -> String;
+
+    /// Returns the keypair under test
+    fn keypair(&self) -> Result<Keypair>;
+}
+
+/// Software keys, generated fresh for every run
+#[derive(Debug, Clone, Copy)]
+pub struct SoftwareBackend(pub KeyTag);
+
+impl BackendUnderTest for SoftwareBackend {
+    fn name(&self) -> String {
+        format!("software/{}", self.0.key_type)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(Keypair::generate(self.0, &mut rand_core::OsRng))
+    }
+}
+
+/// A key in a slot of an ECC608 secure element
+#[cfg(feature = "ecc608")]
+#[derive(Debug, Clone)]
+pub struct Ecc608Backend {
+    pub path: String,
+    pub address: u16,
+    pub slot: u8,
+}
+
+#[cfg(feature = "ecc608")]
+impl BackendUnderTest for Ecc608Backend {
+    fn name(&self) -> String {
+        format!("ecc608/{}@{:#x}/{}", self.path, self.address, self.slot)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        ecc608::init(&self.path, self.address)?;
+        Ok(ecc608::Keypair::from_slot(Network::MainNet, self.slot)?.into())
+    }
+}
+
+/// A key at a TPM FAPI key path
+#[cfg(feature = "tpm")]
+#[derive(Debug, Clone)]
+pub struct TpmBackend {
+    pub key_path: String,
+}
+
+#[cfg(feature = "tpm")]
+impl BackendUnderTest for TpmBackend {
+    fn name(&self) -> String {
+        format!("tpm/{}", self.key_path)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(tpm::Keypair::from_key_path(Network::MainNet, &self.key_path)?.into())
+    }
+}
+
+/// A key with a label in a PKCS#11 token
+#[cfg(feature = "pkcs11")]
+#[derive(Debug, Clone)]
+pub struct Pkcs11Backend {
+    pub module_path: String,
+    pub slot: u64,
+    pub pin: String,
+    pub label: String,
+}
+
+#[cfg(feature = "pkcs11")]
+impl BackendUnderTest for Pkcs11Backend {
+    fn name(&self) -> String {
+        format!("pkcs11/{}/{}", self.slot, self.label)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(pkcs11::Keypair::open(
+            Network::MainNet,
+            &self.module_path,
+            self.slot,
+            &self.pin,
+            &self.label,
+        )?
+        .into())
+    }
+}
+
+/// A key in a secure object of an SE050 secure element
+#[cfg(feature = "se050")]
+#[derive(Debug, Clone)]
+pub struct Se050Backend {
+    pub path: String,
+    pub address: u16,
+    pub object_id: u32,
+}
+
+#[cfg(feature = "se050")]
+impl BackendUnderTest for Se050Backend {
+    fn name(&self) -> String {
+        format!(
+            "se050/{}@{:#x}/{:#x}",
+            self.path, self.address, self.object_id
+        )
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        se050::init(&self.path, self.address)?;
+        Ok(se050::Keypair::from_object(Network::MainNet, self.object_id)?.into())
+    }
+}
+
+/// An asymmetric AWS KMS key
+#[cfg(feature = "aws-kms")]
+#[derive(Debug, Clone)]
+pub struct KmsBackend {
+    pub key_id: String,
+}
+
+#[cfg(feature = "aws-kms")]
+impl BackendUnderTest for KmsBackend {
+    fn name(&self) -> String {
+        format!("kms/{}", self.key_id)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(kms::Keypair::open(Network::MainNet, &self.key_id)?.into())
+    }
+}
+
+/// A key in Azure Key Vault or Managed HSM
+#[cfg(feature = "azure-keyvault")]
+#[derive(Debug, Clone)]
+pub struct KeyVaultBackend {
+    pub vault_url: String,
+    pub key_name: String,
+}
+
+#[cfg(feature = "azure-keyvault")]
+impl BackendUnderTest for KeyVaultBackend {
+    fn name(&self) -> String {
+        format!("keyvault/{}/{}", self.vault_url, self.key_name)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(keyvault::Keypair::open(Network::MainNet, &self.vault_url, &self.key_name)?.into())
+    }
+}
+
+/// A key in the transit secrets engine of HashiCorp Vault
+#[cfg(feature = "vault")]
+#[derive(Debug, Clone)]
+pub struct VaultBackend {
+    pub address: String,
+    pub mount: String,
+    pub key_name: String,
+    pub token: String,
+}
+
+#[cfg(feature = "vault")]
+impl BackendUnderTest for VaultBackend {
+    fn name(&self) -> String {
+        format!("vault/{}/{}/{}", self.address, self.mount, self.key_name)
+    }
+
+    fn keypair(&self) -> Result<Keypair> {
+        Ok(vault::Keypair::open_with_mount(
+            Network::MainNet,
+            &self.address,
+            &self.mount,
+            &self.key_name,
+            vault::Auth::Token(self.token.clone()),
+        )?
+        .into())
+    }
+}
+
+/// Select the backend under test from the environment.
+///
+/// Panics if the environment selects a backend which is unknown or not
+/// enabled in this build, so a misconfigured test rig can not silently fall
+/// back to testing software keys.
+pub fn from_env() -> Box<dyn BackendUnderTest> {
+    let backend = std::env::var(BACKEND_ENV).unwrap_or_else(|_| "software".to_string());
+    match backend.as_str() {
+        "software" => Box::new(SoftwareBackend(KeyTag {
+            network: Network::MainNet,
+            key_type: KeyType::EccCompact,
+        })),
+        #[cfg(feature = "ecc608")]
+        "ecc608" => Box::new(Ecc608Backend {
+            path: env_or("HELIUM_CRYPTO_ECC608_PATH", "/dev/i2c-1"),
+            address: parse_int(&env_or("HELIUM_CRYPTO_ECC608_ADDRESS", "0x60")),
+            slot: parse_int(&env_or("HELIUM_CRYPTO_ECC608_SLOT", "0")),
+        }),
+        #[cfg(feature = "tpm")]
+        "tpm" => Box::new(TpmBackend {
+            key_path: env_or("HELIUM_CRYPTO_TPM_KEY_PATH", "HS/SRK/MinerKey"),
+        }),
+        #[cfg(feature = "pkcs11")]
+        "pkcs11" => Box::new(Pkcs11Backend {
+            module_path: std::env::var("HELIUM_CRYPTO_PKCS11_MODULE")
+                .expect("HELIUM_CRYPTO_PKCS11_MODULE not set"),
+            slot: parse_int(&env_or("HELIUM_CRYPTO_PKCS11_SLOT", "0")),
+            pin: env_or("HELIUM_CRYPTO_PKCS11_PIN", "1234"),
+            label: env_or("HELIUM_CRYPTO_PKCS11_LABEL", "helium"),
+        }),
+        #[cfg(feature = "se050")]
+        "se050" => Box::new(Se050Backend {
+            path: env_or("HELIUM_CRYPTO_SE050_PATH", "/dev/i2c-1"),
+            address: parse_int(&env_or("HELIUM_CRYPTO_SE050_ADDRESS", "0x48")),
+            object_id: parse_int(&env_or("HELIUM_CRYPTO_SE050_OBJECT_ID", "0x10000000")),
+        }),
+        #[cfg(feature = "aws-kms")]
+        "kms" => Box::new(KmsBackend {
+            key_id: std::env::var("HELIUM_CRYPTO_KMS_KEY_ID")
+                .expect("HELIUM_CRYPTO_KMS_KEY_ID not set"),
+        }),
+        #[cfg(feature = "azure-keyvault")]
+        "keyvault" => Box::new(KeyVaultBackend {
+            vault_url: std::env::var("HELIUM_CRYPTO_KEYVAULT_URL")
+                .expect("HELIUM_CRYPTO_KEYVAULT_URL not set"),
+            key_name: std::env::var("HELIUM_CRYPTO_KEYVAULT_KEY")
+                .expect("HELIUM_CRYPTO_KEYVAULT_KEY not set"),
+        }),
+        #[cfg(feature = "vault")]
+        "vault" => Box::new(VaultBackend {
+            address: std::env::var("HELIUM_CRYPTO_VAULT_ADDR")
+                .expect("HELIUM_CRYPTO_VAULT_ADDR not set"),
+            mount: env_or("HELIUM_CRYPTO_VAULT_MOUNT", vault::DEFAULT_MOUNT),
+            key_name: std::env::var("HELIUM_CRYPTO_VAULT_KEY")
+                .expect("HELIUM_CRYPTO_VAULT_KEY not set"),
+            token: std::env::var("HELIUM_CRYPTO_VAULT_TOKEN")
+                .expect("HELIUM_CRYPTO_VAULT_TOKEN not set"),
+        }),
+        other => panic!("unsupported {} backend: {}", BACKEND_ENV, other),
+    }
+}
+
+#[cfg(any(
+    feature = "ecc608",
+    feature = "tpm",
+    feature = "pkcs11",
+    feature = "se050",
+    feature = "vault"
+))]
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+#[cfg(any(feature = "ecc608", feature = "pkcs11", feature = "se050"))]
+fn parse_int<T: TryFrom<u64>>(value: &str) -> T {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed
+        .ok()
+        .and_then(|v| T::try_from(v).ok())
+        .unwrap_or_else(|| panic!("invalid number: {}", value))
+}
+
+/// The outcome of running the harness against a backend
+#[derive(Debug)]
+pub struct Report {
+    pub backend: String,
+    pub checks: conformance::Report,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.checks.is_ok()
+    }
+
+    /// Panics with a description of every failed check, if any
+    pub fn assert_ok(&self) {
+        let failures = self.checks.failures();
+        if !failures.is_empty() {
+            panic!("{} failed: {}", self.backend, failures.join(", "));
+        }
+    }
+}
+
+/// Run the conformance checks, and the serialization and ecdh checks,
+/// against the given backend
+pub fn run(backend: &dyn BackendUnderTest) -> Report {
+    let checks = match backend.keypair() {
+        Ok(keypair) => {
+            let mut checks = conformance::check(&keypair, keypair.public_key());
+            checks
+                .results
+                .push(("public_key_roundtrip", check_public_key_roundtrip(&keypair)));
+            checks.results.push(("ecdh", check_ecdh(&keypair)));
+            checks
+        }
+        Err(err) => conformance::Report {
+            results: vec![("keypair", Err(err))],
+        },
+    };
+    Report {
+        backend: backend.name(),
+        checks,
+    }
+}
+
+/// The error used for a check that ran but did not hold
+fn failed() -> Error {
+    signature::Error::new().into()
+}
+
+fn check_public_key_roundtrip(keypair: &Keypair) -> Result {
+    let public_key = keypair.public_key();
+    let from_bytes = PublicKey::try_from(&public_key.to_vec()[..])?;
+    let from_b58 = PublicKey::from_str(&public_key.to_string())?;
+    if &from_bytes != public_key || &from_b58 != public_key {
+        return Err(failed());
+    }
+    Ok(())
+}
+
+fn check_ecdh(keypair: &Keypair) -> Result {
+    if keypair.key_tag().key_type != KeyType::EccCompact {
+        return Ok(());
+    }
+    let peer = Keypair::generate(keypair.key_tag(), &mut rand_core::OsRng);
+    let shared = keypair.ecdh(peer.public_key())?;
+    let peer_shared = peer.ecdh(keypair.public_key())?;
+    if shared.raw_secret_bytes() != peer_shared.raw_secret_bytes() {
+        return Err(failed());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn software() {
+        for key_type in [KeyType::Ed25519, KeyType::EccCompact] {
+            let report = run(&SoftwareBackend(KeyTag {
+                network: Network::MainNet,
+                key_type,
+            }));
+            report.assert_ok();
+        }
+    }
+
+    #[test]
+    fn from_environment() {
+        run(from_env().as_ref()).assert_ok();
+    }
+}
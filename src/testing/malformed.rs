@@ -0,0 +1,296 @@
+//! Generators of malformed keys and signatures for negative testing.
+//!
+//! Each generator takes a valid key or signature and derives a set of
+//! systematically corrupted variants from it, covering every class of
+//! [`Corruption`] that applies to its key type. A validator should reject
+//! every one of them; the tests in this module check that this crate does.
+//!
+//! Only corruptions this crate rejects are generated. Notably ed25519 public
+//! keys with trailing bytes or a non-canonical `y` are accepted by the parser,
+//! and ECDSA signatures with a high `s` are valid, so neither is included.
+use crate::*;
+
+/// The class of corruption applied to an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Corruption {
+    /// An unknown network or key type in the tag byte
+    WrongTag,
+    /// Bytes missing from the end, up to and including all of them
+    Truncated,
+    /// Bytes added to the end
+    Extended,
+    /// A value outside its canonical range or encoding
+    NonCanonical,
+    /// A point that is not on the curve
+    OffCurve,
+    /// A single flipped bit
+    BitFlip,
+    /// All zero values
+    Zero,
+}
+
+/// A corrupted input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Malformed {
+    pub corruption: Corruption,
+    /// What exactly was corrupted
+    pub description: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+impl Malformed {
+    fn new(corruption: Corruption, description: &'static str, bytes: Vec<u8>) -> Self {
+        Self {
+            corruption,
+            description,
+            bytes,
+        }
+    }
+}
+
+/// A tag byte with a key type that does not exist
+const UNKNOWN_KEY_TYPE: u8 = 0x0f;
+/// A tag byte with a network that does not exist
+const UNKNOWN_NETWORK: u8 = 0x20;
+
+/// The order of the P-256 base field, which is not a canonical coordinate
+const P256_FIELD_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+];
+/// The order of the P-256 group, which is not a valid secret scalar
+const P256_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+/// There is no P-256 point with x = 1
+const P256_OFF_CURVE_X: u8 = 1;
+/// There is no ed25519 point with y = 2
+const ED25519_OFF_CURVE_Y: u8 = 2;
+
+/// Replaces the key type or network in the given tagged bytes
+fn retagged(bytes: &[u8], tag: u8) -> Vec<u8> {
+    let mut result = bytes.to_vec();
+    result[0] = tag;
+    result
+}
+
+/// Corruptions of the tag byte and the length of the given tagged bytes
+fn tagged(bytes: &[u8]) -> Vec<Malformed> {
+    let network = bytes[0] & 0xf0;
+    let key_type = bytes[0] & 0x0f;
+    vec![
+        Malformed::new(
+            Corruption::WrongTag,
+            "unknown key type",
+            retagged(bytes, network | UNKNOWN_KEY_TYPE),
+        ),
+        Malformed::new(
+            Corruption::WrongTag,
+            "unknown network",
+            retagged(bytes, UNKNOWN_NETWORK | key_type),
+        ),
+        Malformed::new(Corruption::Truncated, "empty", vec![]),
+        Malformed::new(
+            Corruption::Truncated,
+            "last byte missing",
+            bytes[..bytes.len() - 1].to_vec(),
+        ),
+    ]
+}
+
+/// Returns malformed variants of the binary form of the given public key
+pub fn public_keys(public_key: &PublicKey) -> Vec<Malformed> {
+    let bytes = public_key.to_vec();
+    let mut result = tagged(&bytes);
+    let mut point = vec![bytes[0]];
+    match public_key.key_type() {
+        KeyType::EccCompact => {
+            point.extend_from_slice(&[0; 31]);
+            point.push(P256_OFF_CURVE_X);
+            result.push(Malformed::new(
+                Corruption::OffCurve,
+                "x without a point",
+                point,
+            ));
+            let mut non_canonical = vec![bytes[0]];
+            non_canonical.extend_from_slice(&P256_FIELD_ORDER);
+            result.push(Malformed::new(
+                Corruption::NonCanonical,
+                "x not reduced",
+                non_canonical,
+            ));
+            result.push(Malformed::new(
+                Corruption::Extended,
+                "trailing byte",
+                [&bytes[..], &[0]].concat(),
+            ));
+        }
+        KeyType::Ed25519 => {
+            point.push(ED25519_OFF_CURVE_Y);
+            point.extend_from_slice(&[0; 31]);
+            result.push(Malformed::new(
+                Corruption::OffCurve,
+                "y without a point",
+                point,
+            ));
+        }
+        #[cfg(feature = "multisig")]
+        KeyType::MultiSig => (),
+        #[cfg(feature = "secp256k1")]
+        KeyType::Secp256k1 => (),
+        #[cfg(feature = "bls")]
+        KeyType::Bls => (),
+        #[cfg(feature = "x25519")]
+        KeyType::X25519 => (),
+        #[cfg(feature = "ed448")]
+        KeyType::Ed448 => (),
+        #[cfg(feature = "sr25519")]
+        KeyType::Sr25519 => (),
+        #[cfg(feature = "p384")]
+        KeyType::EccP384 => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Dilithium => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Hybrid => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Kyber => (),
+    }
+    result
+}
+
+/// Returns malformed variants of the binary form of the given keypair.
+///
+/// Panics for keypairs which can not be converted to their binary form, like
+/// hardware keypairs.
+pub fn keypairs(keypair: &Keypair) -> Vec<Malformed> {
+    let bytes = keypair.to_vec();
+    let mut result = tagged(&bytes);
+    result.push(Malformed::new(
+        Corruption::Truncated,
+        "tag only",
+        bytes[..1].to_vec(),
+    ));
+    if keypair.key_tag().key_type == KeyType::EccCompact {
+        let mut zero = vec![bytes[0]];
+        zero.extend_from_slice(&[0; 32]);
+        result.push(Malformed::new(Corruption::Zero, "zero secret", zero));
+        let mut non_canonical = vec![bytes[0]];
+        non_canonical.extend_from_slice(&P256_ORDER);
+        result.push(Malformed::new(
+            Corruption::NonCanonical,
+            "secret not reduced",
+            non_canonical,
+        ));
+    }
+    result
+}
+
+/// Returns malformed variants of the given valid signature by the given
+/// public key
+pub fn signatures(public_key: &PublicKey, signature: &[u8]) -> Vec<Malformed> {
+    let mut flipped = signature.to_vec();
+    flipped[signature.len() - 1] ^= 0x01;
+    let mut result = vec![
+        Malformed::new(Corruption::Truncated, "empty", vec![]),
+        Malformed::new(
+            Corruption::Truncated,
+            "last byte missing",
+            signature[..signature.len() - 1].to_vec(),
+        ),
+        Malformed::new(
+            Corruption::Extended,
+            "trailing byte",
+            [signature, &[0]].concat(),
+        ),
+        Malformed::new(Corruption::BitFlip, "last bit flipped", flipped),
+    ];
+    match public_key.key_type() {
+        KeyType::EccCompact => {
+            // A DER sequence of two zero integers
+            result.push(Malformed::new(
+                Corruption::Zero,
+                "zero r and s",
+                vec![0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00],
+            ));
+            // Pad r with a redundant leading zero, which is not valid DER
+            let mut padded = vec![0x30, signature[1] + 1, 0x02, signature[3] + 1, 0x00];
+            padded.extend_from_slice(&signature[4..]);
+            result.push(Malformed::new(
+                Corruption::NonCanonical,
+                "r not minimally encoded",
+                padded,
+            ));
+        }
+        KeyType::Ed25519 => {
+            result.push(Malformed::new(
+                Corruption::Zero,
+                "zero r and s",
+                vec![0; 64],
+            ));
+            if let Some(non_canonical) = conformance::non_canonical_ed25519(signature) {
+                result.push(Malformed::new(
+                    Corruption::NonCanonical,
+                    "s not reduced",
+                    non_canonical,
+                ));
+            }
+        }
+        #[cfg(feature = "multisig")]
+        KeyType::MultiSig => (),
+        #[cfg(feature = "secp256k1")]
+        KeyType::Secp256k1 => (),
+        #[cfg(feature = "bls")]
+        KeyType::Bls => (),
+        #[cfg(feature = "x25519")]
+        KeyType::X25519 => (),
+        #[cfg(feature = "ed448")]
+        KeyType::Ed448 => (),
+        #[cfg(feature = "sr25519")]
+        KeyType::Sr25519 => (),
+        #[cfg(feature = "p384")]
+        KeyType::EccP384 => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Dilithium => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Hybrid => (),
+        #[cfg(feature = "pqc")]
+        KeyType::Kyber => (),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::{FIXTURES, MESSAGE};
+
+    #[test]
+    fn rejected() {
+        for fixture in FIXTURES.iter() {
+            let keypair = fixture.keypair();
+            let public_key = keypair.public_key();
+            for malformed in public_keys(public_key) {
+                assert!(
+                    PublicKey::try_from(&malformed.bytes[..]).is_err(),
+                    "public key {:?}",
+                    malformed
+                );
+            }
+            for malformed in keypairs(&keypair) {
+                assert!(
+                    Keypair::try_from(&malformed.bytes[..]).is_err(),
+                    "keypair {:?}",
+                    malformed
+                );
+            }
+            for malformed in signatures(public_key, &fixture.signature()) {
+                assert!(
+                    public_key.verify(MESSAGE, &malformed.bytes).is_err(),
+                    "signature {:?}",
+                    malformed
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Helpers for testing code that uses this crate, and for testing key
+//! backends themselves. Only available with the `testing` feature.
+pub mod fixtures;
+pub mod harness;
+pub mod malformed;
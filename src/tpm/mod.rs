@@ -1,12 +1,17 @@
 mod tpm_wrapper;
 
+pub use tpm_wrapper::{health, shutdown};
+
 use crate::{
-    ecc_compact, ecc_compact::Signature, error, keypair, public_key, KeyTag,
+    deadline, ecc_compact, ecc_compact::Signature, error, keypair, public_key, telemetry, KeyTag,
     KeyType as CrateKeyType, Network, Result,
 };
 use p256::{ecdsa, elliptic_curve::sec1::FromEncodedPoint};
 use sha2::{Digest, Sha256};
-use std::convert::{TryFrom, TryInto};
+use std::{
+    convert::{TryFrom, TryInto},
+    time::Duration,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -23,6 +28,10 @@ pub struct Keypair {
     pub network: Network,
     pub public_key: public_key::PublicKey,
     pub path: String,
+    /// The timeout for sign and ecdh operations. An operation which does not
+    /// complete in time fails with a timeout error, but still runs to
+    /// completion in the background and releases its TPM handles.
+    pub timeout: Option<Duration>,
 }
 
 impl std::fmt::Debug for Keypair {
@@ -36,16 +45,20 @@ impl std::fmt::Debug for Keypair {
 
 impl keypair::Sign for Keypair {
     fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
-        use signature::Signer;
-        let signature = self.try_sign(msg)?;
+        let signature = self.sign_digest(Sha256::new().chain_update(msg))?;
         Ok(signature.to_vec())
     }
 }
 
 impl Keypair {
     pub fn from_key_path(network: Network, key_path: &str) -> Result<Keypair> {
+        let key_tag = KeyTag {
+            network,
+            key_type: CrateKeyType::EccCompact,
+        };
         let key_bytes = {
-            let mut key_bytes: Vec<u8> = Self::public_key(key_path)?;
+            let mut key_bytes: Vec<u8> = telemetry::span("init", Some(key_tag), "tpm")
+                .in_scope(|| Self::public_key(key_path))?;
             key_bytes.push(4);
             key_bytes.rotate_right(1);
             key_bytes
@@ -55,9 +68,15 @@ impl Keypair {
             network,
             public_key: public_key::PublicKey::for_network(network, public_key),
             path: key_path.to_string(),
+            timeout: None,
         })
     }
 
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     fn public_key(key_path: &str) -> Result<Vec<u8>> {
         let res = tpm_wrapper::public_key(key_path)?;
         Ok(res)
@@ -70,6 +89,16 @@ impl Keypair {
         }
     }
 
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        let path = self.path.clone();
+        let sign_slice = deadline::run(self.timeout, move || {
+            tpm_wrapper::sign(&path, &digest.finalize())
+        })?;
+        let signature = ecdsa::Signature::from_der(&sign_slice[..])?;
+        Ok(Signature(signature))
+    }
+
     pub fn ecdh<'a, C>(&self, public_key: C) -> Result<ecc_compact::SharedSecret>
     where
         C: TryInto<&'a ecc_compact::PublicKey, Error = error::Error>,
@@ -77,12 +106,14 @@ impl Keypair {
         use p256::elliptic_curve::sec1::ToEncodedPoint;
         let key = public_key.try_into()?;
         let point = key.0.to_encoded_point(false);
-        let x = point.x().unwrap().as_slice();
-        let y = point.y().unwrap().as_slice();
-        let path = &self.path;
+        let x = point.x().unwrap().to_vec();
+        let y = point.y().unwrap().to_vec();
+        let path = self.path.clone();
 
         let mut shared_secret_bytes = vec![4u8];
-        shared_secret_bytes.extend_from_slice(tpm_wrapper::ecdh(x, y, path)?.as_slice());
+        shared_secret_bytes.extend_from_slice(
+            deadline::run(self.timeout, move || tpm_wrapper::ecdh(&x, &y, &path))?.as_slice(),
+        );
 
         let encoded_point = p256::EncodedPoint::from_bytes(shared_secret_bytes.as_slice())
             .map_err(p256::elliptic_curve::Error::from)?;
@@ -95,11 +126,7 @@ impl Keypair {
 
 impl signature::Signer<Signature> for Keypair {
     fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
-        let digest = Sha256::digest(msg);
-        let sign_slice =
-            tpm_wrapper::sign(&self.path, &digest).map_err(signature::Error::from_source)?;
-
-        let signature = ecdsa::Signature::from_der(&sign_slice[..])?;
-        Ok(Signature(signature))
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
     }
 }
@@ -1,8 +1,17 @@
-use crate::{error, tpm};
-use drop_guard::guard;
+use crate::{
+    error,
+    health::{FailureCount, Health},
+    tpm,
+};
 use lazy_static::lazy_static;
 use libc::c_void;
-use std::{ffi::CString, mem::MaybeUninit, ptr::null_mut, ptr::NonNull, sync::Mutex};
+use std::{
+    ffi::CString,
+    mem::MaybeUninit,
+    ptr::null_mut,
+    ptr::NonNull,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
 use tpm::Error as TpmError;
 use tss2::{
     Esys_ContextLoad, Esys_ECDH_ZGen, Esys_Finalize, Esys_FlushContext, Esys_Free, Esys_Initialize,
@@ -56,16 +65,59 @@ impl Drop for FapiContext {
     }
 }
 
-lazy_static! {
-    // TODO: figure out an ergonomic way to not panic when
-    //       `FapiContext::new()` errors.
-    static ref TPM_CTX: Mutex<FapiContext> = Mutex::new(FapiContext::new().unwrap());
+/// An RAII wrapper for ESYS_CONTEXT.
+struct EsysContext(NonNull<ESYS_CONTEXT>);
+
+impl EsysContext {
+    /// Initializes an ESYS context which shares the TCTI of the given FAPI
+    /// context.
+    pub unsafe fn from_fapi(fapi_ctx: &mut FapiContext) -> Result<Self> {
+        let mut tcti_ctx: *mut TSS2_TCTI_CONTEXT = null_mut();
+        tss2_call!(Fapi_GetTcti(
+            fapi_ctx.as_mut(),
+            // NOTE: we explicitly do not free this out pointer, as we
+            // believe it is part of the context.
+            &mut tcti_ctx as *mut *mut TSS2_TCTI_CONTEXT,
+        ))?;
+
+        let mut esys_ctx: *mut ESYS_CONTEXT = null_mut();
+        tss2_call!(Esys_Initialize(
+            &mut esys_ctx as *mut *mut ESYS_CONTEXT,
+            tcti_ctx,
+            null_mut(),
+        ))?;
+        Ok(Self(NonNull::new(esys_ctx).expect("ptr is null")))
+    }
+
+    pub fn as_ptr(&self) -> *mut ESYS_CONTEXT {
+        self.0.as_ptr()
+    }
 }
 
-pub fn public_key(key_path: &str) -> Result<Vec<u8>> {
-    unsafe {
-        let mut tpm_ctx = TPM_CTX.lock().unwrap();
-        let mut esys_key_handle: ESYS_TR = u32::MAX;
+impl Drop for EsysContext {
+    fn drop(&mut self) {
+        let mut esys_ctx = self.0.as_ptr();
+        unsafe {
+            Esys_Finalize(&mut esys_ctx as *mut *mut ESYS_CONTEXT);
+        }
+    }
+}
+
+/// An RAII wrapper for a key handle in an ESYS context. Keys loaded from a
+/// saved context occupy a transient TPM slot, which is flushed on drop.
+struct KeyHandle<'a> {
+    esys_ctx: &'a EsysContext,
+    handle: ESYS_TR,
+    transient: bool,
+}
+
+impl<'a> KeyHandle<'a> {
+    /// Loads the key at the given FAPI key path into the given ESYS context.
+    pub unsafe fn load(
+        fapi_ctx: &mut FapiContext,
+        esys_ctx: &'a EsysContext,
+        key_path: &str,
+    ) -> Result<Self> {
         let mut blob_type: u8 = 0;
         let mut esys_blob: *mut u8 = null_mut();
         let mut blob_sz: tss2::size_t = 0;
@@ -74,36 +126,20 @@ pub fn public_key(key_path: &str) -> Result<Vec<u8>> {
             .map_err(|_| TpmError::BadKeyPath(key_path.to_owned()))?;
 
         tss2_call!(Fapi_GetEsysBlob(
-            tpm_ctx.as_mut(),
+            fapi_ctx.as_mut(),
             c_path.as_ptr(),
             &mut blob_type as *mut u8,
             &mut esys_blob as *mut *mut u8,
             &mut blob_sz as *mut tss2::size_t,
         ))?;
-        let esys_blob = guard(esys_blob, |p| Esys_Free(p as *mut c_void));
-
-        let mut tcti_ctx: *mut TSS2_TCTI_CONTEXT = null_mut();
-        tss2_call!(Fapi_GetTcti(
-            tpm_ctx.as_mut(),
-            // NOTE: we explicitly do not free this out pointer, as we
-            // believe it is part of the context.
-            &mut tcti_ctx as *mut *mut TSS2_TCTI_CONTEXT,
-        ))?;
-
-        let mut esys_ctx: *mut ESYS_CONTEXT = null_mut();
-
-        tss2_call!(Esys_Initialize(
-            &mut esys_ctx as *mut *mut ESYS_CONTEXT,
-            tcti_ctx,
-            null_mut(),
-        ))?;
-        let esys_ctx = guard(esys_ctx, |mut p| Esys_Finalize(&mut p));
+        let esys_blob = Tss2Buffer(esys_blob);
 
+        let mut handle: ESYS_TR = u32::MAX;
         match blob_type as u32 {
             FAPI_ESYSBLOB_CONTEXTLOAD => {
                 let mut key_context: MaybeUninit<TPMS_CONTEXT> = MaybeUninit::uninit();
                 tss2_call!(Tss2_MU_TPMS_CONTEXT_Unmarshal(
-                    *esys_blob,
+                    esys_blob.0,
                     blob_sz,
                     &mut offset as *mut tss2::size_t,
                     key_context.as_mut_ptr(),
@@ -111,33 +147,136 @@ pub fn public_key(key_path: &str) -> Result<Vec<u8>> {
                 let key_context = key_context.assume_init();
 
                 tss2_call!(Esys_ContextLoad(
-                    *esys_ctx,
+                    esys_ctx.as_ptr(),
                     &key_context,
-                    &mut esys_key_handle as *mut ESYS_TR,
+                    &mut handle as *mut ESYS_TR,
                 ))?;
+                Ok(Self {
+                    esys_ctx,
+                    handle,
+                    transient: true,
+                })
             }
             FAPI_ESYSBLOB_DESERIALIZE => {
                 tss2_call!(Esys_TR_Deserialize(
-                    *esys_ctx,
-                    *esys_blob,
+                    esys_ctx.as_ptr(),
+                    esys_blob.0,
                     blob_sz,
-                    &mut esys_key_handle as *mut ESYS_TR
+                    &mut handle as *mut ESYS_TR
                 ))?;
+                Ok(Self {
+                    esys_ctx,
+                    handle,
+                    transient: false,
+                })
             }
-            _ => {
-                return Err(TpmError::BadKeyPath(key_path.into()).into());
+            _ => Err(TpmError::BadKeyPath(key_path.into()).into()),
+        }
+    }
+}
+
+impl<'a> Drop for KeyHandle<'a> {
+    fn drop(&mut self) {
+        if self.transient {
+            unsafe {
+                Esys_FlushContext(self.esys_ctx.as_ptr(), self.handle);
             }
         }
-        let esys_key_handle = guard(esys_key_handle, |p| {
-            if blob_type as u32 == FAPI_ESYSBLOB_CONTEXTLOAD {
-                Esys_FlushContext(*esys_ctx, p);
+    }
+}
+
+/// An RAII wrapper for memory allocated and returned by the TSS2 libraries.
+struct Tss2Buffer<T>(*mut T);
+
+impl<T> Drop for Tss2Buffer<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe {
+                Esys_Free(self.0 as *mut c_void);
             }
-        });
+        }
+    }
+}
+
+/// The global FAPI context, which is created on first use and recreated
+/// after a TPM failure.
+#[derive(Default)]
+struct Connection {
+    fapi_ctx: Option<FapiContext>,
+    failures: FailureCount,
+    initialized: bool,
+}
+
+lazy_static! {
+    static ref TPM_CTX: Mutex<Connection> = Mutex::new(Connection::default());
+}
+
+/// Locks the global FAPI context. All TPM resources are released by RAII
+/// wrappers, including when a thread panics, so a poisoned lock still guards a
+/// usable context.
+fn lock_context() -> MutexGuard<'static, Connection> {
+    TPM_CTX.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the health of the global TPM context
+pub fn health() -> Health {
+    let connection = lock_context();
+    if connection.initialized {
+        connection.failures.health()
+    } else {
+        Health::Uninitialized
+    }
+}
+
+/// Finalizes the global FAPI context once any in-flight operation completes.
+/// Key handles are released after every operation, so no TPM handles remain
+/// in use afterwards. A later operation creates a new context.
+pub fn shutdown() {
+    // Dropping the context finalizes it
+    lock_context().fapi_ctx = None;
+}
+
+/// Runs the given operation with the global FAPI context, creating the
+/// context if needed. If the operation fails with a TPM error, the context is
+/// recreated and the operation is replayed once.
+fn supervised<F, R>(f: F) -> Result<R>
+where
+    F: Fn(&mut FapiContext) -> Result<R>,
+{
+    let mut connection = lock_context();
+    let mut result = connection.run(&f);
+    if let Err(error::Error::TPM(TpmError::TPMError(_, _))) = result {
+        // Dropping the context finalizes it
+        connection.fapi_ctx = None;
+        result = connection.run(&f);
+    }
+    connection.failures.record(&result);
+    result
+}
+
+impl Connection {
+    fn run<F, R>(&mut self, f: &F) -> Result<R>
+    where
+        F: Fn(&mut FapiContext) -> Result<R>,
+    {
+        if self.fapi_ctx.is_none() {
+            self.initialized = true;
+            self.fapi_ctx = Some(FapiContext::new()?);
+        }
+        // Unwrap ok here since the context was created above
+        f(self.fapi_ctx.as_mut().unwrap())
+    }
+}
+
+pub fn public_key(key_path: &str) -> Result<Vec<u8>> {
+    supervised(|tpm_ctx| unsafe {
+        let esys_ctx = EsysContext::from_fapi(tpm_ctx)?;
+        let key_handle = KeyHandle::load(tpm_ctx, &esys_ctx, key_path)?;
 
         let mut public_part: *mut TPM2B_PUBLIC = null_mut();
         tss2_call!(Esys_ReadPublic(
-            *esys_ctx,
-            *esys_key_handle,
+            esys_ctx.as_ptr(),
+            key_handle.handle,
             ESYS_TR_NONE,
             ESYS_TR_NONE,
             ESYS_TR_NONE,
@@ -145,88 +284,21 @@ pub fn public_key(key_path: &str) -> Result<Vec<u8>> {
             null_mut(),
             null_mut(),
         ))?;
-        let public_part = guard(public_part, |p| Esys_Free(p as *mut c_void));
+        let public_part = Tss2Buffer(public_part);
 
-        let ecc_point = (**public_part).publicArea.unique.ecc;
+        let ecc_point = (*public_part.0).publicArea.unique.ecc;
         let mut key_bytes = Vec::new();
         key_bytes.extend_from_slice(&ecc_point.x.buffer.as_slice()[..ecc_point.x.size as usize]);
         key_bytes.extend_from_slice(&ecc_point.y.buffer.as_slice()[..ecc_point.y.size as usize]);
 
         Ok(key_bytes)
-    }
+    })
 }
 
 pub fn ecdh(x: &[u8], y: &[u8], key_path: &str) -> Result<Vec<u8>> {
-    unsafe {
-        let mut tpm_ctx = TPM_CTX.lock().unwrap();
-        let mut esys_key_handle: ESYS_TR = u32::MAX;
-        let mut blob_type: u8 = 0;
-        let mut esys_blob: *mut u8 = null_mut();
-        let mut blob_sz: tss2::size_t = 0;
-        let mut offset: tss2::size_t = 0;
-        let c_path = CString::new(key_path.as_bytes())
-            .map_err(|_| TpmError::BadKeyPath(key_path.to_string()))?;
-
-        tss2_call!(Fapi_GetEsysBlob(
-            tpm_ctx.as_mut(),
-            c_path.as_ptr(),
-            &mut blob_type as *mut u8,
-            &mut esys_blob as *mut *mut u8,
-            &mut blob_sz as *mut tss2::size_t,
-        ))?;
-        let esys_blob = guard(esys_blob, |p| Esys_Free(p as *mut c_void));
-
-        let mut tcti_ctx: *mut TSS2_TCTI_CONTEXT = null_mut();
-        tss2_call!(Fapi_GetTcti(
-            tpm_ctx.as_mut(),
-            // NOTE: we explicitly do not free this out pointer, as we
-            // believe it is part of the context.
-            &mut tcti_ctx as *mut *mut TSS2_TCTI_CONTEXT,
-        ))?;
-
-        let mut esys_ctx: *mut ESYS_CONTEXT = null_mut();
-
-        tss2_call!(Esys_Initialize(
-            &mut esys_ctx as *mut *mut ESYS_CONTEXT,
-            tcti_ctx,
-            null_mut(),
-        ))?;
-        let esys_ctx = guard(esys_ctx, |mut p| Esys_Finalize(&mut p));
-
-        match blob_type as u32 {
-            FAPI_ESYSBLOB_CONTEXTLOAD => {
-                let mut key_context: MaybeUninit<TPMS_CONTEXT> = MaybeUninit::uninit();
-                tss2_call!(Tss2_MU_TPMS_CONTEXT_Unmarshal(
-                    *esys_blob,
-                    blob_sz,
-                    &mut offset as *mut tss2::size_t,
-                    key_context.as_mut_ptr(),
-                ))?;
-                let key_context = key_context.assume_init();
-
-                tss2_call!(Esys_ContextLoad(
-                    *esys_ctx,
-                    &key_context,
-                    &mut esys_key_handle as *mut ESYS_TR,
-                ))?;
-            }
-            FAPI_ESYSBLOB_DESERIALIZE => {
-                tss2_call!(Esys_TR_Deserialize(
-                    *esys_ctx,
-                    *esys_blob,
-                    blob_sz,
-                    &mut esys_key_handle as *mut ESYS_TR
-                ))?;
-            }
-            _ => {
-                return Err(TpmError::BadKeyPath(key_path.into()).into());
-            }
-        }
-        let esys_key_handle = guard(esys_key_handle, |p| {
-            if blob_type as u32 == FAPI_ESYSBLOB_CONTEXTLOAD {
-                Esys_FlushContext(*esys_ctx, p);
-            }
-        });
+    supervised(|tpm_ctx| unsafe {
+        let esys_ctx = EsysContext::from_fapi(tpm_ctx)?;
+        let key_handle = KeyHandle::load(tpm_ctx, &esys_ctx, key_path)?;
 
         let pub_point = {
             let mut p: MaybeUninit<TPM2B_ECC_POINT> = MaybeUninit::zeroed();
@@ -241,31 +313,27 @@ pub fn ecdh(x: &[u8], y: &[u8], key_path: &str) -> Result<Vec<u8>> {
         let mut secret: *mut TPM2B_ECC_POINT = null_mut();
 
         tss2_call!(Esys_ECDH_ZGen(
-            *esys_ctx,
-            *esys_key_handle,
+            esys_ctx.as_ptr(),
+            key_handle.handle,
             ESYS_TR_PASSWORD,
             ESYS_TR_NONE,
             ESYS_TR_NONE,
             &pub_point,
             &mut secret as *mut *mut TPM2B_ECC_POINT,
         ))?;
-        let secret = guard(secret, |p| Esys_Free(p as *mut libc::c_void));
+        let secret = Tss2Buffer(secret);
 
+        let point = &(*secret.0).point;
         let mut shared_secret_bytes = Vec::new();
-        shared_secret_bytes.extend_from_slice(
-            &(**secret).point.x.buffer.as_slice()[..(**secret).point.x.size as usize],
-        );
-        shared_secret_bytes.extend_from_slice(
-            &(**secret).point.y.buffer.as_slice()[..(**secret).point.y.size as usize],
-        );
+        shared_secret_bytes.extend_from_slice(&point.x.buffer.as_slice()[..point.x.size as usize]);
+        shared_secret_bytes.extend_from_slice(&point.y.buffer.as_slice()[..point.y.size as usize]);
 
         Ok(shared_secret_bytes)
-    }
+    })
 }
 
 pub fn sign(key_path: &str, digest: &[u8]) -> Result<Vec<u8>> {
-    unsafe {
-        let mut tpm_ctx = TPM_CTX.lock().unwrap();
+    supervised(|tpm_ctx| unsafe {
         let mut raw_signature: *mut u8 = null_mut();
         let mut signature_sz: tss2::size_t = 0;
         let c_path =
@@ -281,10 +349,11 @@ pub fn sign(key_path: &str, digest: &[u8]) -> Result<Vec<u8>> {
             null_mut(),
             null_mut(),
         ))?;
+        let raw_signature = Tss2Buffer(raw_signature);
 
-        let sign_slice = std::slice::from_raw_parts(raw_signature, signature_sz as usize).to_vec();
-        Esys_Free(raw_signature as *mut c_void);
+        let sign_slice =
+            std::slice::from_raw_parts(raw_signature.0, signature_sz as usize).to_vec();
 
         Ok(sign_slice)
-    }
+    })
 }
@@ -0,0 +1,192 @@
+//! Signatures that are only valid within a time window.
+//!
+//! A [`TimeBoundSignature`] carries a [`Validity`] window whose not-before and
+//! not-after timestamps are signed together with the message, so they can not
+//! be changed without invalidating the signature. Verification checks the
+//! signature first and then that the current time, read from a [`Clock`], is
+//! within the window.
+use crate::{
+    clock::Clock,
+    signed_message::{read_u32_prefixed, write_u32_prefixed},
+    *,
+};
+use std::io;
+
+/// Domain separator prefixed to the signed bytes of a time bound signature
+const VALIDITY_DOMAIN: &[u8] = b"helium-time-bound-signature";
+
+/// A validity window, in seconds since the unix epoch, including both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Validity {
+    pub not_before: u64,
+    pub not_after: u64,
+}
+
+impl Validity {
+    pub fn new(not_before: u64, not_after: u64) -> Self {
+        Self {
+            not_before,
+            not_after,
+        }
+    }
+
+    /// A window starting at the given time and lasting the given number of
+    /// seconds
+    pub fn starting_at(not_before: u64, secs: u64) -> Self {
+        Self::new(not_before, not_before.saturating_add(secs))
+    }
+
+    /// Returns an error if the given current time is outside the window
+    pub fn check(&self, now: u64) -> Result {
+        if now < self.not_before {
+            return Err(Error::not_yet_valid(self.not_before));
+        }
+        if now > self.not_after {
+            return Err(Error::expired(self.not_after));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeBoundSignature {
+    pub validity: Validity,
+    /// The signature over the validity window and the message
+    pub signature: Vec<u8>,
+}
+
+impl TimeBoundSignature {
+    /// Sign the given message for the given validity window
+    pub fn sign<S: Sign + ?Sized>(signer: &S, msg: &[u8], validity: Validity) -> Result<Self> {
+        let signature = signer.sign(&signing_bytes(&validity, msg))?;
+        Ok(Self {
+            validity,
+            signature,
+        })
+    }
+
+    /// Sign the given message for the given number of seconds from the
+    /// current time of the given clock
+    pub fn sign_for<S: Sign + ?Sized, C: Clock>(
+        signer: &S,
+        msg: &[u8],
+        secs: u64,
+        clock: &C,
+    ) -> Result<Self> {
+        Self::sign(signer, msg, Validity::starting_at(clock.now(), secs))
+    }
+
+    /// Verify the signature over the given message and that the current time
+    /// of the given clock is within the validity window
+    pub fn verify<C: Clock>(&self, public_key: &PublicKey, msg: &[u8], clock: &C) -> Result {
+        self.verify_at(public_key, msg, clock.now())
+    }
+
+    /// Like [`TimeBoundSignature::verify`] but at the given current time
+    pub fn verify_at(&self, public_key: &PublicKey, msg: &[u8], now: u64) -> Result {
+        public_key.verify(&signing_bytes(&self.validity, msg), &self.signature)?;
+        self.validity.check(now)
+    }
+
+    /// Convert the signature to its binary form
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        // Unwrap ok here since writing to a vector can not fail
+        self.write_to(&mut result).unwrap();
+        result
+    }
+}
+
+impl WriteTo for TimeBoundSignature {
+    fn write_to<W: io::Write>(&self, output: &mut W) -> io::Result<()> {
+        output.write_all(&self.validity.not_before.to_be_bytes())?;
+        output.write_all(&self.validity.not_after.to_be_bytes())?;
+        write_u32_prefixed(output, &self.signature)
+    }
+}
+
+impl ReadFrom for TimeBoundSignature {
+    fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let mut not_before = [0u8; 8];
+        input.read_exact(&mut not_before)?;
+        let mut not_after = [0u8; 8];
+        input.read_exact(&mut not_after)?;
+        Ok(Self {
+            validity: Validity::new(
+                u64::from_be_bytes(not_before),
+                u64::from_be_bytes(not_after),
+            ),
+            signature: read_u32_prefixed(input)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for TimeBoundSignature {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = io::Cursor::new(input);
+        Self::read_from(&mut input)
+    }
+}
+
+/// Returns the bytes a time bound signature is calculated over
+fn signing_bytes(validity: &Validity, msg: &[u8]) -> Vec<u8> {
+    let mut result = VALIDITY_DOMAIN.to_vec();
+    result.extend_from_slice(&validity.not_before.to_be_bytes());
+    result.extend_from_slice(&validity.not_after.to_be_bytes());
+    result.extend_from_slice(msg);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn window() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let clock = ManualClock::new(1000);
+        let signature =
+            TimeBoundSignature::sign_for(&keypair, b"hello world", 60, &clock).expect("sign");
+        let public_key = keypair.public_key();
+
+        assert!(signature.verify(public_key, b"hello world", &clock).is_ok());
+        assert!(signature
+            .verify(public_key, b"hello there", &clock)
+            .is_err());
+        assert!(matches!(
+            signature.verify_at(public_key, b"hello world", 999),
+            Err(Error::NotYetValid(1000))
+        ));
+        clock.advance(61);
+        assert!(matches!(
+            signature.verify(public_key, b"hello world", &clock),
+            Err(Error::Expired(1060))
+        ));
+    }
+
+    #[test]
+    fn extended_window() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let mut signature =
+            TimeBoundSignature::sign(&keypair, b"hello world", Validity::new(1000, 1060))
+                .expect("sign");
+        signature.validity.not_after = 2000;
+        assert!(signature
+            .verify_at(keypair.public_key(), b"hello world", 1030)
+            .is_err());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature =
+            TimeBoundSignature::sign(&keypair, b"hello world", Validity::new(1000, 1060))
+                .expect("sign");
+        let decoded = TimeBoundSignature::try_from(&signature.to_vec()[..]).expect("decode");
+        assert_eq!(signature, decoded);
+    }
+}
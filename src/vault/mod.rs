@@ -0,0 +1,370 @@
+//! Keypairs backed by a key in the transit secrets engine of HashiCorp Vault.
+//!
+//! The private key never leaves Vault. Ed25519 keys sign the full message,
+//! and ECDSA P-256 keys sign a SHA-256 digest computed in software. The
+//! public key is fetched once when the keypair is opened, and signing is
+//! pinned to the key version it was fetched for, so rotating the key in Vault
+//! does not silently change the signing key. P-256 keys must have a
+//! compactable public key, and make an ecc_compact keypair.
+//!
+//! Requests authenticate with either a fixed token or an AppRole login.
+//! AppRole tokens expire, so when Vault refuses the token the keypair logs in
+//! again and retries the request once.
+//!
+//! Requests block the calling thread like other hardware keypairs, so async
+//! callers should wrap the keypair in an `AsyncKeypair`.
+use crate::{
+    deadline, ecc_compact::Signature, keypair, public_key::PublicKey, telemetry, KeyTag, KeyType,
+    Network, Result,
+};
+use p256::ecdsa;
+use reqwest::{blocking, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The mount path of the transit secrets engine unless given otherwise
+pub const DEFAULT_MOUNT: &str = "transit";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("vault request failed")]
+    Http(#[from] reqwest::Error),
+
+    #[error("unsupported vault key type: {0}")]
+    UnsupportedKey(String),
+
+    #[error("invalid vault response: {0}")]
+    InvalidResponse(&'static str),
+}
+
+/// How to authenticate with Vault
+#[derive(Clone)]
+pub enum Auth {
+    /// A token, used as is
+    Token(String),
+    /// An AppRole login with the AppRole auth method mounted at `approle`
+    AppRole { role_id: String, secret_id: String },
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Self::Token(_) => f.write_str("Token"),
+            Self::AppRole { role_id, .. } => {
+                f.debug_struct("AppRole").field("role_id", role_id).finish()
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    auth: LoginAuth,
+}
+
+#[derive(Deserialize)]
+struct LoginAuth {
+    client_token: String,
+}
+
+#[derive(Deserialize)]
+struct KeyData {
+    #[serde(rename = "type")]
+    key_type: String,
+    latest_version: u32,
+    /// The versions of the key. Asymmetric keys have an object with the
+    /// public key for every version.
+    keys: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct Signed {
+    signature: String,
+}
+
+/// A Vault client holding the current token
+#[derive(Clone)]
+struct Client {
+    address: String,
+    http: blocking::Client,
+    auth: Auth,
+    token: Arc<Mutex<String>>,
+}
+
+impl Client {
+    fn new(address: &str, auth: Auth) -> std::result::Result<Self, Error> {
+        let client = Self {
+            address: address.trim_end_matches('/').to_string(),
+            http: blocking::Client::new(),
+            auth,
+            token: Arc::new(Mutex::new(String::new())),
+        };
+        client.login()?;
+        Ok(client)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1/{}", self.address, path)
+    }
+
+    fn login(&self) -> std::result::Result<(), Error> {
+        let token = match &self.auth {
+            Auth::Token(token) => token.clone(),
+            Auth::AppRole { role_id, secret_id } => {
+                let login: Login = self
+                    .http
+                    .post(self.url("auth/approle/login"))
+                    .json(&json!({ "role_id": role_id, "secret_id": secret_id }))
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+                login.auth.client_token
+            }
+        };
+        *self.token.lock().unwrap_or_else(PoisonError::into_inner) = token;
+        Ok(())
+    }
+
+    /// Sends a request to the given path, a POST of the given body or a GET
+    /// without one, and returns the data of the response
+    fn request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: Option<&Value>,
+    ) -> std::result::Result<T, Error> {
+        let send = || {
+            let token = self
+                .token
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
+            let request = match body {
+                Some(body) => self.http.post(self.url(path)).json(body),
+                None => self.http.get(self.url(path)),
+            };
+            request.header("X-Vault-Token", token).send()
+        };
+        let mut response = send()?;
+        if response.status() == StatusCode::FORBIDDEN && matches!(self.auth, Auth::AppRole { .. }) {
+            self.login()?;
+            response = send()?;
+        }
+        Ok(response.error_for_status()?.json::<Data<T>>()?.data)
+    }
+}
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: PublicKey,
+    mount: String,
+    key_name: String,
+    key_version: u32,
+    client: Client,
+    timeout: Option<Duration>,
+}
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("key_name", &self.key_name)
+            .field("key_version", &self.key_version)
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl keypair::Sign for Keypair {
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        match self.public_key.key_type() {
+            KeyType::Ed25519 => self.sign_request(json!({
+                "input": base64::encode(msg),
+                "key_version": self.key_version,
+            })),
+            _ => Ok(self.sign_digest(Sha256::new().chain_update(msg))?.to_vec()),
+        }
+    }
+}
+
+impl Keypair {
+    /// Opens the latest version of the `ed25519` or `ecdsa-p256` transit key
+    /// with the given name in the Vault at the given address, like
+    /// `https://vault.example.com:8200`, with the transit secrets engine at
+    /// [`DEFAULT_MOUNT`].
+    pub fn open(network: Network, address: &str, key_name: &str, auth: Auth) -> Result<Keypair> {
+        Self::open_with_mount(network, address, DEFAULT_MOUNT, key_name, auth)
+    }
+
+    /// Opens the latest version of the `ed25519` or `ecdsa-p256` transit key
+    /// with the given name in the Vault at the given address, with the
+    /// transit secrets engine at the given mount path.
+    pub fn open_with_mount(
+        network: Network,
+        address: &str,
+        mount: &str,
+        key_name: &str,
+        auth: Auth,
+    ) -> Result<Keypair> {
+        telemetry::span("init", None, "vault").in_scope(|| {
+            let client = Client::new(address, auth)?;
+            let key: KeyData = client.request(&format!("{}/keys/{}", mount, key_name), None)?;
+            let encoded = key
+                .keys
+                .get(&key.latest_version.to_string())
+                .and_then(|version| version.get("public_key"))
+                .and_then(Value::as_str)
+                .ok_or(Error::InvalidResponse("missing public key"))?;
+            Ok(Keypair {
+                network,
+                public_key: vault_public_key(network, &key.key_type, encoded)?,
+                mount: mount.to_string(),
+                key_name: key_name.to_string(),
+                key_version: key.latest_version,
+                client,
+                timeout: None,
+            })
+        })
+    }
+
+    /// Set the timeout for sign operations on this keypair. An operation
+    /// which does not complete in time fails with a timeout error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        self.public_key.key_tag()
+    }
+
+    /// Sign the given, incrementally computed, SHA-256 digest of a message.
+    /// Only ECDSA P-256 keys support this.
+    pub fn sign_digest(&self, digest: Sha256) -> Result<Signature> {
+        if self.public_key.key_type() != KeyType::EccCompact {
+            return Err(crate::Error::invalid_curve());
+        }
+        let der = self.sign_request(json!({
+            "input": base64::encode(digest.finalize()),
+            "prehashed": true,
+            "hash_algorithm": "sha2-256",
+            "marshaling_algorithm": "asn1",
+            "key_version": self.key_version,
+        }))?;
+        Ok(Signature(ecdsa::Signature::from_der(&der)?))
+    }
+
+    fn sign_request(&self, body: Value) -> Result<Vec<u8>> {
+        let client = self.client.clone();
+        let path = format!("{}/sign/{}", self.mount, self.key_name);
+        let signed: Signed =
+            deadline::run(
+                self.timeout,
+                move || Ok(client.request(&path, Some(&body))?),
+            )?;
+        decode_signature(&signed.signature)
+    }
+}
+
+/// Returns the public key of the given type from its encoding in a transit
+/// key, base64 for ed25519 keys and a PEM document for ECDSA keys
+fn vault_public_key(network: Network, key_type: &str, encoded: &str) -> Result<PublicKey> {
+    match key_type {
+        "ed25519" => {
+            let mut bytes = vec![u8::from(KeyTag {
+                network,
+                key_type: KeyType::Ed25519,
+            })];
+            bytes.extend_from_slice(
+                &base64::decode(encoded)
+                    .map_err(|_| Error::InvalidResponse("invalid public key"))?,
+            );
+            PublicKey::try_from(&bytes[..])
+        }
+        "ecdsa-p256" => PublicKey::from_pem(network, encoded),
+        other => Err(Error::UnsupportedKey(other.to_string()).into()),
+    }
+}
+
+/// Decodes a transit signature, which is prefixed with the key version it
+/// was made with, like `vault:v1:`
+fn decode_signature(signature: &str) -> Result<Vec<u8>> {
+    match signature.splitn(3, ':').collect::<Vec<_>>()[..] {
+        ["vault", version, encoded] if version.starts_with('v') => {
+            Ok(base64::decode(encoded).map_err(|_| Error::InvalidResponse("invalid signature"))?)
+        }
+        _ => Err(Error::InvalidResponse("invalid signature").into()),
+    }
+}
+
+impl signature::Signer<Signature> for Keypair {
+    fn try_sign(&self, msg: &[u8]) -> std::result::Result<Signature, signature::Error> {
+        self.sign_digest(Sha256::new().chain_update(msg))
+            .map_err(signature::Error::from_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn public_keys() {
+        let keypair = crate::Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::Ed25519,
+            },
+            &mut OsRng,
+        );
+        let encoded = base64::encode(&keypair.public_key().to_vec()[1..]);
+        assert_eq!(
+            keypair.public_key(),
+            &vault_public_key(Network::MainNet, "ed25519", &encoded).expect("ed25519")
+        );
+
+        let keypair = crate::Keypair::generate(
+            KeyTag {
+                network: Network::TestNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let pem = keypair.public_key().to_pem().expect("pem");
+        assert_eq!(
+            keypair.public_key(),
+            &vault_public_key(Network::TestNet, "ecdsa-p256", &pem).expect("ecdsa")
+        );
+        assert!(vault_public_key(Network::MainNet, "aes256-gcm96", &pem).is_err());
+    }
+
+    #[test]
+    fn signatures() {
+        assert_eq!(
+            vec![0x01, 0x02, 0x03],
+            decode_signature("vault:v12:AQID").expect("signature")
+        );
+        assert!(decode_signature("AQID").is_err());
+        assert!(decode_signature("vault:AQID").is_err());
+        assert!(decode_signature("vault:v1:not base64!").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Golden test vectors shared between implementations.
+//!
+//! A [`TestVector`] captures a keypair in its binary form together with its
+//! public key, a message, the expected signature over that message and, for
+//! key types that support it, an expected ECDH shared secret. Vectors are
+//! generated deterministically with [`Keypair::generate_deterministic`] and
+//! exchanged as JSON, with binary values hex encoded and public keys in their
+//! base58 form.
+//!
+//! Vectors are generated for every enabled key type that can sign. X25519
+//! keys can not sign and multisig keypairs have no binary form, so neither
+//! has vectors. Apart from sr25519, whose signatures are randomized, all
+//! signatures are deterministic and [`TestVector::check`] compares them byte
+//! for byte in addition to verifying them.
+//!
+//! The golden vectors generated from the seed `golden` are checked in next to
+//! this module for every key type except BLS and sr25519, so a change to key
+//! derivation, signing or ECDH shows up as a test failure.
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+/// The message signed by generated vectors
+const VECTOR_MESSAGE: &[u8] = b"helium-crypto test vector";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// The binary form of the keypair, including its key tag
+    #[serde(with = "hex_bytes")]
+    pub keypair: Vec<u8>,
+    pub public_key: PublicKey,
+    #[serde(with = "hex_bytes")]
+    pub message: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecdh: Option<EcdhVector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EcdhVector {
+    /// The public key of the other party
+    pub public_key: PublicKey,
+    /// The raw shared secret bytes
+    #[serde(with = "hex_bytes")]
+    pub shared_secret: Vec<u8>,
+}
+
+impl TestVector {
+    /// Generate the test vector for the given key tag from the given seed
+    pub fn generate(key_tag: KeyTag, seed: &[u8]) -> Result<Self> {
+        let keypair = Keypair::generate_deterministic(key_tag, seed)?;
+        let ecdh = match key_tag.key_type {
+            KeyType::EccCompact => Some(ecdh_vector(&keypair, key_tag, seed)?),
+            #[cfg(feature = "p384")]
+            KeyType::EccP384 => Some(ecdh_vector(&keypair, key_tag, seed)?),
+            _ => None,
+        };
+        Ok(Self {
+            keypair: keypair.to_vec(),
+            public_key: keypair.public_key().clone(),
+            message: VECTOR_MESSAGE.to_vec(),
+            signature: keypair.sign(VECTOR_MESSAGE)?,
+            ecdh,
+        })
+    }
+
+    /// Check this vector against this implementation. The keypair must decode
+    /// to the public key, the signature must verify and, for deterministic
+    /// signatures, signing and ECDH must reproduce the expected outputs
+    /// exactly.
+    pub fn check(&self) -> Result {
+        let keypair = Keypair::try_from(&self.keypair[..])?;
+        if keypair.public_key() != &self.public_key {
+            return Err(signature::Error::new().into());
+        }
+        self.public_key.verify(&self.message, &self.signature)?;
+        if has_deterministic_signatures(self.public_key.key_type())
+            && keypair.sign(&self.message)? != self.signature
+        {
+            return Err(signature::Error::new().into());
+        }
+        if let Some(ecdh) = &self.ecdh {
+            let shared_secret = keypair.ecdh(&ecdh.public_key)?;
+            if shared_secret.raw_secret_bytes() != &ecdh.shared_secret[..] {
+                return Err(signature::Error::new().into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generate the ECDH vector for the given keypair with a peer derived from
+/// the given seed
+fn ecdh_vector(keypair: &Keypair, key_tag: KeyTag, seed: &[u8]) -> Result<EcdhVector> {
+    let peer_seed = [seed, &b"peer"[..]].concat();
+    let peer = Keypair::generate_deterministic(key_tag, &peer_seed)?;
+    let shared_secret = keypair.ecdh(peer.public_key())?;
+    Ok(EcdhVector {
+        public_key: peer.public_key().clone(),
+        shared_secret: shared_secret.raw_secret_bytes().to_vec(),
+    })
+}
+
+/// The enabled key types vectors are generated for
+fn vector_key_types() -> Vec<KeyType> {
+    vec![
+        KeyType::Ed25519,
+        KeyType::EccCompact,
+        #[cfg(feature = "secp256k1")]
+        KeyType::Secp256k1,
+        #[cfg(feature = "bls")]
+        KeyType::Bls,
+        #[cfg(feature = "ed448")]
+        KeyType::Ed448,
+        #[cfg(feature = "sr25519")]
+        KeyType::Sr25519,
+        #[cfg(feature = "p384")]
+        KeyType::EccP384,
+    ]
+}
+
+/// Whether signing the same message twice gives the same signature
+fn has_deterministic_signatures(key_type: KeyType) -> bool {
+    match key_type {
+        #[cfg(feature = "sr25519")]
+        KeyType::Sr25519 => false,
+        _ => true,
+    }
+}
+
+/// Generate test vectors for every enabled key type and network from the
+/// given seed
+pub fn generate_all(seed: &[u8]) -> Result<Vec<TestVector>> {
+    let mut vectors = vec![];
+    for network in [Network::MainNet, Network::TestNet] {
+        for key_type in vector_key_types() {
+            vectors.push(TestVector::generate(KeyTag { network, key_type }, seed)?);
+        }
+    }
+    Ok(vectors)
+}
+
+/// Check all given vectors, returning the first failure
+pub fn check_all(vectors: &[TestVector]) -> Result {
+    vectors.iter().try_for_each(TestVector::check)
+}
+
+/// Convert the given vectors to pretty printed JSON
+pub fn to_json(vectors: &[TestVector]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(vectors)?)
+}
+
+/// Parse vectors from JSON
+pub fn from_json(json: &str) -> Result<Vec<TestVector>> {
+    Ok(serde_json::from_str(json)?)
+}
+
+mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        hex::decode(encoded).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The checked in golden vectors of the enabled key types
+    fn golden_vectors() -> Vec<TestVector> {
+        let values: Vec<serde_json::Value> =
+            serde_json::from_str(include_str!("vectors.json")).expect("json");
+        values
+            .into_iter()
+            .filter(|value| {
+                value["keypair"]
+                    .as_str()
+                    .and_then(|keypair| hex::decode(keypair).ok())
+                    .and_then(|keypair| KeyTag::try_from(*keypair.first()?).ok())
+                    .is_some()
+            })
+            .map(|value| serde_json::from_value(value).expect("vector"))
+            .collect()
+    }
+
+    #[test]
+    fn golden() {
+        let vectors = golden_vectors();
+        assert!(vectors.len() >= 4);
+        assert!(check_all(&vectors).is_ok());
+        // Generating the vectors again gives the exact same keys and outputs
+        for vector in &vectors {
+            let key_tag = KeyTag::try_from(vector.keypair[0]).expect("key tag");
+            assert_eq!(
+                vector,
+                &TestVector::generate(key_tag, b"golden").expect("vector")
+            );
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let vectors = generate_all(b"golden").expect("vectors");
+        assert_eq!(2 * vector_key_types().len(), vectors.len());
+        let json = to_json(&vectors).expect("json");
+        let decoded = from_json(&json).expect("decoded");
+        assert_eq!(vectors, decoded);
+        assert!(check_all(&decoded).is_ok());
+    }
+
+    #[test]
+    fn mismatch() {
+        let mut vectors = generate_all(b"golden").expect("vectors");
+        let other = vector_key_types().len() + 1;
+        vectors[1].signature = vectors[other].signature.clone();
+        assert!(check_all(&vectors).is_err());
+
+        let mut vector = TestVector::generate(KeyTag::default(), b"golden").expect("vector");
+        vector.message = b"other".to_vec();
+        assert!(vector.check().is_err());
+    }
+}
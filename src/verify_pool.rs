@@ -0,0 +1,262 @@
+//! A pool of worker threads for verifying signatures at high volume.
+//!
+//! Verification jobs are run by a configurable number of worker threads. Each
+//! worker has its own bounded queue, so workers never wait on each other for
+//! a job, and every job goes to the next worker in turn with room in its
+//! queue. Each submitted job returns a [`PendingVerification`] future which
+//! resolves to the verification result.
+//!
+//! When every queue is full, [`VerifyPool::submit`] waits for capacity,
+//! applying backpressure to the caller, while [`VerifyPool::try_submit`] sheds
+//! load by failing immediately with [`Error::Overloaded`].
+use crate::*;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    thread,
+};
+use tokio::sync::{mpsc, oneshot};
+
+struct Job {
+    public_key: PublicKey,
+    msg: Vec<u8>,
+    signature: Vec<u8>,
+    sender: oneshot::Sender<Result>,
+}
+
+impl Job {
+    fn run(self) {
+        // Skip the work if the caller has given up on the result
+        if self.sender.is_closed() {
+            return;
+        }
+        let result = self.public_key.verify(&self.msg, &self.signature);
+        // Send errors mean the caller has gone away, which is fine to ignore
+        let _ = self.sender.send(result);
+    }
+}
+
+/// The result of a submitted verification job
+#[derive(Debug)]
+pub struct PendingVerification(oneshot::Receiver<Result>);
+
+impl Future for PendingVerification {
+    type Output = Result;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|result| result.map_err(|_| Error::worker_unavailable())?)
+    }
+}
+
+/// Bounded queues of verification jobs, each served by its own worker
+/// thread.
+///
+/// The worker threads exit once the pool is dropped and all queued jobs have
+/// completed.
+#[derive(Debug, Clone)]
+pub struct VerifyPool {
+    senders: Arc<Vec<mpsc::Sender<Job>>>,
+    /// The index of the worker queue to try first for the next job
+    next: Arc<AtomicUsize>,
+}
+
+impl VerifyPool {
+    /// Start a pool with the given number of worker threads and room for the
+    /// given number of queued jobs, split evenly over the workers and rounded
+    /// up. Both values are raised to at least one.
+    pub fn new(workers: usize, queue_size: usize) -> Result<Self> {
+        let (pool, receivers) = Self::channels(workers, queue_size);
+        for (index, mut receiver) in receivers.into_iter().enumerate() {
+            thread::Builder::new()
+                .name(format!("helium-crypto-verify-{}", index))
+                .spawn(move || {
+                    while let Some(job) = receiver.blocking_recv() {
+                        job.run();
+                    }
+                })?;
+        }
+        Ok(pool)
+    }
+
+    fn channels(workers: usize, queue_size: usize) -> (Self, Vec<mpsc::Receiver<Job>>) {
+        let workers = workers.max(1);
+        let worker_queue_size = (queue_size.max(1) + workers - 1) / workers;
+        let (senders, receivers) = (0..workers)
+            .map(|_| mpsc::channel(worker_queue_size))
+            .unzip();
+        let pool = Self {
+            senders: Arc::new(senders),
+            next: Arc::default(),
+        };
+        (pool, receivers)
+    }
+
+    /// Returns the number of jobs that can be queued before the pool is full
+    pub fn available_capacity(&self) -> usize {
+        self.senders.iter().map(mpsc::Sender::capacity).sum()
+    }
+
+    /// Returns the index of the worker queue to try first for a new job
+    fn next_queue(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % self.senders.len()
+    }
+
+    /// Queue the job in the given slot with the first worker, starting at the
+    /// given index, that has room. If no worker takes the job it is left in
+    /// the slot, and the error says whether the queues are full or closed.
+    fn try_queue(&self, start: usize, slot: &mut Option<Job>) -> Result {
+        let mut error = Error::worker_unavailable();
+        for offset in 0..self.senders.len() {
+            let job = match slot.take() {
+                Some(job) => job,
+                None => return Ok(()),
+            };
+            let sender = &self.senders[(start + offset) % self.senders.len()];
+            match sender.try_send(job) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Full(job)) => {
+                    error = Error::overloaded();
+                    *slot = Some(job);
+                }
+                Err(mpsc::error::TrySendError::Closed(job)) => *slot = Some(job),
+            }
+        }
+        Err(error)
+    }
+
+    /// Submit a verification job, waiting for room in a queue if all of them
+    /// are full.
+    pub async fn submit(
+        &self,
+        public_key: &PublicKey,
+        msg: &[u8],
+        signature: &[u8],
+    ) -> Result<PendingVerification> {
+        let (job, pending) = Self::job(public_key, msg, signature);
+        let start = self.next_queue();
+        let mut slot = Some(job);
+        if self.try_queue(start, &mut slot).is_err() {
+            if let Some(job) = slot {
+                // Wait for room with the worker that was tried first
+                self.senders[start]
+                    .send(job)
+                    .await
+                    .map_err(|_| Error::worker_unavailable())?;
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Submit a verification job, failing with [`Error::Overloaded`] if all
+    /// queues are full.
+    pub fn try_submit(
+        &self,
+        public_key: &PublicKey,
+        msg: &[u8],
+        signature: &[u8],
+    ) -> Result<PendingVerification> {
+        let (job, pending) = Self::job(public_key, msg, signature);
+        self.try_queue(self.next_queue(), &mut Some(job))?;
+        Ok(pending)
+    }
+
+    /// Verify the given signature on the pool, waiting for room in the queue
+    /// and for the result.
+    pub async fn verify(&self, public_key: &PublicKey, msg: &[u8], signature: &[u8]) -> Result {
+        self.submit(public_key, msg, signature).await?.await
+    }
+
+    fn job(public_key: &PublicKey, msg: &[u8], signature: &[u8]) -> (Job, PendingVerification) {
+        let (sender, receiver) = oneshot::channel();
+        let job = Job {
+            public_key: public_key.clone(),
+            msg: msg.to_vec(),
+            signature: signature.to_vec(),
+            sender,
+        };
+        (job, PendingVerification(receiver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[tokio::test]
+    async fn verify() {
+        let pool = VerifyPool::new(2, 8).expect("pool");
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key = keypair.public_key();
+
+        let pending = vec![
+            pool.submit(public_key, b"hello world", &signature).await,
+            pool.submit(public_key, b"hello there", &signature).await,
+        ];
+        let mut results = vec![];
+        for pending in pending {
+            results.push(pending.expect("submitted").await);
+        }
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(pool
+            .verify(public_key, b"hello world", &signature)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn overloaded() {
+        let (pool, _receivers) = VerifyPool::channels(1, 1);
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key = keypair.public_key();
+
+        assert!(pool
+            .try_submit(public_key, b"hello world", &signature)
+            .is_ok());
+        assert_eq!(0, pool.available_capacity());
+        assert!(matches!(
+            pool.try_submit(public_key, b"hello world", &signature),
+            Err(Error::Overloaded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn spreads_jobs() {
+        let (pool, mut receivers) = VerifyPool::channels(2, 4);
+        let keypair = Keypair::generate(KeyTag::default(), &mut OsRng);
+        let signature = keypair.sign(b"hello world").expect("signature");
+        let public_key = keypair.public_key();
+
+        assert_eq!(4, pool.available_capacity());
+        for _ in 0..2 {
+            assert!(pool
+                .try_submit(public_key, b"hello world", &signature)
+                .is_ok());
+        }
+        // Each worker got one of the jobs
+        for receiver in &mut receivers {
+            assert!(receiver.try_recv().is_ok());
+        }
+
+        // A closed queue does not stop jobs from going to a worker with room
+        drop(receivers.remove(1));
+        for _ in 0..2 {
+            assert!(pool
+                .submit(public_key, b"hello world", &signature)
+                .await
+                .is_ok());
+        }
+        assert!(receivers[0].try_recv().is_ok());
+        assert!(receivers[0].try_recv().is_ok());
+    }
+}
@@ -0,0 +1,186 @@
+//! Verifiable random function over ed25519 keys.
+//!
+//! Implements ECVRF-EDWARDS25519-SHA512-TAI from [RFC 9381][RFC9381]. The
+//! holder of an ed25519 keypair [`prove`]s an input, which yields a
+//! pseudorandom output and a proof. Anyone with the public key can [`verify`]
+//! the proof to get the same output, and be sure it is the only output the
+//! keypair can produce for that input. This makes the output usable for
+//! beacon or lottery selection without a separate key.
+//!
+//! Proofs are 80 bytes long and outputs are 64 bytes long.
+//!
+//! [RFC9381]: https://www.rfc-editor.org/rfc/rfc9381
+use crate::{
+    ed25519::curve::{base_mul, decompress, hash_to_scalar, public_key_point, scalar_from_slice},
+    *,
+};
+use curve25519_dalek::{
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+};
+use sha2::{Digest, Sha512};
+
+pub const PROOF_LENGTH: usize = 80;
+pub const OUTPUT_LENGTH: usize = 64;
+
+/// The RFC 9381 suite string for ECVRF-EDWARDS25519-SHA512-TAI
+const SUITE: u8 = 0x03;
+
+/// Prove the given input with the given keypair. Returns the proof and the
+/// output, which [`verify`] also returns for a valid proof.
+pub fn prove(
+    keypair: &ed25519::Keypair,
+    alpha: &[u8],
+) -> Result<([u8; PROOF_LENGTH], [u8; OUTPUT_LENGTH])> {
+    let public_key: &ed25519::PublicKey = (&keypair.public_key).try_into()?;
+    let secret = keypair.secret_scalar();
+    let h = encode_to_curve(public_key.as_ref(), alpha)?;
+    let h_string = h.compress();
+    let gamma = secret * h;
+    let nonce = hash_to_scalar(&[&keypair.expanded_secret()[32..], h_string.as_bytes()]);
+    let c = challenge(&[
+        public_key.as_ref(),
+        h_string.as_bytes(),
+        gamma.compress().as_bytes(),
+        base_mul(&nonce).compress().as_bytes(),
+        (nonce * h).compress().as_bytes(),
+    ]);
+    let s = nonce + c * secret;
+
+    let mut proof = [0u8; PROOF_LENGTH];
+    proof[..32].copy_from_slice(gamma.compress().as_bytes());
+    proof[32..48].copy_from_slice(&c.as_bytes()[..16]);
+    proof[48..].copy_from_slice(s.as_bytes());
+    Ok((proof, gamma_to_hash(&gamma)))
+}
+
+/// Verify the given proof of the given input by the given public key.
+/// Returns the output of the proof if it is valid.
+pub fn verify(public_key: &PublicKey, alpha: &[u8], proof: &[u8]) -> Result<[u8; OUTPUT_LENGTH]> {
+    let y = public_key_point(public_key)?;
+    if y.is_small_order() {
+        return Err(Error::invalid_curve());
+    }
+    let (gamma, c, s) = decode_proof(proof)?;
+    let public_key = y.compress();
+    let h = encode_to_curve(public_key.as_bytes(), alpha)?;
+    let u = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-c, &y, &s);
+    let v = s * h - c * gamma;
+    let expected = challenge(&[
+        public_key.as_bytes(),
+        h.compress().as_bytes(),
+        &proof[..32],
+        u.compress().as_bytes(),
+        v.compress().as_bytes(),
+    ]);
+    if expected != c {
+        return Err(signature::Error::new().into());
+    }
+    Ok(gamma_to_hash(&gamma))
+}
+
+/// Returns the output of the given proof without verifying it. Only use the
+/// output of a proof that was verified with [`verify`].
+pub fn proof_to_hash(proof: &[u8]) -> Result<[u8; OUTPUT_LENGTH]> {
+    let (gamma, _, _) = decode_proof(proof)?;
+    Ok(gamma_to_hash(&gamma))
+}
+
+fn decode_proof(proof: &[u8]) -> Result<(EdwardsPoint, Scalar, Scalar)> {
+    if proof.len() != PROOF_LENGTH {
+        return Err(signature::Error::new().into());
+    }
+    let gamma = decompress(&proof[..32]).map_err(|_| signature::Error::new())?;
+    let mut c = [0u8; 32];
+    c[..16].copy_from_slice(&proof[32..48]);
+    let s = scalar_from_slice(&proof[48..])?;
+    Ok((gamma, Scalar::from_bits(c), s))
+}
+
+/// Hash the given public key and input to a point in the prime order
+/// subgroup by try and increment
+fn encode_to_curve(public_key: &[u8], alpha: &[u8]) -> Result<EdwardsPoint> {
+    for counter in 0..=255u8 {
+        let digest = Sha512::new()
+            .chain_update([SUITE, 0x01])
+            .chain_update(public_key)
+            .chain_update(alpha)
+            .chain_update([counter, 0x00])
+            .finalize();
+        if let Some(point) = CompressedEdwardsY::from_slice(&digest[..32]).decompress() {
+            return Ok(point.mul_by_cofactor());
+        }
+    }
+    Err(Error::invalid_curve())
+}
+
+/// Returns the 16 byte challenge over the given points as a scalar
+fn challenge(points: &[&[u8]]) -> Scalar {
+    let mut digest = Sha512::new().chain_update([SUITE, 0x02]);
+    for point in points {
+        digest.update(point);
+    }
+    let digest = digest.chain_update([0x00]).finalize();
+    let mut c = [0u8; 32];
+    c[..16].copy_from_slice(&digest[..16]);
+    Scalar::from_bits(c)
+}
+
+fn gamma_to_hash(gamma: &EdwardsPoint) -> [u8; OUTPUT_LENGTH] {
+    Sha512::new()
+        .chain_update([SUITE, 0x03])
+        .chain_update(gamma.mul_by_cofactor().compress().as_bytes())
+        .chain_update([0x00])
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn rfc9381_vectors() {
+        // ECVRF-EDWARDS25519-SHA512-TAI examples 16 and 17 from RFC 9381
+        let vectors: [(&[u8], &[u8], &[u8], &[u8]); 2] = [
+            (
+                &hex!("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+                b"",
+                &hex!("8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805"),
+                &hex!("90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae"),
+            ),
+            (
+                &hex!("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb"),
+                &hex!("72"),
+                &hex!("f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02"),
+                &hex!("eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031"),
+            ),
+        ];
+        for (secret, alpha, expected_proof, expected_output) in vectors {
+            let keypair =
+                ed25519::Keypair::generate_from_entropy(Network::MainNet, secret).expect("keypair");
+            let (proof, output) = prove(&keypair, alpha).expect("prove");
+            assert_eq!(expected_proof, &proof[..]);
+            assert_eq!(expected_output, &output[..]);
+            assert_eq!(
+                output,
+                verify(&keypair.public_key, alpha, &proof).expect("verify")
+            );
+            assert_eq!(output, proof_to_hash(&proof).expect("hash"));
+        }
+    }
+
+    #[test]
+    fn verify_rejects() {
+        let keypair = ed25519::Keypair::generate(Network::MainNet, &mut rand::rngs::OsRng);
+        let other = ed25519::Keypair::generate(Network::MainNet, &mut rand::rngs::OsRng);
+        let (proof, _) = prove(&keypair, b"round 1").expect("prove");
+        assert!(verify(&keypair.public_key, b"round 2", &proof).is_err());
+        assert!(verify(&other.public_key, b"round 1", &proof).is_err());
+        let mut tampered = proof;
+        tampered[40] ^= 1;
+        assert!(verify(&keypair.public_key, b"round 1", &tampered).is_err());
+        assert!(verify(&keypair.public_key, b"round 1", &proof[..79]).is_err());
+    }
+}
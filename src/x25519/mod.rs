@@ -0,0 +1,228 @@
+//! X25519 keypairs for Diffie-Hellman with peers that do not use P-256.
+//!
+//! X25519 keys can only be used for ECDH and can not sign or verify. The
+//! shared secret is the raw 32 byte X25519 output, which is rejected if it is
+//! all zeroes, as happens for low order peer keys.
+use crate::*;
+use curve25519_dalek::{constants::X25519_BASEPOINT, montgomery::MontgomeryPoint, scalar::Scalar};
+use std::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey(pub(crate) MontgomeryPoint);
+
+/// The raw 32 byte output of an X25519 key agreement
+pub struct SharedSecret([u8; 32]);
+
+pub struct Keypair {
+    pub network: Network,
+    pub public_key: public_key::PublicKey,
+    secret: [u8; 32],
+}
+
+pub const KEYPAIR_LENGTH: usize = 33;
+pub const PUBLIC_KEY_LENGTH: usize = 33;
+
+impl PartialEq for Keypair {
+    fn eq(&self, other: &Self) -> bool {
+        self.network == other.network && self.public_key == other.public_key
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("Keypair")
+            .field("tag", &self.key_tag())
+            .field("public", &self.public_key)
+            .finish()
+    }
+}
+
+impl TryFrom<&[u8]> for Keypair {
+    type Error = Error;
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let network = Network::try_from(input[0])?;
+        Self::generate_from_entropy(network, &input[1..usize::min(input.len(), KEYPAIR_LENGTH)])
+    }
+}
+
+impl WriteTo for Keypair {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(&[u8::from(self.key_tag())])?;
+        output.write_all(&self.secret)
+    }
+}
+
+impl Keypair {
+    pub fn generate<R>(network: Network, csprng: &mut R) -> Keypair
+    where
+        R: rand_core::CryptoRng + rand_core::RngCore,
+    {
+        let mut secret = [0u8; 32];
+        csprng.fill_bytes(&mut secret);
+        Self::from_secret(network, secret)
+    }
+
+    pub fn generate_from_entropy(network: Network, entropy: &[u8]) -> Result<Keypair> {
+        let secret = <[u8; 32]>::try_from(entropy).map_err(|_| Error::invalid_curve())?;
+        Ok(Self::from_secret(network, secret))
+    }
+
+    fn from_secret(network: Network, secret: [u8; 32]) -> Self {
+        let public_key = PublicKey(X25519_BASEPOINT * clamped(&secret));
+        Keypair {
+            network,
+            public_key: public_key::PublicKey::for_network(network, public_key),
+            secret,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = vec![0u8; KEYPAIR_LENGTH];
+        self.write_to(&mut std::io::Cursor::new(&mut result))
+            .unwrap();
+        result
+    }
+
+    pub fn key_tag(&self) -> KeyTag {
+        KeyTag {
+            network: self.network,
+            key_type: KeyType::X25519,
+        }
+    }
+
+    pub fn secret_to_vec(&self) -> Vec<u8> {
+        self.secret.to_vec()
+    }
+
+    pub fn ecdh<'a, C>(&self, public_key: C) -> Result<SharedSecret>
+    where
+        C: TryInto<&'a PublicKey, Error = Error>,
+    {
+        let public_key = public_key.try_into()?;
+        let shared_secret = (public_key.0 * clamped(&self.secret)).to_bytes();
+        if shared_secret == [0u8; 32] {
+            return Err(Error::invalid_curve());
+        }
+        Ok(SharedSecret(shared_secret))
+    }
+}
+
+impl SharedSecret {
+    pub fn raw_secret_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Returns the scalar for the given secret, clamped as X25519 requires
+fn clamped(secret: &[u8; 32]) -> Scalar {
+    let mut bytes = *secret;
+    bytes[0] &= 248;
+    bytes[31] &= 127;
+    bytes[31] |= 64;
+    Scalar::from_bits(bytes)
+}
+
+impl PublicKeySize for PublicKey {
+    const PUBLIC_KEY_SIZE: usize = PUBLIC_KEY_LENGTH;
+}
+
+impl public_key::Verify for PublicKey {
+    /// X25519 keys can not verify signatures, so this always fails
+    fn verify(&self, _msg: &[u8], _signature: &[u8]) -> Result {
+        Err(Error::invalid_curve())
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    fn try_from(input: &[u8]) -> Result<Self> {
+        let mut input = std::io::Cursor::new(&input[1..]);
+        Self::read_from(&mut input)
+    }
+}
+
+impl ReadFrom for PublicKey {
+    fn read_from<R: std::io::Read>(input: &mut R) -> Result<Self> {
+        let mut buf = [0u8; PUBLIC_KEY_LENGTH - 1];
+        input.read_exact(&mut buf)?;
+        Ok(PublicKey(MontgomeryPoint(buf)))
+    }
+}
+
+impl WriteTo for PublicKey {
+    fn write_to<W: std::io::Write>(&self, output: &mut W) -> std::io::Result<()> {
+        output.write_all(self.0.as_bytes())
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.0.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn ecdh() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let other = Keypair::generate(Network::MainNet, &mut OsRng);
+        let shared = keypair.ecdh(&other.public_key).expect("ecdh");
+        let other_shared = other.ecdh(&keypair.public_key).expect("other ecdh");
+        assert_eq!(shared.raw_secret_bytes(), other_shared.raw_secret_bytes());
+    }
+
+    #[test]
+    fn rfc7748_vector() {
+        // The Diffie-Hellman test vector from RFC 7748 section 6.1
+        let alice = Keypair::generate_from_entropy(
+            Network::MainNet,
+            &hex!("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a"),
+        )
+        .expect("alice");
+        let bob = Keypair::generate_from_entropy(
+            Network::MainNet,
+            &hex!("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb"),
+        )
+        .expect("bob");
+        assert_eq!(
+            hex!("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")[..],
+            alice.public_key.to_vec()[1..]
+        );
+        let shared = alice.ecdh(&bob.public_key).expect("ecdh");
+        assert_eq!(
+            hex!("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742")[..],
+            shared.raw_secret_bytes()[..]
+        );
+    }
+
+    #[test]
+    fn low_order() {
+        let keypair = Keypair::generate(Network::MainNet, &mut OsRng);
+        let zero = public_key::PublicKey::for_network(
+            Network::MainNet,
+            PublicKey(MontgomeryPoint([0u8; 32])),
+        );
+        assert!(keypair.ecdh(&zero).is_err());
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let keypair = Keypair::generate(Network::TestNet, &mut OsRng);
+        assert_eq!(
+            keypair,
+            Keypair::try_from(&keypair.to_vec()[..]).expect("keypair")
+        );
+        let public_key = &keypair.public_key;
+        let decoded: public_key::PublicKey = public_key.to_string().parse().expect("b58");
+        assert_eq!(public_key, &decoded);
+    }
+}
@@ -0,0 +1,242 @@
+//! Asynchronous key agreement in the style of X3DH.
+//!
+//! X3DH lets an initiator derive a shared key with a responder that is
+//! offline, like a hotspot sending to a control plane through a store and
+//! forward queue. The responder publishes a [`PrekeyBundle`] with its identity
+//! key, a [`SignedPrekey`] and optionally a one-time prekey. The initiator
+//! combines the bundle with its own identity key and a fresh ephemeral key in
+//! [`initiate`], and sends the resulting [`InitialMessage`] along with its
+//! first encrypted payload. The responder derives the same key with
+//! [`respond`] once it comes back online.
+//!
+//! The shared key is derived with HKDF-SHA256 from the concatenation of:
+//!
+//! ```text
+//! DH1 = ECDH(initiator identity, signed prekey)
+//! DH2 = ECDH(ephemeral, responder identity)
+//! DH3 = ECDH(ephemeral, signed prekey)
+//! DH4 = ECDH(ephemeral, one-time prekey)   if a one-time prekey was used
+//! ```
+//!
+//! All keys are ecc_compact keys. Identity keys can be any keypair that
+//! supports ECDH, including the hardware backed ones. Responders must delete
+//! a one-time prekey once it has been used.
+use crate::*;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// Domain separator prefixed to the signed bytes of a signed prekey
+const SIGNED_PREKEY_DOMAIN: &[u8] = b"helium-x3dh-signed-prekey";
+/// The info string used when deriving the shared key
+const X3DH_KDF_INFO: &[u8] = b"helium-x3dh";
+
+/// A medium term prekey signed by the identity key of its owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPrekey {
+    pub public_key: PublicKey,
+    pub signature: Vec<u8>,
+}
+
+impl SignedPrekey {
+    /// Sign the given prekey with the given identity keypair
+    pub fn sign(identity: &Keypair, prekey: &PublicKey) -> Result<Self> {
+        let signature = identity.sign(&signed_prekey_bytes(prekey))?;
+        Ok(Self {
+            public_key: prekey.clone(),
+            signature,
+        })
+    }
+
+    /// Verify the prekey was signed by the given identity key
+    pub fn verify(&self, identity_key: &PublicKey) -> Result {
+        identity_key.verify(&signed_prekey_bytes(&self.public_key), &self.signature)
+    }
+}
+
+/// The keys a responder publishes so initiators can reach it while offline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrekeyBundle {
+    pub identity_key: PublicKey,
+    pub signed_prekey: SignedPrekey,
+    pub one_time_prekey: Option<PublicKey>,
+}
+
+/// The keys the initiator sends to the responder with its first message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitialMessage {
+    pub identity_key: PublicKey,
+    pub ephemeral_key: PublicKey,
+    /// The signed prekey of the responder that was used
+    pub signed_prekey: PublicKey,
+    /// The one-time prekey of the responder that was used, if any
+    pub one_time_prekey: Option<PublicKey>,
+}
+
+/// The result of a key agreement
+pub struct AgreedKey {
+    /// The shared key
+    pub key: [u8; 32],
+    /// The associated data to authenticate with every message encrypted with
+    /// the key, binding it to the identities of both parties
+    pub associated_data: Vec<u8>,
+}
+
+impl std::fmt::Debug for AgreedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("AgreedKey")
+            .field("associated_data", &self.associated_data)
+            .finish()
+    }
+}
+
+/// Agree on a key with the owner of the given prekey bundle. Fails if the
+/// signed prekey in the bundle was not signed by its identity key.
+pub fn initiate<R>(
+    identity: &Keypair,
+    bundle: &PrekeyBundle,
+    csprng: &mut R,
+) -> Result<(AgreedKey, InitialMessage)>
+where
+    R: rand_core::CryptoRng + rand_core::RngCore,
+{
+    bundle.signed_prekey.verify(&bundle.identity_key)?;
+    let ephemeral = Keypair::generate(
+        KeyTag {
+            network: identity.public_key().network,
+            key_type: KeyType::EccCompact,
+        },
+        csprng,
+    );
+    let mut secrets = vec![
+        identity.ecdh(&bundle.signed_prekey.public_key)?,
+        ephemeral.ecdh(&bundle.identity_key)?,
+        ephemeral.ecdh(&bundle.signed_prekey.public_key)?,
+    ];
+    if let Some(one_time_prekey) = &bundle.one_time_prekey {
+        secrets.push(ephemeral.ecdh(one_time_prekey)?);
+    }
+    let agreed = agreed_key(&secrets, identity.public_key(), &bundle.identity_key)?;
+    let message = InitialMessage {
+        identity_key: identity.public_key().clone(),
+        ephemeral_key: ephemeral.public_key().clone(),
+        signed_prekey: bundle.signed_prekey.public_key.clone(),
+        one_time_prekey: bundle.one_time_prekey.clone(),
+    };
+    Ok((agreed, message))
+}
+
+/// Agree on the key for the given initial message using the identity and
+/// prekeys it was sent to. Fails if the message names prekeys other than the
+/// given ones.
+pub fn respond(
+    identity: &Keypair,
+    signed_prekey: &Keypair,
+    one_time_prekey: Option<&Keypair>,
+    message: &InitialMessage,
+) -> Result<AgreedKey> {
+    if &message.signed_prekey != signed_prekey.public_key()
+        || message.one_time_prekey.as_ref() != one_time_prekey.map(Keypair::public_key)
+    {
+        return Err(signature::Error::new().into());
+    }
+    let mut secrets = vec![
+        signed_prekey.ecdh(&message.identity_key)?,
+        identity.ecdh(&message.ephemeral_key)?,
+        signed_prekey.ecdh(&message.ephemeral_key)?,
+    ];
+    if let Some(one_time_prekey) = one_time_prekey {
+        secrets.push(one_time_prekey.ecdh(&message.ephemeral_key)?);
+    }
+    agreed_key(&secrets, &message.identity_key, identity.public_key())
+}
+
+fn signed_prekey_bytes(prekey: &PublicKey) -> Vec<u8> {
+    let mut result = SIGNED_PREKEY_DOMAIN.to_vec();
+    result.extend_from_slice(&prekey.to_vec());
+    result
+}
+
+fn agreed_key(
+    secrets: &[keypair::SharedSecret],
+    initiator: &PublicKey,
+    responder: &PublicKey,
+) -> Result<AgreedKey> {
+    // As in X3DH, the key material starts with a block of 0xff bytes
+    let mut material = vec![0xff; 32];
+    for secret in secrets {
+        material.extend_from_slice(secret.raw_secret_bytes());
+    }
+    let mut associated_data = initiator.to_vec();
+    associated_data.extend_from_slice(&responder.to_vec());
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&[0u8; 32]), &material)
+        .expand(X3DH_KDF_INFO, &mut key)
+        .map_err(|_| Error::encryption())?;
+    Ok(AgreedKey {
+        key,
+        associated_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn ecc_keypair() -> Keypair {
+        Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        )
+    }
+
+    #[test]
+    fn agree() {
+        let (alice, bob) = (ecc_keypair(), ecc_keypair());
+        let signed_prekey = ecc_keypair();
+        let one_time_prekey = ecc_keypair();
+        let bundle = PrekeyBundle {
+            identity_key: bob.public_key().clone(),
+            signed_prekey: SignedPrekey::sign(&bob, signed_prekey.public_key()).expect("sign"),
+            one_time_prekey: Some(one_time_prekey.public_key().clone()),
+        };
+
+        let (initiated, message) = initiate(&alice, &bundle, &mut OsRng).expect("initiate");
+        let responded =
+            respond(&bob, &signed_prekey, Some(&one_time_prekey), &message).expect("respond");
+        assert_eq!(initiated.key, responded.key);
+        assert_eq!(initiated.associated_data, responded.associated_data);
+
+        // The one-time prekey is part of the agreement
+        assert!(respond(&bob, &signed_prekey, None, &message).is_err());
+    }
+
+    #[test]
+    fn without_one_time_prekey() {
+        let (alice, bob) = (ecc_keypair(), ecc_keypair());
+        let signed_prekey = ecc_keypair();
+        let bundle = PrekeyBundle {
+            identity_key: bob.public_key().clone(),
+            signed_prekey: SignedPrekey::sign(&bob, signed_prekey.public_key()).expect("sign"),
+            one_time_prekey: None,
+        };
+        let (initiated, message) = initiate(&alice, &bundle, &mut OsRng).expect("initiate");
+        let responded = respond(&bob, &signed_prekey, None, &message).expect("respond");
+        assert_eq!(initiated.key, responded.key);
+    }
+
+    #[test]
+    fn forged_prekey() {
+        let (alice, bob, mallory) = (ecc_keypair(), ecc_keypair(), ecc_keypair());
+        let signed_prekey = ecc_keypair();
+        let bundle = PrekeyBundle {
+            identity_key: bob.public_key().clone(),
+            signed_prekey: SignedPrekey::sign(&mallory, signed_prekey.public_key()).expect("sign"),
+            one_time_prekey: None,
+        };
+        assert!(initiate(&alice, &bundle, &mut OsRng).is_err());
+    }
+}